@@ -0,0 +1,68 @@
+/*
+ * C interface to the polyscan scanner, for scanning sequences already in
+ * memory (e.g. reads) from C and C++ without shelling out per sequence.
+ *
+ * Link against libpolyscan_c (built by `cargo build --release -p
+ * polyscan-capi`), e.g. `cc qc.c -Icapi/include -Ltarget/release
+ * -lpolyscan_c`.
+ */
+#ifndef POLYSCAN_H
+#define POLYSCAN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* A window passing the scan, in 0-based half-open coordinates. */
+typedef struct polyscan_hit {
+    size_t start;
+    size_t end;
+    /* Percentage of the window made up by the target */
+    double score;
+    /* Index of the target in the comma-separated `bases` of the scan */
+    uint32_t target;
+    /* '+', '-' or '.' (for targets that are their own complement) */
+    char strand;
+} polyscan_hit;
+
+/* The hits of a scan, in the order the command line reports them. `hits`
+ * is NULL when `len` is 0. */
+typedef struct polyscan_hits {
+    polyscan_hit *hits;
+    size_t len;
+} polyscan_hits;
+
+/*
+ * Scan the `len` bases at `seq` (which need not be NUL-terminated) for
+ * windows of `window` bases where one of `bases` makes up at least
+ * `percentage` percent, on both strands.
+ *
+ * `bases` is a NUL-terminated, comma-separated list of targets as given to
+ * --nucleotide, e.g. "A", "A,T" or "GC".
+ *
+ * Returns the hits, to be released with polyscan_hits_free, or NULL on an
+ * invalid argument; polyscan_last_error then says why. Calls from different
+ * threads are independent.
+ */
+polyscan_hits *polyscan_scan_buffer(const char *seq, size_t len, const char *bases, size_t window, double percentage);
+
+/* Release hits returned by polyscan_scan_buffer; NULL is ignored. */
+void polyscan_hits_free(polyscan_hits *hits);
+
+/* Why the last call on this thread failed, or NULL if it didn't. Valid until
+ * the next call on the thread. */
+const char *polyscan_last_error(void);
+
+/* The kind of the last failure on this thread, as the command line's exit
+ * code: 2 for an invalid argument, 3 for unreadable input, 1 for anything
+ * else, or 0 if the last call didn't fail. */
+int polyscan_last_error_code(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* POLYSCAN_H */
@@ -0,0 +1,137 @@
+//! C interface to the scanner, declared by `include/polyscan.h`: scan a
+//! buffer of bases, get back an array of hits, and free it.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use polyscan::error::{PolyscanError, EXIT_PARAMETER};
+use polyscan::scan::BaseClass;
+use polyscan::{ScanParams, Scanner};
+
+/// `polyscan_hit`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PolyscanHit {
+    pub start: usize,
+    pub end: usize,
+    pub score: f64,
+    pub target: u32,
+    pub strand: c_char,
+}
+
+/// `polyscan_hits`
+#[repr(C)]
+#[derive(Debug)]
+pub struct PolyscanHits {
+    pub hits: *mut PolyscanHit,
+    pub len: usize,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<(CString, c_int)>> = const { RefCell::new(None) };
+}
+
+// Failures that aren't a PolyscanError (e.g. a panic) get the command line's
+// generic exit code
+const EXIT_OTHER: c_int = 1;
+
+fn set_last_error(error: Option<(String, c_int)>) {
+    // A message can't hold a NUL for C; none of ours do
+    let error = error.map(|(message, code)| (CString::new(message.replace('\0', " ")).expect("NULs were replaced"), code));
+    LAST_ERROR.with(|last| *last.borrow_mut() = error);
+}
+
+fn scan_buffer(seq: &[u8], bases: &str, window: usize, percentage: f64) -> Result<Vec<PolyscanHit>, PolyscanError> {
+    let targets: Vec<BaseClass> = bases.split(',').map(|target| BaseClass::new(target.trim().as_bytes())).collect();
+    // Hits are labelled with their target as the scanner normalizes it (uppercased), so
+    // they are matched against the same labels, not the bases as given
+    let labels: Vec<String> = targets.iter().map(BaseClass::label).collect();
+    let params = ScanParams { targets, window, percentage, ..ScanParams::default() };
+    let scanner = Scanner::new(params)?;
+    scanner
+        .hits("seq", seq)
+        .map(|hit| {
+            let target = labels.iter().position(|label| *label == hit.target).ok_or_else(|| PolyscanError::Parameter(format!("hit for unknown target '{}'", hit.target)))?;
+            Ok(PolyscanHit {
+                start: hit.start,
+                end: hit.end,
+                score: hit.score,
+                target: target as u32,
+                strand: hit.strand.symbol().as_bytes()[0] as c_char,
+            })
+        })
+        .collect()
+}
+
+/// See `polyscan_scan_buffer` in `include/polyscan.h`.
+///
+/// # Safety
+///
+/// `seq` must point to `len` readable bytes (or be NULL with `len` 0), and
+/// `bases` to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn polyscan_scan_buffer(seq: *const c_char, len: usize, bases: *const c_char, window: usize, percentage: f64) -> *mut PolyscanHits {
+    if (seq.is_null() && len > 0) || bases.is_null() {
+        set_last_error(Some(("seq and bases must not be NULL".to_string(), EXIT_PARAMETER)));
+        return ptr::null_mut();
+    }
+    // SAFETY: the caller passes `len` bytes at `seq`, and a C string at `bases`
+    let seq = if len == 0 { &[][..] } else { unsafe { std::slice::from_raw_parts(seq as *const u8, len) } };
+    let bases = match unsafe { CStr::from_ptr(bases) }.to_str() {
+        Ok(bases) => bases,
+        Err(_) => {
+            set_last_error(Some(("bases must be UTF-8".to_string(), EXIT_PARAMETER)));
+            return ptr::null_mut();
+        }
+    };
+
+    // A panic must not unwind into C
+    let result = panic::catch_unwind(AssertUnwindSafe(|| scan_buffer(seq, bases, window, percentage)));
+    let hits = match result {
+        Ok(Ok(hits)) => hits,
+        Ok(Err(e)) => {
+            set_last_error(Some((e.to_string(), e.exit_code())));
+            return ptr::null_mut();
+        }
+        Err(_) => {
+            set_last_error(Some(("internal error while scanning".to_string(), EXIT_OTHER)));
+            return ptr::null_mut();
+        }
+    };
+    set_last_error(None);
+    let len = hits.len();
+    let hits = if len == 0 { ptr::null_mut() } else { Box::into_raw(hits.into_boxed_slice()) as *mut PolyscanHit };
+    Box::into_raw(Box::new(PolyscanHits { hits, len }))
+}
+
+/// See `polyscan_hits_free` in `include/polyscan.h`.
+///
+/// # Safety
+///
+/// `hits` must be NULL or returned by [`polyscan_scan_buffer`], and not
+/// freed already.
+#[no_mangle]
+pub unsafe extern "C" fn polyscan_hits_free(hits: *mut PolyscanHits) {
+    if hits.is_null() {
+        return;
+    }
+    // SAFETY: `hits` and its array were boxed by polyscan_scan_buffer
+    let hits = unsafe { Box::from_raw(hits) };
+    if !hits.hits.is_null() {
+        drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(hits.hits, hits.len)) });
+    }
+}
+
+/// See `polyscan_last_error` in `include/polyscan.h`.
+#[no_mangle]
+pub extern "C" fn polyscan_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |(message, _)| message.as_ptr()))
+}
+
+/// See `polyscan_last_error_code` in `include/polyscan.h`.
+#[no_mangle]
+pub extern "C" fn polyscan_last_error_code() -> c_int {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(0, |(_, code)| *code))
+}
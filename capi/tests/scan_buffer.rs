@@ -0,0 +1,48 @@
+use std::ffi::{CStr, CString};
+
+use polyscan_c::{polyscan_hits_free, polyscan_last_error, polyscan_last_error_code, polyscan_scan_buffer, PolyscanHit};
+
+// (start, end, target, strand) of the hits of a scan through the C interface
+fn scan(seq: &[u8], bases: &str, window: usize, percentage: f64) -> Vec<(usize, usize, u32, char)> {
+    let bases = CString::new(bases).unwrap();
+    let hits = unsafe { polyscan_scan_buffer(seq.as_ptr().cast(), seq.len(), bases.as_ptr(), window, percentage) };
+    assert!(!hits.is_null(), "scan failed: {:?}", unsafe { CStr::from_ptr(polyscan_last_error()) });
+    let found: Vec<PolyscanHit> = unsafe {
+        let hits = &*hits;
+        if hits.len == 0 { Vec::new() } else { std::slice::from_raw_parts(hits.hits, hits.len).to_vec() }
+    };
+    unsafe { polyscan_hits_free(hits) };
+    found.iter().map(|hit| (hit.start, hit.end, hit.target, hit.strand as u8 as char)).collect()
+}
+
+#[test]
+fn lowercase_targets_keep_their_index() {
+    let seq = b"AAAAAAAACCCCGGGGGGGG";
+    assert_eq!(scan(seq, "a,g", 8, 100.0), vec![(0, 8, 0, '+'), (12, 20, 1, '+')]);
+    assert_eq!(scan(seq, "A,G", 8, 100.0), scan(seq, "a,g", 8, 100.0));
+}
+
+#[test]
+fn minus_strand_hits_name_the_target_as_given() {
+    // TTTT is A on the minus strand, CCCC is G on it
+    let seq = b"GGTTTTTTTTGGCCCCCCCC";
+    assert_eq!(scan(seq, "g, a", 8, 100.0), vec![(2, 10, 1, '-'), (12, 20, 0, '-')]);
+}
+
+#[test]
+fn self_complementary_targets_are_unstranded() {
+    let seq = b"ACACGCGCGCGCACAC";
+    assert_eq!(scan(seq, "t,gc", 8, 100.0), vec![(3, 11, 1, '.'), (4, 12, 1, '.')]);
+}
+
+#[test]
+fn invalid_arguments_report_the_parameter_exit_code() {
+    let bases = CString::new("A").unwrap();
+    let hits = unsafe { polyscan_scan_buffer(b"AAAA".as_ptr().cast(), 4, bases.as_ptr(), 0, 80.0) };
+    assert!(hits.is_null());
+    assert_eq!(polyscan_last_error_code(), 2);
+
+    let hits = unsafe { polyscan_scan_buffer(b"AAAA".as_ptr().cast(), 4, bases.as_ptr(), 4, 80.0) };
+    assert_eq!(polyscan_last_error_code(), 0);
+    unsafe { polyscan_hits_free(hits) };
+}
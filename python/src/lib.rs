@@ -0,0 +1,132 @@
+//! Python bindings for polyscan, built into a `polyscan` module with
+//! maturin (see `pyproject.toml`).
+
+use clap::ValueEnum;
+use pyo3::exceptions::{PyIOError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyByteArray, PyBytes, PyDict};
+
+use polyscan::error::PolyscanError;
+use polyscan::scan::{Alphabet, BaseClass};
+use polyscan::scanner::{MaskedPolicy, NPolicy, StrandChoice};
+use polyscan::{ScanParams, Scanner};
+
+// Bad parameters as ValueError, unreadable or malformed input as IOError
+fn py_error(e: PolyscanError) -> PyErr {
+    match e {
+        PolyscanError::Input(message) => PyIOError::new_err(message),
+        PolyscanError::Parameter(message) | PolyscanError::Empty(message) => PyValueError::new_err(message),
+    }
+}
+
+/// A window passing the scan, in 0-based half-open coordinates.
+#[pyclass(name = "Hit", module = "polyscan", frozen, get_all, skip_from_py_object)]
+#[derive(Clone)]
+struct PyHit {
+    chrom: String,
+    start: usize,
+    end: usize,
+    target: String,
+    score: f64,
+    /// "+", "-" or "."
+    strand: String,
+}
+
+#[pymethods]
+impl PyHit {
+    fn __repr__(&self) -> String {
+        format!("Hit({}:{}-{} {} {:.1} {})", self.chrom, self.start, self.end, self.target, self.score, self.strand)
+    }
+}
+
+impl From<polyscan::Hit> for PyHit {
+    fn from(hit: polyscan::Hit) -> Self {
+        PyHit { chrom: hit.chrom, start: hit.start, end: hit.end, target: hit.target, score: hit.score, strand: hit.strand.symbol().to_string() }
+    }
+}
+
+// An option value by its command-line name (e.g. "count-against")
+fn choice<T: ValueEnum>(option: &str, value: &str) -> PyResult<T> {
+    T::from_str(value, true).map_err(|_| {
+        let names: Vec<String> = T::value_variants().iter().filter_map(|v| v.to_possible_value()).map(|v| v.get_name().to_string()).collect();
+        PyValueError::new_err(format!("invalid {} '{}' (expected one of {})", option, value, names.join(", ")))
+    })
+}
+
+/// Scan a sequence file, or a sequence given as bytes, for windows where
+/// `base` makes up at least `pct` percent of `window` bases.
+///
+/// `source` is a path (FASTA or FASTQ, optionally compressed, .2bit, BAM or
+/// CRAM) or the bases of a single sequence, reported as `name`. `base` takes
+/// one or more comma-separated targets as `--nucleotide` does (e.g. "A,GC"),
+/// and the other options are those of the command line of the same names.
+///
+/// Returns a list of `Hit`s, or with `columns=True` a dict of equal-length
+/// lists (chrom, start, end, target, score, strand) ready for
+/// `pandas.DataFrame` or `polars.DataFrame`.
+#[pyfunction]
+#[pyo3(signature = (source, base = "A", window = 10, pct = 80.0, *, min_count = None, strand = "both", n_policy = "count-against", ignore_masked = None, step = 1, protein = false, name = "seq", columns = false))]
+#[allow(clippy::too_many_arguments)]
+fn scan(
+    py: Python<'_>,
+    source: &Bound<'_, PyAny>,
+    base: &str,
+    window: usize,
+    pct: f64,
+    min_count: Option<usize>,
+    strand: &str,
+    n_policy: &str,
+    ignore_masked: Option<&str>,
+    step: usize,
+    protein: bool,
+    name: &str,
+    columns: bool,
+) -> PyResult<Py<PyAny>> {
+    let params = ScanParams {
+        targets: base.split(',').map(|target| BaseClass::new(target.trim().as_bytes())).collect(),
+        window,
+        percentage: pct,
+        min_count,
+        alphabet: if protein { Alphabet::Protein } else { Alphabet::Dna },
+        strand: choice::<StrandChoice>("strand", strand)?,
+        n_policy: choice::<NPolicy>("n_policy", n_policy)?,
+        ignore_masked: ignore_masked.map(|policy| choice::<MaskedPolicy>("ignore_masked", policy)).transpose()?,
+        step,
+        ..ScanParams::default()
+    };
+    let scanner = Scanner::new(params).map_err(py_error)?;
+
+    // The scan runs without the GIL, so other Python threads carry on
+    let hits = if let Ok(seq) = source.cast::<PyBytes>() {
+        let seq = seq.as_bytes().to_vec();
+        py.detach(|| scanner.scan_seq(name, &seq)).map_err(py_error)?
+    } else if let Ok(seq) = source.cast::<PyByteArray>() {
+        let seq = seq.to_vec();
+        py.detach(|| scanner.scan_seq(name, &seq)).map_err(py_error)?
+    } else {
+        let path: std::path::PathBuf = source.extract().map_err(|_| PyTypeError::new_err("source must be a path or bytes"))?;
+        py.detach(|| scanner.scan_file(&path)).map_err(py_error)?
+    };
+
+    if !columns {
+        let hits: Vec<PyHit> = hits.into_iter().map(PyHit::from).collect();
+        return Ok(hits.into_pyobject(py)?.into_any().unbind());
+    }
+    let table = PyDict::new(py);
+    table.set_item("chrom", hits.iter().map(|hit| hit.chrom.as_str()).collect::<Vec<_>>())?;
+    table.set_item("start", hits.iter().map(|hit| hit.start).collect::<Vec<_>>())?;
+    table.set_item("end", hits.iter().map(|hit| hit.end).collect::<Vec<_>>())?;
+    table.set_item("target", hits.iter().map(|hit| hit.target.as_str()).collect::<Vec<_>>())?;
+    table.set_item("score", hits.iter().map(|hit| hit.score).collect::<Vec<_>>())?;
+    table.set_item("strand", hits.iter().map(|hit| hit.strand.symbol()).collect::<Vec<_>>())?;
+    Ok(table.into_any().unbind())
+}
+
+#[pymodule]
+#[pyo3(name = "polyscan")]
+fn polyscan_py(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyHit>()?;
+    module.add_function(wrap_pyfunction!(scan, module)?)?;
+    module.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    Ok(())
+}
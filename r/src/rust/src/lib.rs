@@ -0,0 +1,40 @@
+//! R bindings for polyscan, built into the `polyscan` R package by
+//! `src/Makevars`. The R side (`R/polyscan.R`) turns the columns returned
+//! here into a GRanges-ready data.frame.
+
+use extendr_api::prelude::*;
+use extendr_api::Result;
+
+use polyscan::scan::BaseClass;
+use polyscan::{ScanParams, Scanner};
+
+/// Scan `fasta` for windows where `base` makes up at least `pct` percent of
+/// `window` bases, as a list of equal-length columns (seqnames, start, end,
+/// strand, target, score) in 0-based half-open coordinates.
+#[extendr]
+fn scan_columns(fasta: &str, base: &str, window: i32, pct: f64) -> Result<List> {
+    let window = usize::try_from(window).map_err(|_| Error::Other(format!("window must be positive, got {}", window)))?;
+    let params = ScanParams {
+        targets: base.split(',').map(|target| BaseClass::new(target.trim().as_bytes())).collect(),
+        window,
+        percentage: pct,
+        ..ScanParams::default()
+    };
+    // The kind of failure leads the message, so R code can tell a bad
+    // argument from a bad file with conditionMessage()
+    let hits = Scanner::new(params).and_then(|scanner| scanner.scan_file(fasta)).map_err(|e| Error::Other(format!("{} error: {}", e.kind(), e)))?;
+
+    Ok(list!(
+        seqnames = hits.iter().map(|hit| hit.chrom.as_str()).collect_robj(),
+        start = hits.iter().map(|hit| hit.start as f64).collect_robj(),
+        end = hits.iter().map(|hit| hit.end as f64).collect_robj(),
+        strand = hits.iter().map(|hit| hit.strand.symbol()).collect_robj(),
+        target = hits.iter().map(|hit| hit.target.as_str()).collect_robj(),
+        score = hits.iter().map(|hit| hit.score).collect_robj()
+    ))
+}
+
+extendr_module! {
+    mod polyscan;
+    fn scan_columns;
+}
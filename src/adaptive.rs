@@ -0,0 +1,146 @@
+use std::error::Error;
+
+use crate::scan::{scan_windows, BaseClass, BaseFraction, ScanHit, Steps, Strand, WindowScorer};
+
+/// Grow `[start, end)` one base at a time (trying the right side first) for as
+/// long as the `target` letters still make up at least `percentage`% of the interval.
+///
+/// Non-target bases are trimmed off both ends before and after extending, so
+/// impure seed edges don't use up the extension budget and the reported
+/// boundaries fall on target bases.
+pub fn extend_seed(seq: &[u8], start: usize, end: usize, target: &BaseClass, percentage: f64) -> (usize, usize) {
+    let is_target = |nuc: u8| target.contains(nuc);
+    let passes = |count: usize, len: usize| (count as f64 / len as f64) * 100.0 >= percentage;
+
+    let (mut start, mut end) = trim(seq, start, end, &is_target);
+    if start == end {
+        return (start, end);
+    }
+    let mut count = seq[start..end].iter().filter(|&&nuc| is_target(nuc)).count();
+
+    loop {
+        if end < seq.len() {
+            let next = count + is_target(seq[end]) as usize;
+            if passes(next, end - start + 1) {
+                count = next;
+                end += 1;
+                continue;
+            }
+        }
+        if start > 0 {
+            let next = count + is_target(seq[start - 1]) as usize;
+            if passes(next, end - start + 1) {
+                count = next;
+                start -= 1;
+                continue;
+            }
+        }
+        break;
+    }
+
+    trim(seq, start, end, &is_target)
+}
+
+fn trim(seq: &[u8], mut start: usize, mut end: usize, is_target: &impl Fn(u8) -> bool) -> (usize, usize) {
+    while start < end && !is_target(seq[start]) {
+        start += 1;
+    }
+    while end > start && !is_target(seq[end - 1]) {
+        end -= 1;
+    }
+    (start, end)
+}
+
+/// Find maximal intervals where the `target` letters make up at least `percentage`% of
+/// the bases, seeded from passing `window`-sized windows.
+///
+/// Each seed is extended with [`extend_seed`]; seeds starting inside an
+/// interval already reported are skipped, so each tract is reported once.
+/// Hits are labelled with `strand` and scored by the interval's purity.
+pub fn adaptive_scan<F>(
+    seq: &[u8],
+    window: usize,
+    percentage: f64,
+    target: &BaseClass,
+    strand: Strand,
+    mut emit: F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(ScanHit) -> Result<(), Box<dyn Error>>,
+{
+    let seed_scorer = BaseFraction::for_class(target, percentage, window);
+    let scorers: [(&dyn WindowScorer, Strand); 1] = [(&seed_scorer, strand)];
+
+    let mut covered_until = 0;
+    scan_windows(seq, window, Steps::EVERY_BASE, &scorers, |seed| {
+        if seed.start < covered_until {
+            return Ok(());
+        }
+
+        let (start, end) = extend_seed(seq, seed.start, seed.end, target, percentage);
+        covered_until = end.max(seed.start + 1);
+        if start == end {
+            return Ok(());
+        }
+
+        let count = seq[start..end].iter().filter(|&&nuc| target.contains(nuc)).count();
+        let score = (count as f64 / (end - start) as f64) * 100.0;
+        emit(ScanHit { start, end, score, strand, scorer: 0 })
+    })
+}
+
+/// Find regions made of consecutive `window`-sized windows where the `target`
+/// letters make up at least `extend_percentage`% of the bases, and at least
+/// one of which reaches the stricter `seed_percentage`% (hysteresis
+/// thresholding).
+///
+/// Each region is the union of its windows and is reported once, so a long,
+/// slightly impure tract is one record rather than many abutting ones. Hits
+/// are labelled with `strand` and scored by the region's purity.
+pub fn hysteresis_scan<F>(
+    seq: &[u8],
+    window: usize,
+    seed_percentage: f64,
+    extend_percentage: f64,
+    target: &BaseClass,
+    strand: Strand,
+    mut emit: F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(ScanHit) -> Result<(), Box<dyn Error>>,
+{
+    let extend_scorer = BaseFraction::for_class(target, extend_percentage, window);
+    let seed_scorer = BaseFraction::for_class(target, seed_percentage, window);
+    // A window passing the seed scorer is reported right after its extend hit
+    let scorers: [(&dyn WindowScorer, Strand); 2] = [(&extend_scorer, strand), (&seed_scorer, strand)];
+
+    let mut close = |(start, end, seeded): (usize, usize, bool)| {
+        if !seeded {
+            return Ok(());
+        }
+        let count = seq[start..end].iter().filter(|&&nuc| target.contains(nuc)).count();
+        let score = (count as f64 / (end - start) as f64) * 100.0;
+        emit(ScanHit { start, end, score, strand, scorer: 0 })
+    };
+
+    // Current region as (start, end, seeded)
+    let mut region: Option<(usize, usize, bool)> = None;
+    scan_windows(seq, window, Steps::EVERY_BASE, &scorers, |hit| {
+        match region.as_mut() {
+            Some(current) if hit.scorer == 1 => current.2 = true,
+            // The next window along still passes
+            Some(current) if hit.start + window == current.1 + 1 => current.1 = hit.end,
+            _ => {
+                if let Some(previous) = region.take() {
+                    close(previous)?;
+                }
+                region = Some((hit.start, hit.end, false));
+            }
+        }
+        Ok(())
+    })?;
+    if let Some(last) = region {
+        close(last)?;
+    }
+    Ok(())
+}
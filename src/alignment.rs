@@ -0,0 +1,173 @@
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::thread::{self, JoinHandle};
+
+use noodles::sam::alignment::record::cigar::op::Kind;
+use noodles::sam::alignment::RecordBuf;
+use noodles::{bam, cram, fasta, sam};
+
+use crate::input::{Records, SeqRecord};
+
+// Records buffered between the decoding thread and the scanner.
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// Where a read sequence aligns on the reference.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alignment {
+    /// Reference sequence name.
+    pub reference: String,
+    /// 0-based reference position of the first aligned base.
+    pub start: usize,
+    /// CIGAR as (operation, length) pairs, using SAM operation characters.
+    pub cigar: Vec<(char, usize)>,
+}
+
+impl Alignment {
+    /// Project the read interval `[start, end)` onto the reference, spanning
+    /// from the first to the last aligned base inside it. Returns `None` if
+    /// no base in the interval is aligned (e.g. it lies in a soft clip).
+    pub fn project(&self, start: usize, end: usize) -> Option<(usize, usize)> {
+        let (mut query, mut reference) = (0, self.start);
+        let mut first = None;
+        let mut last = None;
+
+        for &(op, len) in &self.cigar {
+            match op {
+                'M' | '=' | 'X' => {
+                    // Aligned block: query [query, query + len) <-> reference [reference, ...)
+                    let lo = start.max(query);
+                    let hi = end.min(query + len);
+                    if lo < hi {
+                        first.get_or_insert(reference + (lo - query));
+                        last = Some(reference + (hi - query));
+                    }
+                    query += len;
+                    reference += len;
+                }
+                'I' | 'S' => query += len,
+                'D' | 'N' => reference += len,
+                _ => (),
+            }
+        }
+
+        Some((first?, last?))
+    }
+}
+
+fn op_char(kind: Kind) -> char {
+    match kind {
+        Kind::Match => 'M',
+        Kind::Insertion => 'I',
+        Kind::Deletion => 'D',
+        Kind::Skip => 'N',
+        Kind::SoftClip => 'S',
+        Kind::HardClip => 'H',
+        Kind::Pad => 'P',
+        Kind::SequenceMatch => '=',
+        Kind::SequenceMismatch => 'X',
+    }
+}
+
+fn to_seq_record(header: &sam::Header, record: &RecordBuf) -> SeqRecord {
+    let id = record.name().map(|name| name.to_string()).unwrap_or_else(|| "*".to_string());
+    let seq = record.sequence().as_ref().to_vec();
+
+    let alignment = if record.flags().is_unmapped() {
+        None
+    } else {
+        let reference = record
+            .reference_sequence_id()
+            .and_then(|i| header.reference_sequences().get_index(i))
+            .map(|(name, _)| name.to_string());
+        let start = record.alignment_start().map(|pos| usize::from(pos) - 1);
+        reference.zip(start).map(|(reference, start)| Alignment {
+            reference,
+            start,
+            cigar: record.cigar().as_ref().iter().map(|op| (op_char(op.kind()), op.len())).collect(),
+        })
+    };
+
+    SeqRecord { id, contig_len: seq.len(), seq: seq.into(), alignment, ..Default::default() }
+}
+
+/// Records received from the decoding thread. Once the channel is drained the
+/// thread is joined, so a decoder panic becomes an error instead of a
+/// silently truncated stream.
+struct ThreadedRecords {
+    receiver: Receiver<Result<SeqRecord, String>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Iterator for ThreadedRecords {
+    type Item = Result<SeqRecord, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.receiver.recv() {
+            Ok(result) => Some(result.map_err(Into::into)),
+            Err(_) => match self.handle.take()?.join() {
+                Ok(()) => None,
+                Err(_) => Some(Err("alignment decoder failed unexpectedly".into())),
+            },
+        }
+    }
+}
+
+/// Stream primary read sequences from a BAM or CRAM file.
+///
+/// Secondary and supplementary alignments are skipped so each read is scanned
+/// once. CRAM files need the `reference` FASTA (with a `.fai`) to decode.
+/// Records are decoded on a background thread.
+pub fn alignment_records(path: &Path, cram: bool, reference: Option<&Path>) -> Result<Records, Box<dyn Error>> {
+    let (sender, receiver) = sync_channel::<Result<SeqRecord, String>>(CHANNEL_CAPACITY);
+
+    // Open eagerly so errors like a missing file surface before scanning
+    let file = File::open(path)?;
+    let repository = match reference {
+        Some(reference) => {
+            let reader = fasta::io::indexed_reader::Builder::default().build_from_path(reference)?;
+            fasta::Repository::new(fasta::repository::adapters::IndexedReader::new(reader))
+        }
+        None if cram => return Err("CRAM input requires --reference".into()),
+        None => fasta::Repository::default(),
+    };
+
+    let handle = thread::spawn(move || {
+        let send_all = |records: &mut dyn Iterator<Item = std::io::Result<RecordBuf>>, header: &sam::Header| {
+            for result in records {
+                let message = match result {
+                    Ok(record) if record.flags().is_secondary() || record.flags().is_supplementary() => continue,
+                    Ok(record) => Ok(to_seq_record(header, &record)),
+                    Err(e) => Err(e.to_string()),
+                };
+                if sender.send(message).is_err() {
+                    // The scanner stopped early
+                    return;
+                }
+            }
+        };
+
+        if cram {
+            let mut reader = cram::io::reader::Builder::default()
+                .set_reference_sequence_repository(repository)
+                .build_from_reader(file);
+            match reader.read_header() {
+                Ok(header) => send_all(&mut reader.records(&header), &header),
+                Err(e) => {
+                    let _ = sender.send(Err(e.to_string()));
+                }
+            }
+        } else {
+            let mut reader = bam::io::Reader::new(file);
+            match reader.read_header() {
+                Ok(header) => send_all(&mut reader.record_bufs(&header), &header),
+                Err(e) => {
+                    let _ = sender.send(Err(e.to_string()));
+                }
+            }
+        }
+    });
+
+    Ok(Box::new(ThreadedRecords { receiver, handle: Some(handle) }))
+}
@@ -0,0 +1,248 @@
+//! Background base composition of a contig, and z-scores and binomial
+//! p-values of window fractions against it, for `--score-mode zscore` and
+//! `--pvalue`.
+
+use crate::scan::BaseClass;
+
+/// Bases around a window taken as its local background.
+pub const LOCAL_SPAN: usize = 1_000_000;
+
+// The local background is counted in whole blocks of this many bases
+const BLOCK: usize = 1000;
+
+/// How often the bases of a class occur in a sequence, as a whole or around
+/// a position.
+///
+/// N bases are left out of the counts (unless the class has N in it), so
+/// gaps in an assembly don't lower the background.
+#[derive(Debug, Clone)]
+pub struct Background {
+    // Target and counted bases before each block
+    cumulative: Vec<(u64, u64)>,
+}
+
+impl Background {
+    pub fn new(seq: &[u8], class: &BaseClass) -> Self {
+        let mut cumulative = Vec::with_capacity(seq.len() / BLOCK + 2);
+        let (mut targets, mut counted) = (0, 0);
+        cumulative.push((targets, counted));
+        for block in seq.chunks(BLOCK) {
+            for &nuc in block {
+                if class.contains(nuc) {
+                    targets += 1;
+                    counted += 1;
+                } else if !nuc.eq_ignore_ascii_case(&b'N') {
+                    counted += 1;
+                }
+            }
+            cumulative.push((targets, counted));
+        }
+        Background { cumulative }
+    }
+
+    /// The fraction of the whole sequence's bases that are in the class.
+    pub fn contig(&self) -> f64 {
+        self.between(0, self.cumulative.len() - 1)
+    }
+
+    /// The fraction of the bases in the class within [`LOCAL_SPAN`] bases
+    /// centred on `[start, end)` (fewer near the ends of the sequence), to
+    /// the nearest whole block.
+    pub fn local(&self, start: usize, end: usize) -> f64 {
+        let centre = (start + end) / 2;
+        let blocks = self.cumulative.len() - 1;
+        let from = centre.saturating_sub(LOCAL_SPAN / 2) / BLOCK;
+        let to = (centre + LOCAL_SPAN / 2).div_ceil(BLOCK).min(blocks);
+        self.between(from.min(to), to)
+    }
+
+    fn between(&self, from: usize, to: usize) -> f64 {
+        let (targets, counted) = (self.cumulative[to].0 - self.cumulative[from].0, self.cumulative[to].1 - self.cumulative[from].1);
+        if counted == 0 {
+            0.0
+        } else {
+            targets as f64 / counted as f64
+        }
+    }
+}
+
+/// How many standard deviations a window of `len` bases with `percentage`%
+/// of target bases lies above what a background frequency of `background`
+/// (0 to 1) would give, under a binomial model.
+///
+/// A background of 0 or 1 has no spread; windows then score 0.
+///
+/// ```
+/// # use polyscan::background::z_score;
+/// // 8 of 10 bases against a background of 30%
+/// assert!((z_score(80.0, 10, 0.3) - 3.45).abs() < 0.01);
+/// ```
+pub fn z_score(percentage: f64, len: usize, background: f64) -> f64 {
+    let variance = background * (1.0 - background) / len as f64;
+    if variance <= 0.0 {
+        return 0.0;
+    }
+    (percentage / 100.0 - background) / variance.sqrt()
+}
+
+/// The base-10 logarithm of the probability of `successes` or more target
+/// bases in `trials` bases, each a target base with probability `p`: the
+/// upper tail of the binomial distribution.
+///
+/// Computed in log space, so tiny probabilities (long, pure tracts) don't
+/// underflow to 0.
+///
+/// ```
+/// # use polyscan::background::log10_binomial_tail;
+/// // 8 or more of 10 bases against a background of 30%: p = 0.00159
+/// assert!((log10_binomial_tail(8, 10, 0.3) + 2.799).abs() < 0.001);
+/// ```
+pub fn log10_binomial_tail(successes: usize, trials: usize, p: f64) -> f64 {
+    if successes == 0 || p >= 1.0 {
+        return 0.0;
+    }
+    if successes > trials || p <= 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    let (ln_p, ln_q) = (p.ln(), (1.0 - p).ln());
+    // Each term is the last times (n - i) / (i + 1) * p / q, starting from C(n, k) p^k q^(n-k)
+    let ln_choose: f64 = (1..=successes).map(|j| ((trials - successes + j) as f64 / j as f64).ln()).sum();
+    let mut term = ln_choose + successes as f64 * ln_p + (trials - successes) as f64 * ln_q;
+    let mut terms = Vec::with_capacity(trials - successes + 1);
+    terms.push(term);
+    for i in successes..trials {
+        term += ((trials - i) as f64 / (i + 1) as f64).ln() + ln_p - ln_q;
+        terms.push(term);
+    }
+    let largest = terms.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let sum: f64 = terms.iter().map(|term| (term - largest).exp()).sum();
+    ((largest + sum.ln()) / std::f64::consts::LN_10).min(0.0)
+}
+
+/// The largest p-value (as log10) significant at false discovery rate `rate`
+/// by the Benjamini-Hochberg procedure, over `tests` tests of which
+/// `log10_p` are the p-values of the smallest (the rest are taken to be
+/// higher): the highest rank i whose p-value is at most `rate * i / tests`.
+/// Every p-value up to it, ties included, is significant; `None` if none is.
+///
+/// ```
+/// # use polyscan::background::fdr_cutoff;
+/// let log10_p: Vec<f64> = [0.01, 0.04, 0.03, 0.005].iter().map(|p: &f64| p.log10()).collect();
+/// // Step-up: 0.04 is at most 0.05 * 4/4, so all four are significant
+/// assert_eq!(fdr_cutoff(&log10_p, 4, 0.05), Some(0.04f64.log10()));
+/// ```
+pub fn fdr_cutoff(log10_p: &[f64], tests: u64, rate: f64) -> Option<f64> {
+    let tests = tests.max(log10_p.len() as u64);
+    let mut sorted = log10_p.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let (log10_rate, log10_tests) = (rate.log10(), (tests as f64).log10());
+    sorted
+        .iter()
+        .enumerate()
+        .rev()
+        .find(|&(i, &log10_p)| log10_p <= log10_rate + ((i + 1) as f64).log10() - log10_tests)
+        .map(|(_, &log10_p)| log10_p)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The upper binomial tail summed directly, for small n
+    fn tail(successes: u64, trials: u64, p: f64) -> f64 {
+        let choose = |n: u64, k: u64| (1..=k).fold(1.0, |c, i| c * (n - k + i) as f64 / i as f64);
+        (successes..=trials).map(|k| choose(trials, k) * p.powi(k as i32) * (1.0 - p).powi((trials - k) as i32)).sum()
+    }
+
+    #[test]
+    fn binomial_tail_matches_known_values() {
+        // P(X >= 8), X ~ B(10, 0.3) = 0.0015903864
+        assert!((10f64.powf(log10_binomial_tail(8, 10, 0.3)) - 0.0015903864).abs() < 1e-10);
+        // P(X >= 3), X ~ B(5, 0.5) = 16/32
+        assert!((log10_binomial_tail(3, 5, 0.5) - 0.5f64.log10()).abs() < 1e-12);
+        // All heads: 2^-10
+        assert!((log10_binomial_tail(10, 10, 0.5) + 10.0 * 2f64.log10()).abs() < 1e-12);
+        for (successes, trials, p) in [(1, 20, 0.05), (5, 20, 0.25), (12, 30, 0.4), (29, 30, 0.9)] {
+            let expected = tail(successes, trials, p).log10();
+            assert!((log10_binomial_tail(successes as usize, trials as usize, p) - expected).abs() < 1e-9, "{} of {} at {}", successes, trials, p);
+        }
+    }
+
+    #[test]
+    fn binomial_tail_edges() {
+        assert_eq!(log10_binomial_tail(0, 10, 0.3), 0.0);
+        assert_eq!(log10_binomial_tail(4, 10, 1.0), 0.0);
+        assert_eq!(log10_binomial_tail(11, 10, 0.3), f64::NEG_INFINITY);
+        assert_eq!(log10_binomial_tail(1, 10, 0.0), f64::NEG_INFINITY);
+        // Far below f64's smallest normal, still finite in log space
+        assert!((log10_binomial_tail(1000, 1000, 0.25) - 1000.0 * 0.25f64.log10()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn z_score_is_standardized_fraction() {
+        // (0.8 - 0.3) / sqrt(0.3 * 0.7 / 10)
+        assert!((z_score(80.0, 10, 0.3) - 3.450327796711771).abs() < 1e-12);
+        assert!(z_score(20.0, 10, 0.3) < 0.0);
+        assert_eq!(z_score(80.0, 10, 0.0), 0.0);
+        assert_eq!(z_score(80.0, 10, 1.0), 0.0);
+    }
+
+    // How many of `p` are significant at `rate` over `tests` tests
+    fn significant(p: &[f64], tests: u64, rate: f64) -> usize {
+        let log10_p: Vec<f64> = p.iter().map(|p| p.log10()).collect();
+        fdr_cutoff(&log10_p, tests, rate).map_or(0, |cutoff| log10_p.iter().filter(|&&p| p <= cutoff).count())
+    }
+
+    #[test]
+    fn benjamini_hochberg_by_hand() {
+        // Sorted, against 0.05 * i / 8: 0.001 <= 0.00625 and 0.008 <= 0.0125, then none
+        let p = [0.041, 0.001, 0.039, 0.008, 0.042, 0.06, 0.074, 0.205];
+        assert_eq!(significant(&p, 8, 0.05), 2);
+        // Step-up: a p-value above its own rank's threshold passes with a later rank's
+        assert_eq!(significant(&[0.01, 0.04, 0.03, 0.005], 4, 0.05), 4);
+        assert_eq!(significant(&[0.01, 0.04, 0.03, 0.005], 4, 0.025), 2);
+        // Windows tested but not held count toward the tests
+        assert_eq!(significant(&[0.001, 0.01], 100, 0.05), 0);
+        assert_eq!(significant(&[0.0004, 0.01], 100, 0.05), 1);
+        assert_eq!(significant(&[], 10, 0.05), 0);
+    }
+
+    #[test]
+    fn ties_are_significant_together() {
+        // 0.02 fails rank 1 (0.0125) but passes rank 3 (0.0375), taking all three
+        assert_eq!(significant(&[0.02, 0.5, 0.02, 0.02], 4, 0.05), 3);
+        // Tied at a rank that fails and none later passes
+        assert_eq!(significant(&[0.04, 0.04, 0.5, 0.9], 4, 0.05), 0);
+    }
+
+    #[test]
+    fn significance_is_monotone() {
+        // xorshift p-values
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 11) as f64 / (1u64 << 53) as f64
+        };
+        for _ in 0..200 {
+            let p: Vec<f64> = (0..20).map(|_| next().powi(3).max(1e-12)).collect();
+            let log10_p: Vec<f64> = p.iter().map(|p| p.log10()).collect();
+            // The significant records are those at or below the cutoff, and
+            // each passes its own rank or is carried by a higher one
+            if let Some(cutoff) = fdr_cutoff(&log10_p, 40, 0.1) {
+                let mut sorted = p.clone();
+                sorted.sort_by(f64::total_cmp);
+                let count = sorted.iter().filter(|&&p| p.log10() <= cutoff).count();
+                assert!(sorted[count - 1] <= 0.1 * count as f64 / 40.0 * (1.0 + 1e-9));
+            }
+            // A looser rate or fewer tests never makes fewer records significant
+            assert!(significant(&p, 40, 0.05) <= significant(&p, 40, 0.1));
+            assert!(significant(&p, 40, 0.1) <= significant(&p, 20, 0.1));
+            // Lowering a p-value never makes fewer records significant
+            let mut lower = p.clone();
+            lower[0] /= 10.0;
+            assert!(significant(&p, 40, 0.1) <= significant(&lower, 40, 0.1));
+        }
+    }
+}
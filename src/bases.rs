@@ -0,0 +1,186 @@
+//! Byte-indexed lookup tables classifying sequence characters, built once at
+//! compile time and shared by every scanning mode, so inner loops index a
+//! table instead of matching (and case-folding) each byte.
+
+/// Table entry for bytes outside a table's alphabet.
+const NONE: u8 = u8::MAX;
+
+// Slot of each letter (either case) in the A..Z count array
+const LETTERS: [u8; 256] = {
+    let mut table = [NONE; 256];
+    let mut i = 0;
+    while i < 26 {
+        table[b'A' as usize + i] = i as u8;
+        table[b'a' as usize + i] = i as u8;
+        i += 1;
+    }
+    table
+};
+
+// 2-bit code of each unambiguous nucleotide (A=0, C=1, G=2, T/U=3), either case
+const NUC_CODES: [u8; 256] = {
+    let mut table = [NONE; 256];
+    let bases = [(b'A', 0), (b'C', 1), (b'G', 2), (b'T', 3), (b'U', 3)];
+    let mut i = 0;
+    while i < bases.len() {
+        let (base, code) = bases[i];
+        table[base as usize] = code;
+        table[base.to_ascii_lowercase() as usize] = code;
+        i += 1;
+    }
+    table
+};
+
+// Bitmask of the bases each IUPAC code (either case) stands for
+const IUPAC_MASKS: [u8; 256] = {
+    let mut table = [0; 256];
+    let codes = [
+        (b'A', 0b0001),
+        (b'C', 0b0010),
+        (b'G', 0b0100),
+        (b'T', 0b1000),
+        (b'U', 0b1000),
+        (b'R', 0b0101),
+        (b'Y', 0b1010),
+        (b'S', 0b0110),
+        (b'W', 0b1001),
+        (b'K', 0b1100),
+        (b'M', 0b0011),
+        (b'B', 0b1110),
+        (b'D', 0b1101),
+        (b'H', 0b1011),
+        (b'V', 0b0111),
+        (b'N', 0b1111),
+    ];
+    let mut i = 0;
+    while i < codes.len() {
+        let (code, mask) = codes[i];
+        table[code as usize] = mask;
+        table[code.to_ascii_lowercase() as usize] = mask;
+        i += 1;
+    }
+    table
+};
+
+// Non-zero for every byte but uppercase A, C, G and T
+const NOT_PLAIN: [u8; 256] = {
+    let mut table = [1; 256];
+    table[b'A' as usize] = 0;
+    table[b'C' as usize] = 0;
+    table[b'G' as usize] = 0;
+    table[b'T' as usize] = 0;
+    table
+};
+
+// Non-zero for every byte but A, C, G, T, U and N, either case
+const UNEXPECTED: [u8; 256] = {
+    let mut table = [1; 256];
+    let bases = *b"ACGTUN";
+    let mut i = 0;
+    while i < bases.len() {
+        table[bases[i] as usize] = 0;
+        table[bases[i].to_ascii_lowercase() as usize] = 0;
+        i += 1;
+    }
+    table
+};
+
+/// Window starts taken at a time when looking for stretches of uppercase
+/// A/C/G/T (see [`is_plain_acgt`]).
+pub const PLAIN_CHUNK: usize = 4096;
+
+/// Slot of a letter (either case) in the A..Z count array.
+#[inline]
+pub fn letter_index(c: u8) -> Option<usize> {
+    let index = LETTERS[c as usize];
+    (index != NONE).then_some(index as usize)
+}
+
+/// 2-bit code of an unambiguous nucleotide, either case: A=0, C=1, G=2 and
+/// T or U=3. `None` for N, other IUPAC codes and anything else.
+#[inline]
+pub fn nuc_code(nuc: u8) -> Option<usize> {
+    let code = NUC_CODES[nuc as usize];
+    (code != NONE).then_some(code as usize)
+}
+
+/// IUPAC nucleotide code (either case) as a bitmask of the bases it stands
+/// for (A=1, C=2, G=4, T/U=8); 0 for letters that are not IUPAC codes.
+#[inline]
+pub fn iupac_mask(code: u8) -> u8 {
+    IUPAC_MASKS[code as usize]
+}
+
+/// Whether `nuc` is a purine (A/G, `Some(true)`) or a pyrimidine (C/T/U,
+/// `Some(false)`); `None` for other characters.
+#[inline]
+pub fn is_purine(nuc: u8) -> Option<bool> {
+    // A and G have even codes
+    nuc_code(nuc).map(|code| code % 2 == 0)
+}
+
+/// Whether `seq` holds only uppercase A, C, G and T, as most polished
+/// references do. Every byte is looked at, without branching on any, so the
+/// check runs at memory speed.
+#[inline]
+pub fn is_plain_acgt(seq: &[u8]) -> bool {
+    seq.iter().fold(0, |other, &c| other | NOT_PLAIN[c as usize]) == 0
+}
+
+/// Whether `seq` holds anything but A, C, G, T, U and N (either case), such
+/// as other IUPAC codes, gaps, `*` or whitespace. Branch-free, as
+/// [`is_plain_acgt`].
+#[inline]
+pub fn has_unexpected(seq: &[u8]) -> bool {
+    seq.iter().fold(0, |other, &c| other | UNEXPECTED[c as usize]) != 0
+}
+
+/// Tally of the characters other than A, C, G, T, U and N in a sequence,
+/// by byte, to tell a corrupted or unusual input from a clean one.
+#[derive(Debug, Clone)]
+pub struct CharacterCounts {
+    counts: [u64; 256],
+}
+
+impl Default for CharacterCounts {
+    fn default() -> Self {
+        CharacterCounts { counts: [0; 256] }
+    }
+}
+
+impl CharacterCounts {
+    /// Count the unexpected characters of `seq`; the common clean sequence
+    /// is only looked over once.
+    pub fn add(&mut self, seq: &[u8]) {
+        if is_plain_acgt(seq) || !has_unexpected(seq) {
+            return;
+        }
+        for &c in seq {
+            self.counts[c as usize] += UNEXPECTED[c as usize] as u64;
+        }
+    }
+
+    /// Number of unexpected characters counted.
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Each unexpected character counted and how many times, most frequent
+    /// first.
+    pub fn by_character(&self) -> Vec<(u8, u64)> {
+        let mut counts: Vec<(u8, u64)> = (0..=255u8).map(|c| (c, self.counts[c as usize])).filter(|&(_, n)| n > 0).collect();
+        counts.sort_by_key(|&(c, n)| (std::cmp::Reverse(n), c));
+        counts
+    }
+
+    /// The counts as e.g. `R:5, '-':4, ' ':2`, with characters that aren't
+    /// letters quoted and control or non-ASCII bytes escaped.
+    pub fn describe(&self) -> String {
+        let describe = |c: u8| match c {
+            c if c.is_ascii_alphabetic() => (c as char).to_string(),
+            c if c.is_ascii() => format!("{:?}", c as char),
+            c => format!("0x{:02x}", c),
+        };
+        self.by_character().into_iter().map(|(c, n)| format!("{}:{}", describe(c), n)).collect::<Vec<_>>().join(", ")
+    }
+}
@@ -0,0 +1,176 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches};
+use log::{error, info};
+
+use polyscan::bed::{merge_regions, read_bed_entries};
+use polyscan::error::PolyscanError;
+use polyscan::faidx::Region;
+
+use crate::{run_scan, Args, Cli, DustOutput, Mode, Report};
+
+/// A sample of a `--manifest`.
+#[derive(Debug, Clone)]
+struct Sample {
+    name: String,
+    input: String,
+    /// Extra command-line options for this sample only
+    options: Vec<String>,
+}
+
+/// Read a manifest: `sample<TAB>input[<TAB>options]` per line, where options
+/// are whitespace-separated command-line options. Blank lines, `#` comments
+/// and a `sample` header line are skipped.
+fn read_manifest(path: &Path) -> Result<Vec<Sample>, Box<dyn Error>> {
+    let mut samples: Vec<Sample> = Vec::new();
+    for (i, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        let line = line.trim_end();
+        if line.trim().is_empty() || line.starts_with('#') || (samples.is_empty() && line.starts_with("sample\t")) {
+            continue;
+        }
+        let invalid = |why: &str| format!("{}: line {}: {}: '{}'", path.display(), i + 1, why, line);
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 2 || fields[1].is_empty() {
+            return Err(invalid("expected a sample name and an input path").into());
+        }
+        let name = fields[0].trim();
+        if name.is_empty() || name.contains('/') || name.contains('\\') {
+            return Err(invalid("sample names must be non-empty and cannot contain slashes").into());
+        }
+        if name == "summary" || samples.iter().any(|sample| sample.name == name) {
+            return Err(invalid("sample names must be unique (and not 'summary')").into());
+        }
+        samples.push(Sample {
+            name: name.to_string(),
+            input: fields[1].to_string(),
+            options: fields.get(2).map(|options| options.split_whitespace().map(String::from).collect()).unwrap_or_default(),
+        });
+    }
+    Ok(samples)
+}
+
+// An option whose output would be shared (and overwritten) by every sample,
+// or that doesn't write BED
+fn unbatchable_option(args: &Args) -> Option<&'static str> {
+    let options = [
+        (args.report_dir.is_some(), "--report-dir"),
+        (args.coverage_summary.is_some(), "--coverage-summary"),
+        (args.composition_matrix.is_some(), "--composition-matrix"),
+        (args.gene_report.is_some(), "--gene-report"),
+        (args.tile, "--tile"),
+        (args.validate, "--validate"),
+        (args.progress, "--progress"),
+        (args.output.is_some(), "--output"),
+        (args.report == Report::Composition, "--report composition"),
+        (args.mode == Mode::Skew, "--mode skew"),
+        (args.mode == Mode::Methylation, "--mode methylation"),
+        (args.mode == Mode::Polya, "--mode polya"),
+        (args.mode == Mode::Dust && args.dust_output != DustOutput::Bed, "--dust-output"),
+    ];
+    options.iter().find(|(set, _)| *set).map(|(_, option)| *option)
+}
+
+/// Scan every sample of `manifest` with `args` (plus the sample's own
+/// options), `--jobs` at a time, writing `<outdir>/<sample>.bed` and a summary
+/// of all samples to `<outdir>/summary.tsv`.
+pub fn run(manifest: &Path, args: Args) -> Result<(), Box<dyn Error>> {
+    let outdir = args.outdir.clone().expect("clap requires --outdir with --manifest");
+    let samples = read_manifest(manifest)?;
+
+    // Each sample's options on top of the shared ones
+    let mut jobs: Vec<(Sample, Args)> = Vec::with_capacity(samples.len());
+    for sample in samples {
+        let mut sample_args = args.clone();
+        let argv = ["polyscan".to_string(), "--fasta".to_string(), sample.input.clone()].into_iter().chain(sample.options.iter().cloned());
+        let updated = Cli::command().try_get_matches_from(argv).and_then(|mut matches| {
+            // Only options given for the sample replace the shared ones, not their defaults
+            let defaulted: Vec<String> =
+                matches.ids().map(|id| id.as_str().to_string()).filter(|id| matches.value_source(id) != Some(ValueSource::CommandLine)).collect();
+            for id in defaulted {
+                let _ = matches.try_clear_id(&id);
+            }
+            sample_args.update_from_arg_matches(&matches)
+        });
+        if let Err(e) = updated {
+            return Err(PolyscanError::parameter(format!("invalid options for sample {}: {}", sample.name, e.to_string().trim_end())));
+        }
+        sample_args.manifest = None;
+        if let Some(option) = unbatchable_option(&sample_args) {
+            return Err(PolyscanError::parameter(format!("{} cannot be combined with --manifest.", option)));
+        }
+        jobs.push((sample, sample_args));
+    }
+    fs::create_dir_all(&outdir)?;
+
+    // Workers take the next sample until none are left
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<Result<(), String>>>> = Mutex::new(vec![None; jobs.len()]);
+    std::thread::scope(|scope| {
+        for _ in 0..(args.jobs as usize).min(jobs.len()) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                let Some((sample, sample_args)) = jobs.get(i) else {
+                    break;
+                };
+                let path = outdir.join(format!("{}.bed", sample.name));
+                let result = File::create(&path)
+                    .map_err(|e| Box::new(e) as Box<dyn Error>)
+                    .and_then(|file| run_scan(sample_args.clone(), BufWriter::with_capacity(sample_args.io_buffer, file), None))
+                    .map_err(|e| e.to_string());
+                if let Err(e) = &result {
+                    error!("sample {} failed: {}", sample.name, e);
+                }
+                results.lock().expect("no worker panics while holding the lock")[i] = Some(result);
+            });
+        }
+    });
+
+    // Summarize every sample from its output
+    let results = results.into_inner().expect("workers are done");
+    let mut summary = BufWriter::new(File::create(outdir.join("summary.tsv"))?);
+    writeln!(summary, "sample\tinput\tstatus\trecords\tcovered_bases\tmean_score\tmax_score")?;
+    let (mut failed, mut empty) = (0, 0);
+    for ((sample, _), result) in jobs.iter().zip(results) {
+        if !matches!(result, Some(Ok(()))) {
+            failed += 1;
+            writeln!(summary, "{}\t{}\tfailed\t.\t.\t.\t.", sample.name, sample.input)?;
+            continue;
+        }
+        let entries = read_bed_entries(outdir.join(format!("{}.bed", sample.name)))?;
+        let regions: Vec<Region> = entries.iter().map(|entry| Region { chrom: entry.chrom.clone(), start: entry.start, end: Some(entry.end) }).collect();
+        // Overlapping records cover their shared bases once
+        let covered: u64 = merge_regions(&regions).iter().map(|region| region.end.unwrap_or(region.start) - region.start).sum();
+        let (mean_score, max_score) = match entries.len() {
+            0 => (".".to_string(), ".".to_string()),
+            n => (
+                format!("{:.2}", entries.iter().map(|entry| entry.score).sum::<f64>() / n as f64),
+                entries.iter().map(|entry| entry.score).fold(f64::NEG_INFINITY, f64::max).to_string(),
+            ),
+        };
+        let status = if entries.is_empty() && args.fail_if_empty {
+            empty += 1;
+            "empty"
+        } else {
+            "ok"
+        };
+        writeln!(summary, "{}\t{}\t{}\t{}\t{}\t{}\t{}", sample.name, sample.input, status, entries.len(), covered, mean_score, max_score)?;
+    }
+    summary.flush()?;
+
+    if failed > 0 {
+        return Err(format!("{} of {} samples failed; see {}.", failed, jobs.len(), outdir.join("summary.tsv").display()).into());
+    }
+    if empty > 0 {
+        let message = format!("{} of {} samples produced no records (--fail-if-empty); see {}.", empty, jobs.len(), outdir.join("summary.tsv").display());
+        return Err(Box::new(PolyscanError::Empty(message)));
+    }
+    info!("Scanned {} samples into {}.", jobs.len(), outdir.display());
+    Ok(())
+}
@@ -0,0 +1,385 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+
+use crate::faidx::Region;
+use crate::scan::Strand;
+use crate::scanner::Hit;
+use crate::sink::OutputSink;
+
+/// Read the intervals of a BED file (optionally compressed) as regions.
+///
+/// Only the first three columns are used. Header, `track` and `browser` lines
+/// are skipped.
+pub fn read_bed<P: AsRef<Path>>(path: P) -> Result<Vec<Region>, Box<dyn Error>> {
+    let path = path.as_ref();
+    let (reader, _compression_format) = niffler::get_reader(Box::new(File::open(path)?))?;
+
+    let mut regions = Vec::new();
+    for (i, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("track") || line.starts_with("browser") {
+            continue;
+        }
+        let invalid = || format!("{}: invalid BED line {}: '{}'", path.display(), i + 1, line);
+        let mut fields = line.split('\t');
+        let chrom = fields.next().ok_or_else(invalid)?;
+        let start: u64 = fields.next().and_then(|v| v.parse().ok()).ok_or_else(invalid)?;
+        let end: u64 = fields.next().and_then(|v| v.parse().ok()).ok_or_else(invalid)?;
+        if end < start {
+            return Err(invalid().into());
+        }
+        regions.push(Region { chrom: chrom.to_string(), start, end: Some(end) });
+    }
+    Ok(regions)
+}
+
+// Bytes formatted before they are written to the underlying writer
+const WRITE_BUFFER: usize = 64 * 1024;
+
+/// Writes BED records column by column into a reused buffer, so writing a
+/// record allocates nothing; a dense scan can write tens of millions of them.
+///
+/// ```ignore
+/// writer.record("chr1", 100, 120).column("A").int_column(95).column("+").finish()?;
+/// ```
+///
+/// Buffered records are written out when the buffer fills, on
+/// [`BedWriter::flush`] and when the writer is dropped.
+pub struct BedWriter<W: Write> {
+    inner: W,
+    buf: Vec<u8>,
+    // Added to the start of each record: 1 for 1-based closed coordinates
+    start_offset: u64,
+    // With JSON Lines, the fields of the columns after chrom, start and end,
+    // and how many of them the current record has
+    #[cfg(feature = "json")]
+    json: Option<(Vec<JsonField>, usize)>,
+}
+
+/// Version of the JSON Lines records, written in each as `schema_version`.
+#[cfg(feature = "json")]
+pub const SCHEMA_VERSION: &str = "1.0";
+
+/// JSON Schema of the JSON Lines records (`schema/hits.schema.json`).
+#[cfg(feature = "json")]
+pub const HITS_SCHEMA: &str = include_str!("../schema/hits.schema.json");
+
+/// A field of a JSON Lines record, written from the BED column in its place.
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonField {
+    Text(&'static str),
+    /// Text, or `null` for a column of `.`
+    OptionalText(&'static str),
+    /// A JSON number (or text if the column isn't one), or `null` for `.`
+    Number(&'static str),
+}
+
+impl<W: Write> BedWriter<W> {
+    pub fn new(inner: W) -> Self {
+        BedWriter {
+            inner,
+            buf: Vec::with_capacity(WRITE_BUFFER),
+            start_offset: 0,
+            #[cfg(feature = "json")]
+            json: None,
+        }
+    }
+
+    /// A writer of the same records as JSON Lines: one object per record,
+    /// starting with `schema_version`, `chrom`, `start` and `end`, then
+    /// `fields` for the columns that follow.
+    #[cfg(feature = "json")]
+    pub fn json_lines(inner: W, fields: Vec<JsonField>) -> Self {
+        BedWriter { inner, buf: Vec::with_capacity(WRITE_BUFFER), start_offset: 0, json: Some((fields, 0)) }
+    }
+
+    /// Write 1-based closed coordinates (as in GFF or VCF) rather than BED's
+    /// 0-based half-open ones: the start of each record is moved up by one,
+    /// and the end stays as it is.
+    pub fn one_based(mut self) -> Self {
+        self.start_offset = 1;
+        self
+    }
+
+    /// Start a record with its chrom, start and end columns.
+    pub fn record(&mut self, chrom: &str, start: u64, end: u64) -> &mut Self {
+        let start = start + self.start_offset;
+        #[cfg(feature = "json")]
+        if let Some((_, column)) = self.json.as_mut() {
+            *column = 0;
+            self.buf.extend_from_slice(b"{\"schema_version\":\"");
+            self.buf.extend_from_slice(SCHEMA_VERSION.as_bytes());
+            self.buf.extend_from_slice(b"\",\"chrom\":");
+            json_string(&mut self.buf, chrom);
+            self.buf.extend_from_slice(b",\"start\":");
+            self.buf.extend_from_slice(itoa::Buffer::new().format(start).as_bytes());
+            self.buf.extend_from_slice(b",\"end\":");
+            self.buf.extend_from_slice(itoa::Buffer::new().format(end).as_bytes());
+            return self;
+        }
+        self.buf.extend_from_slice(chrom.as_bytes());
+        self.int_column(start).int_column(end)
+    }
+
+    /// Add a column to the record.
+    pub fn column(&mut self, value: &str) -> &mut Self {
+        #[cfg(feature = "json")]
+        if let Some(field) = self.json_key() {
+            match field {
+                JsonField::OptionalText(_) | JsonField::Number(_) if value == "." => self.buf.extend_from_slice(b"null"),
+                JsonField::Number(_) if value.parse::<f64>().is_ok_and(f64::is_finite) => self.buf.extend_from_slice(value.as_bytes()),
+                _ => json_string(&mut self.buf, value),
+            }
+            return self;
+        }
+        self.buf.push(b'\t');
+        self.buf.extend_from_slice(value.as_bytes());
+        self
+    }
+
+    /// Add an integer column to the record.
+    pub fn int_column(&mut self, value: u64) -> &mut Self {
+        #[cfg(feature = "json")]
+        let json = self.json_key().is_some();
+        #[cfg(not(feature = "json"))]
+        let json = false;
+        if !json {
+            self.buf.push(b'\t');
+        }
+        self.buf.extend_from_slice(itoa::Buffer::new().format(value).as_bytes());
+        self
+    }
+
+    // With JSON Lines, write the key of the next column and return its field
+    #[cfg(feature = "json")]
+    fn json_key(&mut self) -> Option<JsonField> {
+        let (fields, column) = self.json.as_mut()?;
+        let field = fields.get(*column).copied().unwrap_or(JsonField::Text("extra"));
+        *column += 1;
+        let (JsonField::Text(key) | JsonField::OptionalText(key) | JsonField::Number(key)) = field;
+        self.buf.push(b',');
+        json_string(&mut self.buf, key);
+        self.buf.push(b':');
+        Some(field)
+    }
+
+    /// End the record.
+    pub fn finish(&mut self) -> io::Result<()> {
+        #[cfg(feature = "json")]
+        if self.json.is_some() {
+            self.buf.push(b'}');
+        }
+        self.buf.push(b'\n');
+        if self.buf.len() >= WRITE_BUFFER {
+            self.write_buffer()?;
+        }
+        Ok(())
+    }
+
+    /// Write out the buffered records and flush the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.write_buffer()?;
+        self.inner.flush()
+    }
+
+    fn write_buffer(&mut self) -> io::Result<()> {
+        self.inner.write_all(&self.buf)?;
+        self.buf.clear();
+        Ok(())
+    }
+}
+
+/// Hits as the six BED columns of the command line's output: chrom, start,
+/// end, target, percentage (rounded up) and strand.
+impl<W: Write> OutputSink for BedWriter<W> {
+    fn write_hit(&mut self, hit: &Hit) -> Result<(), Box<dyn Error>> {
+        self.record(&hit.chrom, hit.start as u64, hit.end as u64)
+            .column(&hit.target)
+            .int_column(hit.score.ceil() as u64)
+            .column(hit.strand.symbol())
+            .finish()?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(self.flush()?)
+    }
+}
+
+// `value` as a JSON string
+#[cfg(feature = "json")]
+fn json_string(buf: &mut Vec<u8>, value: &str) {
+    serde_json::to_writer(buf, value).expect("strings always serialize");
+}
+
+impl<W: Write> Drop for BedWriter<W> {
+    fn drop(&mut self) {
+        // As BufWriter does: errors here can't be reported, flush first to see them
+        let _ = self.write_buffer();
+    }
+}
+
+/// A record of a BED file with its name, score and strand columns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BedEntry {
+    pub chrom: String,
+    pub start: u64,
+    pub end: u64,
+    /// Name column, `.` when absent
+    pub name: String,
+    /// Score column, 0 when absent
+    pub score: f64,
+    /// Strand column, unstranded when absent or `.`
+    pub strand: Strand,
+}
+
+/// Read the first six columns of a BED file (optionally compressed).
+///
+/// Header, `track` and `browser` lines are skipped.
+pub fn read_bed_entries<P: AsRef<Path>>(path: P) -> Result<Vec<BedEntry>, Box<dyn Error>> {
+    let path = path.as_ref();
+    // An empty BED (e.g. a scan without hits) is too short for niffler to sniff
+    if std::fs::metadata(path)?.len() == 0 {
+        return Ok(Vec::new());
+    }
+    let (reader, _compression_format) = niffler::get_reader(Box::new(File::open(path)?))?;
+
+    let mut entries = Vec::new();
+    for (i, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("track") || line.starts_with("browser") {
+            continue;
+        }
+        let invalid = || format!("{}: invalid BED line {}: '{}'", path.display(), i + 1, line);
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 3 {
+            return Err(invalid().into());
+        }
+        let start: u64 = fields[1].parse().map_err(|_| invalid())?;
+        let end: u64 = fields[2].parse().map_err(|_| invalid())?;
+        if end < start {
+            return Err(invalid().into());
+        }
+        let score: f64 = match fields.get(4) {
+            Some(score) => score.parse().map_err(|_| invalid())?,
+            None => 0.0,
+        };
+        let strand = match fields.get(5) {
+            Some(&"+") => Strand::Plus,
+            Some(&"-") => Strand::Minus,
+            _ => Strand::Unstranded,
+        };
+        entries.push(BedEntry {
+            chrom: fields[0].to_string(),
+            start,
+            end,
+            name: fields.get(3).unwrap_or(&".").to_string(),
+            score,
+            strand,
+        });
+    }
+    Ok(entries)
+}
+
+/// Sort regions by start within each contig (contigs keep their order of first
+/// appearance) and merge the ones that overlap, so no base is covered twice.
+pub fn merge_regions(regions: &[Region]) -> Vec<Region> {
+    let mut order: Vec<&str> = Vec::new();
+    let mut by_chrom: HashMap<&str, Vec<&Region>> = HashMap::new();
+    for region in regions {
+        by_chrom
+            .entry(&region.chrom)
+            .or_insert_with(|| {
+                order.push(&region.chrom);
+                Vec::new()
+            })
+            .push(region);
+    }
+
+    let mut merged: Vec<Region> = Vec::with_capacity(regions.len());
+    for chrom in order {
+        let mut chrom_regions = by_chrom.remove(chrom).unwrap_or_default();
+        chrom_regions.sort_by_key(|r| r.start);
+        let first = merged.len();
+        for region in chrom_regions {
+            match merged[first..].last_mut() {
+                // An open-ended region already reaches the end of the contig
+                Some(last) if last.end.is_none_or(|end| region.start < end) => {
+                    last.end = last.end.zip(region.end).map(|(a, b)| a.max(b));
+                }
+                _ => merged.push(region.clone()),
+            }
+        }
+    }
+    merged
+}
+
+/// The parts of `0..len` not covered by any of the half-open `covered`
+/// intervals (in any order, possibly overlapping), in order.
+pub fn complement_intervals(mut covered: Vec<(u64, u64)>, len: u64) -> Vec<(u64, u64)> {
+    covered.sort_unstable();
+    let mut uncovered = Vec::new();
+    let mut pos = 0;
+    for (start, end) in covered {
+        if start > pos {
+            uncovered.push((pos, start));
+        }
+        pos = pos.max(end);
+    }
+    if pos < len {
+        uncovered.push((pos, len));
+    }
+    uncovered
+}
+
+/// Intervals indexed per contig for fast overlap queries.
+#[derive(Debug, Clone, Default)]
+pub struct IntervalSet {
+    // Sorted, non-overlapping half-open intervals per contig
+    intervals: HashMap<String, Vec<(u64, u64)>>,
+}
+
+impl IntervalSet {
+    /// Build from regions; open-ended regions extend to the end of the contig.
+    pub fn from_regions(regions: &[Region]) -> Self {
+        let mut intervals: HashMap<String, Vec<(u64, u64)>> = HashMap::new();
+        for region in merge_regions(regions) {
+            intervals.entry(region.chrom).or_default().push((region.start, region.end.unwrap_or(u64::MAX)));
+        }
+        IntervalSet { intervals }
+    }
+
+    /// Whether `[start, end)` on `chrom` overlaps any interval.
+    pub fn overlaps(&self, chrom: &str, start: u64, end: u64) -> bool {
+        let Some(intervals) = self.intervals.get(chrom) else {
+            return false;
+        };
+        // First interval ending after `start`; it overlaps if it begins before `end`
+        let i = intervals.partition_point(|&(_, e)| e <= start);
+        intervals.get(i).is_some_and(|&(s, _)| s < end)
+    }
+
+    /// How many bases of `[start, end)` on `chrom` the intervals cover.
+    pub fn overlap_len(&self, chrom: &str, start: u64, end: u64) -> u64 {
+        let Some(intervals) = self.intervals.get(chrom) else {
+            return 0;
+        };
+        let i = intervals.partition_point(|&(_, e)| e <= start);
+        intervals[i..].iter().take_while(|&&(s, _)| s < end).map(|&(s, e)| e.min(end) - s.max(start)).sum()
+    }
+
+    /// The parts of the intervals on `chrom` within `[start, end)`, in order.
+    pub fn clipped(&self, chrom: &str, start: u64, end: u64) -> Vec<(u64, u64)> {
+        let Some(intervals) = self.intervals.get(chrom) else {
+            return Vec::new();
+        };
+        let i = intervals.partition_point(|&(_, e)| e <= start);
+        intervals[i..].iter().take_while(|&&(s, _)| s < end).map(|&(s, e)| (s.max(start), e.min(end))).collect()
+    }
+}
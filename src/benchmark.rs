@@ -0,0 +1,262 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::bed::{merge_regions, IntervalSet};
+use crate::faidx::Region;
+
+/// Precision and recall of the predictions scoring at least `cutoff`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchmarkPoint {
+    pub cutoff: f64,
+    /// Predicted regions (overlapping predictions merged)
+    pub predicted: usize,
+    /// Predicted regions overlapping a truth interval
+    pub true_positives: usize,
+    /// Truth intervals overlapping a predicted region
+    pub recovered: usize,
+    pub truth: usize,
+}
+
+impl BenchmarkPoint {
+    pub fn precision(&self) -> f64 {
+        if self.predicted == 0 {
+            0.0
+        } else {
+            self.true_positives as f64 / self.predicted as f64
+        }
+    }
+
+    pub fn recall(&self) -> f64 {
+        if self.truth == 0 {
+            0.0
+        } else {
+            self.recovered as f64 / self.truth as f64
+        }
+    }
+
+    pub fn f1(&self) -> f64 {
+        let (precision, recall) = (self.precision(), self.recall());
+        if precision + recall == 0.0 {
+            0.0
+        } else {
+            2.0 * precision * recall / (precision + recall)
+        }
+    }
+}
+
+/// Bases of the predictions scoring at least `cutoff` in and out of the
+/// truth intervals, for a ROC curve.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RocPoint {
+    pub cutoff: f64,
+    /// Bases covered by the predictions (overlapping predictions merged)
+    pub predicted_bases: u64,
+    /// Predicted bases inside a truth interval
+    pub true_positive_bases: u64,
+    pub truth_bases: u64,
+    /// Bases of the genome outside the truth intervals
+    pub negative_bases: u64,
+}
+
+impl RocPoint {
+    pub fn false_positive_bases(&self) -> u64 {
+        self.predicted_bases - self.true_positive_bases
+    }
+
+    /// True positive rate (sensitivity): the fraction of truth bases predicted.
+    pub fn tpr(&self) -> f64 {
+        if self.truth_bases == 0 {
+            0.0
+        } else {
+            self.true_positive_bases as f64 / self.truth_bases as f64
+        }
+    }
+
+    /// False positive rate: the fraction of the bases outside the truth predicted.
+    pub fn fpr(&self) -> f64 {
+        if self.negative_bases == 0 {
+            0.0
+        } else {
+            self.false_positive_bases() as f64 / self.negative_bases as f64
+        }
+    }
+
+    pub fn precision(&self) -> f64 {
+        if self.predicted_bases == 0 {
+            0.0
+        } else {
+            self.true_positive_bases as f64 / self.predicted_bases as f64
+        }
+    }
+}
+
+/// Read the intervals and scores (5th column, 0 when absent) of a BED file
+/// (optionally compressed).
+pub fn read_scored_bed<P: AsRef<Path>>(path: P) -> Result<Vec<(Region, f64)>, Box<dyn Error>> {
+    let path = path.as_ref();
+    let (reader, _compression_format) = niffler::get_reader(Box::new(File::open(path)?))?;
+
+    let mut scored = Vec::new();
+    for (i, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("track") || line.starts_with("browser") {
+            continue;
+        }
+        let invalid = || format!("{}: invalid BED line {}: '{}'", path.display(), i + 1, line);
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 3 {
+            return Err(invalid().into());
+        }
+        let start: u64 = fields[1].parse().map_err(|_| invalid())?;
+        let end: u64 = fields[2].parse().map_err(|_| invalid())?;
+        let score: f64 = match fields.get(4) {
+            Some(score) => score.parse().map_err(|_| invalid())?,
+            None => 0.0,
+        };
+        if end < start {
+            return Err(invalid().into());
+        }
+        scored.push((Region { chrom: fields[0].to_string(), start, end: Some(end) }, score));
+    }
+    Ok(scored)
+}
+
+/// Score predictions against truth intervals, whatever their strand.
+///
+/// Predictions scoring at least `cutoff` are merged where they overlap (so
+/// overlapping windows count once); a predicted region is a true positive if
+/// it overlaps a truth interval, and a truth interval is recovered if a
+/// predicted region overlaps it.
+pub fn benchmark_at(predictions: &[(Region, f64)], truth: &[Region], truth_set: &IntervalSet, cutoff: f64) -> BenchmarkPoint {
+    let kept: Vec<Region> = predictions.iter().filter(|(_, score)| *score >= cutoff).map(|(region, _)| region.clone()).collect();
+    let predicted = merge_regions(&kept);
+    let predicted_set = IntervalSet::from_regions(&predicted);
+    let overlaps = |set: &IntervalSet, region: &Region| set.overlaps(&region.chrom, region.start, region.end.unwrap_or(u64::MAX));
+    BenchmarkPoint {
+        cutoff,
+        predicted: predicted.len(),
+        true_positives: predicted.iter().filter(|region| overlaps(truth_set, region)).count(),
+        recovered: truth.iter().filter(|region| overlaps(&predicted_set, region)).count(),
+        truth: truth.len(),
+    }
+}
+
+/// Benchmark at every distinct prediction score, lowest first: a
+/// precision/recall curve.
+pub fn benchmark_curve(predictions: &[(Region, f64)], truth: &[Region]) -> Vec<BenchmarkPoint> {
+    let truth_set = IntervalSet::from_regions(truth);
+    let mut cutoffs: Vec<f64> = predictions.iter().map(|(_, score)| *score).collect();
+    cutoffs.sort_by(f64::total_cmp);
+    cutoffs.dedup();
+    cutoffs.into_iter().map(|cutoff| benchmark_at(predictions, truth, &truth_set, cutoff)).collect()
+}
+
+/// Total length of the contigs in a `.fai` index or chrom.sizes file (name
+/// and length in the first two columns).
+pub fn read_genome_length<P: AsRef<Path>>(path: P) -> Result<u64, Box<dyn Error>> {
+    let path = path.as_ref();
+    let mut total = 0;
+    for (i, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let length = line.split('\t').nth(1).and_then(|length| length.trim().parse::<u64>().ok());
+        total += length.ok_or_else(|| format!("{}: line {} has no contig length: '{}'", path.display(), i + 1, line))?;
+    }
+    Ok(total)
+}
+
+/// Base-level rates at every distinct prediction score, lowest first: a ROC
+/// curve of the predictions against truth intervals on a genome of
+/// `genome_length` bases. Bases are counted whatever their strand.
+pub fn roc_curve(predictions: &[(Region, f64)], truth: &[Region], genome_length: u64) -> Vec<RocPoint> {
+    let truth_set = IntervalSet::from_regions(truth);
+    let truth_bases: u64 = merge_regions(truth).iter().map(|region| region.end.unwrap_or(region.start) - region.start).sum();
+    let mut cutoffs: Vec<f64> = predictions.iter().map(|(_, score)| *score).collect();
+    cutoffs.sort_by(f64::total_cmp);
+    cutoffs.dedup();
+    cutoffs
+        .into_iter()
+        .map(|cutoff| {
+            let kept: Vec<Region> = predictions.iter().filter(|(_, score)| *score >= cutoff).map(|(region, _)| region.clone()).collect();
+            let predicted = merge_regions(&kept);
+            let span = |region: &Region| (region.start, region.end.unwrap_or(region.start));
+            RocPoint {
+                cutoff,
+                predicted_bases: predicted.iter().map(|region| span(region).1 - span(region).0).sum(),
+                true_positive_bases: predicted.iter().map(|region| truth_set.overlap_len(&region.chrom, span(region).0, span(region).1)).sum(),
+                truth_bases,
+                negative_bases: genome_length.saturating_sub(truth_bases),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(chrom: &str, start: u64, end: u64) -> Region {
+        Region { chrom: chrom.to_string(), start, end: Some(end) }
+    }
+
+    fn truth() -> Vec<Region> {
+        vec![region("c", 0, 10), region("c", 20, 30), region("c", 50, 60), region("d", 0, 10)]
+    }
+
+    fn predictions() -> Vec<(Region, f64)> {
+        vec![
+            // Two overlapping windows over the first truth interval: one true positive
+            (region("c", 5, 15), 50.0),
+            (region("c", 12, 18), 60.0),
+            (region("c", 25, 28), 90.0),
+            (region("c", 55, 56), 10.0),
+            (region("c", 100, 110), 40.0),
+            // Same coordinates as truth, on another contig
+            (region("e", 0, 10), 40.0),
+        ]
+    }
+
+    #[test]
+    fn counts_follow_merged_predictions() {
+        let truth = truth();
+        let truth_set = IntervalSet::from_regions(&truth);
+        // 5 predicted regions, 3 of them on truth; 3 of the 4 truth intervals recovered
+        let all = benchmark_at(&predictions(), &truth, &truth_set, 0.0);
+        assert_eq!((all.predicted, all.true_positives, all.recovered, all.truth), (5, 3, 3, 4));
+        assert_eq!((all.precision(), all.recall()), (0.6, 0.75));
+        assert!((all.f1() - 2.0 / 3.0).abs() < 1e-12);
+        // At 50, [5, 18) and [25, 28) are left, both on truth
+        let strict = benchmark_at(&predictions(), &truth, &truth_set, 50.0);
+        assert_eq!((strict.predicted, strict.true_positives, strict.recovered), (2, 2, 2));
+        assert_eq!((strict.precision(), strict.recall()), (1.0, 0.5));
+        assert!((strict.f1() - 2.0 / 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn no_predictions_score_0_without_dividing_by_0() {
+        let truth = truth();
+        let point = benchmark_at(&[], &truth, &IntervalSet::from_regions(&truth), 0.0);
+        assert_eq!((point.predicted, point.true_positives, point.recovered, point.truth), (0, 0, 0, 4));
+        assert_eq!((point.precision(), point.recall(), point.f1()), (0.0, 0.0, 0.0));
+        // Nor without truth
+        let point = benchmark_at(&predictions(), &[], &IntervalSet::from_regions(&[]), 0.0);
+        assert_eq!((point.precision(), point.recall(), point.f1()), (0.0, 0.0, 0.0));
+        assert!(benchmark_curve(&[], &truth).is_empty());
+        assert!(roc_curve(&[], &truth, 1000).is_empty());
+    }
+
+    #[test]
+    fn curves_have_a_point_per_distinct_score() {
+        let curve = benchmark_curve(&predictions(), &truth());
+        assert_eq!(curve.iter().map(|point| (point.cutoff, point.predicted)).collect::<Vec<_>>(), [(10.0, 5), (40.0, 4), (50.0, 2), (60.0, 2), (90.0, 1)]);
+        let roc = roc_curve(&predictions(), &truth(), 1000);
+        // Everything: 13 + 3 + 1 + 10 + 10 bases, of which 5 + 3 + 1 on 40 truth bases
+        assert_eq!((roc[0].predicted_bases, roc[0].true_positive_bases, roc[0].truth_bases, roc[0].negative_bases), (37, 9, 40, 960));
+        assert_eq!((roc[0].false_positive_bases(), roc[0].tpr(), roc[0].fpr()), (28, 9.0 / 40.0, 28.0 / 960.0));
+    }
+}
@@ -0,0 +1,427 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Seek, Write};
+use std::path::Path;
+
+const BIGWIG_MAGIC: u32 = 0x888F_FC26;
+const CHROM_TREE_MAGIC: u32 = 0x78CA_8C91;
+const CIR_TREE_MAGIC: u32 = 0x2468_ACE0;
+
+// Items per data section and children per index node, as in UCSC's tools.
+const ITEMS_PER_SLOT: usize = 1024;
+const BLOCK_SIZE: usize = 256;
+
+/// One bedGraph interval: (chrom, start, end, value).
+pub type BedGraphRecord = (String, u32, u32, f32);
+
+/// Bounds of a data section (or index node): chrom ids and bases, inclusive start / exclusive end.
+#[derive(Debug, Clone, Copy)]
+struct Bounds {
+    start_chrom: u32,
+    start: u32,
+    end_chrom: u32,
+    end: u32,
+}
+
+impl Bounds {
+    fn union(&self, other: &Bounds) -> Bounds {
+        Bounds {
+            start_chrom: self.start_chrom,
+            start: self.start,
+            end_chrom: other.end_chrom,
+            end: other.end,
+        }
+    }
+
+    fn write<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        for v in [self.start_chrom, self.start, self.end_chrom, self.end] {
+            out.write_all(&v.to_le_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+/// Write a bigWig file (uncompressed data, no zoom levels) from bedGraph intervals.
+///
+/// `chrom_sizes` lists every chromosome that may appear; intervals must not
+/// overlap within a chromosome but may be in any order.
+pub fn write_bigwig<P: AsRef<Path>>(
+    path: P,
+    chrom_sizes: &[(String, u32)],
+    records: &[BedGraphRecord],
+) -> Result<(), Box<dyn Error>> {
+    // Chromosome ids follow name order so the B+ tree keys are sorted
+    let mut chroms: Vec<&(String, u32)> = chrom_sizes.iter().collect();
+    chroms.sort_by(|a, b| a.0.cmp(&b.0));
+    chroms.dedup_by(|a, b| a.0 == b.0);
+    let chrom_id = |name: &str| chroms.binary_search_by(|c| c.0.as_str().cmp(name)).map(|i| i as u32);
+
+    let mut items = Vec::with_capacity(records.len());
+    for (chrom, start, end, value) in records {
+        let id = chrom_id(chrom).map_err(|_| format!("bigWig: unknown chromosome '{}'", chrom))?;
+        items.push((id, *start, *end, *value));
+    }
+    items.sort_by_key(|&(id, start, _, _)| (id, start));
+
+    let mut out = BufWriter::new(File::create(path)?);
+
+    // Header placeholder (64 bytes) + total summary (40 bytes); filled in at the end
+    out.write_all(&[0u8; 104])?;
+    let total_summary_offset = 64u64;
+
+    // Chromosome B+ tree
+    let chrom_tree_offset = out.stream_position()?;
+    let names: Vec<(String, u32)> = chroms.iter().map(|c| (c.0.clone(), c.1)).collect();
+    write_chrom_tree(&mut out, &names)?;
+
+    // Data sections: bedGraph items, never spanning two chromosomes
+    let full_data_offset = out.stream_position()?;
+    let sections: Vec<&[(u32, u32, u32, f32)]> = items
+        .chunk_by(|a, b| a.0 == b.0)
+        .flat_map(|chrom_items| chrom_items.chunks(ITEMS_PER_SLOT))
+        .collect();
+    out.write_all(&(sections.len() as u64).to_le_bytes())?;
+
+    let mut leaves = Vec::with_capacity(sections.len());
+    for section in &sections {
+        let offset = out.stream_position()?;
+        let chrom = section[0].0;
+        let start = section[0].1;
+        let end = section.iter().map(|item| item.2).max().unwrap_or(start);
+        for v in [chrom, start, end, 0, 0] {
+            out.write_all(&v.to_le_bytes())?;
+        }
+        out.write_all(&[1u8, 0u8])?; // type 1 = bedGraph
+        out.write_all(&(section.len() as u16).to_le_bytes())?;
+        for &(_, s, e, value) in section.iter() {
+            out.write_all(&s.to_le_bytes())?;
+            out.write_all(&e.to_le_bytes())?;
+            out.write_all(&value.to_le_bytes())?;
+        }
+        let size = out.stream_position()? - offset;
+        leaves.push((Bounds { start_chrom: chrom, start, end_chrom: chrom, end }, offset, size));
+    }
+
+    // R-tree index over the data sections
+    let full_index_offset = out.stream_position()?;
+    let end_file_offset = full_index_offset;
+    let total = leaves.iter().fold(None, |acc: Option<Bounds>, leaf| {
+        Some(acc.map_or(leaf.0, |b| b.union(&leaf.0)))
+    });
+    let total = total.unwrap_or(Bounds { start_chrom: 0, start: 0, end_chrom: 0, end: 0 });
+    out.write_all(&CIR_TREE_MAGIC.to_le_bytes())?;
+    out.write_all(&(BLOCK_SIZE as u32).to_le_bytes())?;
+    out.write_all(&(leaves.len() as u64).to_le_bytes())?;
+    total.write(&mut out)?;
+    out.write_all(&end_file_offset.to_le_bytes())?;
+    out.write_all(&(ITEMS_PER_SLOT as u32).to_le_bytes())?;
+    out.write_all(&0u32.to_le_bytes())?;
+    write_index_tree(&mut out, &leaves)?;
+
+    // Total summary and header
+    let mut bases = 0u64;
+    let (mut min, mut max, mut sum, mut sum_sq) = (f64::INFINITY, f64::NEG_INFINITY, 0.0, 0.0);
+    for &(_, start, end, value) in &items {
+        let len = (end - start) as f64;
+        let value = value as f64;
+        bases += (end - start) as u64;
+        min = min.min(value);
+        max = max.max(value);
+        sum += value * len;
+        sum_sq += value * value * len;
+    }
+    if items.is_empty() {
+        (min, max) = (0.0, 0.0);
+    }
+
+    out.rewind()?;
+    out.write_all(&BIGWIG_MAGIC.to_le_bytes())?;
+    out.write_all(&4u16.to_le_bytes())?; // version
+    out.write_all(&0u16.to_le_bytes())?; // zoom levels
+    out.write_all(&chrom_tree_offset.to_le_bytes())?;
+    out.write_all(&full_data_offset.to_le_bytes())?;
+    out.write_all(&full_index_offset.to_le_bytes())?;
+    out.write_all(&0u16.to_le_bytes())?; // field count
+    out.write_all(&0u16.to_le_bytes())?; // defined field count
+    out.write_all(&0u64.to_le_bytes())?; // autoSql offset
+    out.write_all(&total_summary_offset.to_le_bytes())?;
+    out.write_all(&0u32.to_le_bytes())?; // uncompressBufSize: data is not compressed
+    out.write_all(&0u64.to_le_bytes())?; // extension offset
+    out.write_all(&bases.to_le_bytes())?;
+    for v in [min, max, sum, sum_sq] {
+        out.write_all(&v.to_le_bytes())?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Write the chromosome B+ tree over `chroms` (sorted by name; ids are their indices).
+fn write_chrom_tree<W: Write + Seek>(out: &mut W, chroms: &[(String, u32)]) -> std::io::Result<()> {
+    let key_size = chroms.iter().map(|c| c.0.len()).max().unwrap_or(1).max(1);
+    let key = |name: &str| {
+        let mut key = name.as_bytes().to_vec();
+        key.resize(key_size, 0);
+        key
+    };
+
+    out.write_all(&CHROM_TREE_MAGIC.to_le_bytes())?;
+    out.write_all(&(BLOCK_SIZE as u32).to_le_bytes())?;
+    out.write_all(&(key_size as u32).to_le_bytes())?;
+    out.write_all(&8u32.to_le_bytes())?;
+    out.write_all(&(chroms.len() as u64).to_le_bytes())?;
+    out.write_all(&0u64.to_le_bytes())?;
+
+    // Leaf and internal items are both key + 8 bytes (id + size, or child offset)
+    let item_size = key_size as u64 + 8;
+
+    // levels[0] = index of the first chrom under each leaf item; higher levels group BLOCK_SIZE children
+    let mut levels: Vec<Vec<usize>> = vec![(0..chroms.len()).collect()];
+    while levels.last().unwrap().len() > BLOCK_SIZE {
+        let parents = levels.last().unwrap().chunks(BLOCK_SIZE).map(|group| group[0]).collect();
+        levels.push(parents);
+    }
+    let level_size = |i: usize| -> u64 {
+        let items = levels[i].len() as u64;
+        items.div_ceil(BLOCK_SIZE as u64).max(1) * 4 + items * item_size
+    };
+
+    let mut level_offset = out.stream_position()?;
+    for i in (0..levels.len()).rev() {
+        let child_level_offset = level_offset + level_size(i);
+        let items = &levels[i];
+        let groups: Vec<&[usize]> = if items.is_empty() { vec![&[]] } else { items.chunks(BLOCK_SIZE).collect() };
+        let mut item_index = 0u64;
+        for group in groups {
+            out.write_all(&[(i == 0) as u8, 0u8])?;
+            out.write_all(&(group.len() as u16).to_le_bytes())?;
+            for &chrom in group {
+                out.write_all(&key(&chroms[chrom].0))?;
+                if i == 0 {
+                    out.write_all(&(chrom as u32).to_le_bytes())?;
+                    out.write_all(&chroms[chrom].1.to_le_bytes())?;
+                } else {
+                    // Child nodes below are all full except possibly the last
+                    let child_offset = child_level_offset + item_index * (4 + BLOCK_SIZE as u64 * item_size);
+                    out.write_all(&child_offset.to_le_bytes())?;
+                }
+                item_index += 1;
+            }
+        }
+        level_offset = child_level_offset;
+    }
+    Ok(())
+}
+
+/// Write the R-tree nodes, root first, above `leaves` = (bounds, data offset, data size).
+fn write_index_tree<W: Write + Seek>(out: &mut W, leaves: &[(Bounds, u64, u64)]) -> std::io::Result<()> {
+    const LEAF_ITEM: u64 = 32;
+    const NODE_ITEM: u64 = 24;
+    const NODE_HEADER: u64 = 4;
+
+    // levels[0] = bounds of each leaf item; each higher level groups BLOCK_SIZE children
+    let mut levels: Vec<Vec<Bounds>> = vec![leaves.iter().map(|l| l.0).collect()];
+    while levels.last().unwrap().len() > BLOCK_SIZE {
+        let parents = levels
+            .last()
+            .unwrap()
+            .chunks(BLOCK_SIZE)
+            .map(|group| group[0].union(group.last().unwrap()))
+            .collect();
+        levels.push(parents);
+    }
+
+    // Size in bytes of all nodes holding the items of level `i`
+    let level_size = |i: usize| -> u64 {
+        let items = levels[i].len() as u64;
+        let nodes = items.div_ceil(BLOCK_SIZE as u64).max(1);
+        let item_size = if i == 0 { LEAF_ITEM } else { NODE_ITEM };
+        nodes * NODE_HEADER + items * item_size
+    };
+
+    let mut level_offset = out.stream_position()?;
+    for i in (0..levels.len()).rev() {
+        let child_level_offset = level_offset + level_size(i);
+        let is_leaf = i == 0;
+
+        let items = &levels[i];
+        let groups: Vec<&[Bounds]> = if items.is_empty() { vec![&[]] } else { items.chunks(BLOCK_SIZE).collect() };
+        let mut item_index = 0;
+        for group in groups {
+            out.write_all(&[is_leaf as u8, 0u8])?;
+            out.write_all(&(group.len() as u16).to_le_bytes())?;
+            for bounds in group {
+                bounds.write(out)?;
+                if is_leaf {
+                    let (_, offset, size) = leaves[item_index];
+                    out.write_all(&offset.to_le_bytes())?;
+                    out.write_all(&size.to_le_bytes())?;
+                } else {
+                    // Child node `item_index` of the level below
+                    let child_items = levels[i - 1].len();
+                    let child_item_size = if i - 1 == 0 { LEAF_ITEM } else { NODE_ITEM };
+                    let preceding_items = (item_index * BLOCK_SIZE).min(child_items) as u64;
+                    let child_offset = child_level_offset
+                        + item_index as u64 * NODE_HEADER
+                        + preceding_items * child_item_size;
+                    out.write_all(&child_offset.to_le_bytes())?;
+                }
+                item_index += 1;
+            }
+        }
+        level_offset = child_level_offset;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u16_at(bytes: &[u8], at: u64) -> u16 {
+        u16::from_le_bytes(bytes[at as usize..at as usize + 2].try_into().unwrap())
+    }
+
+    fn u32_at(bytes: &[u8], at: u64) -> u32 {
+        u32::from_le_bytes(bytes[at as usize..at as usize + 4].try_into().unwrap())
+    }
+
+    fn u64_at(bytes: &[u8], at: u64) -> u64 {
+        u64::from_le_bytes(bytes[at as usize..at as usize + 8].try_into().unwrap())
+    }
+
+    fn f64_at(bytes: &[u8], at: u64) -> f64 {
+        f64::from_le_bytes(bytes[at as usize..at as usize + 8].try_into().unwrap())
+    }
+
+    // Write a bigWig to a file of its own and read it back whole
+    fn written(name: &str, chrom_sizes: &[(String, u32)], records: &[BedGraphRecord]) -> Vec<u8> {
+        let path = std::env::temp_dir().join(format!("polyscan-bigwig-{}-{}.bw", name, std::process::id()));
+        write_bigwig(&path, chrom_sizes, records).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        bytes
+    }
+
+    // (name, id, size) of every leaf of the chromosome tree, walking it from the root
+    fn chrom_leaves(bytes: &[u8]) -> Vec<(String, u32, u32)> {
+        let tree = u64_at(bytes, 8);
+        assert_eq!(u32_at(bytes, tree), CHROM_TREE_MAGIC);
+        let key_size = u32_at(bytes, tree + 8) as u64;
+        assert_eq!(u32_at(bytes, tree + 12), 8);
+        let mut leaves = Vec::new();
+        let mut nodes = vec![tree + 32];
+        while let Some(node) = nodes.pop() {
+            let is_leaf = bytes[node as usize] == 1;
+            let count = u16_at(bytes, node + 2) as u64;
+            let mut children = Vec::new();
+            for item in 0..count {
+                let at = node + 4 + item * (key_size + 8);
+                let key = &bytes[at as usize..(at + key_size) as usize];
+                if is_leaf {
+                    let name = String::from_utf8(key.iter().copied().take_while(|&c| c != 0).collect()).unwrap();
+                    leaves.push((name, u32_at(bytes, at + key_size), u32_at(bytes, at + key_size + 4)));
+                } else {
+                    children.push(u64_at(bytes, at + key_size));
+                }
+            }
+            nodes.extend(children.into_iter().rev());
+        }
+        leaves
+    }
+
+    // (chrom id, start, end, value) of every item, reached through the R-tree index
+    fn indexed_items(bytes: &[u8]) -> Vec<(u32, u32, u32, f32)> {
+        let index = u64_at(bytes, 24);
+        assert_eq!(u32_at(bytes, index), CIR_TREE_MAGIC);
+        let sections = u64_at(bytes, index + 8);
+        assert_eq!(u64_at(bytes, u64_at(bytes, 16)), sections);
+        let mut items = Vec::new();
+        let mut found = 0;
+        let mut nodes = vec![index + 48];
+        while let Some(node) = nodes.pop() {
+            let is_leaf = bytes[node as usize] == 1;
+            let count = u16_at(bytes, node + 2) as u64;
+            let mut children = Vec::new();
+            for item in 0..count {
+                if !is_leaf {
+                    children.push(u64_at(bytes, node + 4 + item * 24 + 16));
+                    continue;
+                }
+                let at = node + 4 + item * 32;
+                let (chrom, start, end) = (u32_at(bytes, at), u32_at(bytes, at + 4), u32_at(bytes, at + 12));
+                let (offset, size) = (u64_at(bytes, at + 16), u64_at(bytes, at + 24));
+                // The section header repeats the bounds the index has for it
+                assert_eq!((u32_at(bytes, offset), u32_at(bytes, offset + 4), u32_at(bytes, offset + 8)), (chrom, start, end));
+                assert_eq!(bytes[offset as usize + 20], 1);
+                let count = u16_at(bytes, offset + 22) as u64;
+                assert_eq!(size, 24 + count * 12);
+                for i in 0..count {
+                    let at = offset + 24 + i * 12;
+                    items.push((chrom, u32_at(bytes, at), u32_at(bytes, at + 4), f32::from_le_bytes(bytes[at as usize + 8..at as usize + 12].try_into().unwrap())));
+                }
+                found += 1;
+            }
+            nodes.extend(children.into_iter().rev());
+        }
+        assert_eq!(found, sections);
+        items
+    }
+
+    fn sizes(chroms: &[(&str, u32)]) -> Vec<(String, u32)> {
+        chroms.iter().map(|&(name, size)| (name.to_string(), size)).collect()
+    }
+
+    #[test]
+    fn header_and_total_summary() {
+        let records = vec![("chr2".to_string(), 0, 10, 0.5), ("chr1".to_string(), 5, 7, 2.0)];
+        let bytes = written("header", &sizes(&[("chr2", 100), ("chr1", 50)]), &records);
+        assert_eq!(u32_at(&bytes, 0), BIGWIG_MAGIC);
+        assert_eq!(u16_at(&bytes, 4), 4);
+        assert_eq!(u16_at(&bytes, 6), 0);
+        let (chrom_tree, data, index) = (u64_at(&bytes, 8), u64_at(&bytes, 16), u64_at(&bytes, 24));
+        assert!(104 <= chrom_tree && chrom_tree < data && data < index && index < bytes.len() as u64);
+        assert_eq!(u64_at(&bytes, 44), 64);
+        // Bases covered, then min, max, sum and sum of squares over them
+        assert_eq!(u64_at(&bytes, 64), 12);
+        assert_eq!([72, 80, 88, 96].map(|at| f64_at(&bytes, at)), [0.5, 2.0, 9.0, 10.5]);
+    }
+
+    #[test]
+    fn chrom_tree_lists_every_chrom_by_name() {
+        let bytes = written("chroms", &sizes(&[("chrX", 300), ("chr10", 200), ("chr2", 100), ("chr2", 100)]), &[]);
+        let leaves = chrom_leaves(&bytes);
+        assert_eq!(leaves, vec![("chr10".to_string(), 0, 200), ("chr2".to_string(), 1, 100), ("chrX".to_string(), 2, 300)]);
+        // Nothing to index: no sections, and the summary is of nothing
+        assert!(indexed_items(&bytes).is_empty());
+        assert_eq!(u64_at(&bytes, 64), 0);
+    }
+
+    #[test]
+    fn chrom_tree_with_several_levels() {
+        let names: Vec<(String, u32)> = (0..600).map(|i| (format!("contig{:03}", i), 1000 + i)).collect();
+        let leaves = chrom_leaves(&written("levels", &names, &[]));
+        let expected: Vec<(String, u32, u32)> = names.iter().enumerate().map(|(i, (name, size))| (name.clone(), i as u32, *size)).collect();
+        assert_eq!(leaves, expected);
+    }
+
+    #[test]
+    fn data_sections_read_back_through_the_index() {
+        // Enough items for several sections on one chromosome, and for several index nodes
+        let mut records: Vec<BedGraphRecord> = (0..300_000).rev().map(|i| ("b".to_string(), i * 10, i * 10 + 5, i as f32)).collect();
+        records.push(("a".to_string(), 0, 1, -1.0));
+        let bytes = written("data", &sizes(&[("a", 10), ("b", 3_000_000)]), &records);
+        let items = indexed_items(&bytes);
+        let mut expected: Vec<(u32, u32, u32, f32)> =
+            records.iter().map(|(chrom, start, end, value)| ((chrom == "b") as u32, *start, *end, *value)).collect();
+        expected.sort_by_key(|&(chrom, start, _, _)| (chrom, start));
+        assert_eq!(items, expected);
+    }
+
+    #[test]
+    fn unknown_chromosome_is_refused() {
+        let path = std::env::temp_dir().join(format!("polyscan-bigwig-unknown-{}.bw", std::process::id()));
+        let error = write_bigwig(&path, &sizes(&[("chr1", 10)]), &[("chr9".to_string(), 0, 1, 1.0)]).unwrap_err();
+        assert!(error.to_string().contains("chr9"));
+        let _ = std::fs::remove_file(&path);
+    }
+}
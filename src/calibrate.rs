@@ -0,0 +1,166 @@
+//! Picking the threshold that reports a given fraction of the genome, for
+//! `--target-fraction`.
+
+use std::collections::VecDeque;
+
+use crate::scan::BaseClass;
+
+/// Whether the windows of a sequence stop at its end or wrap around to its
+/// start, as those of a circular contig do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Topology {
+    Linear,
+    Circular,
+}
+
+/// Bases of the sequences added, by the highest count of target bases among
+/// the windows covering them: the bases a scan at a threshold of `c` target
+/// bases reports are those at `c` or above.
+#[derive(Debug, Clone)]
+pub struct CoverageHistogram {
+    window: usize,
+    // Bases by count, from 0 to `window`
+    bases: Vec<u64>,
+    total: u64,
+}
+
+impl CoverageHistogram {
+    pub fn new(window: usize) -> Self {
+        CoverageHistogram { window, bases: vec![0; window + 1], total: 0 }
+    }
+
+    /// Add a sequence, counting each window's target bases as the most it
+    /// holds of any one of `classes` (one per strand scanned).
+    ///
+    /// ```
+    /// # use polyscan::calibrate::CoverageHistogram;
+    /// # use polyscan::scan::BaseClass;
+    /// let mut histogram = CoverageHistogram::new(4);
+    /// histogram.add(b"CCCCAAAACCCC", &[BaseClass::new(b"A")]);
+    /// assert_eq!(histogram.fraction_at(4), 4.0 / 12.0);
+    /// ```
+    pub fn add(&mut self, seq: &[u8], classes: &[BaseClass]) {
+        self.add_masked(seq, classes, Topology::Linear, &[]);
+    }
+
+    /// Add a sequence as a scan sees it: windows overlapping `excluded`
+    /// (sorted, non-overlapping half-open intervals, as `--exclude-bed`
+    /// drops them) count for nothing and the excluded bases are left out,
+    /// and the windows of a circular sequence wrap around its end.
+    pub fn add_masked(&mut self, seq: &[u8], classes: &[BaseClass], topology: Topology, excluded: &[(usize, usize)]) {
+        let (len, window) = (seq.len(), self.window);
+        let is_excluded = |start: usize, end: usize| {
+            let i = excluded.partition_point(|&(_, e)| e <= start);
+            excluded.get(i).is_some_and(|&(s, _)| s < end)
+        };
+        if len < window || window == 0 {
+            let kept = len - (0..len).filter(|&base| is_excluded(base, base + 1)).count();
+            self.bases[0] += kept as u64;
+            self.total += kept as u64;
+            return;
+        }
+        // A circular sequence has a window starting at every base, running on
+        // into a copy of its start; the bases of that copy are its first ones
+        let circular = topology == Topology::Circular;
+        let wrapped: Vec<u8>;
+        let (seq, starts) = if circular {
+            wrapped = [seq, &seq[..window - 1]].concat();
+            (&wrapped[..], len)
+        } else {
+            (seq, len - window + 1)
+        };
+        let excluded_window = |start: usize| match start + window > len {
+            true => is_excluded(start, len) || is_excluded(0, start + window - len),
+            false => is_excluded(start, start + window),
+        };
+
+        let mut counts: Vec<usize> = classes.iter().map(|class| seq[..window].iter().filter(|&&nuc| class.contains(nuc)).count()).collect();
+        // Windows still covering the current base, their counts decreasing
+        // from the front, so the front is the highest
+        let mut covering: VecDeque<(usize, usize)> = VecDeque::new();
+        // The coverage of the first bases of a circular sequence, until the
+        // windows wrapping around to them are seen
+        let mut head: Vec<usize> = Vec::new();
+        for base in 0..seq.len() {
+            // The window starting here
+            if base < starts {
+                if base > 0 {
+                    let (gone, new) = (seq[base - 1], seq[base + window - 1]);
+                    for (count, class) in counts.iter_mut().zip(classes) {
+                        *count = *count + class.contains(new) as usize - class.contains(gone) as usize;
+                    }
+                }
+                if !excluded_window(base) {
+                    let count = counts.iter().copied().max().unwrap_or(0);
+                    while covering.back().is_some_and(|&(_, last)| last <= count) {
+                        covering.pop_back();
+                    }
+                    covering.push_back((base, count));
+                }
+            }
+            while covering.front().is_some_and(|&(start, _)| start + window <= base) {
+                covering.pop_front();
+            }
+            let count = covering.front().map_or(0, |&(_, count)| count);
+            let position = if base < len { base } else { base - len };
+            if circular && base < window - 1 {
+                head.push(count);
+            } else if !is_excluded(position, position + 1) {
+                let count = if base < len { count } else { count.max(head[position]) };
+                self.bases[count] += 1;
+                self.total += 1;
+            }
+        }
+    }
+
+    /// The fraction of the bases added that windows holding at least `count`
+    /// target bases cover.
+    pub fn fraction_at(&self, count: usize) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.bases[count.min(self.window + 1)..].iter().sum::<u64>() as f64 / self.total as f64
+    }
+
+    /// The count of target bases (at least 1) whose windows cover the
+    /// fraction of the bases closest to `fraction`; the higher count on a tie.
+    pub fn count_for(&self, fraction: f64) -> usize {
+        (1..=self.window)
+            .rev()
+            .min_by(|&a, &b| (self.fraction_at(a) - fraction).abs().total_cmp(&(self.fraction_at(b) - fraction).abs()))
+            .unwrap_or(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn excluded_windows_and_bases_are_left_out() {
+        let a = [BaseClass::new(b"A")];
+        let mut histogram = CoverageHistogram::new(4);
+        histogram.add_masked(b"CCCCAAAACCCCAAAA", &a, Topology::Linear, &[(10, 11)]);
+        // The exclusion drops the windows across base 10, none of them a
+        // tract's, and base 10 itself from the bases counted
+        assert_eq!(histogram.fraction_at(4), 8.0 / 15.0);
+        let mut histogram = CoverageHistogram::new(4);
+        histogram.add_masked(b"CCCCAAAACCCCAAAA", &a, Topology::Linear, &[(13, 14)]);
+        assert_eq!(histogram.fraction_at(4), 4.0 / 15.0);
+        // CAAA and AAAC around the first tract still pass at 3
+        assert_eq!(histogram.fraction_at(3), 6.0 / 15.0);
+    }
+
+    #[test]
+    fn circular_windows_cover_the_origin() {
+        let a = [BaseClass::new(b"A")];
+        // AA at either end make a tract of 4 across the origin
+        let mut linear = CoverageHistogram::new(4);
+        linear.add(b"AACCCCCCAA", &a);
+        assert_eq!(linear.fraction_at(4), 0.0);
+        let mut circular = CoverageHistogram::new(4);
+        circular.add_masked(b"AACCCCCCAA", &a, Topology::Circular, &[]);
+        assert_eq!(circular.fraction_at(4), 4.0 / 10.0);
+        assert_eq!(circular.total, 10);
+    }
+}
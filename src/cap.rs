@@ -0,0 +1,93 @@
+//! Capping the records written per contig, for `--max-hits-per-contig`.
+
+use std::io::{self, Write};
+
+/// Passes BED lines on to `inner` until `max` records of a contig (the 1st
+/// column) have been written, then counts the rest of that contig's records
+/// without writing them.
+///
+/// Header and track lines (`#`, `track`, `browser`) always go through. A
+/// contig's records need not be contiguous: the count follows the contig
+/// wherever its records appear.
+///
+/// ```
+/// # use std::io::Write;
+/// # use polyscan::cap::HitCapWriter;
+/// # fn main() -> std::io::Result<()> {
+/// let mut capped = HitCapWriter::new(Vec::new(), 1);
+/// capped.write_all(b"chr1\t0\t10\nchr1\t20\t30\nchr2\t0\t10\n")?;
+/// assert_eq!(capped.finish()?, vec![("chr1".to_string(), 2, 1)]);
+/// # Ok(())
+/// # }
+/// ```
+pub struct HitCapWriter<W: Write> {
+    inner: W,
+    max: u64,
+    // Records found and left out, by contig in order of first appearance
+    contigs: Vec<(String, u64, u64)>,
+    // The start of a line not yet ended
+    partial: Vec<u8>,
+}
+
+impl<W: Write> HitCapWriter<W> {
+    pub fn new(inner: W, max: u64) -> Self {
+        HitCapWriter { inner, max, contigs: Vec::new(), partial: Vec::new() }
+    }
+
+    /// Write out any unfinished last line, flush `inner` and return the
+    /// contigs that went over the cap, with the records found on each and
+    /// how many of them were left out.
+    pub fn finish(&mut self) -> io::Result<Vec<(String, u64, u64)>> {
+        if !self.partial.is_empty() {
+            let line = std::mem::take(&mut self.partial);
+            self.cap_line(&line)?;
+        }
+        self.inner.flush()?;
+        Ok(self.contigs.iter().filter(|(_, _, dropped)| *dropped > 0).cloned().collect())
+    }
+
+    fn cap_line(&mut self, line: &[u8]) -> io::Result<()> {
+        if [b"#".as_slice(), b"track", b"browser"].iter().any(|prefix| line.starts_with(prefix)) || line.trim_ascii().is_empty() {
+            return self.inner.write_all(line);
+        }
+        let chrom = line.split(|&c| c == b'\t').next().unwrap_or_default();
+        // Contigs mostly come one after the other, so the last one is checked first
+        let index = match self.contigs.iter().rposition(|(name, _, _)| name.as_bytes() == chrom) {
+            Some(index) => index,
+            None => {
+                self.contigs.push((String::from_utf8_lossy(chrom).into_owned(), 0, 0));
+                self.contigs.len() - 1
+            }
+        };
+        let (_, found, dropped) = &mut self.contigs[index];
+        *found += 1;
+        if *found > self.max {
+            *dropped += 1;
+            return Ok(());
+        }
+        self.inner.write_all(line)
+    }
+}
+
+impl<W: Write> Write for HitCapWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut rest = buf;
+        while let Some(end) = rest.iter().position(|&c| c == b'\n') {
+            let (line, next) = rest.split_at(end + 1);
+            if self.partial.is_empty() {
+                self.cap_line(line)?;
+            } else {
+                let mut whole = std::mem::take(&mut self.partial);
+                whole.extend_from_slice(line);
+                self.cap_line(&whole)?;
+            }
+            rest = next;
+        }
+        self.partial.extend_from_slice(rest);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
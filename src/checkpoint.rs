@@ -0,0 +1,92 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// A contig whose records are all in the output.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompletedContig {
+    pub contig: String,
+    /// Size of the output once the contig's records were written
+    pub output_bytes: u64,
+}
+
+/// What a checkpoint file holds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckpointState {
+    /// The scan options, so a resumed run can't mix outputs of different ones
+    pub options: String,
+    pub output: PathBuf,
+    /// In the order they were completed
+    pub completed: Vec<CompletedContig>,
+}
+
+/// The contigs a scan has completed, saved to a JSON file after each one so
+/// an interrupted scan can resume after the last of them.
+#[derive(Debug)]
+pub struct Checkpoint {
+    path: PathBuf,
+    state: CheckpointState,
+}
+
+impl Checkpoint {
+    /// Start a checkpoint at `path` for a scan writing `output` with `options`.
+    pub fn create(path: &Path, output: &Path, options: String) -> Result<Self, Box<dyn Error>> {
+        let checkpoint = Checkpoint {
+            path: path.to_path_buf(),
+            state: CheckpointState { options, output: output.to_path_buf(), completed: Vec::new() },
+        };
+        checkpoint.save()?;
+        Ok(checkpoint)
+    }
+
+    /// Load the checkpoint saved at `path`.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let state: CheckpointState =
+            serde_json::from_reader(BufReader::new(File::open(path)?)).map_err(|e| format!("{}: invalid checkpoint: {}", path.display(), e))?;
+        Ok(Checkpoint { path: path.to_path_buf(), state })
+    }
+
+    pub fn state(&self) -> &CheckpointState {
+        &self.state
+    }
+
+    pub fn completed_contigs(&self) -> HashSet<String> {
+        self.state.completed.iter().map(|completed| completed.contig.clone()).collect()
+    }
+
+    /// Cut the output back to the end of the last completed contig, dropping
+    /// the records of a contig the interrupted scan was part way through.
+    pub fn truncate_output(&self) -> Result<(), Box<dyn Error>> {
+        let keep = self.state.completed.last().map_or(0, |completed| completed.output_bytes);
+        let output = OpenOptions::new().write(true).create(true).truncate(false).open(&self.state.output)?;
+        if output.metadata()?.len() < keep {
+            return Err(format!("{} is shorter than the checkpoint records; it can't be resumed", self.state.output.display()).into());
+        }
+        output.set_len(keep)?;
+        Ok(())
+    }
+
+    /// Record `contig` as completed; its records must have been written to
+    /// the output file (not just buffered).
+    pub fn complete(&mut self, contig: &str) -> Result<(), Box<dyn Error>> {
+        let output_bytes = fs::metadata(&self.state.output)?.len();
+        self.state.completed.push(CompletedContig { contig: contig.to_string(), output_bytes });
+        self.save()
+    }
+
+    // Replace the file in one step, so an interruption can't leave it half written
+    fn save(&self) -> Result<(), Box<dyn Error>> {
+        let mut partial = self.path.clone().into_os_string();
+        partial.push(".tmp");
+        let mut writer = BufWriter::new(File::create(&partial)?);
+        serde_json::to_writer_pretty(&mut writer, &self.state)?;
+        writeln!(writer)?;
+        writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&partial, &self.path)?;
+        Ok(())
+    }
+}
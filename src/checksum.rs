@@ -0,0 +1,61 @@
+//! MD5 and SHA-256 checksums of contig sequences, as in the `M5` tag of SAM
+//! `@SQ` lines, for `--contig-checksums`.
+
+use md5::{Digest, Md5};
+use sha2::Sha256;
+
+/// Hashes a contig's sequence fed to it in pieces, in order.
+///
+/// Bases are uppercased first, as the SAM specification does for `M5`, so
+/// soft-masking doesn't change the checksum of a build.
+///
+/// ```
+/// # use polyscan::checksum::ContigHasher;
+/// let mut hasher = ContigHasher::new();
+/// hasher.update(b"ACGT");
+/// hasher.update(b"acgt");
+/// let (md5, sha256) = hasher.finish();
+/// // Soft-masking doesn't change the checksum
+/// let mut upper = ContigHasher::new();
+/// upper.update(b"ACGTACGT");
+/// assert_eq!(upper.finish(), (md5, sha256));
+/// ```
+#[derive(Default)]
+pub struct ContigHasher {
+    md5: Md5,
+    sha256: Sha256,
+    length: u64,
+}
+
+impl ContigHasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bases hashed so far.
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+
+    pub fn update(&mut self, bases: &[u8]) {
+        // Uppercased a buffer at a time, as sequences can be long
+        let mut upper = [0u8; 8192];
+        for piece in bases.chunks(upper.len()) {
+            let upper = &mut upper[..piece.len()];
+            upper.copy_from_slice(piece);
+            upper.make_ascii_uppercase();
+            self.md5.update(&*upper);
+            self.sha256.update(&*upper);
+        }
+        self.length += bases.len() as u64;
+    }
+
+    /// The MD5 and SHA-256 of the sequence, in lowercase hex.
+    pub fn finish(self) -> (String, String) {
+        (hex(&self.md5.finalize()), hex(&self.sha256.finalize()))
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,210 @@
+use std::error::Error;
+use std::io::BufRead;
+use std::ops::Range;
+
+use crate::input::{Records, SeqRecord};
+
+/// Streams FASTA contigs as overlapping chunks, so memory use depends on the
+/// chunk size rather than the contig length.
+///
+/// Each chunk holds `chunk_size` window starts plus `overlap` trailing bases
+/// (`window - 1`), so every window lies wholly inside exactly one chunk. A
+/// chunk is only released once `overlap + 1` further bases have been read or
+/// the contig has ended; until then `contig_len` is a lower bound that is
+/// still long enough for near-contig-end checks to be exact.
+struct ChunkedFasta<B> {
+    input: B,
+    chunk_size: usize,
+    overlap: usize,
+    /// Name of the contig being read
+    id: Option<String>,
+    /// Header of the next contig, read while finishing the current one
+    next_id: Option<String>,
+    /// Pending bases of the current contig, starting at contig position `offset`
+    buf: Vec<u8>,
+    offset: usize,
+    /// Every base of the current contig has been read into `buf`
+    contig_done: bool,
+    /// The next input byte starts a line
+    at_line_start: bool,
+    line: Vec<u8>,
+}
+
+fn header_id(line: &[u8]) -> String {
+    let header = String::from_utf8_lossy(&line[1..]);
+    header.split_whitespace().next().unwrap_or("").to_string()
+}
+
+impl<B: BufRead> ChunkedFasta<B> {
+    /// Read a header line into `self.line` and return its sequence name.
+    fn read_header(&mut self) -> std::io::Result<String> {
+        self.line.clear();
+        self.input.read_until(b'\n', &mut self.line)?;
+        self.at_line_start = true;
+        Ok(header_id(&self.line))
+    }
+
+    /// Append sequence bases to `buf` until it holds `target` bases. Reaching
+    /// the next header or the end of input marks the contig done instead.
+    /// Sequence lines are consumed piecewise, so even single-line contigs are
+    /// never held in memory whole.
+    fn fill(&mut self, target: usize) -> std::io::Result<()> {
+        while self.buf.len() < target {
+            let available = self.input.fill_buf()?;
+            if available.is_empty() {
+                self.contig_done = true;
+                return Ok(());
+            }
+            if self.at_line_start && available[0] == b'>' {
+                self.next_id = Some(self.read_header()?);
+                self.contig_done = true;
+                return Ok(());
+            }
+
+            let mut used = 0;
+            for &b in available {
+                used += 1;
+                if b == b'\n' {
+                    self.at_line_start = true;
+                    break;
+                }
+                self.at_line_start = false;
+                if !b.is_ascii_whitespace() {
+                    self.buf.push(b);
+                    if self.buf.len() == target {
+                        break;
+                    }
+                }
+            }
+            self.input.consume(used);
+        }
+        Ok(())
+    }
+
+    fn chunk(&self, len: usize, contig_len: usize) -> SeqRecord {
+        SeqRecord {
+            id: self.id.clone().unwrap_or_default(),
+            seq: self.buf[..len].to_vec().into(),
+            start: self.offset,
+            contig_len,
+            chunked: true,
+            ..Default::default()
+        }
+    }
+
+    fn next_chunk(&mut self) -> Result<Option<SeqRecord>, Box<dyn Error>> {
+        loop {
+            // Find the next contig header
+            if self.id.is_none() {
+                match self.next_id.take() {
+                    Some(id) => self.id = Some(id),
+                    None => loop {
+                        let available = self.input.fill_buf()?;
+                        match available.first() {
+                            None => return Ok(None),
+                            Some(b'>') => {
+                                self.id = Some(self.read_header()?);
+                                break;
+                            }
+                            Some(b) if b.is_ascii_whitespace() => self.input.consume(1),
+                            Some(_) => return Err("expected FASTA header ('>') at start of input".into()),
+                        }
+                    },
+                }
+                self.buf.clear();
+                self.offset = 0;
+                self.contig_done = false;
+            }
+
+            let slice_len = self.chunk_size + self.overlap;
+
+            if self.contig_done {
+                let contig_len = self.offset + self.buf.len();
+                if self.buf.len() > slice_len {
+                    let chunk = self.chunk(slice_len, contig_len);
+                    self.buf.drain(..self.chunk_size);
+                    self.offset += self.chunk_size;
+                    return Ok(Some(chunk));
+                }
+
+                // Last chunk; skip it if it only repeats the previous chunk's overlap
+                let chunk = (self.offset == 0 || self.buf.len() > self.overlap)
+                    .then(|| self.chunk(self.buf.len(), contig_len));
+                self.id = None;
+                if chunk.is_some() {
+                    return Ok(chunk);
+                }
+                continue;
+            }
+
+            // Read ahead one window past the chunk before releasing it
+            if self.buf.len() > slice_len + self.overlap {
+                let chunk = self.chunk(slice_len, self.offset + self.buf.len());
+                self.buf.drain(..self.chunk_size);
+                self.offset += self.chunk_size;
+                return Ok(Some(chunk));
+            }
+
+            self.fill(slice_len + self.overlap + 1)?;
+        }
+    }
+}
+
+impl<B: BufRead> Iterator for ChunkedFasta<B> {
+    type Item = Result<SeqRecord, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_chunk().transpose()
+    }
+}
+
+/// Stream FASTA records in chunks of `chunk_size` window starts, each
+/// extended by `window - 1` bases so windows spanning a chunk boundary are
+/// still scanned exactly once.
+pub fn chunked_fasta_records<B: BufRead + 'static>(input: B, chunk_size: usize, window: usize) -> Records {
+    Box::new(ChunkedFasta {
+        input,
+        chunk_size: chunk_size.max(1),
+        overlap: window.saturating_sub(1),
+        id: None,
+        next_id: None,
+        buf: Vec::new(),
+        offset: 0,
+        contig_done: false,
+        at_line_start: true,
+        line: Vec::new(),
+    })
+}
+
+/// Split a record already in memory into chunks of `chunk_size` window
+/// starts, each extended by `window - 1` bases, like
+/// [`chunked_fasta_records`] (so the chunks can be scanned independently).
+/// Mapped sequences are not copied.
+pub fn split_record(record: &SeqRecord, chunk_size: usize, window: usize) -> Vec<SeqRecord> {
+    let len = record.seq.len();
+    if len <= chunk_size.max(1) + window.saturating_sub(1) {
+        return vec![record.clone()];
+    }
+    piece_ranges(len, chunk_size, window)
+        .into_iter()
+        .map(|range| SeqRecord {
+            id: record.id.clone(),
+            seq: record.seq.slice(range.clone()),
+            start: record.start + range.start,
+            contig_len: record.contig_len,
+            alignment: record.alignment.clone(),
+            chunked: true,
+            circular: record.circular,
+        })
+        .collect()
+}
+
+/// The ranges of the chunks [`split_record`] splits `len` bases into.
+pub fn piece_ranges(len: usize, chunk_size: usize, window: usize) -> Vec<Range<usize>> {
+    let chunk_size = chunk_size.max(1);
+    let overlap = window.saturating_sub(1);
+    if len <= chunk_size + overlap {
+        return std::iter::once(0..len).collect();
+    }
+    (0..len.saturating_sub(overlap)).step_by(chunk_size).map(|from| from..(from + chunk_size + overlap).min(len)).collect()
+}
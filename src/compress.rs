@@ -0,0 +1,132 @@
+//! Compressing the output, for `--compress-codec` and `--compress-level`.
+
+use std::io::{self, Write};
+use std::path::Path;
+
+use flate2::write::GzEncoder;
+use noodles::bgzf;
+
+/// A compression format for the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "kebab-case"))]
+pub enum Codec {
+    /// gzip, readable by zcat and anything else
+    Gzip,
+    /// Blocked gzip (bgzip), which tabix can index
+    Bgzf,
+    /// Zstandard, much faster than gzip at a similar size
+    Zstd,
+    /// xz, the smallest and slowest
+    Xz,
+}
+
+impl Codec {
+    /// The codec a file name asks for by its extension: `.gz`, `.bgz`, `.zst`
+    /// or `.xz`.
+    pub fn from_path(path: &Path) -> Option<Codec> {
+        match path.extension()?.to_str()? {
+            "gz" => Some(Codec::Gzip),
+            "bgz" => Some(Codec::Bgzf),
+            "zst" => Some(Codec::Zstd),
+            "xz" => Some(Codec::Xz),
+            _ => None,
+        }
+    }
+
+    /// The lowest and highest compression levels the codec takes.
+    pub fn levels(&self) -> (u32, u32) {
+        match self {
+            Codec::Gzip | Codec::Bgzf | Codec::Xz => (0, 9),
+            Codec::Zstd => (1, 22),
+        }
+    }
+
+    /// The level used unless one is given: each library's own default.
+    pub fn default_level(&self) -> u32 {
+        match self {
+            Codec::Gzip | Codec::Bgzf | Codec::Xz => 6,
+            Codec::Zstd => 3,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Codec::Gzip => "gzip",
+            Codec::Bgzf => "bgzf",
+            Codec::Zstd => "zstd",
+            Codec::Xz => "xz",
+        }
+    }
+}
+
+/// Compresses what is written through it with a [`Codec`].
+///
+/// The compressed stream only ends properly when [`CompressedWriter::finish`]
+/// is called; dropping the writer instead loses the end of the stream (or
+/// ignores any error writing it).
+///
+/// ```ignore
+/// let mut writer = CompressedWriter::new(File::create("hits.bed.zst")?, Codec::Zstd, 3)?;
+/// writer.write_all(b"chr1\t0\t10\n")?;
+/// writer.finish()?;
+/// ```
+pub enum CompressedWriter<W: Write> {
+    Gzip(GzEncoder<W>),
+    Bgzf(bgzf::io::Writer<W>),
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+    Xz(liblzma::write::XzEncoder<W>),
+}
+
+impl<W: Write> CompressedWriter<W> {
+    /// Fails if `level` is outside [`Codec::levels`].
+    pub fn new(inner: W, codec: Codec, level: u32) -> io::Result<Self> {
+        let (lowest, highest) = codec.levels();
+        if !(lowest..=highest).contains(&level) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} compression levels go from {} to {}, not {}", codec.name(), lowest, highest, level),
+            ));
+        }
+        Ok(match codec {
+            Codec::Gzip => CompressedWriter::Gzip(GzEncoder::new(inner, flate2::Compression::new(level))),
+            Codec::Bgzf => {
+                let level = bgzf::io::writer::CompressionLevel::new(level as u8).unwrap_or_default();
+                CompressedWriter::Bgzf(bgzf::io::writer::Builder::default().set_compression_level(level).build_from_writer(inner))
+            }
+            Codec::Zstd => CompressedWriter::Zstd(zstd::stream::write::Encoder::new(inner, level as i32)?),
+            Codec::Xz => CompressedWriter::Xz(liblzma::write::XzEncoder::new(inner, level)),
+        })
+    }
+
+    /// End the compressed stream (BGZF with its empty end-of-file block) and
+    /// return the inner writer.
+    pub fn finish(self) -> io::Result<W> {
+        match self {
+            CompressedWriter::Gzip(writer) => writer.finish(),
+            CompressedWriter::Bgzf(writer) => writer.finish(),
+            CompressedWriter::Zstd(writer) => writer.finish(),
+            CompressedWriter::Xz(writer) => writer.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for CompressedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            CompressedWriter::Gzip(writer) => writer.write(buf),
+            CompressedWriter::Bgzf(writer) => writer.write(buf),
+            CompressedWriter::Zstd(writer) => writer.write(buf),
+            CompressedWriter::Xz(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            CompressedWriter::Gzip(writer) => writer.flush(),
+            CompressedWriter::Bgzf(writer) => writer.flush(),
+            CompressedWriter::Zstd(writer) => writer.flush(),
+            CompressedWriter::Xz(writer) => writer.flush(),
+        }
+    }
+}
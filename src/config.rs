@@ -0,0 +1,131 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use clap::parser::ValueSource;
+use clap::{Arg, Command, CommandFactory, ValueEnum};
+
+use polyscan::error::PolyscanError;
+
+use crate::{Cli, Preset};
+
+/// The options of a `--preset`, as long option names and values (empty for flags).
+fn preset_options(preset: Preset) -> &'static [(&'static str, &'static str)] {
+    match preset {
+        Preset::OntHomopolymer => &[("nucleotide", "A,C,G,T"), ("window-size", "6"), ("percentage", "100"), ("merge", "")],
+        Preset::IlluminaPolyA => &[("nucleotide", "A"), ("window-size", "15"), ("percentage", "90"), ("merge", "")],
+        Preset::AtIsland => &[("nucleotide", "AT"), ("window-size", "100"), ("percentage", "80"), ("merge", "")],
+        // About 60% of the CG expected at 50% GC (6.25% of pairs), as in the usual island definition
+        Preset::Cpg => &[
+            ("stat", "dinuc"),
+            ("pair", "CG"),
+            ("window-size", "200"),
+            ("percentage", "4"),
+            ("allow-low-threshold", ""),
+            ("merge", ""),
+            ("min-length", "200"),
+        ],
+    }
+}
+
+fn long_arg<'a>(command: &'a Command, long: &str) -> Option<&'a Arg> {
+    command.get_arguments().find(|arg| arg.get_long() == Some(long))
+}
+
+/// Add the options of a `--config` TOML file and of a `--preset` to the
+/// command line `argv`.
+///
+/// Config keys are the long names of scan options (`window-size = [10, 50]`,
+/// `percentage = 90`, `merge = true`, `preset = "cpg"`). Options given on the
+/// command line win over the file, and both win over the preset. Without
+/// either, `argv` is unchanged.
+pub fn apply(argv: Vec<OsString>) -> Result<Vec<OsString>, Box<dyn Error>> {
+    // A lenient first pass, since required options may only be in the file
+    let Ok(first) = Cli::command().ignore_errors(true).try_get_matches_from(&argv) else {
+        return Ok(argv);
+    };
+    let matches = match first.subcommand() {
+        Some(("scan", matches)) => matches,
+        _ => &first,
+    };
+    let command = Cli::command();
+    // Ids of the options set so far, which later layers leave alone
+    let mut explicit: HashSet<String> =
+        matches.ids().map(|id| id.as_str().to_string()).filter(|id| matches.value_source(id) == Some(ValueSource::CommandLine)).collect();
+    let mut options: Vec<OsString> = Vec::new();
+
+    let mut preset = matches.try_get_one::<Preset>("preset").ok().flatten().copied();
+    if let Ok(Some(path)) = matches.try_get_one::<PathBuf>("config") {
+        let (config_options, config_preset) = config_options(path, &command, &mut explicit)?;
+        options.extend(config_options);
+        preset = preset.or(config_preset);
+    }
+
+    if let Some(preset) = preset {
+        for &(long, value) in preset_options(preset) {
+            let arg = long_arg(&command, long).expect("presets use existing options");
+            // Skip options already set, or conflicting with one that is (e.g. --percentage with --min-count)
+            let conflicts = command.get_arguments().filter(|other| explicit.contains(other.get_id().as_str())).any(|other| {
+                command.get_arg_conflicts_with(other).contains(&arg) || command.get_arg_conflicts_with(arg).contains(&other)
+            });
+            if explicit.contains(arg.get_id().as_str()) || conflicts {
+                continue;
+            }
+            options.push(if value.is_empty() { format!("--{}", long) } else { format!("--{}={}", long, value) }.into());
+        }
+    }
+
+    // After everything else, so they go to `polyscan scan` too
+    Ok(argv.into_iter().chain(options).collect())
+}
+
+// The options of a config file not already in `explicit` (which gains them),
+// and the preset it names
+fn config_options(path: &Path, command: &Command, explicit: &mut HashSet<String>) -> Result<(Vec<OsString>, Option<Preset>), Box<dyn Error>> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let table: toml::Table = text.parse().map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut options: Vec<OsString> = Vec::new();
+    let mut preset = None;
+    for (key, value) in &table {
+        let Some(arg) = long_arg(command, key) else {
+            return Err(PolyscanError::parameter(format!("{}: unknown option '{}' (keys are long option names, e.g. window-size).", path.display(), key)));
+        };
+        let id = arg.get_id().as_str();
+        if id == "config" || id == "self_test" {
+            return Err(PolyscanError::parameter(format!("{}: --{} cannot be set in a config file.", path.display(), key)));
+        }
+        // The command line wins over the file
+        if explicit.contains(id) {
+            continue;
+        }
+        if id == "preset" {
+            // Applied with the other presets, under the file's own options
+            let name = value.as_str().unwrap_or_default();
+            match Preset::from_str(name, false) {
+                Ok(named) => preset = Some(named),
+                Err(_) => {
+                    return Err(PolyscanError::parameter(format!("{}: unknown preset '{}'.", path.display(), name)));
+                }
+            }
+            continue;
+        }
+        let values = match value {
+            toml::Value::Array(values) => values.clone(),
+            value => vec![value.clone()],
+        };
+        for value in values {
+            match value {
+                toml::Value::Boolean(true) => options.push(format!("--{}", key).into()),
+                toml::Value::Boolean(false) => {}
+                toml::Value::String(s) => options.push(format!("--{}={}", key, s).into()),
+                toml::Value::Integer(_) | toml::Value::Float(_) => options.push(format!("--{}={}", key, value).into()),
+                _ => {
+                    return Err(PolyscanError::parameter(format!("{}: '{}' must be a string, number, boolean or an array of them.", path.display(), key)));
+                }
+            }
+        }
+        explicit.insert(id.to_string());
+    }
+    Ok((options, preset))
+}
@@ -0,0 +1,117 @@
+use crate::bed::complement_intervals;
+
+/// The records of one contig, indexed so that the bases they cover can be
+/// reported once each, however much the records overlap.
+#[derive(Debug, Clone, Default)]
+pub struct CoverageIndex {
+    // Half-open intervals with their score and the index of their name
+    intervals: Vec<(u64, u64, f64, usize)>,
+    // Record names in order of first appearance
+    names: Vec<String>,
+    sorted: bool,
+}
+
+/// A maximal run of bases covered by at least one record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnionInterval {
+    pub start: u64,
+    pub end: u64,
+    /// Highest score of the records covering it
+    pub max_score: f64,
+    /// Names of the records covering it, in order of first appearance
+    pub names: Vec<String>,
+}
+
+impl CoverageIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, start: u64, end: u64, score: f64, name: &str) {
+        let name = match self.names.iter().position(|known| known == name) {
+            Some(i) => i,
+            None => {
+                self.names.push(name.to_string());
+                self.names.len() - 1
+            }
+        };
+        self.intervals.push((start, end, score, name));
+        self.sorted = false;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    fn sort(&mut self) {
+        if !self.sorted {
+            self.intervals.sort_unstable_by_key(|&(start, end, _, _)| (start, end));
+            self.sorted = true;
+        }
+    }
+
+    /// The union of the records as sorted, non-overlapping intervals; records
+    /// that overlap or touch end up in the same interval.
+    pub fn union(&mut self) -> Vec<UnionInterval> {
+        self.sort();
+        let mut union: Vec<UnionInterval> = Vec::new();
+        let mut name_sets: Vec<Vec<usize>> = Vec::new();
+        for &(start, end, score, name) in &self.intervals {
+            match union.last_mut() {
+                Some(last) if start <= last.end => {
+                    last.end = last.end.max(end);
+                    last.max_score = last.max_score.max(score);
+                    let names = name_sets.last_mut().unwrap();
+                    if !names.contains(&name) {
+                        names.push(name);
+                    }
+                }
+                _ => {
+                    union.push(UnionInterval { start, end, max_score: score, names: Vec::new() });
+                    name_sets.push(vec![name]);
+                }
+            }
+        }
+        for (interval, mut names) in union.iter_mut().zip(name_sets) {
+            names.sort_unstable();
+            interval.names = names.into_iter().map(|i| self.names[i].clone()).collect();
+        }
+        union
+    }
+
+    /// The union of the records split where the number of records covering
+    /// a base changes, as sorted, non-overlapping `(start, end, depth)`
+    /// intervals; bases no record covers are left out.
+    ///
+    /// ```
+    /// # use polyscan::coverage::CoverageIndex;
+    /// let mut index = CoverageIndex::new();
+    /// index.insert(0, 10, 1.0, "a");
+    /// index.insert(5, 15, 1.0, "b");
+    /// assert_eq!(index.depth(), vec![(0, 5, 1), (5, 10, 2), (10, 15, 1)]);
+    /// ```
+    pub fn depth(&self) -> Vec<(u64, u64, u32)> {
+        // +1 where a record starts and -1 where one ends, ends first at the same base
+        let mut events: Vec<(u64, i32)> = self.intervals.iter().flat_map(|&(start, end, _, _)| [(start, 1), (end, -1)]).collect();
+        events.sort_unstable();
+        let mut segments: Vec<(u64, u64, u32)> = Vec::new();
+        let mut depth: i32 = 0;
+        let mut last = 0;
+        for (position, change) in events {
+            if depth > 0 && position > last {
+                match segments.last_mut() {
+                    Some(segment) if segment.1 == last && segment.2 == depth as u32 => segment.1 = position,
+                    _ => segments.push((last, position, depth as u32)),
+                }
+            }
+            depth += change;
+            last = position;
+        }
+        segments
+    }
+
+    /// The intervals of `0..len` not covered by any record.
+    pub fn complement(&self, len: u64) -> Vec<(u64, u64)> {
+        complement_intervals(self.intervals.iter().map(|&(start, end, _, _)| (start, end)).collect(), len)
+    }
+}
@@ -0,0 +1,68 @@
+//! Hits as a polars [`DataFrame`] (with the `polars` feature), for analysis
+//! in Rust without writing them out as text and parsing them back.
+
+#[cfg(feature = "io")]
+use std::error::Error;
+#[cfg(feature = "io")]
+use std::path::Path;
+
+use polars::prelude::{Column, DataFrame, PolarsResult};
+
+use crate::scanner::Hit;
+#[cfg(feature = "io")]
+use crate::scanner::{ScanParams, Scanner};
+
+/// `hits` as a data frame with the columns chrom, start and end (0-based,
+/// half-open, as u64), target, score (f64) and strand ("+", "-" or ".").
+pub fn hits_to_dataframe(hits: &[Hit]) -> PolarsResult<DataFrame> {
+    DataFrame::new(
+        hits.len(),
+        vec![
+            Column::new("chrom".into(), hits.iter().map(|hit| hit.chrom.as_str()).collect::<Vec<_>>()),
+            Column::new("start".into(), hits.iter().map(|hit| hit.start as u64).collect::<Vec<_>>()),
+            Column::new("end".into(), hits.iter().map(|hit| hit.end as u64).collect::<Vec<_>>()),
+            Column::new("target".into(), hits.iter().map(|hit| hit.target.as_str()).collect::<Vec<_>>()),
+            Column::new("score".into(), hits.iter().map(|hit| hit.score).collect::<Vec<_>>()),
+            Column::new("strand".into(), hits.iter().map(|hit| hit.strand.symbol()).collect::<Vec<_>>()),
+        ],
+    )
+}
+
+/// Scan every record of a sequence file with `params` into a data frame of
+/// its hits (see [`hits_to_dataframe`] for the columns).
+#[cfg(feature = "io")]
+pub fn scan_to_dataframe<P: AsRef<Path>>(path: P, params: &ScanParams) -> Result<DataFrame, Box<dyn Error>> {
+    let hits = Scanner::new(params.clone())?.scan_file(path)?;
+    Ok(hits_to_dataframe(&hits)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scan::Strand;
+
+    fn hit(chrom: &str, start: usize, target: &str, score: f64, strand: Strand) -> Hit {
+        Hit { chrom: chrom.to_string(), start, end: start + 10, target: target.to_string(), score, strand }
+    }
+
+    #[test]
+    fn hits_become_rows() {
+        let hits = [hit("chr1", 5, "A", 90.0, Strand::Plus), hit("chr2", 0, "GC", 80.0, Strand::Unstranded)];
+        let df = hits_to_dataframe(&hits).unwrap();
+        assert_eq!(df.height(), 2);
+        let names: Vec<&str> = df.get_column_names().into_iter().map(|name| name.as_str()).collect();
+        assert_eq!(names, ["chrom", "start", "end", "target", "score", "strand"]);
+        assert_eq!(df.column("chrom").unwrap().str().unwrap().get(1), Some("chr2"));
+        assert_eq!(df.column("start").unwrap().u64().unwrap().get(0), Some(5));
+        assert_eq!(df.column("end").unwrap().u64().unwrap().get(0), Some(15));
+        assert_eq!(df.column("target").unwrap().str().unwrap().get(1), Some("GC"));
+        assert_eq!(df.column("score").unwrap().f64().unwrap().get(0), Some(90.0));
+        assert_eq!(df.column("strand").unwrap().str().unwrap().get(1), Some("."));
+    }
+
+    #[test]
+    fn no_hits_make_an_empty_frame() {
+        let df = hits_to_dataframe(&[]).unwrap();
+        assert_eq!((df.height(), df.width()), (0, 6));
+    }
+}
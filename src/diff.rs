@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Intervals of a BED file grouped by contig, name and strand, in order of
+/// first appearance.
+pub type GroupedIntervals = Vec<(GroupKey, Vec<(u64, u64)>)>;
+
+/// Contig, name and strand columns of a group of intervals.
+pub type GroupKey = (String, String, String);
+
+// Half-open intervals of one contig and strand
+type Intervals = Vec<(u64, u64)>;
+
+/// How a region changed between two runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    /// Only in the first run
+    Lost,
+    /// Only in the second run
+    Gained,
+    /// In both runs
+    Shared,
+}
+
+impl Change {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Change::Lost => "lost",
+            Change::Gained => "gained",
+            Change::Shared => "shared",
+        }
+    }
+}
+
+/// A cluster of overlapping regions from either run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffRegion {
+    pub chrom: String,
+    pub start: u64,
+    pub end: u64,
+    /// Name column of the regions, e.g. the target they were found for
+    pub name: String,
+    /// Strand column of the regions, or `.` when strands are ignored
+    pub strand: String,
+    pub change: Change,
+}
+
+/// Region counts and covered bases of a comparison.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiffStats {
+    pub lost: usize,
+    pub gained: usize,
+    pub shared: usize,
+    /// Bases covered by the first run, the second, and both
+    pub old_bases: u64,
+    pub new_bases: u64,
+    pub shared_bases: u64,
+}
+
+impl DiffStats {
+    /// Shared bases over the bases covered by either run (1 when both are empty).
+    pub fn jaccard(&self) -> f64 {
+        let union = self.old_bases + self.new_bases - self.shared_bases;
+        if union == 0 {
+            1.0
+        } else {
+            self.shared_bases as f64 / union as f64
+        }
+    }
+}
+
+/// Read the intervals of a BED file (optionally compressed), grouped by contig,
+/// name and, if `stranded`, strand column (`.` when absent).
+///
+/// Header, `track` and `browser` lines are skipped.
+pub fn read_grouped_intervals<P: AsRef<Path>>(path: P, stranded: bool) -> Result<GroupedIntervals, Box<dyn Error>> {
+    let path = path.as_ref();
+    let (reader, _compression_format) = niffler::get_reader(Box::new(File::open(path)?))?;
+
+    let mut groups: GroupedIntervals = Vec::new();
+    let mut index: HashMap<GroupKey, usize> = HashMap::new();
+    for (i, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("track") || line.starts_with("browser") {
+            continue;
+        }
+        let invalid = || format!("{}: invalid BED line {}: '{}'", path.display(), i + 1, line);
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 3 {
+            return Err(invalid().into());
+        }
+        let start: u64 = fields[1].parse().map_err(|_| invalid())?;
+        let end: u64 = fields[2].parse().map_err(|_| invalid())?;
+        if end < start {
+            return Err(invalid().into());
+        }
+        let strand = if stranded { fields.get(5).copied().unwrap_or(".") } else { "." };
+        let name = fields.get(3).copied().unwrap_or(".");
+        let key = (fields[0].to_string(), name.to_string(), strand.to_string());
+        let group = *index.entry(key.clone()).or_insert_with(|| {
+            groups.push((key, Vec::new()));
+            groups.len() - 1
+        });
+        groups[group].1.push((start, end));
+    }
+    Ok(groups)
+}
+
+// Sort intervals and merge the ones that overlap or touch
+fn merge_intervals(mut intervals: Vec<(u64, u64)>) -> Vec<(u64, u64)> {
+    intervals.sort_unstable();
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(intervals.len());
+    for (start, end) in intervals {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+fn covered_bases(intervals: &[(u64, u64)]) -> u64 {
+    intervals.iter().map(|(start, end)| end - start).sum()
+}
+
+// Bases covered by both sorted, non-overlapping interval lists
+fn shared_bases(a: &[(u64, u64)], b: &[(u64, u64)]) -> u64 {
+    let (mut i, mut j, mut shared) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        let (start, end) = (a[i].0.max(b[j].0), a[i].1.min(b[j].1));
+        if start < end {
+            shared += end - start;
+        }
+        if a[i].1 < b[j].1 {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    shared
+}
+
+/// Compare the regions of two runs, contig by contig, name by name (regions
+/// of different targets are not compared) and strand by strand.
+///
+/// Overlapping regions of either run are clustered; a cluster is `shared` if
+/// it holds regions of both runs, `lost` or `gained` if only of the first or
+/// second. Clusters are reported with their whole span, in order.
+pub fn diff_regions(old: GroupedIntervals, new: GroupedIntervals) -> (Vec<DiffRegion>, DiffStats) {
+    // Contigs, names and strands in order of first appearance in either run
+    let mut keys: Vec<GroupKey> = Vec::new();
+    let mut pairs: HashMap<GroupKey, (Intervals, Intervals)> = HashMap::new();
+    for (is_new, groups) in [(false, old), (true, new)] {
+        for (key, intervals) in groups {
+            let pair = pairs.entry(key.clone()).or_insert_with(|| {
+                keys.push(key);
+                (Vec::new(), Vec::new())
+            });
+            if is_new { &mut pair.1 } else { &mut pair.0 }.extend(intervals);
+        }
+    }
+
+    let mut regions = Vec::new();
+    let mut stats = DiffStats::default();
+    for key in keys {
+        let (old, new) = pairs.remove(&key).unwrap_or_default();
+        let (old, new) = (merge_intervals(old), merge_intervals(new));
+        stats.old_bases += covered_bases(&old);
+        stats.new_bases += covered_bases(&new);
+        stats.shared_bases += shared_bases(&old, &new);
+
+        let mut tagged: Vec<(u64, u64, bool)> = old.iter().map(|&(s, e)| (s, e, false)).chain(new.iter().map(|&(s, e)| (s, e, true))).collect();
+        tagged.sort_unstable();
+        // The open cluster: its span, and whether it has old and new regions
+        let mut open: Option<(u64, u64, bool, bool)> = None;
+        let mut close = |cluster: (u64, u64, bool, bool)| {
+            let change = match (cluster.2, cluster.3) {
+                (true, true) => Change::Shared,
+                (true, false) => Change::Lost,
+                _ => Change::Gained,
+            };
+            match change {
+                Change::Lost => stats.lost += 1,
+                Change::Gained => stats.gained += 1,
+                Change::Shared => stats.shared += 1,
+            }
+            regions.push(DiffRegion { chrom: key.0.clone(), start: cluster.0, end: cluster.1, name: key.1.clone(), strand: key.2.clone(), change });
+        };
+        for (start, end, is_new) in tagged {
+            match open.as_mut() {
+                Some(cluster) if start < cluster.1 => {
+                    cluster.1 = cluster.1.max(end);
+                    if is_new { cluster.3 = true } else { cluster.2 = true }
+                }
+                _ => {
+                    if let Some(cluster) = open.replace((start, end, !is_new, is_new)) {
+                        close(cluster);
+                    }
+                }
+            }
+        }
+        if let Some(cluster) = open {
+            close(cluster);
+        }
+    }
+    (regions, stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(chrom: &str, name: &str, strand: &str, intervals: &[(u64, u64)]) -> (GroupKey, Vec<(u64, u64)>) {
+        ((chrom.to_string(), name.to_string(), strand.to_string()), intervals.to_vec())
+    }
+
+    fn changes(regions: &[DiffRegion]) -> Vec<(u64, u64, Change)> {
+        regions.iter().map(|region| (region.start, region.end, region.change)).collect()
+    }
+
+    #[test]
+    fn disjoint_runs_share_nothing() {
+        let (regions, stats) = diff_regions(vec![group("c", "A", "+", &[(0, 10)])], vec![group("c", "A", "+", &[(20, 25)])]);
+        assert_eq!(changes(&regions), [(0, 10, Change::Lost), (20, 25, Change::Gained)]);
+        assert_eq!((stats.lost, stats.gained, stats.shared), (1, 1, 0));
+        assert_eq!((stats.old_bases, stats.new_bases, stats.shared_bases), (10, 5, 0));
+        assert_eq!(stats.jaccard(), 0.0);
+    }
+
+    #[test]
+    fn identical_runs_have_a_jaccard_index_of_1() {
+        let intervals = [(0, 10), (5, 15), (30, 40)];
+        let (regions, stats) = diff_regions(vec![group("c", "A", "+", &intervals)], vec![group("c", "A", "+", &intervals)]);
+        assert_eq!(changes(&regions), [(0, 15, Change::Shared), (30, 40, Change::Shared)]);
+        assert_eq!((stats.old_bases, stats.new_bases, stats.shared_bases), (25, 25, 25));
+        assert_eq!(stats.jaccard(), 1.0);
+        assert_eq!(diff_regions(Vec::new(), Vec::new()).1.jaccard(), 1.0);
+    }
+
+    #[test]
+    fn overlapping_regions_are_shared_over_their_whole_span() {
+        let (regions, stats) = diff_regions(vec![group("c", "A", "+", &[(0, 10), (50, 60)])], vec![group("c", "A", "+", &[(5, 20)])]);
+        assert_eq!(changes(&regions), [(0, 20, Change::Shared), (50, 60, Change::Lost)]);
+        assert_eq!((stats.old_bases, stats.new_bases, stats.shared_bases), (20, 15, 5));
+        assert_eq!(stats.jaccard(), 5.0 / 30.0);
+        // Regions that only touch are not a cluster
+        let (regions, _) = diff_regions(vec![group("c", "A", "+", &[(0, 10)])], vec![group("c", "A", "+", &[(10, 20)])]);
+        assert_eq!(changes(&regions), [(0, 10, Change::Lost), (10, 20, Change::Gained)]);
+    }
+
+    #[test]
+    fn other_contigs_names_and_strands_are_not_compared() {
+        let old = vec![group("c1", "A", "+", &[(0, 10)])];
+        for new in [group("c2", "A", "+", &[(0, 10)]), group("c1", "T", "+", &[(0, 10)]), group("c1", "A", "-", &[(0, 10)])] {
+            let (regions, stats) = diff_regions(old.clone(), vec![new.clone()]);
+            assert_eq!(changes(&regions), [(0, 10, Change::Lost), (0, 10, Change::Gained)]);
+            assert_eq!((&regions[1].chrom, &regions[1].name, &regions[1].strand), (&new.0 .0, &new.0 .1, &new.0 .2));
+            assert_eq!(stats.shared_bases, 0);
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use std::error::Error;
+
+use crate::kmer::iupac_mask;
+use crate::scan::{ScanHit, Steps, Strand};
+
+/// Density of one dinucleotide (IUPAC codes allowed, e.g. `TA` or `RY`) among
+/// the adjacent base pairs of a window.
+#[derive(Debug, Clone)]
+pub struct DinucleotideDensity {
+    masks: [u8; 2],
+    threshold_count: usize,
+}
+
+impl DinucleotideDensity {
+    /// Require at least `percentage`% of the `window - 1` pairs of a window to
+    /// be `pair`.
+    pub fn new(pair: [u8; 2], percentage: f64, window: usize) -> Self {
+        let pairs = window.saturating_sub(1);
+        DinucleotideDensity {
+            masks: [iupac_mask(pair[0]), iupac_mask(pair[1])],
+            threshold_count: ((percentage / 100.0) * pairs as f64).ceil() as usize,
+        }
+    }
+
+    /// Whether the bases at `i` and `i + 1` spell the dinucleotide. N never matches.
+    fn matches_at(&self, seq: &[u8], i: usize) -> bool {
+        let fits = |mask: u8, nuc: u8| {
+            let base = iupac_mask(nuc);
+            base.count_ones() == 1 && base & mask != 0
+        };
+        fits(self.masks[0], seq[i]) && fits(self.masks[1], seq[i + 1])
+    }
+}
+
+/// Slide a `window`-sized window across `seq` and call `emit` for every window
+/// starting on `steps` and scorer where the scorer's dinucleotide makes up enough of the window's
+/// adjacent pairs, with the scorer's index in `ScanHit::scorer`.
+///
+/// The score is the percentage of the window's pairs that are the dinucleotide.
+pub fn dinucleotide_windows<F>(seq: &[u8], window: usize, steps: Steps, scorers: &[(&DinucleotideDensity, Strand)], mut emit: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(ScanHit) -> Result<(), Box<dyn Error>>,
+{
+    if window < 2 || seq.len() < window {
+        return Ok(());
+    }
+    let pairs = window - 1;
+
+    // Matching pairs (i, i + 1) for i in [start, start + window - 1), per scorer
+    let mut counts: Vec<usize> = scorers
+        .iter()
+        .map(|(scorer, _)| (0..pairs).filter(|&i| scorer.matches_at(seq, i)).count())
+        .collect();
+    for start in 0..=(seq.len() - window) {
+        for (i, (scorer, strand)) in scorers.iter().enumerate() {
+            if start > 0 {
+                counts[i] -= scorer.matches_at(seq, start - 1) as usize;
+                counts[i] += scorer.matches_at(seq, start + window - 2) as usize;
+            }
+            if counts[i] >= scorer.threshold_count && steps.contains(start) {
+                let score = counts[i] as f64 / pairs as f64 * 100.0;
+                emit(ScanHit { start, end: start + window, score, strand: *strand, scorer: i })?;
+            }
+        }
+    }
+    Ok(())
+}
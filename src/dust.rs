@@ -0,0 +1,186 @@
+use std::collections::VecDeque;
+
+use crate::bases::nuc_code;
+
+/// Length of the words (triplets) whose over-representation DUST scores.
+const WORD_LEN: usize = 3;
+const WORDS: usize = 1 << (2 * WORD_LEN);
+
+/// Default SDUST window and score threshold, as used by minimap2 and BLAST.
+pub const DEFAULT_WINDOW: usize = 64;
+pub const DEFAULT_THRESHOLD: usize = 20;
+
+/// A "perfect interval": a stretch whose DUST score is above the threshold and
+/// at least as high as that of every stretch inside it.
+#[derive(Debug, Clone, Copy)]
+struct PerfectInterval {
+    start: usize,
+    finish: usize,
+    r: i64,
+    l: i64,
+}
+
+/// Rolling state of the symmetric DUST algorithm (SDUST; Morgulis et al.
+/// 2006), following minimap2's implementation.
+struct Sdust {
+    window: usize,
+    threshold: i64,
+    /// Word codes in the current window
+    words: VecDeque<usize>,
+    /// Word counts and scores in the whole window (`cw`, `rw`) and in its
+    /// suffix of length `l` without any word repeated too often (`cv`, `rv`)
+    cw: [i64; WORDS],
+    cv: [i64; WORDS],
+    rw: i64,
+    rv: i64,
+    l: usize,
+    /// Perfect intervals not yet saved, by decreasing start
+    perfect: Vec<PerfectInterval>,
+    masked: Vec<(usize, usize)>,
+}
+
+impl Sdust {
+    fn new(window: usize, threshold: usize) -> Self {
+        Sdust {
+            window: window.max(WORD_LEN),
+            threshold: threshold as i64,
+            words: VecDeque::new(),
+            cw: [0; WORDS],
+            cv: [0; WORDS],
+            rw: 0,
+            rv: 0,
+            l: 0,
+            perfect: Vec::new(),
+            masked: Vec::new(),
+        }
+    }
+
+    fn reset_window(&mut self) {
+        self.words.clear();
+        self.cw = [0; WORDS];
+        self.cv = [0; WORDS];
+        self.rw = 0;
+        self.rv = 0;
+        self.l = 0;
+    }
+
+    fn shift_window(&mut self, word: usize) {
+        if self.words.len() > self.window - WORD_LEN {
+            let old = self.words.pop_front().unwrap();
+            self.cw[old] -= 1;
+            self.rw -= self.cw[old];
+            if self.l > self.words.len() {
+                self.l -= 1;
+                self.cv[old] -= 1;
+                self.rv -= self.cv[old];
+            }
+        }
+        self.words.push_back(word);
+        self.l += 1;
+        self.rw += self.cw[word];
+        self.cw[word] += 1;
+        self.rv += self.cv[word];
+        self.cv[word] += 1;
+
+        // Shrink the suffix until no word is repeated too often in it
+        if self.cv[word] * 10 > self.threshold * 2 {
+            loop {
+                let s = self.words[self.words.len() - self.l];
+                self.cv[s] -= 1;
+                self.rv -= self.cv[s];
+                self.l -= 1;
+                if s == word {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Save the earliest perfect interval once the window has moved past its start.
+    fn save_masked_regions(&mut self, start: usize) {
+        let Some(&last) = self.perfect.last() else {
+            return;
+        };
+        if last.start >= start {
+            return;
+        }
+        match self.masked.last_mut() {
+            Some((_, finish)) if last.start <= *finish => *finish = (*finish).max(last.finish),
+            _ => self.masked.push((last.start, last.finish)),
+        }
+        while self.perfect.last().is_some_and(|p| p.start < start) {
+            self.perfect.pop();
+        }
+    }
+
+    fn find_perfect(&mut self, start: usize) {
+        let mut counts = self.cv;
+        let mut r = self.rv;
+        let (mut max_r, mut max_l) = (0i64, 0i64);
+        for i in (0..self.words.len() - self.l).rev() {
+            let word = self.words[i];
+            r += counts[word];
+            counts[word] += 1;
+            let (new_r, new_l) = (r, (self.words.len() - i - 1) as i64);
+            if new_r * 10 <= self.threshold * new_l {
+                continue;
+            }
+
+            let mut j = 0;
+            while j < self.perfect.len() && self.perfect[j].start >= i + start {
+                let p = self.perfect[j];
+                if max_r == 0 || p.r * max_l > max_r * p.l {
+                    max_r = p.r;
+                    max_l = p.l;
+                }
+                j += 1;
+            }
+            if max_r == 0 || new_r * max_l >= max_r * new_l {
+                max_r = new_r;
+                max_l = new_l;
+                let finish = self.words.len() + WORD_LEN - 1 + start;
+                self.perfect.insert(j, PerfectInterval { start: i + start, finish, r: new_r, l: new_l });
+            }
+        }
+    }
+}
+
+/// Low-complexity intervals of `seq` found by the SDUST algorithm, sorted and
+/// non-overlapping: stretches whose triplet over-representation score
+/// exceeds `threshold` within a `window`-base window (64 and 20 in minimap2).
+///
+/// Bases other than A/C/G/T break the sequence into independent pieces.
+pub fn sdust(seq: &[u8], window: usize, threshold: usize) -> Vec<(usize, usize)> {
+    let mut state = Sdust::new(window, threshold);
+    let w = state.window;
+    let (mut run, mut word) = (0usize, 0usize);
+
+    for i in 0..=seq.len() {
+        match seq.get(i).and_then(|&nuc| nuc_code(nuc)) {
+            Some(code) => {
+                run += 1;
+                word = ((word << 2) | code) & (WORDS - 1);
+                if run >= WORD_LEN {
+                    let start = run.saturating_sub(w) + (i + 1 - run);
+                    state.save_masked_regions(start);
+                    state.shift_window(word);
+                    if state.rw * 10 > state.l as i64 * state.threshold {
+                        state.find_perfect(start);
+                    }
+                }
+            }
+            None => {
+                // Flush the pending intervals, then start afresh after the N
+                let mut start = (run + 1).saturating_sub(w) + (i + 1 - run);
+                while !state.perfect.is_empty() {
+                    state.save_masked_regions(start);
+                    start += 1;
+                }
+                state.reset_window();
+                run = 0;
+                word = 0;
+            }
+        }
+    }
+    state.masked
+}
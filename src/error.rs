@@ -0,0 +1,71 @@
+//! The kinds of failure a scan can end in, each with the exit code the
+//! command line reports it with, so callers (and workflow engines reading
+//! `--error-format json`) can tell a bad option from a bad input.
+//!
+//! The scan parameters, [`crate::Scanner::new`], the scans of
+//! [`crate::Scanner`] that return their hits and the sequence readers of
+//! [`crate::input`] return a `PolyscanError` as it is. Other functions of the
+//! library return `Box<dyn Error>`, where the failures below can be told
+//! apart with `downcast_ref::<PolyscanError>()`.
+
+use thiserror::Error;
+
+/// Exit code for invalid options or combinations of them, as for clap's own usage errors.
+/// Other failures (e.g. writing the output) exit with 1.
+pub const EXIT_PARAMETER: i32 = 2;
+/// Exit code for missing, unreadable or malformed input.
+pub const EXIT_INPUT: i32 = 3;
+/// Exit code for a scan without any output under `--fail-if-empty`.
+pub const EXIT_EMPTY: i32 = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum PolyscanError {
+    /// Invalid options or combinations of them
+    #[error("{0}")]
+    Parameter(String),
+    /// A failure to read the sequence input, as opposed to writing the output
+    #[error("{0}")]
+    Input(String),
+    /// A scan that wrote no records, when that counts as a failure
+    #[error("{0}")]
+    Empty(String),
+}
+
+impl PolyscanError {
+    /// A [`PolyscanError::Parameter`], boxed to be returned as is.
+    pub fn parameter(message: impl Into<String>) -> Box<dyn std::error::Error> {
+        Box::new(PolyscanError::Parameter(message.into()))
+    }
+
+    /// `e` as an input error, keeping its message.
+    pub fn input(e: impl std::fmt::Display) -> Box<dyn std::error::Error> {
+        Box::new(PolyscanError::Input(e.to_string()))
+    }
+
+    /// `e` from reading the input as an input error, unless it already is
+    /// one of these failures.
+    pub fn reading(e: Box<dyn std::error::Error>) -> PolyscanError {
+        match e.downcast::<PolyscanError>() {
+            Ok(e) => *e,
+            Err(e) => PolyscanError::Input(e.to_string()),
+        }
+    }
+
+    /// The exit code of the command line for this failure.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            PolyscanError::Parameter(_) => EXIT_PARAMETER,
+            PolyscanError::Input(_) => EXIT_INPUT,
+            PolyscanError::Empty(_) => EXIT_EMPTY,
+        }
+    }
+
+    /// Short name of the kind of failure: `parameter`, `input` or `empty`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PolyscanError::Parameter(_) => "parameter",
+            PolyscanError::Input(_) => "input",
+            PolyscanError::Empty(_) => "empty",
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use std::error::Error;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::scan::{letter_index, Composition, WindowScorer};
+
+/// A parsed `--expr` window predicate, e.g. `A+T >= 0.8 && N == 0`.
+///
+/// Letters stand for their fraction of the window (0 to 1). Comparisons and
+/// `&&`, `||` and `!` evaluate to 1 (true) or 0 (false), and `+ - * /` and
+/// parentheses work as usual.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(f64),
+    /// Fraction of the window made up of the letter at this index (A = 0)
+    Letter(usize),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(Box<Expr>, Op, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    And,
+    Or,
+}
+
+/// Comparisons allow for this much rounding, so that e.g. `A+T >= 0.8` holds
+/// for a 20 bp window with 16 A/T even though `A` and `T` are summed as
+/// separate fractions.
+const TOLERANCE: f64 = 1e-9;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Letter(u8),
+    Op(Op),
+    Not,
+    Open,
+    Close,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, Box<dyn Error>> {
+    let mut tokens = Vec::new();
+    let mut chars: Peekable<Chars> = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        // Two-character operators
+        let mut followed_by = |next: char| chars.next_if_eq(&next).is_some();
+        let token = match c {
+            ' ' | '\t' => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '+' => Token::Op(Op::Add),
+            '-' => Token::Op(Op::Sub),
+            '*' => Token::Op(Op::Mul),
+            '/' => Token::Op(Op::Div),
+            '<' if followed_by('=') => Token::Op(Op::Le),
+            '<' => Token::Op(Op::Lt),
+            '>' if followed_by('=') => Token::Op(Op::Ge),
+            '>' => Token::Op(Op::Gt),
+            '=' if followed_by('=') => Token::Op(Op::Eq),
+            '!' if followed_by('=') => Token::Op(Op::Ne),
+            '!' => Token::Not,
+            '&' if followed_by('&') => Token::Op(Op::And),
+            '|' if followed_by('|') => Token::Op(Op::Or),
+            c if c.is_ascii_alphabetic() => {
+                let mut name = c.to_string();
+                while let Some(letter) = chars.next_if(|l| l.is_ascii_alphanumeric() || *l == '_') {
+                    name.push(letter);
+                }
+                if name.len() > 1 {
+                    return Err(format!("unknown name '{}' (letters stand alone, e.g. G+C)", name).into());
+                }
+                Token::Letter(c.to_ascii_uppercase() as u8)
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                let mut number = c.to_string();
+                while let Some(digit) = chars.next_if(|d| d.is_ascii_digit() || *d == '.') {
+                    number.push(digit);
+                }
+                Token::Number(number.parse().map_err(|_| format!("invalid number '{}'", number))?)
+            }
+            c => return Err(format!("unexpected '{}'", c).into()),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Recursive-descent parser, from the loosest binding operator (`||`) to the tightest.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek_op(&self, ops: &[Op]) -> Option<Op> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) if ops.contains(op) => Some(*op),
+            _ => None,
+        }
+    }
+
+    /// Left-associative chain of `ops` between operands parsed by `operand`.
+    fn binary(&mut self, ops: &[Op], operand: fn(&mut Parser) -> Result<Expr, Box<dyn Error>>) -> Result<Expr, Box<dyn Error>> {
+        let mut lhs = operand(self)?;
+        while let Some(op) = self.peek_op(ops) {
+            self.pos += 1;
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(operand(self)?));
+        }
+        Ok(lhs)
+    }
+
+    fn or(&mut self) -> Result<Expr, Box<dyn Error>> {
+        self.binary(&[Op::Or], Parser::and)
+    }
+
+    fn and(&mut self) -> Result<Expr, Box<dyn Error>> {
+        self.binary(&[Op::And], Parser::not)
+    }
+
+    fn not(&mut self) -> Result<Expr, Box<dyn Error>> {
+        if self.tokens.get(self.pos) == Some(&Token::Not) {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, Box<dyn Error>> {
+        let lhs = self.sum()?;
+        match self.peek_op(&[Op::Lt, Op::Le, Op::Gt, Op::Ge, Op::Eq, Op::Ne]) {
+            Some(op) => {
+                self.pos += 1;
+                Ok(Expr::Binary(Box::new(lhs), op, Box::new(self.sum()?)))
+            }
+            None => Ok(lhs),
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, Box<dyn Error>> {
+        self.binary(&[Op::Add, Op::Sub], Parser::product)
+    }
+
+    fn product(&mut self) -> Result<Expr, Box<dyn Error>> {
+        self.binary(&[Op::Mul, Op::Div], Parser::unary)
+    }
+
+    fn unary(&mut self) -> Result<Expr, Box<dyn Error>> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        match token {
+            Some(Token::Op(Op::Sub)) => Ok(Expr::Neg(Box::new(self.unary()?))),
+            Some(Token::Number(value)) => Ok(Expr::Number(value)),
+            Some(Token::Letter(letter)) => Ok(Expr::Letter(letter_index(letter).expect("tokenizer keeps ASCII letters"))),
+            Some(Token::Open) => {
+                let inner = self.or()?;
+                if self.tokens.get(self.pos) != Some(&Token::Close) {
+                    return Err("missing ')'".into());
+                }
+                self.pos += 1;
+                Ok(inner)
+            }
+            Some(_) => Err("expected a letter, a number or '('".into()),
+            None => Err("unexpected end of expression".into()),
+        }
+    }
+}
+
+impl Expr {
+    /// Parse an expression such as `A+T >= 0.8 && N == 0`.
+    pub fn parse(text: &str) -> Result<Expr, Box<dyn Error>> {
+        let mut parser = Parser { tokens: tokenize(text)?, pos: 0 };
+        let expr = parser.or()?;
+        if parser.pos < parser.tokens.len() {
+            return Err("expected an operator".into());
+        }
+        Ok(expr)
+    }
+
+    /// Value of the expression for a window's composition.
+    pub fn eval(&self, comp: &Composition) -> f64 {
+        let truth = |value: bool| if value { 1.0 } else { 0.0 };
+        match self {
+            Expr::Number(value) => *value,
+            Expr::Letter(i) => comp.letters[*i] as f64 / comp.len.max(1) as f64,
+            Expr::Neg(inner) => -inner.eval(comp),
+            Expr::Not(inner) => truth(inner.eval(comp) == 0.0),
+            Expr::Binary(lhs, op, rhs) => {
+                let (a, b) = (lhs.eval(comp), rhs.eval(comp));
+                match op {
+                    Op::Add => a + b,
+                    Op::Sub => a - b,
+                    Op::Mul => a * b,
+                    Op::Div => a / b,
+                    Op::Lt => truth(a < b - TOLERANCE),
+                    Op::Le => truth(a <= b + TOLERANCE),
+                    Op::Gt => truth(a > b + TOLERANCE),
+                    Op::Ge => truth(a >= b - TOLERANCE),
+                    Op::Eq => truth((a - b).abs() <= TOLERANCE),
+                    Op::Ne => truth((a - b).abs() > TOLERANCE),
+                    Op::And => truth(a != 0.0 && b != 0.0),
+                    Op::Or => truth(a != 0.0 || b != 0.0),
+                }
+            }
+        }
+    }
+}
+
+/// Windows where an [`Expr`] is true (or, for plain arithmetic, positive),
+/// scored by its value times 100.
+impl WindowScorer for Expr {
+    fn score(&self, comp: &Composition) -> Option<f64> {
+        let value = self.eval(comp);
+        (value > 0.0).then_some(value * 100.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(text: &str, seq: &[u8]) -> f64 {
+        Expr::parse(text).unwrap().eval(&Composition::from_seq(seq))
+    }
+
+    fn error(text: &str) -> String {
+        Expr::parse(text).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn operators_bind_by_precedence() {
+        assert_eq!(eval("1 + 2 * 3", b""), 7.0);
+        assert_eq!(eval("(1 + 2) * 3", b""), 9.0);
+        assert_eq!(eval("8 - 4 - 2", b""), 2.0);
+        assert_eq!(eval("8 / 4 / 2", b""), 1.0);
+        assert_eq!(eval("1 || 0 && 0", b""), 1.0);
+        assert_eq!(eval("!0 && 0", b""), 0.0);
+        assert_eq!(eval("A + T >= 0.5 && N == 0", b"AATG"), 1.0);
+        assert_eq!(eval("A + T >= 0.5 && N == 0", b"AATN"), 0.0);
+    }
+
+    #[test]
+    fn minus_negates() {
+        assert_eq!(eval("-A + 1", b"AACC"), 0.5);
+        assert_eq!(eval("--1", b""), 1.0);
+        assert_eq!(eval("2 * -3", b""), -6.0);
+        assert_eq!(eval("-(1 + 2)", b""), -3.0);
+    }
+
+    #[test]
+    fn division_by_zero_follows_floats() {
+        assert_eq!(eval("A / C", b"AAAA"), f64::INFINITY);
+        assert!(eval("(G - C) / (G + C)", b"AAAA").is_nan());
+        assert_eq!(eval("(G - C) / (G + C) < 0", b"AAAA"), 0.0);
+        assert_eq!(Expr::parse("(G - C) / (G + C)").unwrap().score(&Composition::from_seq(b"AAAA")), None);
+    }
+
+    #[test]
+    fn letters_are_case_insensitive_fractions() {
+        assert_eq!(Expr::parse("a").unwrap(), Expr::Letter(0));
+        assert_eq!(eval("a + z", b"AZZT"), 0.75);
+        assert_eq!(eval("A", b""), 0.0);
+    }
+
+    #[test]
+    fn unknown_names_and_bad_syntax_are_errors() {
+        assert_eq!(error("GC >= 0.5"), "unknown name 'GC' (letters stand alone, e.g. G+C)");
+        assert_eq!(error("len > 10"), "unknown name 'len' (letters stand alone, e.g. G+C)");
+        assert_eq!(error("A $ 1"), "unexpected '$'");
+        assert_eq!(error("A = 1"), "unexpected '='");
+        assert_eq!(error("1..2"), "invalid number '1..2'");
+        assert_eq!(error("A +"), "unexpected end of expression");
+        assert_eq!(error(""), "unexpected end of expression");
+        assert_eq!(error("(A + T"), "missing ')'");
+        assert_eq!(error("A * )"), "expected a letter, a number or '('");
+        assert_eq!(error("A 1"), "expected an operator");
+    }
+}
@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use flate2::read::MultiGzDecoder;
+
+/// One line of a samtools `.fai` index.
+#[derive(Debug, Clone)]
+pub struct FaiEntry {
+    pub name: String,
+    pub length: u64,
+    pub offset: u64,
+    pub line_bases: u64,
+    pub line_width: u64,
+}
+
+impl FaiEntry {
+    /// Byte offset (in the uncompressed FASTA) of 0-based position `pos`.
+    pub fn position_offset(&self, pos: u64) -> u64 {
+        self.offset + (pos / self.line_bases) * self.line_width + pos % self.line_bases
+    }
+}
+
+/// A samtools `.fai` index.
+#[derive(Debug, Clone, Default)]
+pub struct FaiIndex {
+    pub entries: Vec<FaiEntry>,
+    /// Entry position by contig name
+    positions: HashMap<String, usize>,
+}
+
+impl FaiIndex {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Self::from_text(&std::fs::read_to_string(path)?)
+    }
+
+    /// The index in the text of a `.fai` file, e.g. one fetched remotely.
+    pub fn from_text(text: &str) -> Result<Self, Box<dyn Error>> {
+        let mut entries = Vec::new();
+        for line in text.lines() {
+            if line.is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() < 5 {
+                return Err(format!("malformed .fai line: {}", line).into());
+            }
+            entries.push(FaiEntry {
+                name: fields[0].to_string(),
+                length: fields[1].parse()?,
+                offset: fields[2].parse()?,
+                line_bases: fields[3].parse()?,
+                line_width: fields[4].parse()?,
+            });
+        }
+        let positions = entries.iter().enumerate().map(|(i, e)| (e.name.clone(), i)).collect();
+        Ok(FaiIndex { entries, positions })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&FaiEntry> {
+        self.position(name).map(|i| &self.entries[i])
+    }
+
+    /// Index of `name` in file order.
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.positions.get(name).copied()
+    }
+
+    /// Byte range (in the uncompressed FASTA) holding the header line of the
+    /// contig at `position`, between the end of the previous contig's bases
+    /// and the start of its own; see [`definition_line`].
+    pub fn header_range(&self, position: usize) -> Range<u64> {
+        let start = match position {
+            0 => 0,
+            // An empty contig has no lines (and a line length of 0)
+            _ => match &self.entries[position - 1] {
+                previous if previous.length == 0 => previous.offset,
+                previous => previous.position_offset(previous.length),
+            },
+        };
+        start..self.entries[position].offset
+    }
+}
+
+/// The definition line (name and description, without the `>`) in the bytes
+/// of a [`FaiIndex::header_range`].
+pub fn definition_line(raw: &[u8]) -> String {
+    let raw = String::from_utf8_lossy(raw);
+    let line = raw.lines().map(str::trim).find(|line| line.starts_with('>')).unwrap_or_default();
+    line.trim_start_matches('>').to_string()
+}
+
+/// A bgzip `.gzi` index: (compressed, uncompressed) offsets of each BGZF block.
+#[derive(Debug, Clone, Default)]
+pub struct GziIndex {
+    blocks: Vec<(u64, u64)>,
+}
+
+impl GziIndex {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut word = [0u8; 8];
+        reader.read_exact(&mut word)?;
+        let count = u64::from_le_bytes(word);
+
+        // The first block (0, 0) is implicit
+        let mut blocks = vec![(0, 0)];
+        for _ in 0..count {
+            reader.read_exact(&mut word)?;
+            let compressed = u64::from_le_bytes(word);
+            reader.read_exact(&mut word)?;
+            let uncompressed = u64::from_le_bytes(word);
+            blocks.push((compressed, uncompressed));
+        }
+        Ok(GziIndex { blocks })
+    }
+
+    /// The block containing uncompressed offset `pos`, as (compressed, uncompressed) offsets.
+    pub fn block_for(&self, pos: u64) -> (u64, u64) {
+        let i = self.blocks.partition_point(|&(_, u)| u <= pos);
+        self.blocks[i.saturating_sub(1)]
+    }
+}
+
+/// A FASTA with a `.fai` index (and a `.gzi` index if it is bgzip-compressed),
+/// allowing sequence ranges to be fetched without reading the whole file.
+pub struct IndexedFasta {
+    path: PathBuf,
+    pub fai: FaiIndex,
+    gzi: Option<GziIndex>,
+}
+
+impl IndexedFasta {
+    /// Open `path` for random access, or return `None` if the required index
+    /// files are missing (or the file is compressed with something other than bgzip).
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Option<Self>, Box<dyn Error>> {
+        let path = path.as_ref().to_path_buf();
+        let fai_path = with_suffix(&path, ".fai");
+        if !fai_path.exists() {
+            return Ok(None);
+        }
+
+        let mut magic = [0u8; 2];
+        let compressed = File::open(&path)?.read_exact(&mut magic).is_ok() && magic == [0x1f, 0x8b];
+        let gzi = if compressed {
+            let gzi_path = with_suffix(&path, ".gzi");
+            if !gzi_path.exists() {
+                return Ok(None);
+            }
+            Some(GziIndex::from_path(gzi_path)?)
+        } else {
+            None
+        };
+
+        Ok(Some(IndexedFasta { fai: FaiIndex::from_path(fai_path)?, path, gzi }))
+    }
+
+    /// Fetch bases `[start, end)` of contig `name` (0-based), with line breaks removed.
+    pub fn fetch(&self, name: &str, start: u64, end: u64) -> Result<Vec<u8>, Box<dyn Error>> {
+        let entry = self.fai.get(name).ok_or_else(|| format!("contig '{}' not found in .fai index", name))?;
+        let end = end.min(entry.length);
+        if start >= end {
+            return Ok(Vec::new());
+        }
+
+        let mut raw = self.read_raw(entry.position_offset(start)..entry.position_offset(end - 1) + 1)?;
+        raw.retain(|&b| b != b'\n' && b != b'\r');
+        Ok(raw)
+    }
+
+    /// The definition line (name and description, without the `>`) of the
+    /// contig at `position` in the index.
+    pub fn definition(&self, position: usize) -> Result<String, Box<dyn Error>> {
+        Ok(definition_line(&self.read_raw(self.fai.header_range(position))?))
+    }
+
+    // Bytes `range` of the uncompressed file
+    fn read_raw(&self, range: Range<u64>) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut file = File::open(&self.path)?;
+        let mut raw = vec![0u8; (range.end - range.start) as usize];
+        match &self.gzi {
+            None => {
+                file.seek(SeekFrom::Start(range.start))?;
+                file.read_exact(&mut raw)?;
+            }
+            Some(gzi) => {
+                // Jump to the BGZF block holding the start, then skip into it
+                let (compressed, uncompressed) = gzi.block_for(range.start);
+                file.seek(SeekFrom::Start(compressed))?;
+                let mut decoder = MultiGzDecoder::new(BufReader::new(file));
+                std::io::copy(&mut (&mut decoder).take(range.start - uncompressed), &mut std::io::sink())?;
+                decoder.read_exact(&mut raw)?;
+            }
+        }
+        Ok(raw)
+    }
+}
+
+pub(crate) fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut s = path.as_os_str().to_owned();
+    s.push(suffix);
+    PathBuf::from(s)
+}
+
+/// A genomic region from `--region`, in 0-based half-open coordinates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub chrom: String,
+    pub start: u64,
+    /// `None` means "to the end of the contig".
+    pub end: Option<u64>,
+}
+
+impl Region {
+    /// The bases of a contig of `contig_len` bases the region covers, cut at
+    /// the contig end, or an error if the region starts past that end.
+    pub fn span(&self, contig_len: u64) -> Result<Range<u64>, String> {
+        if self.start > 0 && self.start >= contig_len {
+            return Err(format!("region {} starts past the end of {}, which has {} bases", self, self.chrom, contig_len));
+        }
+        Ok(self.start..self.end.unwrap_or(contig_len).min(contig_len))
+    }
+}
+
+impl std::str::FromStr for Region {
+    type Err = String;
+
+    /// Parse samtools-style `chrom`, `chrom:start` or `chrom:start-end` (1-based, inclusive).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((chrom, range)) = s.rsplit_once(':') else {
+            return Ok(Region { chrom: s.to_string(), start: 0, end: None });
+        };
+        let parse = |v: &str| -> Result<u64, String> {
+            v.replace(',', "").parse::<u64>().map_err(|_| format!("invalid region '{}'", s))
+        };
+        let (start, end) = match range.split_once('-') {
+            Some((a, b)) => (parse(a)?, Some(parse(b)?)),
+            None => (parse(range)?, None),
+        };
+        if start == 0 || end.is_some_and(|e| e < start) {
+            return Err(format!("invalid region '{}'", s));
+        }
+        Ok(Region { chrom: chrom.to_string(), start: start - 1, end })
+    }
+}
+
+impl std::fmt::Display for Region {
+    /// Format back into samtools style (1-based, inclusive).
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.end {
+            Some(end) => write!(f, "{}:{}-{}", self.chrom, self.start + 1, end),
+            None if self.start == 0 => write!(f, "{}", self.chrom),
+            None => write!(f, "{}:{}", self.chrom, self.start + 1),
+        }
+    }
+}
@@ -0,0 +1,115 @@
+/// Per-hit caveat flags, stored as a bitfield.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HitFlags(pub u8);
+
+impl HitFlags {
+    /// Window lies within one window length of either contig end.
+    pub const NEAR_CONTIG_END: u8 = 1 << 0;
+    /// Window contains at least one N.
+    pub const CONTAINS_N: u8 = 1 << 1;
+    /// Window overlaps an assembly gap (a run of at least `MIN_GAP_LEN` Ns).
+    pub const OVERLAPS_GAP: u8 = 1 << 2;
+    /// Hit was cut short at a chunk boundary while scanning in chunks.
+    pub const TRUNCATED_BY_CHUNKING: u8 = 1 << 3;
+    /// Window is shorter than the requested window size.
+    pub const PARTIAL_WINDOW: u8 = 1 << 4;
+
+    const TAGS: [(u8, &'static str); 5] = [
+        (Self::NEAR_CONTIG_END, "near-contig-end"),
+        (Self::CONTAINS_N, "contains-N"),
+        (Self::OVERLAPS_GAP, "overlaps-gap"),
+        (Self::TRUNCATED_BY_CHUNKING, "truncated-by-chunking"),
+        (Self::PARTIAL_WINDOW, "partial-window"),
+    ];
+
+    pub fn set(&mut self, flag: u8) {
+        self.0 |= flag;
+    }
+
+    pub fn clear(&mut self, flag: u8) {
+        self.0 &= !flag;
+    }
+
+    pub fn contains(&self, flag: u8) -> bool {
+        self.0 & flag != 0
+    }
+
+    /// Comma-separated tag list, or "." when no flag is set.
+    pub fn to_tags(&self) -> String {
+        let tags: Vec<&str> = Self::TAGS
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, tag)| *tag)
+            .collect();
+        if tags.is_empty() {
+            ".".to_string()
+        } else {
+            tags.join(",")
+        }
+    }
+}
+
+/// Minimum length of an N run treated as an assembly gap.
+pub const MIN_GAP_LEN: usize = 10;
+
+/// Assembly gaps (runs of at least `MIN_GAP_LEN` Ns) in one sequence, sorted by start.
+#[derive(Debug, Clone, Default)]
+pub struct GapIndex {
+    gaps: Vec<(usize, usize)>,
+}
+
+impl GapIndex {
+    pub fn from_seq(seq: &[u8]) -> Self {
+        let mut gaps = Vec::new();
+        let mut run_start = None;
+        for (i, &nuc) in seq.iter().chain(std::iter::once(&b'$')).enumerate() {
+            match (nuc == b'N' || nuc == b'n', run_start) {
+                (true, None) => run_start = Some(i),
+                (false, Some(start)) => {
+                    if i - start >= MIN_GAP_LEN {
+                        gaps.push((start, i));
+                    }
+                    run_start = None;
+                }
+                _ => (),
+            }
+        }
+        GapIndex { gaps }
+    }
+
+    /// Does `[start, end)` overlap any gap?
+    pub fn overlaps(&self, start: usize, end: usize) -> bool {
+        // First gap ending after `start`; gaps don't overlap each other so ends are sorted too
+        let i = self.gaps.partition_point(|&(_, gap_end)| gap_end <= start);
+        self.gaps.get(i).is_some_and(|&(gap_start, _)| gap_start < end)
+    }
+}
+
+/// Flags for the window `[start, end)` of `seq`, a sequence that begins at
+/// `offset` within a contig of `contig_len` bases.
+pub fn window_flags(
+    seq: &[u8],
+    gaps: &GapIndex,
+    start: usize,
+    end: usize,
+    offset: usize,
+    contig_len: usize,
+    window: usize,
+) -> HitFlags {
+    let mut flags = HitFlags::default();
+
+    if offset + start < window || offset + end + window > contig_len {
+        flags.set(HitFlags::NEAR_CONTIG_END);
+    }
+    if seq[start..end].iter().any(|&b| b == b'N' || b == b'n') {
+        flags.set(HitFlags::CONTAINS_N);
+    }
+    if gaps.overlaps(start, end) {
+        flags.set(HitFlags::OVERLAPS_GAP);
+    }
+    if end - start < window {
+        flags.set(HitFlags::PARTIAL_WINDOW);
+    }
+
+    flags
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::error::Error;
+
+use crate::scan::{ScanHit, Steps, Strand};
+
+/// Shortest G-tract in the canonical motif.
+const MIN_TRACT: usize = 3;
+/// Loop length range in the canonical motif.
+const MIN_LOOP: usize = 1;
+const MAX_LOOP: usize = 7;
+
+/// Per-base G4Hunter scores: bases in a run of G score the run length (capped
+/// at 4), bases in a run of C score minus the run length, anything else 0.
+pub fn g4hunter_scores(seq: &[u8]) -> Vec<i8> {
+    let mut scores = vec![0i8; seq.len()];
+    let mut i = 0;
+    while i < seq.len() {
+        let base = seq[i].to_ascii_uppercase();
+        let run = seq[i..].iter().take_while(|b| b.to_ascii_uppercase() == base).count();
+        let value = match base {
+            b'G' => run.min(4) as i8,
+            b'C' => -(run.min(4) as i8),
+            _ => 0,
+        };
+        scores[i..i + run].fill(value);
+        i += run;
+    }
+    scores
+}
+
+/// Mean G4Hunter score of `seq`: positive for G-rich, negative for C-rich
+/// (i.e. G-rich on the minus strand) sequence.
+pub fn g4hunter_score(seq: &[u8]) -> f64 {
+    if seq.is_empty() {
+        return 0.0;
+    }
+    g4hunter_scores(seq).iter().map(|&s| s as f64).sum::<f64>() / seq.len() as f64
+}
+
+/// End of a `tracts`-tract motif starting at `pos` with tracts of `base`,
+/// matched like the regex `(G{3,}.{1,7}){3}G{3,}`: longest tracts and loops first.
+///
+/// Results are memoized in `seen`, so long G runs don't make backtracking explode.
+fn motif_end(seq: &[u8], pos: usize, tracts: usize, base: u8, seen: &mut HashMap<(usize, usize), Option<usize>>) -> Option<usize> {
+    if let Some(&end) = seen.get(&(pos, tracts)) {
+        return end;
+    }
+    let end = motif_end_uncached(seq, pos, tracts, base, seen);
+    seen.insert((pos, tracts), end);
+    end
+}
+
+fn motif_end_uncached(seq: &[u8], pos: usize, tracts: usize, base: u8, seen: &mut HashMap<(usize, usize), Option<usize>>) -> Option<usize> {
+    let run = seq[pos..].iter().take_while(|b| b.to_ascii_uppercase() == base).count();
+    if run < MIN_TRACT {
+        return None;
+    }
+    if tracts == 1 {
+        return Some(pos + run);
+    }
+    for tract in (MIN_TRACT..=run).rev() {
+        for loop_len in (MIN_LOOP..=MAX_LOOP).rev() {
+            let next = pos + tract + loop_len;
+            if next >= seq.len() {
+                continue;
+            }
+            if let Some(end) = motif_end(seq, next, tracts - 1, base, seen) {
+                return Some(end);
+            }
+        }
+    }
+    None
+}
+
+/// Call `emit` for every canonical G-quadruplex motif (`G3+N1-7G3+N1-7G3+N1-7G3+`)
+/// on the plus strand, and every `C3+N1-7...` motif as a minus-strand G4.
+///
+/// Motifs are matched left to right without overlap on each strand, like a
+/// regex search, and scored by their absolute G4Hunter score times 100.
+pub fn find_g4_motifs<F>(seq: &[u8], mut emit: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(ScanHit) -> Result<(), Box<dyn Error>>,
+{
+    let mut hits = Vec::new();
+    for (base, strand) in [(b'G', Strand::Plus), (b'C', Strand::Minus)] {
+        let mut seen = HashMap::new();
+        let mut pos = 0;
+        while pos < seq.len() {
+            // Only positions already tried can be cached, so drop the rest as we go
+            if seen.len() > 4096 {
+                seen.retain(|&(at, _), _| at >= pos);
+            }
+            match motif_end(seq, pos, 4, base, &mut seen) {
+                Some(end) => {
+                    let score = g4hunter_score(&seq[pos..end]).abs() * 100.0;
+                    hits.push(ScanHit { start: pos, end, score, strand, scorer: 0 });
+                    pos = end;
+                }
+                None => pos += 1,
+            }
+        }
+    }
+
+    hits.sort_by_key(|hit| (hit.start, hit.strand == Strand::Minus));
+    for hit in hits {
+        emit(hit)?;
+    }
+    Ok(())
+}
+
+/// Slide a `window`-sized window across `seq` and call `emit` for every window
+/// starting on `steps` whose mean G4Hunter score reaches `threshold` (plus strand) or falls to
+/// `-threshold` (minus strand), scored by the absolute mean times 100.
+pub fn g4hunter_windows<F>(seq: &[u8], window: usize, steps: Steps, threshold: f64, mut emit: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(ScanHit) -> Result<(), Box<dyn Error>>,
+{
+    if window == 0 || seq.len() < window {
+        return Ok(());
+    }
+    let scores = g4hunter_scores(seq);
+    let mut sum: i64 = scores[..window].iter().map(|&s| s as i64).sum();
+    for start in 0..=(seq.len() - window) {
+        if start > 0 {
+            sum += scores[start + window - 1] as i64 - scores[start - 1] as i64;
+        }
+        if !steps.contains(start) {
+            continue;
+        }
+        let mean = sum as f64 / window as f64;
+        let strand = if mean >= threshold {
+            Strand::Plus
+        } else if mean <= -threshold {
+            Strand::Minus
+        } else {
+            continue;
+        };
+        emit(ScanHit { start, end: start + window, score: mean.abs() * 100.0, strand, scorer: 0 })?;
+    }
+    Ok(())
+}
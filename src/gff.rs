@@ -0,0 +1,472 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::scan::Strand;
+
+/// An intron, in 0-based half-open coordinates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Intron {
+    pub chrom: String,
+    pub start: u64,
+    pub end: u64,
+    pub strand: Strand,
+    /// `ID` of an `intron` feature, or `<transcript>.intron<n>` (numbered 5' to 3')
+    pub id: String,
+}
+
+impl Intron {
+    /// The last `length` bases of the intron in transcription order (its 3'
+    /// end, next to the acceptor site), clipped to the intron.
+    pub fn three_prime_end(&self, length: u64) -> (u64, u64) {
+        match self.strand {
+            Strand::Minus => (self.start, (self.start + length).min(self.end)),
+            _ => (self.end.saturating_sub(length).max(self.start), self.end),
+        }
+    }
+}
+
+fn attribute<'a>(attributes: &'a str, key: &str) -> Option<&'a str> {
+    attributes.split(';').find_map(|field| field.trim().strip_prefix(key)?.strip_prefix('='))
+}
+
+/// Read the introns of a GFF3 file (optionally compressed): `intron` features
+/// as given, and the gaps between consecutive exons of each transcript
+/// (exons are grouped by their `Parent`).
+///
+/// An intron shared by several transcripts is reported once, under the first
+/// name it gets.
+pub fn read_introns<P: AsRef<Path>>(path: P) -> Result<Vec<Intron>, Box<dyn Error>> {
+    let path = path.as_ref();
+    let (reader, _compression_format) = niffler::get_reader(Box::new(File::open(path)?))?;
+
+    let mut introns: Vec<Intron> = Vec::new();
+    // Exons per transcript, with the transcripts in order of first appearance
+    let mut order: Vec<String> = Vec::new();
+    let mut exons: HashMap<String, Vec<(String, u64, u64, Strand)>> = HashMap::new();
+
+    for (i, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        if line.starts_with("##FASTA") {
+            break;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = || format!("{}: invalid GFF3 line {}", path.display(), i + 1);
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 9 {
+            return Err(invalid().into());
+        }
+        let feature = fields[2];
+        if feature != "exon" && feature != "intron" {
+            continue;
+        }
+        let start: u64 = fields[3].parse().map_err(|_| invalid())?;
+        let end: u64 = fields[4].parse().map_err(|_| invalid())?;
+        if start == 0 || end < start {
+            return Err(invalid().into());
+        }
+        let strand = match fields[6] {
+            "+" => Strand::Plus,
+            "-" => Strand::Minus,
+            _ => Strand::Unstranded,
+        };
+        let (chrom, attributes) = (fields[0].to_string(), fields[8]);
+
+        if feature == "intron" {
+            let id = attribute(attributes, "ID")
+                .map(str::to_string)
+                .or_else(|| attribute(attributes, "Parent").map(|parent| format!("{}.intron", parent)))
+                .unwrap_or_else(|| format!("intron{}", introns.len() + 1));
+            introns.push(Intron { chrom, start: start - 1, end, strand, id });
+            continue;
+        }
+        for parent in attribute(attributes, "Parent").unwrap_or("").split(',').filter(|p| !p.is_empty()) {
+            exons
+                .entry(parent.to_string())
+                .or_insert_with(|| {
+                    order.push(parent.to_string());
+                    Vec::new()
+                })
+                .push((chrom.clone(), start - 1, end, strand));
+        }
+    }
+
+    for transcript in order {
+        let mut transcript_exons = exons.remove(&transcript).unwrap_or_default();
+        transcript_exons.sort_by_key(|&(_, start, _, _)| start);
+        let gaps: Vec<(String, u64, u64, Strand)> = transcript_exons
+            .windows(2)
+            .filter(|pair| pair[0].0 == pair[1].0 && pair[0].2 < pair[1].1)
+            .map(|pair| (pair[0].0.clone(), pair[0].2, pair[1].1, pair[0].3))
+            .collect();
+        let count = gaps.len();
+        for (i, (chrom, start, end, strand)) in gaps.into_iter().enumerate() {
+            let number = if strand == Strand::Minus { count - i } else { i + 1 };
+            introns.push(Intron { chrom, start, end, strand, id: format!("{}.intron{}", transcript, number) });
+        }
+    }
+
+    let mut seen = HashSet::new();
+    introns.retain(|intron| seen.insert((intron.chrom.clone(), intron.start, intron.end, intron.strand)));
+    Ok(introns)
+}
+
+/// Where a region lies relative to the genes of an [`Annotation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GeneContext {
+    Intergenic,
+    Intron,
+    Exon,
+}
+
+impl GeneContext {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GeneContext::Intergenic => "intergenic",
+            GeneContext::Intron => "intron",
+            GeneContext::Exon => "exon",
+        }
+    }
+}
+
+/// Intervals of one contig sorted by start, with the running maximum of their
+/// ends so overlap queries can stop early.
+#[derive(Debug, Default)]
+struct FeatureIndex {
+    // (start, end, gene), 0-based half-open
+    features: Vec<(u64, u64, String)>,
+    max_end: Vec<u64>,
+}
+
+impl FeatureIndex {
+    fn build(mut features: Vec<(u64, u64, String)>) -> Self {
+        features.sort();
+        let max_end = features
+            .iter()
+            .scan(0, |max_end, &(_, end, _)| {
+                *max_end = end.max(*max_end);
+                Some(*max_end)
+            })
+            .collect();
+        FeatureIndex { features, max_end }
+    }
+
+    /// The feature closest to `start..end` and the number of bases between
+    /// them (0 if they overlap or touch); ties go to the one on the left.
+    fn nearest(&self, start: u64, end: u64) -> Option<(&(u64, u64, String), u64)> {
+        if let Some(overlapping) = self.overlapping(start, end).last() {
+            return Some((overlapping, 0));
+        }
+        // Features starting before `start` all end by then; the one ending last is closest
+        let before = self.features.partition_point(|&(feature_start, _, _)| feature_start < start);
+        let left = before.checked_sub(1).map(|last| {
+            let max_end = self.max_end[last];
+            let i = (0..=last).rev().find(|&i| self.features[i].1 == max_end).unwrap_or(last);
+            (&self.features[i], start - max_end)
+        });
+        let after = self.features.partition_point(|&(feature_start, _, _)| feature_start < end);
+        let right = self.features.get(after).map(|feature| (feature, feature.0 - end));
+        match (left, right) {
+            (Some(left), Some(right)) if right.1 < left.1 => Some(right),
+            (Some(left), _) => Some(left),
+            (None, right) => right,
+        }
+    }
+
+    /// The features overlapping `start..end`.
+    fn overlapping(&self, start: u64, end: u64) -> impl Iterator<Item = &(u64, u64, String)> {
+        let before_end = self.features.partition_point(|&(feature_start, _, _)| feature_start < end);
+        (0..before_end)
+            .rev()
+            .take_while(move |&i| self.max_end[i] > start)
+            .map(|i| &self.features[i])
+            .filter(move |&&(_, feature_end, _)| feature_end > start)
+    }
+
+    /// Bases of `start..end` covered by the features of `gene`, each base counted once.
+    fn gene_coverage(&self, gene: &str, start: u64, end: u64) -> u64 {
+        let mut clipped: Vec<(u64, u64)> = self
+            .overlapping(start, end)
+            .filter(|(_, _, feature_gene)| feature_gene == gene)
+            .map(|&(feature_start, feature_end, _)| (feature_start.max(start), feature_end.min(end)))
+            .collect();
+        clipped.sort_unstable();
+        let (mut covered, mut reached) = (0, start);
+        for (clip_start, clip_end) in clipped {
+            let clip_start = clip_start.max(reached);
+            if clip_end > clip_start {
+                covered += clip_end - clip_start;
+                reached = clip_end;
+            }
+        }
+        covered
+    }
+}
+
+/// Bases of a region in the coding sequence, the UTRs (the rest of the
+/// exons) and the introns (the rest) of one gene.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GeneBases {
+    pub cds: u64,
+    pub utr: u64,
+    pub intron: u64,
+}
+
+/// Genes, their exons and their coding sequences, per contig, for annotating hits.
+#[derive(Debug, Default)]
+pub struct Annotation {
+    genes: HashMap<String, FeatureIndex>,
+    // Exonic features: exons, and the CDS and UTRs that lie in them, as
+    // files without exon lines (e.g. NCBI and Prokka prokaryotes) give only those
+    exons: HashMap<String, FeatureIndex>,
+    cds: HashMap<String, FeatureIndex>,
+    // Strand of every gene, as first given
+    strands: HashMap<String, Strand>,
+}
+
+impl Annotation {
+    /// Whether `start..end` of `chrom` overlaps an exon (or a CDS or UTR),
+    /// only introns (the rest of a gene) or no gene, and the IDs of the genes
+    /// it overlaps.
+    pub fn annotate(&self, chrom: &str, start: u64, end: u64) -> (GeneContext, Vec<&str>) {
+        let mut genes: Vec<&str> = Vec::new();
+        for (_, _, gene) in self.genes.get(chrom).into_iter().flat_map(|index| index.overlapping(start, end)) {
+            if !genes.contains(&gene.as_str()) {
+                genes.push(gene);
+            }
+        }
+        genes.sort_unstable();
+        let in_exon = self.exons.get(chrom).is_some_and(|index| index.overlapping(start, end).next().is_some());
+        let context = if in_exon {
+            GeneContext::Exon
+        } else if !genes.is_empty() {
+            GeneContext::Intron
+        } else {
+            GeneContext::Intergenic
+        };
+        (context, genes)
+    }
+
+    /// The gene of `chrom` closest to `start..end`, and the number of bases
+    /// between them: 0 when they overlap or touch, negative when the region
+    /// lies upstream of the gene (relative to its strand), positive downstream.
+    pub fn closest(&self, chrom: &str, start: u64, end: u64) -> Option<(&str, i64)> {
+        let ((gene_start, _, gene), distance) = self.genes.get(chrom)?.nearest(start, end)?;
+        let before_gene = end <= *gene_start;
+        let upstream = match self.strands.get(gene) {
+            Some(Strand::Minus) => !before_gene,
+            _ => before_gene,
+        };
+        let distance = distance as i64;
+        Some((gene, if upstream { -distance } else { distance }))
+    }
+
+    /// The genes of `chrom` that `start..end` overlaps, with how many of its
+    /// bases fall in their CDS, UTRs (the rest of their exons) and introns.
+    /// Genes without CDS features have all their exonic bases counted as UTR,
+    /// and CDS outside any exon line (files without exons) are still CDS.
+    pub fn gene_bases(&self, chrom: &str, start: u64, end: u64) -> Vec<(&str, GeneBases)> {
+        let Some(genes) = self.genes.get(chrom) else {
+            return Vec::new();
+        };
+        let mut overlapped: Vec<(&str, GeneBases)> = Vec::new();
+        for (gene_start, gene_end, gene) in genes.overlapping(start, end) {
+            let (start, end) = (start.max(*gene_start), end.min(*gene_end));
+            // The exonic index holds the CDS too, so its coverage is of exons and CDS together
+            let exon = self.exons.get(chrom).map_or(0, |exons| exons.gene_coverage(gene, start, end));
+            let cds = self.cds.get(chrom).map_or(0, |cds| cds.gene_coverage(gene, start, end));
+            overlapped.push((gene, GeneBases { cds, utr: exon - cds, intron: end - start - exon }));
+        }
+        overlapped.sort_unstable_by_key(|(gene, _)| *gene);
+        overlapped
+    }
+
+    /// Every gene as `(chrom, start, end, id)`, by contig name and then position.
+    pub fn genes(&self) -> Vec<(&str, u64, u64, &str)> {
+        let mut genes: Vec<(&str, u64, u64, &str)> = self
+            .genes
+            .iter()
+            .flat_map(|(chrom, index)| index.features.iter().map(move |(start, end, gene)| (chrom.as_str(), *start, *end, gene.as_str())))
+            .collect();
+        genes.sort_unstable();
+        genes
+    }
+}
+
+// UTR feature types of GFF3 (SO terms) and GTF
+fn is_utr(feature: &str) -> bool {
+    matches!(feature, "five_prime_UTR" | "three_prime_UTR" | "UTR" | "5UTR" | "3UTR" | "five_prime_utr" | "three_prime_utr")
+}
+
+// A GTF attribute, e.g. `gene_id "ENSG0001";`
+fn gtf_attribute<'a>(attributes: &'a str, key: &str) -> Option<&'a str> {
+    attributes.split(';').find_map(|field| field.trim().strip_prefix(key)?.strip_prefix(' ')?.trim().strip_prefix('"')?.strip_suffix('"'))
+}
+
+/// Read the genes, exons and CDS of a GFF3 or GTF file (optionally compressed).
+///
+/// In GFF3, features are assigned to the gene at the top of their `Parent`
+/// chain; in GTF, to their `gene_id`. A gene spans its `gene` feature and
+/// everything assigned to it, so GTF files without gene lines work too.
+pub fn read_annotation<P: AsRef<Path>>(path: P) -> Result<Annotation, Box<dyn Error>> {
+    let path = path.as_ref();
+    let (reader, _compression_format) = niffler::get_reader(Box::new(File::open(path)?))?;
+
+    // GFF3 IDs with their parent (if any) and feature type
+    let mut parents: HashMap<String, (Option<String>, String)> = HashMap::new();
+    // Features as read: chrom, start, end, strand, type, and the gene (GTF) or the
+    // parent or own ID (GFF3) they belong to
+    let mut features: Vec<(String, u64, u64, Strand, String, String)> = Vec::new();
+
+    for (i, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        if line.starts_with("##FASTA") {
+            break;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = || format!("{}: invalid GFF3/GTF line {}", path.display(), i + 1);
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 9 {
+            return Err(invalid().into());
+        }
+        let start: u64 = fields[3].parse().map_err(|_| invalid())?;
+        let end: u64 = fields[4].parse().map_err(|_| invalid())?;
+        if start == 0 || end < start {
+            return Err(invalid().into());
+        }
+        let (chrom, feature, attributes) = (fields[0].to_string(), fields[2].to_string(), fields[8]);
+        let strand = match fields[6] {
+            "+" => Strand::Plus,
+            "-" => Strand::Minus,
+            _ => Strand::Unstranded,
+        };
+
+        let owner = if let Some(gene) = gtf_attribute(attributes, "gene_id") {
+            gene.to_string()
+        } else {
+            let id = attribute(attributes, "ID");
+            // Only the first parent is followed; alternative parents belong to the same gene
+            let parent = attribute(attributes, "Parent").and_then(|parents| parents.split(',').next());
+            if let Some(id) = id {
+                parents.insert(id.to_string(), (parent.map(str::to_string), feature.clone()));
+            }
+            match parent.or(id) {
+                Some(owner) => owner.to_string(),
+                None => continue,
+            }
+        };
+        features.push((chrom, start - 1, end, strand, feature, owner));
+    }
+
+    // Follow GFF3 parents up to the top-level feature; GTF gene IDs are their own top
+    let top = |id: &str| -> (String, bool) {
+        let mut id = id.to_string();
+        for _ in 0..parents.len() {
+            match parents.get(&id) {
+                Some((Some(parent), _)) => id = parent.clone(),
+                Some((None, feature)) => return (id, feature.ends_with("gene")),
+                None => break,
+            }
+        }
+        (id, true)
+    };
+
+    let mut genes: HashMap<String, Vec<(u64, u64, String)>> = HashMap::new();
+    let mut spans: HashMap<(String, String), (u64, u64)> = HashMap::new();
+    let mut exons: HashMap<String, Vec<(u64, u64, String)>> = HashMap::new();
+    let mut cds: HashMap<String, Vec<(u64, u64, String)>> = HashMap::new();
+    let mut strands: HashMap<String, Strand> = HashMap::new();
+    for (chrom, start, end, strand, feature, owner) in features {
+        let (gene, is_gene) = top(&owner);
+        if feature == "CDS" {
+            cds.entry(chrom.clone()).or_default().push((start, end, gene.clone()));
+        }
+        if feature == "exon" || feature == "CDS" || is_utr(&feature) {
+            exons.entry(chrom.clone()).or_default().push((start, end, gene.clone()));
+        } else if !is_gene {
+            // e.g. GFF3 `region` lines for whole chromosomes
+            continue;
+        }
+        strands.entry(gene.clone()).or_insert(strand);
+        let span = spans.entry((chrom, gene)).or_insert((start, end));
+        *span = (span.0.min(start), span.1.max(end));
+    }
+    for ((chrom, gene), (start, end)) in spans {
+        genes.entry(chrom).or_default().push((start, end, gene));
+    }
+
+    Ok(Annotation {
+        genes: genes.into_iter().map(|(chrom, genes)| (chrom, FeatureIndex::build(genes))).collect(),
+        exons: exons.into_iter().map(|(chrom, exons)| (chrom, FeatureIndex::build(exons))).collect(),
+        cds: cds.into_iter().map(|(chrom, cds)| (chrom, FeatureIndex::build(cds))).collect(),
+        strands,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The annotation of a GFF3 or GTF file holding `lines`
+    fn annotation(name: &str, lines: &[&str]) -> Annotation {
+        let path = std::env::temp_dir().join(format!("polyscan-gff-{}-{}", std::process::id(), name));
+        std::fs::write(&path, lines.join("\n") + "\n").unwrap();
+        let annotation = read_annotation(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        annotation
+    }
+
+    #[test]
+    fn cds_without_exon_lines_is_exonic() {
+        // The NCBI/Prokka layout of prokaryotes: a gene and its CDS, no exons
+        let annotation = annotation(
+            "prokaryote.gff3",
+            &[
+                "##gff-version 3",
+                "chr\tProkka\tgene\t101\t400\t.\t+\t.\tID=g1",
+                "chr\tProkka\tCDS\t101\t400\t.\t+\t0\tID=c1;Parent=g1",
+            ],
+        );
+        assert_eq!(annotation.annotate("chr", 200, 240), (GeneContext::Exon, vec!["g1"]));
+        assert_eq!(annotation.annotate("chr", 500, 540), (GeneContext::Intergenic, vec![]));
+    }
+
+    #[test]
+    fn gene_bases_count_cds_without_exon_lines() {
+        let annotation = annotation(
+            "gene-bases.gff3",
+            &[
+                "chr\tProkka\tgene\t101\t400\t.\t+\t.\tID=g1",
+                "chr\tProkka\tCDS\t101\t300\t.\t+\t0\tID=c1;Parent=g1",
+            ],
+        );
+        assert_eq!(annotation.gene_bases("chr", 200, 240), vec![("g1", GeneBases { cds: 40, utr: 0, intron: 0 })]);
+        // 20 bases of CDS, then 20 of the gene past it
+        assert_eq!(annotation.gene_bases("chr", 280, 320), vec![("g1", GeneBases { cds: 20, utr: 0, intron: 20 })]);
+    }
+
+    #[test]
+    fn utrs_and_exons_are_exonic_and_the_rest_intronic() {
+        let annotation = annotation(
+            "eukaryote.gtf",
+            &[
+                "chr\tsrc\tfive_prime_utr\t101\t150\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";",
+                "chr\tsrc\texon\t151\t200\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";",
+                "chr\tsrc\tCDS\t401\t500\t.\t+\t0\tgene_id \"g1\"; transcript_id \"t1\";",
+            ],
+        );
+        assert_eq!(annotation.annotate("chr", 110, 120).0, GeneContext::Exon);
+        assert_eq!(annotation.annotate("chr", 160, 170).0, GeneContext::Exon);
+        assert_eq!(annotation.annotate("chr", 250, 300).0, GeneContext::Intron);
+        assert_eq!(annotation.annotate("chr", 450, 460).0, GeneContext::Exon);
+        // 40 of UTR, 50 of exon beyond it (counted as UTR, being outside the CDS) and 10 of intron
+        assert_eq!(annotation.gene_bases("chr", 110, 210), vec![("g1", GeneBases { cds: 0, utr: 90, intron: 10 })]);
+        assert_eq!(annotation.gene_bases("chr", 390, 420), vec![("g1", GeneBases { cds: 20, utr: 0, intron: 10 })]);
+    }
+}
@@ -0,0 +1,46 @@
+//! IGV batch scripts that load a reference and tracks and snapshot regions,
+//! for `--igv-batch`.
+
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Bases of context shown on each side of a snapshotted region.
+pub const SNAPSHOT_FLANK: u64 = 50;
+
+/// A region to snapshot, in 0-based half-open coordinates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotRegion {
+    pub chrom: String,
+    pub start: u64,
+    pub end: u64,
+}
+
+/// Write an IGV batch script that opens `genome` with `tracks` loaded,
+/// then goes to each of `regions` in turn (with [`SNAPSHOT_FLANK`] bases on
+/// each side) and saves a PNG of it in `snapshot_dir`, named after its rank
+/// and position (e.g. `001_chr1_1000_1020.png`), before exiting.
+///
+/// Paths are written as they are given; IGV resolves relative ones against
+/// its own working directory, so absolute paths are safest.
+pub fn write_batch<W: Write>(mut output: W, genome: &Path, tracks: &[&Path], snapshot_dir: &Path, regions: &[SnapshotRegion]) -> io::Result<()> {
+    writeln!(output, "new")?;
+    writeln!(output, "genome {}", genome.display())?;
+    for track in tracks {
+        writeln!(output, "load {}", track.display())?;
+    }
+    writeln!(output, "snapshotDirectory {}", snapshot_dir.display())?;
+    let digits = regions.len().to_string().len().max(3);
+    for (rank, region) in regions.iter().enumerate() {
+        // IGV positions are 1-based and inclusive
+        let (from, to) = (region.start.saturating_sub(SNAPSHOT_FLANK) + 1, region.end + SNAPSHOT_FLANK);
+        writeln!(output, "goto {}:{}-{}", region.chrom, from, to)?;
+        writeln!(output, "snapshot {:0width$}_{}_{}_{}.png", rank + 1, file_name_safe(&region.chrom), region.start, region.end, width = digits)?;
+    }
+    writeln!(output, "exit")?;
+    output.flush()
+}
+
+// Contig names may hold characters file names (or IGV commands) can't
+fn file_name_safe(name: &str) -> String {
+    name.chars().map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' }).collect()
+}
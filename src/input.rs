@@ -0,0 +1,570 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader, ErrorKind, Read};
+use std::num::NonZero;
+use std::ops::{Deref, DerefMut, Range};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use memmap2::Mmap;
+
+use needletail::parser::{FastaReader, FastqReader, FastxReader};
+use noodles::{bgzf, fasta, fastq};
+
+use crate::alignment::{alignment_records, Alignment};
+use crate::chunked::chunked_fasta_records;
+use crate::error::PolyscanError;
+use crate::faidx::{FaiIndex, IndexedFasta, Region};
+use crate::mmap::MappedFasta;
+#[cfg(feature = "remote")]
+use crate::remote::{self, RemoteFasta};
+use crate::timings::TimedReader;
+use crate::twobit::{TwoBitReader, TWOBIT_SIGNATURE};
+
+/// Sequence bytes, either owned or borrowed in place from a memory-mapped file.
+///
+/// Dereferences to `[u8]`; mutable access copies a mapped sequence into an
+/// owned buffer first.
+#[derive(Debug, Clone)]
+pub enum Sequence {
+    Owned(Vec<u8>),
+    Mapped { map: Arc<Mmap>, range: Range<usize> },
+}
+
+impl Sequence {
+    /// The bases in `range`, sharing the map of a mapped sequence.
+    pub fn slice(&self, range: Range<usize>) -> Sequence {
+        match self {
+            Sequence::Owned(seq) => Sequence::Owned(seq[range].to_vec()),
+            Sequence::Mapped { map, range: mapped } => {
+                Sequence::Mapped { map: Arc::clone(map), range: mapped.start + range.start..mapped.start + range.end }
+            }
+        }
+    }
+}
+
+impl Default for Sequence {
+    fn default() -> Self {
+        Sequence::Owned(Vec::new())
+    }
+}
+
+impl From<Vec<u8>> for Sequence {
+    fn from(seq: Vec<u8>) -> Self {
+        Sequence::Owned(seq)
+    }
+}
+
+impl Deref for Sequence {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Sequence::Owned(seq) => seq,
+            Sequence::Mapped { map, range } => &map[range.clone()],
+        }
+    }
+}
+
+impl DerefMut for Sequence {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        if let Sequence::Mapped { .. } = self {
+            *self = Sequence::Owned(self.to_vec());
+        }
+        match self {
+            Sequence::Owned(seq) => seq,
+            Sequence::Mapped { .. } => unreachable!("mapped sequences were copied above"),
+        }
+    }
+}
+
+/// A named sequence read from any supported input format.
+#[derive(Debug, Clone, Default)]
+pub struct SeqRecord {
+    pub id: String,
+    pub seq: Sequence,
+    /// 0-based contig position of `seq[0]` (non-zero when only a region was read).
+    pub start: usize,
+    /// Length of the whole contig `seq` was taken from.
+    pub contig_len: usize,
+    /// Reference alignment, for reads taken from BAM/CRAM.
+    pub alignment: Option<Alignment>,
+    /// Streamed in overlapping chunks: `seq` may be cut at either end, and
+    /// `contig_len` is only a lower bound until the contig's last chunk.
+    pub chunked: bool,
+    /// Marked `topology=circular` in its FASTA header (plasmids, organelles).
+    pub circular: bool,
+}
+
+/// Boxed stream of sequence records.
+pub type Records = Box<dyn Iterator<Item = Result<SeqRecord, Box<dyn Error>>>>;
+
+/// Sequence input formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum InputFormat {
+    /// Detect from the file contents
+    #[default]
+    Auto,
+    Fasta,
+    Fastq,
+    #[cfg_attr(feature = "clap", value(name = "2bit"))]
+    TwoBit,
+    /// Read sequences from a BAM file
+    Bam,
+    /// Read sequences from a CRAM file (usually needs the reference FASTA)
+    Cram,
+}
+
+/// Library that parses FASTA and FASTQ text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum InputBackend {
+    /// noodles, reading each record line by line
+    #[default]
+    Noodles,
+    /// needletail, parsing records in place in its read buffer
+    Needletail,
+}
+
+/// Whether a FASTA or FASTQ file must have been read to its end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReadCheck {
+    /// Stop at the end of the last record, whatever follows it
+    #[default]
+    Lenient,
+    /// End the records in an error if any of the file was left unread
+    /// (truncated input, or data after the end of a compressed stream)
+    WholeFile,
+}
+
+/// Read FASTA records from an (already decompressed) stream.
+pub fn fasta_records<R: Read + 'static>(input: R) -> Records {
+    fasta_records_buffered(BufReader::new(input))
+}
+
+fn fasta_records_buffered<B: BufRead + 'static>(input: B) -> Records {
+    let mut reader = fasta::io::Reader::new(input);
+    let mut definition = fasta::record::Definition::default();
+    Box::new(std::iter::from_fn(move || {
+        match reader.read_definition(&mut definition) {
+            Ok(0) => return None,
+            Ok(_) => {}
+            // e.g. text that isn't FASTA at all
+            Err(e) if e.kind() == ErrorKind::InvalidData => return Some(Err(format!("Expected > at record start ({}).", e).into())),
+            Err(e) => return Some(Err(e.into())),
+        }
+        let mut seq = Vec::new();
+        if let Err(e) = reader.read_sequence(&mut seq) {
+            return Some(Err(e.into()));
+        }
+        let circular = definition.description().is_some_and(|desc| is_circular_description(&String::from_utf8_lossy(desc)));
+        let id = String::from_utf8_lossy(definition.name()).into_owned();
+        Some(Ok(SeqRecord { id, contig_len: seq.len(), seq: seq.into(), circular, ..Default::default() }))
+    }))
+}
+
+/// Does a FASTA description declare a circular topology, as NCBI's
+/// `[topology=circular]` or a bare `topology=circular` does?
+fn is_circular_description(desc: &str) -> bool {
+    desc.split_whitespace()
+        .any(|field| field.trim_matches(|c| c == '[' || c == ']').eq_ignore_ascii_case("topology=circular"))
+}
+
+/// Read FASTQ records from an (already decompressed) stream, keeping only
+/// the read name and sequence.
+pub fn fastq_records<R: Read + 'static>(input: R) -> Records {
+    fastq_records_buffered(BufReader::new(input))
+}
+
+fn fastq_records_buffered<B: BufRead + 'static>(input: B) -> Records {
+    let mut reader = fastq::io::Reader::new(input);
+    let mut record = fastq::Record::default();
+    Box::new(std::iter::from_fn(move || match reader.read_record(&mut record) {
+        Ok(0) => None,
+        Ok(_) => {
+            let seq = record.sequence().to_vec();
+            let id = String::from_utf8_lossy(record.name()).into_owned();
+            Some(Ok(SeqRecord { id, contig_len: seq.len(), seq: seq.into(), ..Default::default() }))
+        }
+        Err(e) => Some(Err(e.into())),
+    }))
+}
+
+// FASTA or FASTQ records parsed by needletail. Records are found in place in
+// its read buffer, and each sequence is copied out once: borrowed from the
+// buffer when on a single line, joined from its lines otherwise
+fn needletail_records(mut reader: Box<dyn FastxReader>) -> Records {
+    Box::new(std::iter::from_fn(move || {
+        let record = match reader.next()? {
+            Ok(record) => record,
+            Err(e) => return Some(Err(e.into())),
+        };
+        // The header is the ID, then the description after the first space
+        let header = record.id();
+        let (id, desc) = match header.iter().position(u8::is_ascii_whitespace) {
+            Some(i) => (&header[..i], Some(&header[i + 1..])),
+            None => (header, None),
+        };
+        let circular = desc.is_some_and(|desc| is_circular_description(&String::from_utf8_lossy(desc)));
+        let id = String::from_utf8_lossy(id).into_owned();
+        let seq = record.seq().into_owned();
+        Some(Ok(SeqRecord { id, contig_len: seq.len(), seq: seq.into(), circular, ..Default::default() }))
+    }))
+}
+
+/// Is `head` the start of a BGZF file (bgzip, or the blocks of BAM): a gzip
+/// member whose extra field has a `BC` subfield?
+fn is_bgzf(head: &[u8]) -> bool {
+    head.len() >= 16 && head[..4] == [0x1f, 0x8b, 8, 4] && head[12..14] == *b"BC"
+}
+
+/// Is `path` a URL (with the `remote` feature) rather than a local file?
+#[cfg_attr(not(feature = "remote"), allow(unused_variables))]
+pub fn is_remote_path(path: &Path) -> bool {
+    #[cfg(feature = "remote")]
+    return path.to_str().is_some_and(remote::is_remote);
+    #[cfg(not(feature = "remote"))]
+    false
+}
+
+// The raw bytes of a local file, or of a URL as it downloads
+fn open_input(path: &Path) -> Result<Box<dyn Read + Send>, Box<dyn Error>> {
+    #[cfg(feature = "remote")]
+    if let Some(url) = path.to_str().filter(|path| remote::is_remote(path)) {
+        return remote::open_remote(url);
+    }
+    Ok(Box::new(File::open(path)?))
+}
+
+// A decompressed file and the count of its bytes read so far
+type Decompressed = (Box<dyn Read + Send>, Arc<AtomicU64>);
+
+// Counts the bytes read from the file underneath the decompression
+struct ConsumedReader<R> {
+    inner: R,
+    consumed: Arc<AtomicU64>,
+}
+
+impl<R: Read> Read for ConsumedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.consumed.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+/// The contents of `path`, decompressed if needed, and a count of the bytes
+/// of the file read so far. BGZF is inflated on `threads` worker threads
+/// when there are more than one, block by block, since plain gzip inflation
+/// on one thread is slower than fast storage; anything else is detected and
+/// decompressed by niffler. Both read every member of concatenated gzip or
+/// BGZF files (as made by `cat`), not just the first. `path` may be a URL
+/// with the `remote` feature, which is read as it downloads.
+fn decompressed(path: &Path, threads: usize) -> Result<Decompressed, Box<dyn Error>> {
+    let mut file = open_input(path)?;
+    let mut head = Vec::with_capacity(16);
+    file.by_ref().take(16).read_to_end(&mut head)?;
+    let bgzf = is_bgzf(&head);
+    let consumed = Arc::new(AtomicU64::new(0));
+    // What was read to look at the head goes back in front of the rest
+    let file = ConsumedReader { inner: std::io::Cursor::new(head).chain(file), consumed: Arc::clone(&consumed) };
+    let reader: Box<dyn Read + Send> = match NonZero::new(threads).filter(|threads| bgzf && threads.get() > 1) {
+        Some(threads) => Box::new(bgzf::io::MultithreadedReader::with_worker_count(threads, file)),
+        None => {
+            // niffler::get_reader takes a "Box<dyn Read>", returns (reader, format);
+            // needletail needs a reader it can send
+            let (reader, _compression_format) = niffler::send::get_reader(Box::new(file))?;
+            reader
+        }
+    };
+    Ok((reader, consumed))
+}
+
+/// `records`, failing once they run out if fewer bytes of `path` were read
+/// than it holds, so a decompressor stopping short of the end of the file
+/// (after the first of several gzip members, say) can't truncate the input
+/// unnoticed.
+fn consumed_records(records: Records, path: &Path, consumed: Arc<AtomicU64>) -> Records {
+    let mut records = records.fuse();
+    let path = path.to_path_buf();
+    let mut checked = false;
+    Box::new(std::iter::from_fn(move || match records.next() {
+        None if !checked => {
+            checked = true;
+            let size = match std::fs::metadata(&path) {
+                Ok(metadata) => metadata.len(),
+                Err(e) => return Some(Err(e.into())),
+            };
+            let read = consumed.load(Ordering::Relaxed);
+            (read < size).then(|| Err(format!("only {} of the {} bytes of {} were read; the input may be truncated or have data after the end of its compressed stream.", read, size, path.display()).into()))
+        }
+        next => next,
+    }))
+}
+
+/// Open a sequence file. With `InputFormat::Auto`, .2bit, BAM and CRAM are
+/// detected by their signatures and FASTA/FASTQ (optionally compressed) by
+/// their first character. `reference` is only used to decode CRAM.
+pub fn open_records<P: AsRef<Path>>(
+    path: P,
+    format: InputFormat,
+    reference: Option<&Path>,
+) -> Result<Records, PolyscanError> {
+    open_records_with(path, format, reference, InputBackend::default(), 1, ReadCheck::Lenient)
+}
+
+/// [`open_records`], parsing FASTA and FASTQ with `backend` and inflating
+/// BGZF-compressed input on `decompress_threads` threads, and checking the
+/// end of FASTA and FASTQ input as `check` says.
+pub fn open_records_with<P: AsRef<Path>>(
+    path: P,
+    format: InputFormat,
+    reference: Option<&Path>,
+    backend: InputBackend,
+    decompress_threads: usize,
+    check: ReadCheck,
+) -> Result<Records, PolyscanError> {
+    read_records(path.as_ref(), format, reference, backend, decompress_threads, check).map_err(PolyscanError::reading)
+}
+
+fn read_records(
+    path: &Path,
+    format: InputFormat,
+    reference: Option<&Path>,
+    backend: InputBackend,
+    decompress_threads: usize,
+    check: ReadCheck,
+) -> Result<Records, Box<dyn Error>> {
+
+    // A URL is streamed, so only formats read front to back (FASTA and FASTQ) can be
+    let remote = is_remote_path(path);
+    if remote && matches!(format, InputFormat::TwoBit | InputFormat::Bam | InputFormat::Cram) {
+        return Err(format!("only FASTA and FASTQ can be read from a URL, not {}", path.display()).into());
+    }
+    let mut magic = [0u8; 4];
+    let is_twobit = !remote
+        && File::open(path)?.read_exact(&mut magic).is_ok()
+        && (u32::from_le_bytes(magic) == TWOBIT_SIGNATURE
+            || u32::from_be_bytes(magic) == TWOBIT_SIGNATURE);
+    if format == InputFormat::TwoBit || (format == InputFormat::Auto && is_twobit) {
+        return Ok(Box::new(TwoBitReader::from_path(path)?));
+    }
+    if format == InputFormat::Cram || (format == InputFormat::Auto && &magic == b"CRAM") {
+        return alignment_records(path, true, reference);
+    }
+
+    let (reader, consumed) = decompressed(path, decompress_threads)?;
+    let mut buf = BufReader::new(TimedReader::new(reader));
+
+    let format = match format {
+        InputFormat::Auto => {
+            // BAM starts with "BAM\1" once decompressed; FASTQ records start with '@', FASTA with '>'
+            let head = buf.fill_buf()?;
+            let first = head.iter().find(|b| !b.is_ascii_whitespace()).copied();
+            if head.starts_with(b"BAM\x01") {
+                InputFormat::Bam
+            } else if first == Some(b'@') {
+                InputFormat::Fastq
+            } else {
+                InputFormat::Fasta
+            }
+        }
+        other => other,
+    };
+
+    let records = match (format, backend) {
+        (InputFormat::Bam, _) if remote => return Err(format!("only FASTA and FASTQ can be read from a URL, not {}", path.display()).into()),
+        (InputFormat::Bam, _) => return alignment_records(path, false, reference),
+        (InputFormat::Fastq, InputBackend::Noodles) => fastq_records_buffered(buf),
+        (InputFormat::Fastq, InputBackend::Needletail) => needletail_records(Box::new(FastqReader::new(buf))),
+        (_, InputBackend::Noodles) => fasta_records_buffered(buf),
+        (_, InputBackend::Needletail) => needletail_records(Box::new(FastaReader::new(buf))),
+    };
+    Ok(match check {
+        ReadCheck::Lenient => records,
+        ReadCheck::WholeFile => consumed_records(records, path, consumed),
+    })
+}
+
+/// Open a (optionally compressed) FASTA file for chunked streaming; see
+/// [`chunked_fasta_records`]. Other formats are rejected. `check` is as
+/// for [`open_records_with`].
+pub fn open_chunked_records<P: AsRef<Path>>(
+    path: P,
+    format: InputFormat,
+    chunk_size: usize,
+    window: usize,
+    decompress_threads: usize,
+    check: ReadCheck,
+) -> Result<Records, PolyscanError> {
+    read_chunked_records(path.as_ref(), format, chunk_size, window, decompress_threads, check).map_err(PolyscanError::reading)
+}
+
+fn read_chunked_records(
+    path: &Path,
+    format: InputFormat,
+    chunk_size: usize,
+    window: usize,
+    decompress_threads: usize,
+    check: ReadCheck,
+) -> Result<Records, Box<dyn Error>> {
+    let (reader, consumed) = decompressed(path, decompress_threads)?;
+    let mut buf = BufReader::new(TimedReader::new(reader));
+
+    let head = buf.fill_buf()?;
+    let first = head.iter().find(|b| !b.is_ascii_whitespace()).copied();
+    let is_fasta = matches!(first, None | Some(b'>'));
+    if !matches!(format, InputFormat::Auto | InputFormat::Fasta) || !is_fasta {
+        return Err("chunked scanning (--chunk-size) only supports FASTA input".into());
+    }
+    let records = chunked_fasta_records(buf, chunk_size, window);
+    Ok(match check {
+        ReadCheck::Lenient => records,
+        ReadCheck::WholeFile => consumed_records(records, path, consumed),
+    })
+}
+
+/// Read only the given regions of a sequence file.
+///
+/// Indexed FASTA (plain with `.fai`, or bgzip with `.fai` + `.gzi`) is read by
+/// random access, as is a remote uncompressed FASTA with a `.fai` next to it
+/// (by range requests); anything else is read sequentially and sliced.
+pub fn region_records<P: AsRef<Path>>(
+    path: P,
+    format: InputFormat,
+    reference: Option<&Path>,
+    regions: &[Region],
+) -> Result<Records, PolyscanError> {
+    read_region_records(path.as_ref(), format, reference, regions).map_err(PolyscanError::reading)
+}
+
+fn read_region_records(path: &Path, format: InputFormat, reference: Option<&Path>, regions: &[Region]) -> Result<Records, Box<dyn Error>> {
+    if let Some(indexed) = IndexedSource::open(path, format, false)? {
+        return indexed.region_records(regions);
+    }
+
+    // Group by contig so each record only looks at its own regions
+    let mut by_chrom: HashMap<String, Vec<Region>> = HashMap::new();
+    for region in regions {
+        by_chrom.entry(region.chrom.clone()).or_default().push(region.clone());
+    }
+    let records = open_records(path, format, reference)?;
+    Ok(Box::new(records.flat_map(move |result| {
+        let slices: Vec<Result<SeqRecord, Box<dyn Error>>> = match result {
+            Err(e) => vec![Err(e)],
+            Ok(record) => by_chrom
+                .get(&record.id)
+                .map_or(&[][..], Vec::as_slice)
+                .iter()
+                .map(|r| {
+                    let len = record.seq.len() as u64;
+                    let Range { start, end } = r.span(len)?;
+                    Ok(SeqRecord {
+                        id: record.id.clone(),
+                        seq: record.seq[start as usize..end as usize].to_vec().into(),
+                        start: start as usize,
+                        contig_len: len as usize,
+                        alignment: record.alignment.clone(),
+                        ..Default::default()
+                    })
+                })
+                .collect(),
+        };
+        slices
+    })))
+}
+
+/// A `.fai`-indexed FASTA read by random access: mapped into memory
+/// (`--mmap`), or read by seeking, through the `.gzi` blocks when it is
+/// bgzip-compressed, or (with the `remote` feature) fetched by range
+/// requests from a URL. Contigs and regions can be fetched in any order, and
+/// from several threads at once.
+pub enum IndexedSource {
+    Mapped(MappedFasta),
+    Seek(IndexedFasta),
+    #[cfg(feature = "remote")]
+    Remote(RemoteFasta),
+}
+
+impl IndexedSource {
+    /// Open `path` for random access (memory-mapped with `mmap`), or return
+    /// `None` if it can't be read that way; `--mmap` errors instead.
+    pub fn open<P: AsRef<Path>>(path: P, format: InputFormat, mmap: bool) -> Result<Option<Self>, Box<dyn Error>> {
+        if mmap {
+            return Ok(Some(IndexedSource::Mapped(MappedFasta::open(path)?)));
+        }
+        if !matches!(format, InputFormat::Auto | InputFormat::Fasta) {
+            return Ok(None);
+        }
+        #[cfg(feature = "remote")]
+        if let Some(url) = path.as_ref().to_str().filter(|path| remote::is_remote(path)) {
+            return Ok(RemoteFasta::open(url)?.map(IndexedSource::Remote));
+        }
+        Ok(IndexedFasta::open(path)?.map(IndexedSource::Seek))
+    }
+
+    pub fn fai(&self) -> &FaiIndex {
+        match self {
+            IndexedSource::Mapped(fasta) => &fasta.fai,
+            IndexedSource::Seek(fasta) => &fasta.fai,
+            #[cfg(feature = "remote")]
+            IndexedSource::Remote(fasta) => &fasta.fai,
+        }
+    }
+
+    /// `regions` in the contig order of the file, as read sequentially.
+    pub fn sorted_regions(&self, regions: &[Region]) -> Vec<Region> {
+        let mut regions = regions.to_vec();
+        regions.sort_by_key(|r| self.fai().position(&r.chrom).unwrap_or(usize::MAX));
+        regions
+    }
+
+    /// Index position of the contig of `region` and the bases it covers,
+    /// cut at the contig end.
+    pub fn span(&self, region: &Region) -> Result<(usize, Range<u64>), Box<dyn Error>> {
+        let position = self.fai().position(&region.chrom).ok_or_else(|| format!("contig '{}' not found in .fai index", region.chrom))?;
+        Ok((position, region.span(self.fai().entries[position].length)?))
+    }
+
+    /// Is the contig at `position` marked `topology=circular` in its header?
+    pub fn is_circular(&self, position: usize) -> Result<bool, Box<dyn Error>> {
+        let definition = match self {
+            IndexedSource::Mapped(fasta) => fasta.definition(position)?,
+            IndexedSource::Seek(fasta) => fasta.definition(position)?,
+            #[cfg(feature = "remote")]
+            IndexedSource::Remote(fasta) => fasta.definition(position)?,
+        };
+        Ok(definition.split_once(char::is_whitespace).is_some_and(|(_, desc)| is_circular_description(desc)))
+    }
+
+    /// The bases in `range` of the contig at `position`, as a record.
+    pub fn fetch_record(&self, position: usize, range: Range<u64>, circular: bool) -> Result<SeqRecord, Box<dyn Error>> {
+        let entry = &self.fai().entries[position];
+        let seq = match self {
+            IndexedSource::Mapped(fasta) => fasta.fetch(entry, range.start, range.end)?,
+            IndexedSource::Seek(fasta) => fasta.fetch(&entry.name, range.start, range.end)?.into(),
+            #[cfg(feature = "remote")]
+            IndexedSource::Remote(fasta) => fasta.fetch(entry, range.start, range.end)?.into(),
+        };
+        Ok(SeqRecord { id: entry.name.clone(), seq, start: range.start as usize, contig_len: entry.length as usize, circular, ..Default::default() })
+    }
+
+    /// Read only `regions`, in file order, once every one of them is found
+    /// to be on a contig of the index.
+    pub fn region_records(self, regions: &[Region]) -> Result<Records, Box<dyn Error>> {
+        let spans = self.sorted_regions(regions).iter().map(|region| self.span(region)).collect::<Result<Vec<_>, _>>()?;
+        Ok(Box::new(spans.into_iter().map(move |(position, range)| self.fetch_record(position, range, false))))
+    }
+
+    /// Read every contig whole, in file order.
+    pub fn contig_records(self) -> Records {
+        Box::new((0..self.fai().entries.len()).map(move |position| {
+            let length = self.fai().entries[position].length;
+            let circular = self.is_circular(position)?;
+            self.fetch_record(position, 0..length, circular)
+        }))
+    }
+}
@@ -0,0 +1,128 @@
+//! Hits as the interval types of other crates, so they can go straight into
+//! existing interval code: `bio_types::genome::Interval` and `Locus` (with
+//! the `bio-types` feature) and rust-lapper entries (with `rust-lapper`).
+
+#[cfg(feature = "rust-lapper")]
+use std::collections::HashMap;
+
+#[cfg(feature = "bio-types")]
+use bio_types::genome;
+#[cfg(feature = "rust-lapper")]
+use rust_lapper::Lapper;
+
+#[cfg(feature = "bio-types")]
+use crate::scan::Strand;
+use crate::scanner::Hit;
+
+/// The window of a hit on its contig, 0-based and half-open as the hit.
+#[cfg(feature = "bio-types")]
+impl From<&Hit> for genome::Interval {
+    fn from(hit: &Hit) -> Self {
+        genome::Interval::new(hit.chrom.clone(), hit.start as u64..hit.end as u64)
+    }
+}
+
+#[cfg(feature = "bio-types")]
+impl From<Hit> for genome::Interval {
+    fn from(hit: Hit) -> Self {
+        genome::Interval::new(hit.chrom, hit.start as u64..hit.end as u64)
+    }
+}
+
+/// The first base of a hit's window.
+#[cfg(feature = "bio-types")]
+impl From<&Hit> for genome::Locus {
+    fn from(hit: &Hit) -> Self {
+        genome::Locus::new(hit.chrom.clone(), hit.start as u64)
+    }
+}
+
+#[cfg(feature = "bio-types")]
+impl From<Hit> for genome::Locus {
+    fn from(hit: Hit) -> Self {
+        genome::Locus::new(hit.chrom, hit.start as u64)
+    }
+}
+
+/// Unstranded hits (protein sequences) have an unknown strand.
+#[cfg(feature = "bio-types")]
+impl From<Strand> for bio_types::strand::Strand {
+    fn from(strand: Strand) -> Self {
+        match strand {
+            Strand::Plus => bio_types::strand::Strand::Forward,
+            Strand::Minus => bio_types::strand::Strand::Reverse,
+            Strand::Unstranded => bio_types::strand::Strand::Unknown,
+        }
+    }
+}
+
+#[cfg(feature = "rust-lapper")]
+impl Hit {
+    /// The window of the hit as a rust-lapper interval carrying `val`.
+    /// rust-lapper intervals have no contig, so keep one `Lapper` per contig
+    /// (see [`hits_to_lappers`]).
+    pub fn lapper_interval<T: Eq + Clone + Send + Sync>(&self, val: T) -> rust_lapper::Interval<usize, T> {
+        rust_lapper::Interval { start: self.start, stop: self.end, val }
+    }
+}
+
+/// One `Lapper` per contig of `hits`, each interval carrying the index of
+/// its hit in `hits` (a `Hit` can't be the value itself: its score isn't
+/// `Eq`).
+///
+/// ```ignore
+/// let lappers = hits_to_lappers(&hits);
+/// for interval in lappers["chr1"].find(1000, 2000) {
+///     println!("{:?}", hits[interval.val]);
+/// }
+/// ```
+#[cfg(feature = "rust-lapper")]
+pub fn hits_to_lappers(hits: &[Hit]) -> HashMap<String, Lapper<usize, usize>> {
+    let mut intervals: HashMap<String, Vec<rust_lapper::Interval<usize, usize>>> = HashMap::new();
+    for (i, hit) in hits.iter().enumerate() {
+        intervals.entry(hit.chrom.clone()).or_default().push(hit.lapper_interval(i));
+    }
+    intervals.into_iter().map(|(chrom, intervals)| (chrom, Lapper::new(intervals))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scan::Strand;
+
+    fn hits() -> Vec<Hit> {
+        let hit = |chrom: &str, start: usize, strand: Strand| Hit { chrom: chrom.to_string(), start, end: start + 10, target: "A".to_string(), score: 90.0, strand };
+        vec![hit("chr1", 0, Strand::Plus), hit("chr1", 25, Strand::Minus), hit("chr2", 5, Strand::Unstranded)]
+    }
+
+    #[cfg(feature = "bio-types")]
+    #[test]
+    fn hits_become_genome_intervals_and_loci() {
+        use bio_types::genome::AbstractInterval;
+        use bio_types::genome::AbstractLocus;
+
+        for hit in hits() {
+            let interval = genome::Interval::from(&hit);
+            assert_eq!((interval.contig(), interval.range()), (hit.chrom.as_str(), hit.start as u64..hit.end as u64));
+            let locus = genome::Locus::from(&hit);
+            assert_eq!((locus.contig(), locus.pos()), (hit.chrom.as_str(), hit.start as u64));
+            assert_eq!(genome::Interval::from(hit.clone()), interval);
+        }
+        // Unknown strands are never equal to each other, so compare symbols
+        let strands: Vec<String> = hits().into_iter().map(|hit| bio_types::strand::Strand::from(hit.strand).strand_symbol().to_string()).collect();
+        assert_eq!(strands, ["+", "-", "."]);
+    }
+
+    #[cfg(feature = "rust-lapper")]
+    #[test]
+    fn lappers_find_hits_by_contig() {
+        let hits = hits();
+        let lappers = hits_to_lappers(&hits);
+        assert_eq!(lappers.len(), 2);
+        let found: Vec<&Hit> = lappers["chr1"].find(5, 30).map(|interval| &hits[interval.val]).collect();
+        assert_eq!(found, [&hits[0], &hits[1]]);
+        let found: Vec<&Hit> = lappers["chr2"].find(0, 6).map(|interval| &hits[interval.val]).collect();
+        assert_eq!(found, [&hits[2]]);
+        assert_eq!(lappers["chr1"].find(10, 25).count(), 0);
+    }
+}
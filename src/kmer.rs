@@ -0,0 +1,252 @@
+use std::collections::VecDeque;
+use std::error::Error;
+
+pub use crate::bases::iupac_mask;
+use crate::bases::nuc_code;
+use crate::scan::{complement_char, ScanHit, Steps, Strand};
+
+/// Reverse complement of a nucleotide k-mer.
+pub fn reverse_complement(kmer: &[u8]) -> Vec<u8> {
+    kmer.iter().rev().map(|&nuc| complement_char(nuc.to_ascii_uppercase() as char) as u8).collect()
+}
+
+/// Reverse complement of a motif that may contain IUPAC codes (e.g. `GGNRG`
+/// becomes `CYNCC`).
+pub fn reverse_complement_motif(motif: &[u8]) -> Vec<u8> {
+    const CODES: &[u8] = b"ACGTRYSWKMBDHVN";
+    motif
+        .iter()
+        .rev()
+        .map(|&code| {
+            // Complementing swaps A<->T and C<->G, i.e. reverses the 4 mask bits
+            let mask = iupac_mask(code);
+            let complement = (0..4).fold(0, |acc, bit| acc | (((mask >> bit) & 1) << (3 - bit)));
+            CODES.iter().copied().find(|&c| iupac_mask(c) == complement).unwrap_or(b'N')
+        })
+        .collect()
+}
+
+/// Scores windows by k-mer density: the percentage of the window's bases
+/// covered by occurrences of `kmer` lying wholly inside the window.
+///
+/// Overlapping occurrences are only counted once per base, so a perfect
+/// `(AAT)n` tract scores 100% for `AAT`, as does a poly-A run for `AA`.
+#[derive(Debug, Clone)]
+pub struct KmerDensity {
+    kmer: Vec<u8>,
+    threshold_count: usize,
+    // Set for motifs: IUPAC codes in `kmer` match any base they stand for
+    degenerate: bool,
+    max_mismatches: usize,
+}
+
+impl KmerDensity {
+    /// Passes windows where occurrences of `kmer` cover at least `percentage`% of `window` bases.
+    pub fn new(kmer: &[u8], percentage: f64, window: usize) -> Self {
+        let threshold_count = ((percentage / 100.0) * (window as f64)).ceil() as usize;
+        KmerDensity { kmer: kmer.to_ascii_uppercase(), threshold_count, degenerate: false, max_mismatches: 0 }
+    }
+
+    /// Like [`KmerDensity::new`] for a degenerate motif such as `GGNGG`:
+    /// IUPAC codes match any base they stand for, and occurrences may differ
+    /// from the motif at up to `max_mismatches` positions. Sequence bases
+    /// other than A/C/G/T/U never match.
+    pub fn motif(motif: &[u8], max_mismatches: usize, percentage: f64, window: usize) -> Self {
+        KmerDensity { degenerate: true, max_mismatches, ..KmerDensity::new(motif, percentage, window) }
+    }
+
+    pub fn kmer(&self) -> &[u8] {
+        &self.kmer
+    }
+
+    fn matches_at(&self, seq: &[u8], pos: usize) -> bool {
+        let bases = &seq[pos..pos + self.kmer.len()];
+        if !self.degenerate {
+            return bases.eq_ignore_ascii_case(&self.kmer);
+        }
+
+        let mut mismatches = 0;
+        for (&code, &nuc) in self.kmer.iter().zip(bases) {
+            let nuc_mask = if nuc_code(nuc).is_some() { iupac_mask(nuc) } else { 0 };
+            if iupac_mask(code) & nuc_mask == 0 {
+                mismatches += 1;
+                if mismatches > self.max_mismatches {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Start of every occurrence of a degenerate `motif` (IUPAC codes, no
+/// mismatches) in `seq`, such as a restriction enzyme recognition site.
+pub fn motif_sites<'a>(seq: &'a [u8], motif: &[u8]) -> impl Iterator<Item = usize> + 'a {
+    let matcher = KmerDensity::motif(motif, 0, 100.0, motif.len());
+    let last = (seq.len() + 1).saturating_sub(motif.len().max(1));
+    (0..last).filter(move |&pos| matcher.matches_at(seq, pos))
+}
+
+/// Rolling state of one k-mer across the sliding window.
+#[derive(Default)]
+struct KmerState {
+    /// Occurrence starts inside the window, with the bases each adds to the
+    /// union of occurrences beyond the one before it
+    occurrences: VecDeque<(usize, usize)>,
+    /// Sum of the additions in `occurrences`
+    sum: usize,
+    /// Most recent occurrence start (possibly already left of the window)
+    previous: Option<usize>,
+}
+
+impl KmerState {
+    fn enter(&mut self, pos: usize, k: usize) {
+        let added = self.previous.map_or(k, |previous| k.min(pos - previous));
+        self.occurrences.push_back((pos, added));
+        self.sum += added;
+        self.previous = Some(pos);
+    }
+
+    fn leave(&mut self, pos: usize) {
+        if self.occurrences.front().is_some_and(|&(start, _)| start == pos) {
+            let (_, added) = self.occurrences.pop_front().unwrap();
+            self.sum -= added;
+        }
+    }
+
+    /// Bases covered by the occurrences in the window. The first one
+    /// contributes all `k` bases, whatever preceded it outside the window.
+    fn covered(&self, k: usize) -> usize {
+        self.occurrences.front().map_or(0, |&(_, added)| self.sum - added + k)
+    }
+}
+
+/// Slide a `window`-sized window across `seq` and call `emit` for every
+/// (window, k-mer scorer) pair that passes, among the windows starting on
+/// `steps`.
+///
+/// Like [`scan_windows`](crate::scan::scan_windows), hits for the same window
+/// are emitted in scorer order and carry the scorer's index.
+pub fn scan_kmers<F>(
+    seq: &[u8],
+    window: usize,
+    steps: Steps,
+    scorers: &[(&KmerDensity, Strand)],
+    mut emit: F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(ScanHit) -> Result<(), Box<dyn Error>>,
+{
+    if window == 0 || seq.len() < window {
+        return Ok(());
+    }
+
+    let mut states: Vec<KmerState> = scorers.iter().map(|_| KmerState::default()).collect();
+
+    for start in 0..=(seq.len() - window) {
+        let end = start + window;
+        for (index, ((scorer, strand), state)) in scorers.iter().zip(states.iter_mut()).enumerate() {
+            let k = scorer.kmer.len();
+            if k == 0 || k > window {
+                continue;
+            }
+
+            // Occurrences may start anywhere in [start, end - k]
+            if start == 0 {
+                for pos in 0..=(window - k) {
+                    if scorer.matches_at(seq, pos) {
+                        state.enter(pos, k);
+                    }
+                }
+            } else {
+                state.leave(start - 1);
+                if scorer.matches_at(seq, end - k) {
+                    state.enter(end - k, k);
+                }
+            }
+
+            if !steps.contains(start) {
+                continue;
+            }
+            let covered = state.covered(k);
+            if covered > 0 && covered >= scorer.threshold_count {
+                let score = (covered as f64 / window as f64) * 100.0;
+                emit(ScanHit { start, end, score, strand: *strand, scorer: index })?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (start, score, scorer) of every window with any occurrence
+    fn densities(seq: &[u8], window: usize, scorers: &[(&KmerDensity, Strand)]) -> Vec<(usize, f64, usize)> {
+        let mut hits = Vec::new();
+        scan_kmers(seq, window, Steps::EVERY_BASE, scorers, |hit| {
+            hits.push((hit.start, (hit.score * 10.0).round() / 10.0, hit.scorer));
+            Ok(())
+        })
+        .unwrap();
+        hits
+    }
+
+    #[test]
+    fn density_is_the_share_of_bases_covered_by_occurrences() {
+        let aat = KmerDensity::new(b"AAT", 0.0, 9);
+        let hits = densities(b"GGAATAATAATGG", 9, &[(&aat, Strand::Plus)]);
+        assert_eq!(hits, [(0, 66.7, 0), (1, 66.7, 0), (2, 100.0, 0), (3, 66.7, 0), (4, 66.7, 0)]);
+        // Only windows reaching the threshold pass
+        let aat = KmerDensity::new(b"AAT", 100.0, 9);
+        assert_eq!(densities(b"GGAATAATAATGG", 9, &[(&aat, Strand::Plus)]), [(2, 100.0, 0)]);
+    }
+
+    #[test]
+    fn overlapping_occurrences_cover_each_base_once() {
+        let aa = KmerDensity::new(b"AA", 0.0, 6);
+        assert_eq!(densities(b"AAAAAC", 6, &[(&aa, Strand::Plus)]), [(0, 83.3, 0)]);
+        let hits = densities(b"CAAAAAACCC", 6, &[(&aa, Strand::Plus)]);
+        assert_eq!(hits, [(0, 83.3, 0), (1, 100.0, 0), (2, 83.3, 0), (3, 66.7, 0), (4, 50.0, 0)]);
+    }
+
+    #[test]
+    fn lowercase_bases_match() {
+        let aat = KmerDensity::new(b"aat", 100.0, 6);
+        assert_eq!(aat.kmer(), b"AAT");
+        assert_eq!(densities(b"aatAAT", 6, &[(&aat, Strand::Plus)]), [(0, 100.0, 0)]);
+    }
+
+    #[test]
+    fn reverse_complements() {
+        assert_eq!(reverse_complement(b"AAC"), b"GTT");
+        assert_eq!(reverse_complement(b"aacg"), b"CGTT");
+        assert_eq!(reverse_complement(b"AATT"), b"AATT");
+        assert_eq!(reverse_complement_motif(b"GGNRG"), b"CYNCC");
+        assert_eq!(reverse_complement_motif(b"TATAWR"), b"YWTATA");
+        assert_eq!(reverse_complement_motif(b"BDHV"), b"BDHV");
+    }
+
+    #[test]
+    fn the_reverse_complement_scores_the_minus_strand() {
+        let plus = KmerDensity::new(b"AAC", 100.0, 6);
+        let minus = KmerDensity::new(&reverse_complement(b"AAC"), 100.0, 6);
+        let scorers = [(&plus, Strand::Plus), (&minus, Strand::Minus)];
+        assert_eq!(densities(b"CCGTTGTTCC", 6, &scorers), [(2, 100.0, 1)]);
+        assert_eq!(densities(b"CCAACAACCC", 6, &scorers), [(2, 100.0, 0)]);
+    }
+
+    #[test]
+    fn motifs_match_iupac_codes_and_mismatches() {
+        let exact = KmerDensity::motif(b"GGNGG", 0, 100.0, 5);
+        let lax = KmerDensity::motif(b"GGNGG", 1, 100.0, 5);
+        assert_eq!(densities(b"GGTGG", 5, &[(&exact, Strand::Plus)]), [(0, 100.0, 0)]);
+        assert!(densities(b"GGTGC", 5, &[(&exact, Strand::Plus)]).is_empty());
+        assert_eq!(densities(b"GGTGC", 5, &[(&lax, Strand::Plus)]), [(0, 100.0, 0)]);
+        // N in the sequence matches nothing, not even an N of the motif
+        assert!(densities(b"GGNGG", 5, &[(&exact, Strand::Plus)]).is_empty());
+        assert_eq!(motif_sites(b"GAATTCGAATTC", b"GAATTC").collect::<Vec<_>>(), [0, 6]);
+    }
+}
@@ -0,0 +1,98 @@
+//! Core scanning machinery behind the `polyscan` command-line tool.
+//!
+//! A [`Scanner`] built from [`ScanParams`] finds the windows of sequences,
+//! records or files that pass a scan, as [`Hit`]s. Underneath, the [`scan`]
+//! module holds the rolling-window counter and the
+//! [`WindowScorer`](scan::WindowScorer) trait used to decide which windows are
+//! reported.
+
+pub mod adaptive;
+#[cfg(feature = "io")]
+pub mod alignment;
+pub mod background;
+pub mod bases;
+#[cfg(feature = "io")]
+pub mod bed;
+#[cfg(feature = "io")]
+pub mod benchmark;
+#[cfg(feature = "io")]
+pub mod bigwig;
+pub mod calibrate;
+pub mod cap;
+#[cfg(feature = "json")]
+pub mod checkpoint;
+#[cfg(feature = "io")]
+pub mod checksum;
+#[cfg(feature = "io")]
+pub mod chunked;
+#[cfg(feature = "io")]
+pub mod compress;
+#[cfg(feature = "io")]
+pub mod coverage;
+#[cfg(feature = "polars")]
+pub mod dataframe;
+#[cfg(feature = "io")]
+pub mod diff;
+pub mod dinuc;
+pub mod dust;
+pub mod error;
+pub mod expr;
+#[cfg(feature = "io")]
+pub mod faidx;
+pub mod flags;
+pub mod g4;
+#[cfg(feature = "io")]
+pub mod gff;
+pub mod igv;
+#[cfg(feature = "io")]
+pub mod input;
+#[cfg(any(feature = "bio-types", feature = "rust-lapper"))]
+pub mod intervals;
+pub mod kmer;
+pub mod merge;
+pub mod methylation;
+pub mod microsat;
+#[cfg(feature = "io")]
+pub mod mmap;
+pub mod name_template;
+pub mod packed;
+pub mod palindrome;
+#[cfg(feature = "plot")]
+pub mod plot;
+#[cfg(feature = "pipeline")]
+pub mod pipeline;
+pub mod polya;
+pub mod pretty;
+#[cfg(feature = "cli")]
+pub mod progress;
+#[cfg(feature = "io")]
+pub mod pwm;
+#[cfg(feature = "remote")]
+pub mod remote;
+#[cfg(feature = "io")]
+pub mod report;
+pub mod run_stats;
+pub mod runs;
+pub mod scan;
+pub mod scanner;
+pub mod simulate;
+pub mod sink;
+pub mod skew;
+pub mod smooth;
+pub mod sort;
+pub mod split;
+pub mod svg;
+pub mod telomere;
+#[cfg(feature = "io")]
+pub mod timings;
+pub mod top;
+pub mod trim;
+#[cfg(feature = "io")]
+pub mod twobit;
+#[cfg(feature = "io")]
+pub mod vcf;
+pub mod weighted;
+pub mod zdna;
+
+pub use scanner::{scan_windows, Hit, ScanParams, ScanParamsBuilder, Scanner, WindowHits};
+pub use sink::OutputSink;
@@ -1,245 +1,3412 @@
-use std::fs::File;
-use std::io::{BufReader, Write};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, ErrorKind, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::error::Error;
+use std::ffi::OsString;
 
-use clap::Parser;
-use bio::io::fasta;
-use bio::io::bed::{Writer, Record as BedRecord};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use log::{debug, info, trace, warn, Level, LevelFilter};
 
-/// Command-line arguments
+mod batch;
+mod config;
+mod self_test;
+mod serve;
+#[cfg(feature = "tui")]
+mod view;
+
+use polyscan::benchmark::{benchmark_at, benchmark_curve, read_genome_length, read_scored_bed, roc_curve};
+use polyscan::checkpoint::Checkpoint;
+use polyscan::bases::CharacterCounts;
+use polyscan::bed::{merge_regions, read_bed, read_bed_entries, BedEntry, BedWriter, IntervalSet, HITS_SCHEMA};
+use polyscan::compress::{Codec, CompressedWriter};
+use polyscan::calibrate::{CoverageHistogram, Topology};
+use polyscan::cap::HitCapWriter;
+use polyscan::checksum::ContigHasher;
+use polyscan::expr::Expr;
+use polyscan::faidx::{FaiIndex, Region};
+use polyscan::mmap::mapped_records;
+use polyscan::merge::{Clusters, Reduction};
+use polyscan::methylation::context_tiles;
+use polyscan::name_template::NameTemplate;
+use polyscan::microsat::{DEFAULT_MIN_COPIES, MAX_UNIT};
+use polyscan::diff::{diff_regions, read_grouped_intervals};
+use polyscan::dust::{sdust, DEFAULT_THRESHOLD, DEFAULT_WINDOW};
+use polyscan::error::{PolyscanError, EXIT_INPUT};
+use polyscan::gff::{read_annotation, read_introns};
+use polyscan::polya::find_tail;
+use polyscan::scanner::{strand_targets, MaskedPolicy, NPolicy, StrandChoice};
+use polyscan::weighted::triangular;
+use polyscan::run_stats::{self, CappedContig, ContigChecksum};
+use polyscan::timings::{self, timed_records, TimedWriter};
+use polyscan::telomere::DEFAULT_MOTIF;
+use polyscan::trim::{keep_interval, TrimWriter};
+use polyscan::vcf::{annotate_vcf, filter_vcf, open_vcf, SequenceContext};
+use polyscan::progress::with_progress;
+use polyscan::pwm::read_pwms;
+use polyscan::kmer::{iupac_mask, reverse_complement};
+use polyscan::input::{is_remote_path, open_chunked_records, open_records, open_records_with, region_records, IndexedSource, InputBackend, InputFormat, ReadCheck, Records, SeqRecord};
+use polyscan::report::ReportCollector;
+use polyscan::simulate::{simulate, LengthDistribution, PlantedTract, SimulationParams};
+use polyscan::plot::{write_density_plot, PlotFormat};
+use polyscan::smooth::{density_runs, smoothed_density, Kernel};
+use polyscan::skew::{predict_origin_terminus, skew_profile, SkewWindow};
+use polyscan::igv::{self, SnapshotRegion};
+use polyscan::pipeline::{finish_trim, rename_record, scan_records, scan_records_parallel, trim_writer, write_bed_record, BedScore, CaseMode, Mode, OverlapMode, ParallelInput, PvalueFormat, ScanConfig, ScoreBackground, ScoreMode, ShortContigPolicy, Stat, PIECES_PER_THREAD};
+use polyscan::pretty::PrettyWriter;
+use polyscan::sort::{SortOrder, SortedWriter};
+use polyscan::split::{SplitBy, StrandSplitWriter};
+use polyscan::scan::{composition_windows, has_uracil, BaseClass, Composition, rna_to_dna, Alphabet, tile_windows, ScanHit, Strand};
+
+/// Command-line arguments: a subcommand, or the arguments of `scan` for
+/// backward compatibility
 #[derive(Parser, Debug)]
 #[command(name = "polyscan",
           version = "0.1.0",
           author = "Maximillian Marin <maximilliangmarin@gmail.com>",
-          about = "Find windows in DNA sequences that have >= threshold% of a nucleotide. Outputs 6-column BED.")]
+          about = "Find windows in DNA sequences that have >= threshold% of a nucleotide. Outputs 6-column BED.",
+          subcommand_negates_reqs = true,
+          args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(flatten)]
+    scan: Args,
+
+    /// More detail on stderr
+    #[arg(short = 'v', long = "verbose", action = clap::ArgAction::Count, global = true,
+          help = "Log more to stderr: -v adds per-contig timings, hit counts and skipped contigs, -vv also the parsed options")]
+    verbose: u8,
+
+    /// Only errors on stderr
+    #[arg(short = 'q', long = "quiet", global = true, conflicts_with = "verbose",
+          help = "Only report errors on stderr, not warnings or summaries")]
+    quiet: bool,
+
+    /// How a failure is reported on stderr
+    #[arg(long = "error-format", value_enum, default_value_t = ErrorFormat::Text, global = true,
+          help = "How a failure is reported on stderr: 'text' (Error: and the message) or 'json' (one object with the kind of error, its exit code and the message; kinds are parameter, input, empty and other)")]
+    error_format: ErrorFormat,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Arguments of `polyscan scan` (the default command)
+#[derive(clap::Args, Debug, Clone)]
 struct Args {
-    /// Path to input FASTA file
-    #[arg(short, long)]
-    fasta: String,
+    /// Path to input FASTA/FASTQ (optionally compressed) or .2bit file
+    #[arg(short, long, required_unless_present_any = ["self_test", "manifest", "schema"])]
+    fasta: Option<String>,
 
-    /// Window size
-    #[arg(short = 'w', long = "window-size", default_value_t = 10,
-          help = "Length of the sliding window")]
-    window_size: usize,
+    /// Scan every sample of a manifest instead of one input
+    #[arg(long = "manifest", value_name = "TSV", conflicts_with = "fasta", requires = "outdir",
+          help = "Instead of --fasta, scan every sample of this TSV (sample name, input path, and optionally extra options for that sample), writing OUTDIR/<sample>.bed and OUTDIR/summary.tsv")]
+    manifest: Option<PathBuf>,
+
+    /// Directory for the outputs of --manifest
+    #[arg(long = "outdir", value_name = "DIR", requires = "manifest",
+          help = "With --manifest, the directory to write each sample's BED output and the combined summary to")]
+    outdir: Option<PathBuf>,
+
+    /// Samples scanned at once with --manifest
+    #[arg(long = "jobs", short = 'j', value_name = "N", default_value_t = 1,
+          value_parser = clap::value_parser!(u64).range(1..),
+          help = "With --manifest, scan this many samples at once")]
+    jobs: u64,
+
+    /// TOML file of scan options
+    #[arg(long = "config", value_name = "TOML",
+          help = "Read scan options from this TOML file, keyed by long option name (e.g. window-size = [10, 50]); options given on the command line take precedence")]
+    config: Option<PathBuf>,
+
+    /// Named bundle of scan options
+    #[arg(long = "preset", value_enum,
+          help = "Start from sensible options for a common use case: 'ont-homopolymer' (-n A,C,G,T -w 6 -p 100 --merge), 'illumina-polyA' (-n A -w 15 -p 90 --merge), 'at-island' (-n AT -w 100 -p 80 --merge) or 'cpg' (--stat dinuc --pair CG -w 200 -p 4 --allow-low-threshold --merge --min-length 200); options given on the command line or in --config take precedence")]
+    preset: Option<Preset>,
+
+    /// Window size, or several for a multi-scale scan
+    #[arg(short = 'w', long = "window-size", value_delimiter = ',', default_values_t = [10],
+          value_parser = clap::value_parser!(u64).range(1..),
+          help = "Length of the sliding window; several comma-separated sizes (e.g. 10,50,200) scan every scale in one run and add a window size column")]
+    window_size: Vec<u64>,
 
     /// Percentage threshold (e.g. 80.0 for 80%)
     #[arg(short = 'p', long = "percentage", default_value_t = 80.0,
           help = "Percentage of target nucleotide required in the window")]
     percentage: f64,
 
+    /// Several thresholds evaluated in one pass
+    #[arg(long = "percentage-sweep", value_name = "START:STOP:STEP", conflicts_with = "percentage",
+          help = "Instead of --percentage, evaluate the thresholds START, START+STEP, ... STOP in one pass (e.g. 70:95:5), reporting each window once with the highest threshold it meets in an extra column")]
+    percentage_sweep: Option<String>,
+
+    /// Pick the threshold reporting this fraction of the genome
+    #[arg(long = "target-fraction", value_name = "FRACTION", conflicts_with_all = ["percentage", "percentage_sweep", "min_count"],
+          help = "Instead of --percentage, read the input once to find the threshold at which the reported windows cover about FRACTION (0 to 1) of the genome, e.g. 0.02 to mask the most target-rich 2%, then scan with it",
+          long_help = "Instead of --percentage, read the input once to find the threshold at which the reported windows cover about FRACTION (0 to 1) of the genome, e.g. 0.02 to mask the most target-rich 2%, then scan with it.\n\n\
+                       The first pass finds, for every base, the most target bases held by a window covering it (on either strand scanned, as --strand says), which gives the genome fraction reported at every threshold. \
+                       The threshold closest to FRACTION is logged as both --min-count and --percentage, either of which gives the same threshold when passed back. \
+                       A window holds a whole number of target bases, so the fractions within reach are steps apart for small windows.\n\n\
+                       Windows wrap around circular contigs and windows overlapping --exclude-bed are dropped as in the scan (excluded bases are left out of the genome); other filters applied afterwards (--min-length, --max-n-frac, ...) are not taken into account. \
+                       The input is read twice, so it can't be --fasta -.")]
+    target_fraction: Option<f64>,
+
+    /// Accept percentages below 50
+    #[arg(long = "allow-low-threshold",
+          help = "Allow --percentage (and --seed-percentage/--extend-percentage) below 50, e.g. -p 20 -n N for windows with at least 20% N")]
+    allow_low_threshold: bool,
+
+    /// Absolute count threshold, instead of --percentage
+    #[arg(long = "min-count", value_name = "N", conflicts_with = "percentage",
+          value_parser = clap::value_parser!(u64).range(1..),
+          help = "Instead of --percentage, the number of target bases required in the window")]
+    min_count: Option<u64>,
+
     /// Single nucleotide to check (A,C,G,T,N). Its complement is automatically handled.
     #[arg(short = 'n', long = "nucleotide", default_value = "A",
-          help = "nucleotide base to search for (i.e A, C, T, or G)")]
+          help = "nucleotide base to search for (i.e A, C, T, or G), a class counted together (e.g. GC), or a comma-separated list of these")]
     nucleotide: String,
-}
 
-fn main() -> Result<(), Box<dyn Error>> {
-    // Parse CLI
-    let args = Args::parse();
+    /// Sequence alphabet
+    #[arg(long = "alphabet", value_enum, default_value_t = Alphabet::Dna,
+          help = "Sequence alphabet; with 'protein', --nucleotide takes any residue letter (e.g. Q for poly-Q) and hits are unstranded")]
+    alphabet: Alphabet,
 
-    let fasta_path = args.fasta;
-    let w = args.window_size;
-    let p = args.percentage;
-    let user_base = args.nucleotide.to_uppercase();
+    /// Score windows by k-mer density instead of base fraction
+    #[arg(long = "kmer", value_name = "KMER", conflicts_with_all = ["adaptive", "tile"],
+          help = "Score windows by the % of bases covered by occurrences of this k-mer (e.g. AAT; comma-separated for several) instead of a single base; the reverse complement is scanned on the minus strand")]
+    kmer: Option<String>,
+
+    /// File of k-mers, one per line
+    #[arg(long = "kmer-file", value_name = "FILE", conflicts_with_all = ["adaptive", "tile"],
+          help = "Like --kmer, reading k-mers from a file (one per line; blank lines and lines starting with '#' are skipped)")]
+    kmer_file: Option<PathBuf>,
+
+    /// Score windows by degenerate motif density
+    #[arg(long = "motif", value_name = "MOTIF", conflicts_with_all = ["kmer", "kmer_file", "adaptive", "tile"],
+          help = "Like --kmer for a motif with IUPAC codes (e.g. GGNGG or TATAWR; comma-separated for several); DNA only")]
+    motif: Option<String>,
+
+    /// Mismatches allowed per motif occurrence
+    #[arg(long = "max-mismatches", value_name = "N", default_value_t = 0,
+          help = "Number of positions at which a --motif occurrence may differ from the motif, substituted bases tolerated inside a --mode str repeat, or unpaired positions in --mode palindrome arms")]
+    max_mismatches: usize,
+
+    /// Score windows by their best position weight matrix site
+    #[arg(long = "pwm", value_name = "FILE", conflicts_with_all = ["kmer", "kmer_file", "motif", "adaptive", "tile"],
+          help = "Report windows whose best site for a position weight matrix (JASPAR or MEME file; every matrix is scanned) reaches --pwm-cutoff, on both strands; DNA only")]
+    pwm: Option<PathBuf>,
+
+    /// Relative PWM score cutoff
+    #[arg(long = "pwm-cutoff", value_name = "PCT", default_value_t = 80.0, requires = "pwm",
+          help = "Minimum relative site score for --pwm, from 0 (worst possible site) to 100 (consensus)")]
+    pwm_cutoff: f64,
+
+    /// Input format (detected from the file contents by default)
+    #[arg(long = "input-format", value_enum, default_value_t = InputFormat::Auto,
+          help = "Input format; FASTQ reads are reported with the read name as the chrom")]
+    input_format: InputFormat,
+
+    /// FASTA/FASTQ parser
+    #[arg(long = "input-backend", value_enum, default_value_t = InputBackend::Noodles,
+          conflicts_with_all = ["chunk_size", "mmap", "region", "include_bed"],
+          help = "Library parsing FASTA and FASTQ input: 'noodles', or 'needletail', which parses records in place in its read buffer and is faster on inputs of many small contigs")]
+    input_backend: InputBackend,
+
+    /// Compression format of the output
+    #[arg(long = "compress-codec", value_enum, value_name = "CODEC", conflicts_with_all = ["checkpoint", "report_dir", "manifest"],
+          help = "Compress the output with CODEC: 'gzip', 'bgzf' (bgzip, which tabix can index), 'zstd' or 'xz' [default: from the --output extension (.gz, .bgz, .zst, .xz), or zstd with --compress-level]")]
+    compress_codec: Option<Codec>,
+
+    /// Compression level of the output
+    #[arg(long = "compress-level", value_name = "N", conflicts_with_all = ["checkpoint", "report_dir", "manifest"],
+          help = "Compression level of the output: 0-9 for gzip, bgzf and xz (default 6), 1-22 for zstd (default 3). Compresses the output, with zstd unless --compress-codec or the --output extension says otherwise")]
+    compress_level: Option<u32>,
+
+    /// Threads inflating BGZF input
+    #[arg(long = "decompress-threads", value_name = "N", value_parser = clap::value_parser!(u64).range(1..),
+          help = "Inflate BGZF-compressed input (bgzip) on N threads, block by block [default: the --threads value]")]
+    decompress_threads: Option<u64>,
+
+    /// Fail unless the whole input file was read
+    #[arg(long = "io-strict", conflicts_with_all = ["region", "include_bed", "mmap"],
+          help = "Check that every byte of the FASTA/FASTQ file was read by the time its last record was, and fail if not, so a compressed input can't be silently truncated (e.g. after the first of several concatenated gzip members)")]
+    io_strict: bool,
+
+    /// Reference FASTA used to decode CRAM input
+    #[arg(long = "reference",
+          help = "Reference FASTA (with .fai) used to decode CRAM input")]
+    reference: Option<PathBuf>,
+
+    /// Report BAM/CRAM read hits in reference coordinates
+    #[arg(long = "project-to-reference",
+          help = "For BAM/CRAM input, project read hits onto the reference using the CIGAR (unmapped reads are skipped)")]
+    project_to_reference: bool,
+
+    /// Regions to scan (samtools-style chrom:start-end, 1-based inclusive)
+    #[arg(short = 'r', long = "region",
+          help = "Only scan this region (chrom, chrom:start or chrom:start-end; repeatable). Uses .fai/.gzi indexes for random access when present")]
+    region: Vec<Region>,
+
+    /// Only scan inside the intervals of this BED file
+    #[arg(long = "include-bed", value_name = "BED", conflicts_with = "region",
+          help = "Only scan bases inside the intervals of this BED file; windows never cross an interval boundary (overlapping intervals are merged first)")]
+    include_bed: Option<PathBuf>,
+
+    /// Stream FASTA contigs in chunks of this many bases
+    #[arg(long = "chunk-size", value_name = "BASES", conflicts_with_all = ["region", "include_bed", "tile"],
+          value_parser = clap::value_parser!(u64).range(1..),
+          help = "Stream FASTA input in overlapping chunks of this many bases instead of loading whole contigs, so memory use no longer grows with contig length (e.g. 10000000)")]
+    chunk_size: Option<u64>,
+
+    /// Memory-map an uncompressed, indexed FASTA
+    #[arg(long = "mmap", conflicts_with = "chunk_size",
+          help = "Memory-map an uncompressed FASTA with a .fai index and scan sequences in place instead of copying them (contigs on a single line are never copied)")]
+    mmap: bool,
+
+    /// Suppress windows overlapping the intervals of this BED file
+    #[arg(long = "exclude-bed", value_name = "BED",
+          help = "Suppress windows that overlap any interval of this BED file (e.g. RepeatMasker output or assembly gaps)")]
+    exclude_bed: Option<PathBuf>,
+
+    /// Keep or drop records by their overlap with the intervals of this BED file
+    #[arg(long = "filter-overlap", value_name = "BED",
+          help = "Keep or drop records (see --overlap-mode) by how much of them the intervals of this BED file cover, e.g. RepeatMasker repeats; unlike masking the input, the scan itself is unchanged")]
+    filter_overlap: Option<PathBuf>,
+
+    /// What --filter-overlap does with overlapping records
+    #[arg(long = "overlap-mode", value_enum, default_value_t = OverlapMode::Remove, requires = "filter_overlap",
+          help = "With --filter-overlap, 'remove' (the default) drops the records that overlap its intervals, 'keep' only keeps those")]
+    overlap_mode: OverlapMode,
+
+    /// Fraction of a record that must be covered to count as overlapping
+    #[arg(long = "min-overlap-frac", value_name = "FRACTION", default_value_t = 0.0, requires = "filter_overlap",
+          help = "With --filter-overlap, the fraction of a record its intervals must cover for it to overlap them, e.g. 0.5 (the default, 0, means any overlap)")]
+    min_overlap_frac: f64,
+
+    /// Append a 7th column of caveat flags
+    #[arg(long = "flags",
+          help = "Append a column of comma-separated caveat flags (near-contig-end, contains-N, overlaps-gap, truncated-by-chunking, partial-window)")]
+    flags: bool,
+
+    /// Window statistic
+    #[arg(long = "stat", value_enum, default_value_t = Stat::Fraction,
+          help = "Window statistic: 'fraction' (the --nucleotide target makes up at least --percentage of the window) 'entropy' (Shannon entropy of the window's bases is at most --max-entropy) or 'dinuc' (the --pair dinucleotide makes up at least --percentage of the window's adjacent pairs)")]
+    stat: Stat,
+
+    /// Weight of each position in the window
+    #[arg(long = "window-weights", value_name = "PROFILE",
+          help = "Weight the positions of the window instead of counting every base alike: 'uniform' (the default), 'triangular' (rising from the edges to the centre), a comma-separated list of --window-size weights, or a file of them (whitespace-separated); windows pass when target bases hold at least --percentage of the total weight")]
+    window_weights: Option<String>,
+
+    /// What the score column of a record holds
+    #[arg(long = "score-mode", value_enum, default_value_t = ScoreMode::Percentage,
+          help = "Score column of target windows: 'percentage' (the default: the percentage of target bases, rounded up) or 'zscore' (how many standard deviations that percentage lies above the --score-background composition, to two decimals)")]
+    score_mode: ScoreMode,
+
+    /// Composition z-scores are measured against
+    #[arg(long = "score-background", value_enum, default_value_t = ScoreBackground::Contig,
+          help = "With --score-mode zscore, the base composition to score against: 'contig' (the default: the whole contig) or 'local' (the megabase around the window)")]
+    score_background: ScoreBackground,
+
+    /// Decimals of the score column
+    #[arg(long = "score-precision", value_name = "N", value_parser = clap::value_parser!(u8).range(0..=10),
+          conflicts_with = "report_dir",
+          help = "Write scores to N decimal places (0 to 10) instead of rounding percentages up to whole numbers (83.333 becomes 84) and z-scores to two decimals")]
+    score_precision: Option<u8>,
+
+    /// Entropy threshold for --stat entropy
+    #[arg(long = "max-entropy", value_name = "BITS", default_value_t = 1.5,
+          help = "With --stat entropy, the highest entropy in bits to report (2 is an even mix of A/C/G/T, 1 e.g. an (AT)n repeat)")]
+    max_entropy: f64,
+
+    /// Window predicate over letter fractions
+    #[arg(long = "expr", value_name = "EXPR",
+          help = "Report windows where this expression over letter fractions is true, instead of --nucleotide/--percentage (e.g. \"A+T >= 0.8 && N == 0\"; letters are fractions of the window, with + - * / ( ) < <= > >= == != && || !)")]
+    expr: Option<String>,
+
+    /// Dinucleotide for --stat dinuc
+    #[arg(long = "pair", value_name = "XY",
+          help = "With --stat dinuc, the dinucleotide to score, with IUPAC codes (e.g. TA, GT); its reverse complement is scanned on the minus strand")]
+    pair: Option<String>,
+
+    /// What to detect: fixed windows, or exact runs
+    #[arg(long = "mode", value_enum, default_value_t = Mode::Windows,
+          help = "What to detect: 'windows' (sliding windows above --percentage), 'runs' (maximal exact runs of the target of at least --min-run bases, length in the score column), 'str' (tandem repeats of 1-6 bp units, named e.g. (AT)12, purity in the score column), 'tandem' (approximate tandem repeats with units up to --max-unit bases), 'palindrome' (inverted repeats, one record per arm), 'g4' (G-quadruplex motifs G3+N1-7G3+N1-7G3+N1-7G3+ on both strands, G4Hunter score x100), 'skew' (GC skew of non-overlapping windows and the cumulative skew as bedGraph, with predicted origin/terminus), 'dust' (SDUST low-complexity intervals), 'telomere' (blocks of --telomere-motif repeats, with copy counts and the contig end they belong to), 'zdna' (windows where at least --percentage of adjacent bases alternate purine/pyrimidine), 'ppt' (C/T-rich windows at the 3' end of the --gff introns, named by intron), 'sites' (every occurrence of the --site recognition sequences), 'gaps' (maximal runs of N of at least --min-run bases, length in the score column) or 'methylation' (CpG, CHG and CHH cytosine densities of non-overlapping windows, as a table)")]
+    mode: Mode,
+
+    /// Shortest run reported by --mode runs or gaps
+    #[arg(long = "min-run", value_name = "BASES", default_value_t = 8,
+          value_parser = clap::value_parser!(u64).range(1..),
+          help = "With --mode runs or gaps, the shortest run (or gap) to report")]
+    min_run: u64,
+
+    /// Minimum copy numbers for --mode str
+    #[arg(long = "min-copies", value_name = "N[,N...]", value_delimiter = ',', default_values_t = DEFAULT_MIN_COPIES,
+          help = "With --mode str, the fewest copies of a 1-6 bp unit to report: one value for every unit size, or six for units of 1, 2, ... 6 bp")]
+    min_copies: Vec<usize>,
+
+    /// Longest unit for --mode tandem
+    #[arg(long = "max-unit", value_name = "BASES", default_value_t = 100,
+          value_parser = clap::value_parser!(u64).range(1..=10000),
+          help = "With --mode tandem, the longest repeat unit to look for")]
+    max_unit: u64,
+
+    /// Divergence allowed between copies for --mode tandem
+    #[arg(long = "max-divergence", value_name = "PCT", default_value_t = 10.0,
+          help = "With --mode tandem, the highest percentage of bases that may differ from the copy before them")]
+    max_divergence: f64,
+
+    /// Shortest arm for --mode palindrome
+    #[arg(long = "min-arm", value_name = "BASES", default_value_t = 10,
+          value_parser = clap::value_parser!(u64).range(1..),
+          help = "With --mode palindrome, the shortest arm to report")]
+    min_arm: u64,
+
+    /// Longest loop for --mode palindrome
+    #[arg(long = "max-loop", value_name = "BASES", default_value_t = 20,
+          help = "With --mode palindrome, the longest loop between the arms (0 for perfect palindromes only)")]
+    max_loop: u64,
+
+    /// G4Hunter window threshold for --mode g4
+    #[arg(long = "g4hunter", value_name = "THRESHOLD",
+          help = "With --mode g4, report --window-size windows (25 is usual) whose mean G4Hunter score is at least THRESHOLD (e.g. 1.2), or at most -THRESHOLD on the minus strand, instead of regex motifs")]
+    g4hunter: Option<f64>,
+
+    /// SDUST window for --mode dust
+    #[arg(long = "dust-window", value_name = "BASES", default_value_t = DEFAULT_WINDOW as u64,
+          value_parser = clap::value_parser!(u64).range(4..),
+          help = "With --mode dust, the SDUST window length")]
+    dust_window: u64,
+
+    /// SDUST score threshold for --mode dust
+    #[arg(long = "dust-threshold", value_name = "SCORE", default_value_t = DEFAULT_THRESHOLD as u64,
+          help = "With --mode dust, the SDUST score threshold (lower masks more)")]
+    dust_threshold: u64,
+
+    /// Output of --mode dust
+    #[arg(long = "dust-output", value_enum, default_value_t = DustOutput::Bed,
+          help = "With --mode dust, write 'bed' intervals, or the input as 'soft-masked' (lowercase) or 'hard-masked' (N) FASTA")]
+    dust_output: DustOutput,
+
+    /// Telomeric repeat(s) for --mode telomere
+    #[arg(long = "telomere-motif", value_name = "MOTIF", default_value = DEFAULT_MOTIF,
+          help = "With --mode telomere, the telomeric repeat unit (e.g. TTTAGGG for plants; comma-separated for several); its reverse complement is found on the minus strand")]
+    telomere_motif: String,
+
+    /// GFF3 annotation for --mode ppt
+    #[arg(long = "gff", value_name = "GFF3",
+          help = "With --mode ppt, the GFF3 annotation (optionally compressed) whose introns are scanned: intron features, and the gaps between the exons of each transcript")]
+    gff: Option<PathBuf>,
+
+    /// Length of the intron 3' ends scanned by --mode ppt
+    #[arg(long = "ppt-region", value_name = "BASES", default_value_t = 50,
+          value_parser = clap::value_parser!(u64).range(1..),
+          help = "With --mode ppt, how many bases at the 3' end of each intron to scan")]
+    ppt_region: u64,
+
+    /// Recognition sequences for --mode sites
+    #[arg(long = "site", value_name = "[NAME=]SEQ",
+          help = "With --mode sites, the recognition sequences to report, with IUPAC codes and optional names (e.g. EcoRI=GAATTC,HinfI=GANTC)")]
+    site: Option<String>,
+
+    /// Experimental: grow seed windows into maximal intervals
+    #[arg(long = "adaptive",
+          help = "Experimental: extend each passing seed window greedily into the maximal interval that stays above the threshold, reporting one interval per tract")]
+    adaptive: bool,
+
+    /// Hysteresis thresholds: seed windows, then windows extending them
+    #[arg(long = "seed-percentage", value_name = "PERCENT", requires = "extend_percentage",
+          help = "Instead of --percentage, seed regions at windows with at least this percentage of the target and extend them over consecutive windows with at least --extend-percentage, reporting one region per tract")]
+    seed_percentage: Option<f64>,
+
+    /// Laxer threshold for extending --seed-percentage regions
+    #[arg(long = "extend-percentage", value_name = "PERCENT", requires = "seed_percentage",
+          help = "With --seed-percentage, the lower percentage of the target that windows extending a region must reach")]
+    extend_percentage: Option<f64>,
+
+    /// Report every non-overlapping tile's composition instead of thresholded windows
+    #[arg(long = "tile",
+          help = "Partition each contig into non-overlapping windows and report every tile's A/C/G/T/N counts as TSV, regardless of threshold")]
+    tile: bool,
+
+    /// Report a smoothed per-base density track instead of thresholded windows
+    #[arg(long = "smooth", value_name = "KERNEL:BASES",
+          help = "Write the density of the target along each contig as a bedGraph track, smoothed with 'gaussian:SIGMA' (a Gaussian with a standard deviation of SIGMA bases, e.g. gaussian:25) or 'box:WIDTH' (a moving average over WIDTH bases), instead of thresholded windows")]
+    smooth: Option<String>,
+
+    /// Write a results bundle (hits, merged regions, bigWig, summary, HTML) to this directory
+    #[arg(long = "report-dir", value_name = "DIR",
+          help = "Write a results bundle to DIR: hits.bed (the BED output, instead of stdout), merged.bed, density.bw, summary.tsv, parameters.tsv and report.html")]
+    report_dir: Option<PathBuf>,
+
+    /// What to report: thresholded hits, or the composition of every window
+    #[arg(long = "report", value_enum, default_value_t = Report::Hits, conflicts_with = "tile",
+          help = "What to report: 'hits' (thresholded windows as BED) or 'composition' (A/C/G/T/N counts of every window as TSV, regardless of threshold)")]
+    report: Report,
+
+    /// With --report composition, only report every Nth window
+    #[arg(long = "report-every", value_name = "N", default_value_t = 1,
+          value_parser = clap::value_parser!(u64).range(1..),
+          help = "With --report composition, report every Nth window (windows starting at multiples of N)")]
+    report_every: u64,
+
+    /// Take soft-masked (lowercase) bases out of the scan
+    #[arg(long = "ignore-masked", value_name = "POLICY", value_enum, num_args = 0..=1, default_missing_value = "exclude",
+          help = "Ignore soft-masked (lowercase) bases, e.g. RepeatMasker repeats: 'exclude' (the default) leaves them out of the window so --percentage is of the unmasked bases, 'non-matching' counts them against the target")]
+    ignore_masked: Option<MaskedPolicy>,
+
+    /// Case of the bases scanned
+    #[arg(long = "case-mode", value_enum, default_value_t = CaseMode::Insensitive, value_name = "MODE",
+          help = "Which bases to scan by case: 'insensitive' (default: all), 'upper-only' (unmasked bases only, as --ignore-masked) or 'lower-only' (soft-masked bases only, e.g. repeats). Bases of the other case are left out of the window, or counted against the target with --ignore-masked non-matching")]
+    case_mode: CaseMode,
+
+    /// Strands to report
+    #[arg(long = "strand", value_enum, default_value_t = StrandChoice::Both,
+          help = "Strands to report: 'both' (the default: the target on + and its complement on -), 'plus' or 'minus' (only that strand, plus unstranded hits), or 'collapse' (one unstranded record per window where either strand passes, with the better score)")]
+    strand: StrandChoice,
+
+    /// Report a target and its complement as one unstranded track
+    #[arg(long = "combine-complements",
+          help = "Report one unstranded record, named e.g. A/T, for every window where the target or its complement passes, scored by the better of the two (--strand collapse, with records named by both bases rather than the target alone)")]
+    combine_complements: bool,
+
+    /// Shrink windows to the target bases inside them
+    #[arg(long = "trim-to-content",
+          help = "Shrink each reported window to its first and last target base, so records give tract boundaries rather than window-padded coordinates; overlapping windows trimming to the same tract are reported once")]
+    trim_to_content: bool,
+
+    /// Reduce each cluster of overlapping windows to its best one
+    #[arg(long = "best-per-cluster",
+          help = "Report only the best window of every cluster of overlapping passing windows (highest score, then longest, then leftmost), so a long tract gives one record instead of hundreds")]
+    best_per_cluster: bool,
+
+    /// Merge overlapping or adjacent windows into maximal regions
+    #[arg(long = "merge", conflicts_with = "best_per_cluster",
+          help = "Merge overlapping or adjacent passing windows (of the same target and strand) into maximal regions, instead of piping the output through bedtools merge")]
+    merge: bool,
+
+    /// Score of merged regions
+    #[arg(long = "merge-score", value_enum, default_value_t = MergeScore::Max, requires = "merge",
+          help = "Score of merged regions: 'max' (the default: their best window) or 'mean' (the mean of their windows)")]
+    merge_score: MergeScore,
+
+    /// Whether plus and minus windows are merged together
+    #[arg(long = "merge-strand", value_enum, default_value_t = MergeStrand::Same, requires = "merge",
+          help = "With --merge, 'same' (the default) merges plus and minus windows separately; 'any' merges a target's windows on both strands together, reporting regions with windows on both strands as unstranded (e.g. poly-A and poly-T internal-priming sites)")]
+    merge_strand: MergeStrand,
+
+    /// Also merge regions this close to each other
+    #[arg(long = "max-gap", value_name = "BASES", default_value_t = 0, requires = "merge",
+          help = "With --merge, also join regions separated by at most this many bases, e.g. 1 to bridge a single substitution in a homopolymer tract")]
+    max_gap: u64,
+
+    /// Drop merged regions shorter than this
+    #[arg(long = "min-length", value_name = "BASES", default_value_t = 0, requires = "merge",
+          help = "With --merge, only report regions of at least this many bases (after any --max-gap joining and --trim-to-content), e.g. 30 for masking")]
+    min_length: u64,
+
+    /// Extend every record by this many bases on each side
+    #[arg(long = "slop", value_name = "BASES",
+          help = "Extend every record by BASES on each side, clamped to the contig ends, e.g. as a safety margin around primer-design exclusion zones")]
+    slop: Option<u64>,
+
+    /// Extend every record by this many bases on its left side
+    #[arg(long = "slop-left", value_name = "BASES",
+          help = "Extend every record by BASES on its left (lower coordinate) side, whatever its strand; overrides --slop on that side")]
+    slop_left: Option<u64>,
+
+    /// Extend every record by this many bases on its right side
+    #[arg(long = "slop-right", value_name = "BASES",
+          help = "Extend every record by BASES on its right (higher coordinate) side, whatever its strand; overrides --slop on that side")]
+    slop_right: Option<u64>,
+
+    /// Annotate records with the genes they overlap
+    #[arg(long = "annotate", value_name = "GFF",
+          help = "Add two columns (before any --flags) from this GFF3 or GTF annotation (optionally compressed): where each record lies ('exon', 'intron' or 'intergenic') and the IDs of the genes it overlaps ('.' for none)")]
+    annotate: Option<PathBuf>,
+
+    /// Attach the nearest gene and the distance to it
+    #[arg(long = "closest", value_name = "GFF",
+          help = "Add two columns (after any --annotate ones) from this GFF3 or GTF annotation: the ID of the gene closest to each record and the signed distance to it (0 when overlapping or adjacent, negative upstream of the gene on its strand, positive downstream; '.' for contigs without genes)")]
+    closest: Option<PathBuf>,
+
+    /// Summarize the records of every gene to this file
+    #[arg(long = "gene-report", value_name = "FILE", requires = "annotate",
+          help = "With --annotate, also write a TSV to FILE with a row per gene of the annotation: the number of records overlapping it, the bases of those records in its CDS, UTRs and introns, and the highest score among them")]
+    gene_report: Option<PathBuf>,
+
+    /// Write the base composition of every record to this file
+    #[arg(long = "composition-matrix", value_name = "FILE",
+          help = "Also write a TSV to FILE with, for every record written, its coordinates, name and strand, its A/C/G/T/N counts and its GC% (of the A/C/G/T bases), e.g. as classifier features")]
+    composition_matrix: Option<PathBuf>,
+
+    /// Report the parts of every contig without hits instead
+    #[arg(long = "complement",
+          help = "Report the intervals of every contig NOT covered by any record (whatever its target or strand) as BED3, e.g. as a clean-sequence whitelist, instead of the records themselves")]
+    complement: bool,
+
+    /// Report the union of the records instead
+    #[arg(long = "unique-bases", conflicts_with = "complement",
+          help = "Report the union of the records (whatever their target or strand) as non-overlapping BED6 intervals, named after the targets they join and scored with the highest score among their records, so that summing coverage downstream counts every base once, instead of the records themselves")]
+    unique_bases: bool,
+
+    /// Write per-contig and genome-wide hit coverage to this file
+    #[arg(long = "coverage-summary", value_name = "FILE",
+          help = "Write a TSV to FILE with, for every contig and the whole genome, the number of bases covered by at least one record and the fraction of its length they represent")]
+    coverage_summary: Option<PathBuf>,
+
+    /// Write how many records cover each base of their union to this file
+    #[arg(long = "occupancy", value_name = "FILE",
+          help = "Write the union of the records (whatever their target or strand) to FILE as non-overlapping intervals with a 4th column giving how many records (windows, unless merged) cover each of their bases, split where that support depth changes, to tell marginal single-window hits from robust regions")]
+    occupancy: Option<PathBuf>,
+
+    /// Write every contig and what became of it to this file
+    #[arg(long = "contig-report", value_name = "FILE", conflicts_with = "checkpoint",
+          help = "Write a TSV to FILE listing every contig scanned with its length, number of records and status: 'reported', 'no-hits', 'excluded' (every window found was dropped by --exclude-bed or --filter-overlap), 'shorter-than-window' or 'empty'")]
+    contig_report: Option<PathBuf>,
+
+    /// Write the part of every read to keep, once terminal tails are trimmed, as BED
+    #[arg(long = "trim-bed", value_name = "FILE", conflicts_with_all = ["checkpoint", "manifest"],
+          help = "Write BED to FILE with the part of every read to keep once the records at its ends (poly(A) tails, low-complexity runs) are trimmed off, e.g. for seqkit subseq --bed")]
+    trim_bed: Option<PathBuf>,
+
+    /// Write the trimmed bases and keep-coordinates of every read as a TSV
+    #[arg(long = "trim-tsv", value_name = "FILE", conflicts_with_all = ["checkpoint", "manifest"],
+          help = "Write a TSV to FILE with, for every read, its length, the part to keep once the records at its ends are trimmed off, and the bases trimmed off each end")]
+    trim_tsv: Option<PathBuf>,
+
+    /// Write an IGV batch script snapshotting the records
+    #[arg(long = "igv-batch", value_name = "FILE", requires = "output", conflicts_with = "manifest",
+          help = "Write an IGV batch script to FILE that loads the reference (--fasta) and the BED output (--output), then snapshots every record, best score first, as a PNG (combine with --top to snapshot only the N best)")]
+    igv_batch: Option<PathBuf>,
+
+    /// Directory for the IGV snapshots
+    #[arg(long = "snapshot-dir", value_name = "DIR", requires = "igv_batch",
+          help = "Directory the snapshots of --igv-batch are saved to (created if needed); defaults to the directory of the batch script")]
+    snapshot_dir: Option<PathBuf>,
+
+    /// Keep only the N best-scoring records
+    #[arg(long = "top", value_name = "N",
+          help = "Only report the N highest-scoring records (genome-wide, or per contig with --top-per-contig), in their original order; ties go to the earlier record")]
+    top: Option<u64>,
+
+    /// Apply --top to each contig separately
+    #[arg(long = "top-per-contig", requires = "top",
+          help = "With --top, keep the N best records of every contig rather than of the whole input")]
+    top_per_contig: bool,
+
+    /// Pair nearby plus- and minus-strand records into BEDPE records
+    #[arg(long = "pair-strands", value_name = "BP",
+          conflicts_with_all = ["checkpoint", "report_dir", "manifest", "validate", "split_by"],
+          help = "Write BEDPE records instead of BED: each pair of a plus- and a minus-strand record of a contig at most BP apart (from the end of the first to the start of the second; overlapping records are not paired), e.g. the A- and T-tract arms around a hairpin or terminator. Best with --merge, as overlapping windows pair many times over")]
+    pair_strands: Option<u64>,
+
+    /// Report every window, whatever its score
+    #[arg(long = "all-windows",
+          help = "Report every window with its score, ignoring --percentage (or --max-entropy), for a continuous signal; usually combined with --step")]
+    all_windows: bool,
+
+    /// Add a column with the longest target run in each record
+    #[arg(long = "max-run",
+          help = "Add a column (before any --flags) giving the length of the longest uninterrupted run of the target inside each reported window or region")]
+    max_run: bool,
+
+    /// Only report windows holding a run of at least this many consecutive target bases
+    #[arg(long = "min-consecutive", value_name = "K", value_parser = clap::value_parser!(u64).range(1..),
+          help = "Only report windows (or --adaptive and --seed-percentage regions) that also hold an uninterrupted run of at least K target bases, dropping windows of scattered target bases that pass --percentage")]
+    min_consecutive: Option<u64>,
+
+    /// Add a column with the binomial p-value of each record against the background composition
+    #[arg(long = "pvalue", value_name = "FORMAT", value_enum, num_args = 0..=1, default_missing_value = "raw",
+          help = "Add a column (after any --max-run) with the probability of at least as many target bases in the window by chance, given the --score-background composition (binomial upper tail): 'raw' (the default) or 'neg-log10' for -log10 of it")]
+    pvalue: Option<PvalueFormat>,
+
+    /// Report only records significant at this false discovery rate
+    #[arg(long = "fdr", value_name = "RATE",
+          help = "Only report records whose binomial p-value (see --pvalue) is significant at this false discovery rate, e.g. 0.05, by the Benjamini-Hochberg procedure over every window evaluated in the genome; records are held back until the scan ends")]
+    fdr: Option<f64>,
+
+    /// How N bases interact with the threshold
+    #[arg(long = "n-policy", value_enum, default_value_t = NPolicy::CountAgainst,
+          help = "How N bases affect the target fraction: 'count-against' (the default: they never match but take up room in the window), 'ignore' (left out of the window, so --percentage is of the other bases), 'count-for' (counted as target bases) or 'skip-window' (windows with any N are not reported)")]
+    n_policy: NPolicy,
+
+    /// Never report windows with more than this fraction of N bases
+    #[arg(long = "max-n-frac", value_name = "FRACTION",
+          help = "Never report windows (or regions) in which more than FRACTION (0 to 1) of the bases are N, whatever their score, e.g. 0.1 to keep calls away from assembly gaps")]
+    max_n_frac: Option<f64>,
+
+    /// Count ambiguity codes in part toward the target
+    #[arg(long = "fractional-iupac",
+          help = "Count IUPAC ambiguity codes other than N toward the target by the share of their bases that match it (W counts as half an A, S as a whole GC base, B as two thirds of a GC base) instead of as non-matching bases; DNA target fractions only")]
+    fractional_iupac: bool,
+
+    /// Contigs shorter than the window
+    #[arg(long = "short-contig-policy", value_enum, default_value_t = ShortContigPolicy::Skip, value_name = "POLICY",
+          help = "What to do with contigs shorter than the window: 'skip' (the default: they have no window, so no records), 'whole' (evaluate the whole contig as one window, with --percentage taken of its length) or 'warn' (skip them with a warning naming each)")]
+    short_contig_policy: ShortContigPolicy,
+
+    /// Let windows wrap around the origin of every contig
+    #[arg(long = "circular",
+          help = "Treat every contig as circular (plasmids, organelles) so windows wrap around the origin; contigs whose FASTA header says topology=circular are always treated so")]
+    circular: bool,
+
+    /// Only evaluate windows starting every S bases
+    #[arg(long = "step", value_name = "S", default_value_t = 1,
+          value_parser = clap::value_parser!(u64).range(1..),
+          help = "Evaluate (and report) sliding windows starting at every S-th base of the contig instead of every base; cannot be combined with --merge or --best-per-cluster")]
+    step: u64,
+
+    /// Scan built-in sequences and compare against known-good output, then exit
+    #[arg(long = "self-test",
+          help = "Verify the installation against built-in sequences with known results, then exit")]
+    self_test: bool,
+
+    /// Repeated contig IDs
+    #[arg(long = "on-duplicate", value_enum, default_value_t = DuplicatePolicy::Warn, value_name = "POLICY",
+          help = "What to do when a contig ID repeats in the input: 'warn' (default: scan it under the same ID and warn once at the end), 'error' (the IDs are checked, from the .fai or by reading the input once more, before anything is scanned), 'rename' (to <id>_2, <id>_3, ...) or 'merge' (scan it as the continuation of the earlier contigs of that ID, as if they were concatenated: its coordinates are offset by their length, so base 10 of a second 'chr1' after one of 1000 bases is written as 1010, and no window spans the join). BAM/CRAM reads and --region scans are not checked")]
+    on_duplicate: DuplicatePolicy,
+
+    /// Rename contigs in the output
+    #[arg(long = "chrom-alias", value_name = "FILE",
+          help = "Write contigs under other names, e.g. chr1 for NC_000001.11: FILE is tab-separated with a contig's name in the input in the first column and its name in the output in the second (as in UCSC chromAlias.txt files; further columns are ignored). Contigs not in FILE keep their names. --on-duplicate applies to the names as written: a repeated contig is renamed from its alias (e.g. chr1_2)")]
+    chrom_alias: Option<PathBuf>,
+
+    /// Fail on unexpected characters
+    #[arg(long = "strict",
+          help = "Treat characters other than A, C, G, T, U and N (other IUPAC codes, gaps, '*', whitespace) as an input error rather than a warning; with --validate, fail after the report")]
+    strict: bool,
+
+    /// Check the input and parameters, then exit without scanning
+    #[arg(long = "validate", conflicts_with = "chunk_size",
+          help = "Pre-flight check: parse the whole input and report its contig count and lengths, N content, duplicate contig IDs and the window threshold the parameters work out to, then exit without scanning (non-zero if contig IDs repeat)")]
+    validate: bool,
+
+    /// Fail when nothing is found
+    #[arg(long = "fail-if-empty", conflicts_with = "report_dir",
+          help = "Exit with code 4 instead of 0 when the scan writes no records, so workflow engines don't take an empty output for success")]
+    fail_if_empty: bool,
+
+    /// Write to a file instead of stdout
+    #[arg(short = 'o', long = "output", value_name = "FILE",
+          help = "Write the output to FILE instead of stdout")]
+    output: Option<PathBuf>,
+
+    /// Write records as BED or as JSON Lines
+    #[arg(long = "output-format", value_enum, default_value_t = OutputFormat::Bed,
+          conflicts_with_all = ["report_dir", "manifest", "validate"],
+          help = "Format of the records: 'bed' or 'jsonl' (one JSON object per record, with the BED columns as fields and a schema_version; see --schema)")]
+    output_format: OutputFormat,
+
+    /// Human-friendly output on the terminal
+    #[arg(long = "pretty",
+          help = "For reading results on the terminal: align the columns, add thousands separators to positions, color records by score (unless NO_COLOR is set) and end with a summary line. Has no effect unless the output goes to a terminal, so redirected and piped output stays plain")]
+    pretty: bool,
+
+    /// Order of the records
+    #[arg(long = "sort", value_enum, default_value_t = SortOrder::Input,
+          conflicts_with_all = ["checkpoint", "report_dir", "manifest", "validate"],
+          help = "Order of the records: 'input' (default: contigs as they come, records as they are found), or sorted by contig, start and end, with contigs 'lexicographic' (chr1, chr10, chr2) or 'natural' (chr1, chr2, chr10). Sorting holds the whole output in memory")]
+    sort: SortOrder,
+
+    /// Also write the records of each strand to a file of their own
+    #[arg(long = "split-by", value_enum, value_name = "KEY",
+          conflicts_with_all = ["checkpoint", "report_dir", "manifest", "validate"],
+          help = "Also write the records of each strand to files of their own, PREFIX.plus.bed and PREFIX.minus.bed (unstranded records go to both), where PREFIX is --split-prefix or --output without its extension")]
+    split_by: Option<SplitBy>,
+
+    /// Prefix of the --split-by files
+    #[arg(long = "split-prefix", value_name = "PREFIX", requires = "split_by",
+          help = "Prefix of the --split-by files (needed when the output goes to stdout)")]
+    split_prefix: Option<PathBuf>,
+
+    /// Only write the --split-by files
+    #[arg(long = "no-combined", requires = "split_by", conflicts_with_all = ["fail_if_empty", "igv_batch"],
+          help = "Only write the --split-by files, not the combined output")]
+    no_combined: bool,
+
+    /// Stop writing the records of a contig after the first N
+    #[arg(long = "max-hits-per-contig", value_name = "N", value_parser = clap::value_parser!(u64).range(1..),
+          conflicts_with_all = ["checkpoint", "report_dir", "manifest", "validate"],
+          help = "Write at most N records per contig (the first N, after --sort); the rest are counted, not written, and the contigs over the cap are reported with a warning and in --stats-json. A guard against runaway output on repeat-rich genomes")]
+    max_hits_per_contig: Option<u64>,
 
-    // Validate user_base is exactly one char from {A,C,G,T,N}
-    if user_base.len() != 1 {
-        eprintln!("Error: --nucleotide must be a single character (A, C, G, T, or N).");
-        std::process::exit(1);
+    /// Coordinate system of the records
+    #[arg(long = "coordinates", value_enum, default_value_t = Coordinates::ZeroBased,
+          conflicts_with_all = ["report_dir", "manifest", "validate"],
+          help = "Coordinates of the records: '0-based' (default: half-open, as BED) or '1-based' (closed, as GFF3, VCF and most LIMS: the start moves up by one, the end stays)")]
+    coordinates: Coordinates,
+
+    /// Name column made from the record
+    #[arg(long = "name-template", value_name = "TEMPLATE",
+          conflicts_with_all = ["complement", "unique_bases"],
+          help = "Name each record from this template instead of its target, e.g. '{base}{strand}_{percent}_{length}'. Placeholders: {base}, {strand}, {percent} (the score column), {length}, {chrom}, {start} and {end} (as written); {{ and }} for braces")]
+    name_template: Option<String>,
+
+    /// Print the JSON Schema of the JSON Lines records, then exit
+    #[arg(long = "schema",
+          help = "Print the JSON Schema of the --output-format jsonl records, then exit")]
+    schema: bool,
+
+    /// Save progress after each contig
+    #[arg(long = "checkpoint", value_name = "JSON", requires = "output",
+          conflicts_with_all = ["region", "include_bed", "chunk_size", "top", "complement", "unique_bases", "coverage_summary", "composition_matrix", "gene_report", "report_dir", "manifest", "validate", "tile"],
+          help = "Record each contig as it is completed in this JSON file, so an interrupted scan can pick up where it left off with --resume (needs --output)")]
+    checkpoint: Option<PathBuf>,
+
+    /// Resume an interrupted scan
+    #[arg(long = "resume", requires = "checkpoint",
+          help = "Resume the scan recorded in --checkpoint: keep the output of its completed contigs, drop any partly written one, and scan only the rest. The options must be the same as the interrupted run's")]
+    resume: bool,
+
+    /// Contigs scanned at once
+    #[arg(short = 't', long = "threads", value_name = "N", default_value_t = 1,
+          value_parser = clap::value_parser!(u64).range(1..),
+          help = "Scan this many contigs at once, writing their records in input order")]
+    threads: u64,
+
+    /// Progress bar on stderr
+    #[arg(long = "progress",
+          help = "Show a progress bar on stderr (bases scanned, the contig being scanned and, when the FASTA has a .fai index, the total and an ETA); it advances per contig, or per chunk with --chunk-size")]
+    progress: bool,
+
+    /// Output buffer size
+    #[arg(long = "io-buffer", value_name = "SIZE", default_value = "1M", value_parser = parse_byte_size,
+          help = "Buffer this many bytes of output (e.g. 64K, 8M) before writing it to stdout or the --output file")]
+    io_buffer: usize,
+
+    /// Keep the scan within this much memory
+    #[arg(long = "max-memory", value_name = "SIZE", value_parser = parse_byte_size, conflicts_with = "manifest",
+          help = "Keep the scan within about SIZE of memory (e.g. 2G): cap --io-buffer, scan on fewer --threads if their contigs don't fit, or stream contigs in chunks (as --chunk-size) if a whole contig doesn't, and fail at once if the budget is too small for that")]
+    max_memory: Option<usize>,
+    /// Whether --max-memory chose to stream contigs in chunks, so options that
+    /// need whole contigs name it rather than a --chunk-size never given
+    #[arg(skip)]
+    memory_chunked: bool,
+
+    /// Time the stages of the scan
+    #[arg(long = "timings", conflicts_with = "manifest",
+          help = "Print where the time went at exit: decompression, parsing, scanning and writing, and the throughput (Mb/s) of the slowest contigs")]
+    timings: bool,
+
+    /// Write a summary of the run as JSON to this file
+    #[arg(long = "stats-json", value_name = "FILE", conflicts_with = "manifest",
+          help = "Write a JSON summary of the run to FILE at exit: contigs scanned, bases processed, records written per strand, wall time and peak memory (RSS, where the system reports it)")]
+    stats_json: Option<PathBuf>,
+
+    /// Add the checksums of every contig to --stats-json
+    #[arg(long = "contig-checksums", requires = "stats_json", conflicts_with_all = ["region", "include_bed"],
+          help = "Add the length, MD5 and SHA-256 of every contig read (uppercased, as in the M5 tag of SAM @SQ lines) to the --stats-json summary, to tell which exact reference build the records come from")]
+    contig_checksums: bool,
+}
+
+impl Args {
+    /// The options that make the run write a table or a track rather than
+    /// BED records, those set.
+    fn table_modes(&self) -> Vec<&'static str> {
+        let modes = [
+            (self.tile, "--tile"),
+            (self.report == Report::Composition, "--report composition"),
+            (self.smooth.is_some(), "--smooth"),
+            (self.mode == Mode::Skew, "--mode skew"),
+            (self.mode == Mode::Methylation, "--mode methylation"),
+            (self.mode == Mode::Polya, "--mode polya"),
+            (self.mode == Mode::Dust && self.dust_output != DustOutput::Bed, "--dust-output"),
+        ];
+        modes.iter().filter(|(set, _)| *set).map(|(_, option)| *option).collect()
+    }
+
+    fn is_table_mode(&self) -> bool {
+        !self.table_modes().is_empty()
     }
-    let base_char = user_base.chars().next().unwrap();
-    match base_char {
-        'A' | 'C' | 'G' | 'T' | 'N' => (),
-        _ => {
-            eprintln!("Error: --nucleotide must be one of A, C, G, T, or N.");
-            std::process::exit(1);
+
+    /// The option that has contigs streamed in chunks, for messages about
+    /// the options that need them whole.
+    fn chunking(&self) -> &'static str {
+        if self.memory_chunked {
+            "--max-memory (streaming contigs in chunks, as the longest doesn't fit whole)"
+        } else {
+            "--chunk-size"
         }
     }
+}
 
-    // Validate percentage
-    if p < 50.0 || p > 100.0 {
-        eprintln!("Error: --percentage must be between 50.0 and 100.0");
-        std::process::exit(1);
+/// Parse a byte count with an optional K, M or G suffix (powers of 1024).
+fn parse_byte_size(value: &str) -> Result<usize, String> {
+    let (digits, unit) = match value.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&value[..i], c.to_ascii_uppercase()),
+        _ => (value, 'B'),
+    };
+    let multiplier: usize = match unit {
+        'B' => 1,
+        'K' => 1 << 10,
+        'M' => 1 << 20,
+        'G' => 1 << 30,
+        _ => return Err(format!("unknown unit '{}' (use K, M or G)", unit)),
+    };
+    let count: usize = digits.parse().map_err(|_| format!("'{}' is not a size such as 8M", value))?;
+    match count.checked_mul(multiplier) {
+        Some(0) => Err("must be at least 1 byte".to_string()),
+        Some(size) => Ok(size),
+        None => Err(format!("'{}' is too large", value)),
     }
+}
 
-    // The minimum count needed in a window to be considered "passing"
-    let threshold_count: usize = ((p / 100.0) * (w as f64)).ceil() as usize;
+/// Bundles of scan options for common use cases, for `--preset`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum Preset {
+    /// Homopolymers of any base at least 6 bases long, where nanopore reads err
+    OntHomopolymer,
+    /// Poly(A) tracts of at least 15 bases with a few mismatches
+    #[value(name = "illumina-polyA")]
+    IlluminaPolyA,
+    /// AT-rich regions of at least 100 bases
+    AtIsland,
+    /// CpG islands: CG dinucleotides dense over at least 200 bases
+    Cpg,
+}
 
-    // We track frequencies in [A,C,G,T,N] => [0..4]
-    // Let's define mapping:
-    fn nuc_to_index(nuc: u8) -> Option<usize> {
-        match nuc {
-            b'A' | b'a' => Some(0),
-            b'C' | b'c' => Some(1),
-            b'G' | b'g' => Some(2),
-            b'T' | b't' => Some(3),
-            b'N' | b'n' => Some(4),
-            _ => None,
-        }
-    }
+/// Output formats for `--mode dust`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum DustOutput {
+    /// Low-complexity intervals as BED
+    Bed,
+    /// The input as FASTA with low-complexity bases in lowercase
+    SoftMasked,
+    /// The input as FASTA with low-complexity bases replaced by N
+    HardMasked,
+}
 
+/// Score of `--merge` regions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum MergeScore {
+    /// The best window's score
+    Max,
+    /// The mean score of the windows
+    Mean,
+}
 
-    // Complement function for one char
-    fn complement_char(c: char) -> char {
-        match c {
-            'A' => 'T',
-            'T' => 'A',
-            'C' => 'G',
-            'G' => 'C',
-            'N' => 'N',
-            _ => 'N', // fallback
-        }
-    }
+/// Strands merged together, for `--merge-strand`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum MergeStrand {
+    /// Each strand on its own
+    Same,
+    /// Both strands of a target together
+    Any,
+}
 
-    // We'll find the array indices for user base + complement
-    let comp_char = complement_char(base_char);
-    let user_idx = nuc_to_index(base_char as u8).unwrap();
-    let comp_idx = nuc_to_index(comp_char as u8).unwrap();
+/// Tasks run as `polyscan <COMMAND>`; without one, `scan` is run.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Scan sequences for windows rich in a nucleotide (the default)
+    Scan(Box<Args>),
+    /// Merge the overlapping records of a polyscan BED output, per contig, name and strand
+    Merge {
+        /// BED output of polyscan
+        bed: PathBuf,
+        /// Also merge records this close
+        #[arg(long = "max-gap", value_name = "BASES", default_value_t = 0,
+              help = "Also merge records separated by at most this many bases")]
+        max_gap: u64,
+        /// Score of merged regions
+        #[arg(long = "merge-score", value_enum, default_value_t = MergeScore::Max,
+              help = "Score of each merged region: 'max' (the default) or 'mean' of its records' scores")]
+        merge_score: MergeScore,
+        /// Merge records regardless of strand
+        #[arg(long = "merge-strand", value_enum, default_value_t = MergeStrand::Same,
+              help = "Merge records of the same name on 'same' strand only (the default), or on 'any' strand, reporting mixed regions unstranded")]
+        merge_strand: MergeStrand,
+    },
+    /// Summarize a polyscan BED output per name and strand: records, bases, lengths and scores
+    Stats {
+        /// BED output of polyscan
+        bed: PathBuf,
+    },
+    /// Mask the regions of a BED file in a FASTA file
+    Mask {
+        /// Input FASTA/FASTQ (optionally compressed) or .2bit file
+        #[arg(short, long)]
+        fasta: PathBuf,
+        /// Regions to mask, e.g. polyscan output
+        bed: PathBuf,
+        /// Replace masked bases with N instead of lowercasing them
+        #[arg(long = "hard",
+              help = "Hard-mask the regions (replace them with N) instead of soft-masking them (lowercase)")]
+        hard: bool,
+    },
+    /// Extract the sequences of the records of a BED file as FASTA
+    Extract {
+        /// Input FASTA/FASTQ (optionally compressed) or .2bit file
+        #[arg(short, long)]
+        fasta: PathBuf,
+        /// Regions to extract, e.g. polyscan output
+        bed: PathBuf,
+    },
+    /// Tag the variants of a VCF that lie in or next to homopolymers or low-complexity regions
+    #[command(name = "annotate-vcf")]
+    AnnotateVcf {
+        /// Reference FASTA (optionally compressed) or .2bit file the variants were called against
+        #[arg(short, long)]
+        fasta: PathBuf,
+        /// Variants to annotate (optionally compressed)
+        vcf: PathBuf,
+        /// Shortest homopolymer to tag variants with
+        #[arg(long = "min-run", value_name = "N", default_value_t = 4,
+              value_parser = clap::value_parser!(u64).range(1..),
+              help = "Only give HRUN for homopolymers of at least N bases")]
+        min_run: u64,
+    },
+    /// Filter the indels of a VCF that lie in or next to long homopolymers
+    #[command(name = "filter-vcf")]
+    FilterVcf {
+        /// Reference FASTA (optionally compressed) or .2bit file the variants were called against
+        #[arg(short, long)]
+        fasta: PathBuf,
+        /// Variants to filter (optionally compressed)
+        vcf: PathBuf,
+        /// Longest homopolymer indels are trusted next to
+        #[arg(long = "max-run", value_name = "N", default_value_t = 5,
+              help = "Filter indels overlapping or adjacent to a homopolymer longer than N bases")]
+        max_run: u64,
+        /// Leave filtered indels out instead of flagging them
+        #[arg(long = "drop",
+              help = "Leave the filtered indels out of the output instead of setting the HRUN filter on them")]
+        drop: bool,
+    },
+    /// Simulate a genome with planted homopolymers and STRs, and a truth BED of them
+    Simulate {
+        /// Write PREFIX.fa and PREFIX.truth.bed
+        #[arg(short, long = "output", value_name = "PREFIX",
+              help = "Write the genome to PREFIX.fa and the planted tracts to PREFIX.truth.bed")]
+        output: PathBuf,
+        /// Length of the simulated contig
+        #[arg(long = "genome-size", value_name = "BASES", default_value_t = 1_000_000)]
+        genome_size: u64,
+        /// G+C fraction of the background sequence
+        #[arg(long = "gc", default_value_t = 0.5,
+              help = "Expected G+C fraction of the background sequence, between 0 and 1")]
+        gc: f64,
+        /// Number of tracts to plant
+        #[arg(long = "tracts", value_name = "N", default_value_t = 100)]
+        tracts: u64,
+        /// Fraction of the tracts that are STRs
+        #[arg(long = "str-fraction", default_value_t = 0.0,
+              help = "Fraction of the tracts that are STRs (of a random 2-6 base unit) rather than homopolymers")]
+        str_fraction: f64,
+        /// Shortest tract
+        #[arg(long = "min-tract", value_name = "BASES", default_value_t = 8)]
+        min_tract: u64,
+        /// Longest tract
+        #[arg(long = "max-tract", value_name = "BASES", default_value_t = 30)]
+        max_tract: u64,
+        /// Distribution of tract lengths
+        #[arg(long = "length-distribution", value_enum, default_value_t = LengthDistribution::Uniform,
+              help = "How tract lengths are drawn between --min-tract and --max-tract: 'uniform' (the default) or 'geometric' (shorter tracts more likely)")]
+        length_distribution: LengthDistribution,
+        /// Seed of the random generator
+        #[arg(long = "seed", default_value_t = 1,
+              help = "Seed of the random generator; the same seed and parameters give the same genome")]
+        seed: u64,
+    },
+    /// Serve on-demand scans of an indexed FASTA over HTTP
+    Serve {
+        /// Indexed FASTA (plain with .fai, or bgzip with .fai and .gzi)
+        #[arg(short, long)]
+        fasta: PathBuf,
+        /// Address to listen on
+        #[arg(long = "listen", value_name = "ADDRESS", default_value = "127.0.0.1:8080",
+              help = "Address and port to listen on; use 0.0.0.0:PORT to accept connections from other hosts")]
+        listen: String,
+        /// Largest region a request may scan
+        #[arg(long = "max-region", value_name = "BASES", default_value_t = 10_000_000,
+              help = "Reject requests for regions longer than this many bases")]
+        max_region: u64,
+    },
+    /// Write a hit density plot per contig as SVG or PNG
+    Plot {
+        /// FASTA the contig lengths (and, without --bed, the hits) come from
+        #[arg(short, long)]
+        fasta: PathBuf,
+        /// Plot this scan output instead of scanning
+        #[arg(long = "bed", value_name = "BED",
+              help = "Plot the records of this BED (e.g. a polyscan output) instead of scanning the FASTA")]
+        bed: Option<PathBuf>,
+        /// Directory to write the plots to
+        #[arg(long = "outdir", value_name = "DIR",
+              help = "Directory to write one <contig>.svg (or .png) per contig to")]
+        outdir: PathBuf,
+        /// Image format
+        #[arg(long = "format", value_enum, default_value_t = PlotFormat::Svg,
+              help = "Image format of the plots; PNG plots have no title or axis labels")]
+        format: PlotFormat,
+        /// Nucleotide(s) to scan for, without --bed
+        #[arg(short = 'n', long = "nucleotide", default_value = "A", conflicts_with = "bed",
+              help = "Without --bed, the nucleotide, class (e.g. AT) or comma-separated list of these to scan for")]
+        nucleotide: String,
+        /// Window size, without --bed
+        #[arg(short = 'w', long = "window-size", default_value_t = 10, conflicts_with = "bed",
+              value_parser = clap::value_parser!(u64).range(1..),
+              help = "Without --bed, the length of the sliding window")]
+        window_size: u64,
+        /// Percentage threshold, without --bed
+        #[arg(short = 'p', long = "percentage", default_value_t = 80.0, conflicts_with = "bed",
+              help = "Without --bed, the percentage of the target required in the window")]
+        percentage: f64,
+        /// Bars per plot
+        #[arg(long = "bins", default_value_t = 200,
+              value_parser = clap::value_parser!(u64).range(1..),
+              help = "Number of bars along each contig, each showing the fraction of its bases covered by records")]
+        bins: u64,
+    },
+    /// Compare two polyscan BED outputs: regions lost, gained and shared, and their Jaccard index
+    Diff {
+        /// BED output of the first (e.g. unpolished) run
+        old: PathBuf,
+        /// BED output of the second (e.g. polished) run
+        new: PathBuf,
+        /// Compare regions whatever their strand
+        #[arg(long = "ignore-strand",
+              help = "Compare regions whatever their strand column, instead of plus with plus and minus with minus")]
+        ignore_strand: bool,
+    },
+    /// Score a polyscan BED output against a truth BED: precision, recall and F1
+    Benchmark {
+        /// BED output of polyscan (the predictions)
+        predictions: PathBuf,
+        /// BED of curated regions (e.g. annotated homopolymers)
+        truth: PathBuf,
+        /// One row per score cutoff instead of a single row
+        #[arg(long = "pr-curve",
+              help = "Report one row per distinct prediction score, keeping the predictions scoring at least that much, for a precision/recall curve")]
+        pr_curve: bool,
+        /// Also write a base-level ROC curve to this file
+        #[arg(long = "roc", value_name = "FILE", requires = "genome",
+              help = "Also write a ROC curve to FILE as a TSV: for every distinct prediction score, the bases predicted by the predictions scoring at least that much, with the true positive rate (truth bases predicted), false positive rate (bases outside the truth predicted, out of all of them in --genome) and precision")]
+        roc: Option<PathBuf>,
+        /// Contig lengths, for the false positive rate of --roc
+        #[arg(long = "genome", value_name = "FILE", requires = "roc",
+              help = "A .fai index or chrom.sizes file of the genome the predictions are on, whose length makes the negatives of the --roc false positive rate")]
+        genome: Option<PathBuf>,
+    },
+    /// Browse the records of a BED file in the terminal, with their sequence
+    #[cfg(feature = "tui")]
+    View {
+        /// Records to browse, e.g. polyscan output
+        bed: PathBuf,
+        /// FASTA/FASTQ (optionally compressed) or .2bit file the records are on
+        #[arg(short, long)]
+        fasta: PathBuf,
+    },
+    /// Print a shell completion script
+    Completions {
+        /// Shell to complete in
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+    /// Print the man page in roff format
+    Man,
+}
 
-    // Prepare a BED writer to stdout
-    let stdout = std::io::stdout();
-    let mut bed_writer = Writer::new(stdout.lock());
-
-    /// Write a BED record, placing the "strand" in aux[2].
-    ///
-    ///  columns: chrom, start, end, name, score, strand
-    ///
-    ///  - name => user base
-    ///  - score => integer (rounded up) percentage
-    ///  - strand => plus or minus
-    fn write_bed_record<W: Write>(
-        writer: &mut Writer<W>,
-        chrom: &str,
-        start: u64,
-        end: u64,
-        name: char,        // user-chosen base (not the complement)
-        score_percentage: f64,  // we will round up
-        strand_symbol: &str,    // e.g. "+"
-    ) -> Result<(), Box<dyn Error>> {
-        let mut record = BedRecord::new();
-
-        // columns 1..3
-        record.set_chrom(chrom);
-        record.set_start(start);
-        record.set_end(end);
-
-        // aux[0] => name
-        record.set_name(&name.to_string());
-
-        // aux[1] => score (round up to integer)
-        let ceil_int = score_percentage.ceil() as u64;
-        record.set_score(&ceil_int.to_string());
-
-        // aux[2] => strand
-        record.push_aux(strand_symbol);
-
-        writer.write(&record)?;
-        Ok(())
-    }
+/// Output modes for `--report`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Report {
+    /// Thresholded windows as BED
+    Hits,
+    /// Per-window base counts as TSV
+    Composition,
+}
 
-    // Open FASTA
-    // let reader = fasta::Reader::new(BufReader::new(File::open(fasta_path)?));
+/// Format of the records written by a scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum OutputFormat {
+    /// Tab-separated BED columns
+    Bed,
+    /// One JSON object per record
+    Jsonl,
+}
+
+/// What to do with a contig ID seen before in the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum DuplicatePolicy {
+    /// Scan it under the same ID, with a warning
+    Warn,
+    /// Stop with an input error, before anything is scanned
+    Error,
+    /// Scan it as <id>_2, <id>_3, ...
+    Rename,
+    /// Scan it as the continuation of the earlier contig(s) of that ID, its
+    /// coordinates offset by their length
+    Merge,
+}
 
+/// Coordinates of the records written by a scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Coordinates {
+    /// 0-based half-open, as BED
+    #[value(name = "0-based")]
+    ZeroBased,
+    /// 1-based closed, as GFF and VCF
+    #[value(name = "1-based")]
+    OneBased,
+}
 
-    // Use Niffler to automatically detect compression
-    let file = File::open(&fasta_path)?;
-    // niffler::get_reader takes a "Box<dyn Read>", returns (reader, format)
-    let (niffler_reader, _compression_format) = niffler::get_reader(Box::new(file))?;
-    
-    // Wrap the decompressed reader in a BufReader
-    let buf = BufReader::new(niffler_reader);
+/// How a failure is reported on stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum ErrorFormat {
+    /// "Error: " and the message
+    Text,
+    /// One JSON object with the kind of error, the exit code and the message
+    Json,
+}
 
-    // Now create a Rust-Bio FASTA reader from that
-    let reader = fasta::Reader::new(buf);
+// The parser, with -v described for what it adds to each subcommand: only
+// scans (the default command, `scan` and `serve`) log per-contig details
+fn cli_command() -> clap::Command {
+    let command = Cli::command();
+    let verbose = command
+        .get_arguments()
+        .find(|arg| arg.get_id() == "verbose")
+        .cloned()
+        .expect("-v is an argument of the parser");
+    command.mut_subcommands(|sc| match sc.get_name() {
+        "scan" => sc,
+        "serve" => sc.arg(verbose.clone().help("Log more to stderr: -v adds the per-contig timings, hit counts and skipped contigs of each query")),
+        _ => sc.arg(verbose.clone().help("Log more to stderr with -v or -vv; only scans have more to log (per-contig timings and hit counts)")),
+    })
+}
 
+// Set from the command line as soon as it is known, so failures while
+// parsing it are reported in the chosen format too
+static JSON_ERRORS: AtomicBool = AtomicBool::new(false);
 
+// Whether `--error-format json` is among the raw arguments
+fn json_errors_requested(args: &[OsString]) -> bool {
+    args.windows(2).any(|pair| pair[0] == "--error-format" && pair[1] == "json") || args.iter().any(|arg| arg == "--error-format=json")
+}
 
+// The exit code of an error returned to `main`; files that can't be opened
+// are input errors too (BED, GFF, ...)
+fn exit_code(e: &(dyn Error + 'static)) -> i32 {
+    if let Some(e) = e.downcast_ref::<PolyscanError>() {
+        return e.exit_code();
+    }
+    let unopened = e.downcast_ref::<std::io::Error>().is_some_and(|e| matches!(e.kind(), ErrorKind::NotFound | ErrorKind::PermissionDenied));
+    if unopened {
+        EXIT_INPUT
+    } else {
+        1
+    }
+}
 
+// Report `e` on stderr in the chosen format, as the process exits with `code`
+fn report_error(e: &(dyn Error + 'static), code: i32) {
+    if !JSON_ERRORS.load(Ordering::Relaxed) {
+        eprintln!("Error: {}", e);
+        return;
+    }
+    let kind = match e.downcast_ref::<PolyscanError>() {
+        Some(e) => e.kind(),
+        None if code == EXIT_INPUT => "input",
+        None => "other",
+    };
+    eprintln!("{}", serde_json::json!({ "error": kind, "exit_code": code, "message": e.to_string() }));
+}
 
-    // For each contig
-    for result_record in reader.records() {
-        let record = result_record?;
-        let contig_id = record.id();
-        let seq = record.seq();
+// Summaries go to stderr as they are, warnings and errors with their usual
+// prefix, and the details of -v/-vv with their level
+fn init_logging(verbose: u8, quiet: bool) {
+    let level = match (quiet, verbose) {
+        (true, _) => LevelFilter::Error,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    };
+    env_logger::Builder::new()
+        .filter_module("polyscan", level)
+        .format(|buf, record| match record.level() {
+            Level::Info => writeln!(buf, "{}", record.args()),
+            Level::Warn => writeln!(buf, "Warning: {}", record.args()),
+            Level::Error => writeln!(buf, "Error: {}", record.args()),
+            level => writeln!(buf, "[{}] {}", level.as_str().to_lowercase(), record.args()),
+        })
+        .init();
+}
 
-        // Skip if contig too short
-        if seq.len() < w {
-            continue;
+/// Counts the bytes written through it, for `--fail-if-empty`.
+struct CountingWriter<W: Write> {
+    inner: W,
+    written: usize,
+}
+
+/// Where the output goes once it is counted and timed: as is, or through a
+/// compressor that has to be finished at the end.
+enum OutputFile {
+    Plain(Box<dyn Write>),
+    Compressed(Box<CompressedWriter<Box<dyn Write>>>),
+}
+
+impl OutputFile {
+    fn finish(self) -> std::io::Result<()> {
+        match self {
+            OutputFile::Plain(mut file) => file.flush(),
+            OutputFile::Compressed(file) => file.finish()?.flush(),
         }
+    }
+}
 
-        // freq array for [A,C,G,T,N]
-        let mut freq = [0_usize; 5];
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            OutputFile::Plain(file) => file.write(buf),
+            OutputFile::Compressed(file) => file.write(buf),
+        }
+    }
 
-        // Initialize freq in the first window
-        for &nuc in &seq[0..w] {
-            if let Some(i) = nuc_to_index(nuc) {
-                freq[i] += 1;
-            }
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            OutputFile::Plain(file) => file.flush(),
+            OutputFile::Compressed(file) => file.flush(),
         }
+    }
+}
 
-        // Check the first window
-        {
-            let user_count = freq[user_idx];
-            let comp_count = freq[comp_idx];
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.written += n;
+        Ok(n)
+    }
 
-            // If user base >= threshold => output plus
-            if user_count >= threshold_count {
-                let perc = (user_count as f64 / w as f64) * 100.0;
-                write_bed_record(&mut bed_writer, contig_id, 0, w as u64, base_char, perc, "+")?;
-            }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
 
-            // If complement base >= threshold => minus
-            if comp_count >= threshold_count {
-                let perc = (comp_count as f64 / w as f64) * 100.0;
-                // We STILL label the record with the user's base, but mark strand="-"
-                write_bed_record(&mut bed_writer, contig_id, 0, w as u64, base_char, perc, "-")?;
-            }
+fn main() {
+    if let Err(e) = run() {
+        let code = exit_code(e.as_ref());
+        report_error(e.as_ref(), code);
+        std::process::exit(code);
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    // Parse CLI
+    let raw_args: Vec<OsString> = std::env::args_os().collect();
+    JSON_ERRORS.store(json_errors_requested(&raw_args), Ordering::Relaxed);
+    // The matches are kept for the options given, which a checkpoint records
+    let parsed = cli_command()
+        .try_get_matches_from(config::apply(raw_args)?)
+        .and_then(|matches| Ok((Cli::from_arg_matches(&matches)?, matches)));
+    let (cli, matches) = match parsed {
+        Ok(parsed) => parsed,
+        // Usage errors as JSON; help and version are printed as usual
+        Err(e) if e.use_stderr() && JSON_ERRORS.load(Ordering::Relaxed) => {
+            let message = e.render().to_string();
+            let message = message.lines().next().unwrap_or_default().trim_start_matches("error: ");
+            return Err(PolyscanError::parameter(message));
         }
+        Err(e) => e.exit(),
+    };
+    JSON_ERRORS.store(cli.error_format == ErrorFormat::Json, Ordering::Relaxed);
+    init_logging(cli.verbose, cli.quiet);
+    let scan_matches = match matches.subcommand() {
+        Some(("scan", scan_matches)) => scan_matches,
+        _ => &matches,
+    };
+    let mut args = match cli.command {
+        Some(Command::Scan(args)) => *args,
+        Some(command) => return run_command(command),
+        None => cli.scan,
+    };
 
-        // Slide the window
-        for start in 1..=(seq.len() - w) {
-            let leaving = seq[start - 1];
-            if let Some(i) = nuc_to_index(leaving) {
-                freq[i] = freq[i].saturating_sub(1);
-            }
+    if args.self_test {
+        return self_test::run();
+    }
+    if args.schema {
+        print!("{}", HITS_SCHEMA);
+        return Ok(());
+    }
+    if let Some(manifest) = args.manifest.clone() {
+        return batch::run(&manifest, args);
+    }
+    if let Some(budget) = args.max_memory {
+        plan_memory(&mut args, budget)?;
+    }
 
-            let entering = seq[start + w - 1];
-            if let Some(i) = nuc_to_index(entering) {
-                freq[i] += 1;
+    // A resumed scan appends to the output of the completed contigs
+    let checkpoint = match (&args.checkpoint, &args.output) {
+        (Some(path), Some(output)) if args.resume => {
+            let checkpoint = Checkpoint::load(path)?;
+            if checkpoint.state().options != checkpoint_options(scan_matches) || checkpoint.state().output != *output {
+                return Err(PolyscanError::parameter(format!("{} was saved by a scan with other options or another --output; it can only be resumed with the same ones.", path.display())));
             }
+            checkpoint.truncate_output()?;
+            Some(checkpoint)
+        }
+        (Some(path), Some(output)) => Some(Checkpoint::create(path, output, checkpoint_options(scan_matches))?),
+        _ => None,
+    };
+    let (fail_if_empty, timed, stats_json) = (args.fail_if_empty, args.timings, args.stats_json.clone());
+    let igv = args.igv_batch.clone().map(|batch| (batch, args.snapshot_dir.clone(), args.fasta.clone(), args.output.clone()));
+    let started = std::time::Instant::now();
+    if timed {
+        timings::enable();
+    }
+    if stats_json.is_some() {
+        run_stats::enable();
+    }
+    let stdout = std::io::stdout();
+    let inner: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(OpenOptions::new().write(true).create(true).append(args.resume).truncate(!args.resume).open(path)?),
+        None => Box::new(stdout.lock()),
+    };
+    // --pretty is for reading results on the terminal, so output going elsewhere stays plain
+    let pretty = args.pretty && args.output.is_none() && std::io::stdout().is_terminal();
+    let color = std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty());
+    // Compress when asked to, or when the output file name says so
+    let codec = match (args.compress_codec, args.compress_level) {
+        (Some(codec), _) => Some(codec),
+        (None, level) => args.output.as_deref().and_then(Codec::from_path).or(level.map(|_| Codec::Zstd)),
+    };
+    let inner = match codec {
+        Some(codec) => {
+            // A checkpoint truncates the output to where a contig ended, which compressed output has no byte offset for
+            if args.checkpoint.is_some() {
+                return Err(PolyscanError::parameter(format!("--checkpoint cannot be combined with {} output.", codec.name())));
+            }
+            let level = args.compress_level.unwrap_or(codec.default_level());
+            let (lowest, highest) = codec.levels();
+            if !(lowest..=highest).contains(&level) {
+                return Err(PolyscanError::parameter(format!("--compress-level must be from {} to {} for {}.", lowest, highest, codec.name())));
+            }
+            OutputFile::Compressed(Box::new(CompressedWriter::new(inner, codec, level)?))
+        }
+        None => OutputFile::Plain(inner),
+    };
+    let mut output = CountingWriter { inner: BufWriter::with_capacity(args.io_buffer, TimedWriter::new(inner)), written: 0 };
+    if pretty {
+        let mut pretty = PrettyWriter::new(&mut output, color);
+        run_split_scan(args, &mut pretty, checkpoint)?;
+        pretty.finish()?;
+    } else {
+        run_split_scan(args, &mut output, checkpoint)?;
+    }
+    output.flush()?;
+    let written = output.written;
+    output.inner.into_inner().map_err(|e| e.into_error())?.into_inner().finish()?;
+    if timed {
+        for line in timings::report(started.elapsed()) {
+            info!("{}", line);
+        }
+    }
+    if let Some((batch, snapshot_dir, Some(fasta), Some(bed))) = &igv {
+        write_igv_batch(batch, snapshot_dir.as_deref(), Path::new(fasta), bed)?;
+    }
+    if let Some(path) = &stats_json {
+        write_run_stats(path, started.elapsed())?;
+    }
+    if fail_if_empty && written == 0 {
+        return Err(Box::new(PolyscanError::Empty("the scan produced no records (--fail-if-empty).".to_string())));
+    }
+    Ok(())
+}
 
-            let user_count = freq[user_idx];
-            let comp_count = freq[comp_idx];
+// Memory a base of a contig held whole takes: the sequence as read, and the
+// copy made of it while reading or scanning
+const HELD_BYTES_PER_BASE: usize = 2;
+// Memory for everything but sequences and the output buffer: the program,
+// indexes, BED and annotation intervals
+const BASE_MEMORY: usize = 32 << 20;
+// Shortest chunk --max-memory streams contigs in
+const MIN_MEMORY_CHUNK: usize = 64 << 10;
 
-            if user_count >= threshold_count {
-                let perc = (user_count as f64 / w as f64) * 100.0;
-                let end = start + w;
-                write_bed_record(
-                    &mut bed_writer,
-                    contig_id,
-                    start as u64,
-                    end as u64,
-                    base_char,
-                    perc,
-                    "+"
-                )?;
-            }
+/// `bytes` for messages, in the largest unit that keeps it at least 1.
+fn byte_size(bytes: usize) -> String {
+    match bytes {
+        b if b >= 1 << 30 => format!("{:.1}G", b as f64 / (1u64 << 30) as f64),
+        b if b >= 1 << 20 => format!("{:.1}M", b as f64 / (1u64 << 20) as f64),
+        b if b >= 1 << 10 => format!("{:.1}K", b as f64 / (1u64 << 10) as f64),
+        b => format!("{}B", b),
+    }
+}
 
-            if comp_count >= threshold_count {
-                let perc = (comp_count as f64 / w as f64) * 100.0;
-                let end = start + w;
-                write_bed_record(
-                    &mut bed_writer,
-                    contig_id,
-                    start as u64,
-                    end as u64,
-                    base_char,
-                    perc,
-                    "-"
-                )?;
+/// Fit the scan into `--max-memory`: cap the output buffer, hold contigs
+/// whole on as many `--threads` as fit (a parallel scan also keeps its
+/// queued pieces packed at 2 bits per base), or stream them in chunks when
+/// not even one whole contig fits.
+///
+/// The longest contig comes from the `.fai` index (also of a remote FASTA),
+/// or is at most the size of an uncompressed FASTA; when it can't be known
+/// (compressed input without an index, stdin), contigs are streamed.
+fn plan_memory(args: &mut Args, budget: usize) -> Result<(), Box<dyn Error>> {
+    let window = args.window_size.iter().copied().max().unwrap_or(0) as usize;
+    // The output buffer saves system calls, not memory worth having
+    args.io_buffer = args.io_buffer.min(budget / 16);
+    let smallest = (MIN_MEMORY_CHUNK.max(2 * window) + window)
+        .checked_mul(HELD_BYTES_PER_BASE)
+        .and_then(|chunk| chunk.checked_add(BASE_MEMORY + args.io_buffer));
+    let too_small = || match smallest {
+        Some(smallest) => PolyscanError::parameter(format!("--max-memory {} is too small to scan in; it needs at least {}.", byte_size(budget), byte_size(smallest))),
+        None => PolyscanError::parameter(format!("--max-memory {} is too small to scan windows of {} bases in.", byte_size(budget), window)),
+    };
+    if smallest.is_none_or(|smallest| budget < smallest) {
+        return Err(too_small());
+    }
+    let available = budget.checked_sub(BASE_MEMORY + args.io_buffer).ok_or_else(too_small)?;
+    if let Some(chunk_size) = args.chunk_size {
+        let needed = (chunk_size as usize + window) * HELD_BYTES_PER_BASE;
+        if needed > available {
+            return Err(PolyscanError::parameter(format!("--chunk-size {} needs about {} for its chunks, more than --max-memory {} leaves.", chunk_size, byte_size(needed), byte_size(budget))));
+        }
+        return Ok(());
+    }
+    let longest = args.fasta.as_deref().filter(|&path| path != "-").and_then(|path| {
+        match IndexedSource::open(path, args.input_format, false) {
+            Ok(Some(fasta)) => fasta.fai().entries.iter().map(|entry| entry.length as usize).max(),
+            // Without an index, a contig of uncompressed FASTA is at most the whole file
+            _ => {
+                let (_, format) = niffler::send::from_path(path).ok()?;
+                match format {
+                    niffler::send::compression::Format::No => std::fs::metadata(path).ok().map(|metadata| metadata.len() as usize),
+                    _ => None,
+                }
+            }
+        }
+    });
+    if let Some(longest) = longest {
+        let threads = args.threads as usize;
+        // Each thread scans a contig while the others wait in the queue, packed
+        let needed = |threads: usize| match threads {
+            1 => longest * HELD_BYTES_PER_BASE,
+            _ => threads * longest * HELD_BYTES_PER_BASE + threads * PIECES_PER_THREAD * longest.div_ceil(4),
+        };
+        if let Some(fitting) = (1..=threads).rev().find(|&threads| needed(threads) <= available) {
+            if fitting < threads {
+                warn!("--max-memory {}: scanning on {} of the {} --threads, as the contigs of more don't fit.", byte_size(budget), fitting, threads);
+                args.threads = fitting as u64;
             }
+            debug!("--max-memory {}: holding contigs of up to {} bases whole", byte_size(budget), longest);
+            return Ok(());
+        }
+    }
+    let chunk_size = (available / HELD_BYTES_PER_BASE).checked_sub(window).ok_or_else(too_small)?;
+    if chunk_size < MIN_MEMORY_CHUNK.max(2 * window) {
+        return Err(too_small());
+    }
+    let reason = match longest {
+        Some(longest) => format!("a contig of {} bases doesn't fit whole", longest),
+        None => "the contig lengths aren't known without a .fai index".to_string(),
+    };
+    info!("--max-memory {}: streaming contigs in chunks of {} bases (as --chunk-size), as {}.", byte_size(budget), chunk_size, reason);
+    if args.threads > 1 {
+        warn!("--max-memory {}: scanning on 1 thread, as chunks are read in turn.", byte_size(budget));
+        args.threads = 1;
+    }
+    // clap keeps these from a --chunk-size given, not from one chosen here
+    let whole_options = [(!args.region.is_empty(), "--region"), (args.include_bed.is_some(), "--include-bed"), (args.tile, "--tile")];
+    if let Some((_, option)) = whole_options.iter().find(|(set, _)| *set) {
+        return Err(PolyscanError::parameter(format!("--max-memory {} would stream contigs in chunks, as {}, which cannot be combined with {}.", byte_size(budget), reason, option)));
+    }
+    args.chunk_size = Some(chunk_size as u64);
+    args.memory_chunked = true;
+    Ok(())
+}
+
+/// The `--percentage` at which the `window`-sized windows of `records`
+/// cover about `fraction` of their bases, from a first pass over the input.
+/// Each window counts the most target bases it holds of any of `classes`;
+/// as in the scan, windows wrap around circular contigs (all of them with
+/// `circular`) and those overlapping `exclude` are dropped.
+fn calibrated_percentage(records: Records, window: usize, classes: &[BaseClass], fraction: f64, circular: bool, exclude: Option<&IntervalSet>) -> Result<f64, Box<dyn Error>> {
+    let mut histogram = CoverageHistogram::new(window);
+    for record in records {
+        let record = record.map_err(PolyscanError::input)?;
+        let topology = if (circular || record.circular) && record.alignment.is_none() { Topology::Circular } else { Topology::Linear };
+        let excluded: Vec<(usize, usize)> = match exclude {
+            Some(exclude) => exclude.clipped(&record.id, 0, record.seq.len() as u64).into_iter().map(|(start, end)| (start as usize, end as usize)).collect(),
+            None => Vec::new(),
+        };
+        histogram.add_masked(&record.seq, classes, topology, &excluded);
+    }
+    let count = histogram.count_for(fraction);
+    // The threshold of a percentage is rounded up to a count, which a
+    // percentage of exactly count/window could overshoot by a rounding error
+    let mut percentage = 100.0 * count as f64 / window as f64;
+    while ((percentage / 100.0) * window as f64).ceil() as usize > count {
+        percentage = percentage.next_down();
+    }
+    // The percentage is printed in full, so it gives the same count when passed back
+    info!(
+        "--target-fraction {}: at least {} of {} bases (--min-count {}, or --percentage {}) reports {:.2}% of the genome",
+        fraction,
+        count,
+        window,
+        count,
+        percentage,
+        histogram.fraction_at(count) * 100.0
+    );
+    Ok(percentage)
+}
+
+/// Refuse the options a table or track mode would leave out: they select,
+/// reshape, annotate or write BED records, and the tables and tracks are
+/// written as they are computed, with no records to apply them to.
+fn check_table_mode(args: &Args) -> Result<(), Box<dyn Error>> {
+    if !args.is_table_mode() {
+        return Ok(());
+    }
+    let modes = args.table_modes();
+    let table = modes[0];
+    if let [_, other, ..] = modes[..] {
+        return Err(PolyscanError::parameter(format!("{} cannot be combined with {}.", other, table)));
+    }
+    let record_options = [
+        (args.output_format == OutputFormat::Jsonl, "--output-format jsonl"),
+        (args.coordinates == Coordinates::OneBased, "--coordinates 1-based"),
+        (args.igv_batch.is_some(), "--igv-batch"),
+        (args.split_by.is_some(), "--split-by"),
+        (args.max_hits_per_contig.is_some(), "--max-hits-per-contig"),
+        (args.occupancy.is_some(), "--occupancy"),
+        (args.report_dir.is_some(), "--report-dir"),
+        (args.flags, "--flags"),
+        (args.merge, "--merge"),
+        (args.best_per_cluster, "--best-per-cluster"),
+        (args.top.is_some(), "--top"),
+        (args.exclude_bed.is_some(), "--exclude-bed"),
+        (args.filter_overlap.is_some(), "--filter-overlap"),
+        (args.slop.is_some() || args.slop_left.is_some() || args.slop_right.is_some(), "--slop"),
+        (args.trim_to_content, "--trim-to-content"),
+        (args.min_consecutive.is_some(), "--min-consecutive"),
+        (args.min_count.is_some(), "--min-count"),
+        (args.max_run, "--max-run"),
+        (args.adaptive, "--adaptive"),
+        (args.seed_percentage.is_some(), "--seed-percentage"),
+        (args.all_windows, "--all-windows"),
+        (args.percentage_sweep.is_some(), "--percentage-sweep"),
+        (args.pvalue.is_some(), "--pvalue"),
+        (args.fdr.is_some(), "--fdr"),
+        (args.score_mode == ScoreMode::Zscore, "--score-mode zscore"),
+        (args.score_precision.is_some(), "--score-precision"),
+        (args.ignore_masked.is_some(), "--ignore-masked"),
+        (args.max_n_frac.is_some(), "--max-n-frac"),
+        (args.n_policy != NPolicy::CountAgainst, "--n-policy"),
+        (args.fractional_iupac, "--fractional-iupac"),
+        (args.strand != StrandChoice::Both, "--strand"),
+        (args.combine_complements, "--combine-complements"),
+        (args.pair_strands.is_some(), "--pair-strands"),
+        (args.name_template.is_some(), "--name-template"),
+        (args.annotate.is_some(), "--annotate"),
+        (args.closest.is_some(), "--closest"),
+        (args.gene_report.is_some(), "--gene-report"),
+        (args.composition_matrix.is_some(), "--composition-matrix"),
+        (args.coverage_summary.is_some(), "--coverage-summary"),
+        (args.contig_report.is_some(), "--contig-report"),
+        (args.complement, "--complement"),
+        (args.unique_bases, "--unique-bases"),
+        // --mode polya writes the trimmed tails itself
+        (args.mode != Mode::Polya && args.trim_bed.is_some(), "--trim-bed"),
+        (args.mode != Mode::Polya && args.trim_tsv.is_some(), "--trim-tsv"),
+    ];
+    if let Some((_, option)) = record_options.iter().find(|(set, _)| *set) {
+        return Err(PolyscanError::parameter(format!("{} cannot be combined with {}.", option, table)));
+    }
+    // Tiles and window compositions count the bases of plain windows of one size
+    if args.tile || args.report == Report::Composition {
+        let window_options = [
+            (args.mode != Mode::Windows, "--mode"),
+            (args.stat != Stat::Fraction, "--stat"),
+            (args.expr.is_some(), "--expr"),
+            (args.window_weights.as_deref().is_some_and(|profile| profile != "uniform"), "--window-weights"),
+            (args.window_size.len() > 1, "several window sizes"),
+            (args.short_contig_policy == ShortContigPolicy::Whole, "--short-contig-policy whole"),
+            (args.kmer.is_some() || args.kmer_file.is_some(), "--kmer"),
+            (args.motif.is_some(), "--motif"),
+            (args.pwm.is_some(), "--pwm"),
+        ];
+        if let Some((_, option)) = window_options.iter().find(|(set, _)| *set) {
+            return Err(PolyscanError::parameter(format!("{} cannot be combined with {}.", option, table)));
         }
     }
+    Ok(())
+}
+
+/// [`run_sorted_scan`], with its records also split into files as
+/// `--split-by` says.
+fn run_split_scan<W: Write>(args: Args, output: W, checkpoint: Option<Checkpoint>) -> Result<(), Box<dyn Error>> {
+    check_table_mode(&args)?;
+    if args.split_by.is_none() {
+        return run_capped_scan(args, output, checkpoint);
+    }
+    // Strands are the 6th column of BED records
+    let split_options = [
+        (args.output_format == OutputFormat::Jsonl, "--output-format jsonl"),
+    ];
+    if let Some((_, option)) = split_options.iter().find(|(set, _)| *set) {
+        return Err(PolyscanError::parameter(format!("{} cannot be combined with --split-by.", option)));
+    }
+    let prefix = match (&args.split_prefix, &args.output) {
+        (Some(prefix), _) => prefix.clone(),
+        (None, Some(output)) => output.with_extension(""),
+        (None, None) => return Err(PolyscanError::parameter("--split-by needs --split-prefix when the output goes to stdout.")),
+    };
+    let split_file = |strand: &str| -> Result<BufWriter<File>, Box<dyn Error>> {
+        let mut path = prefix.clone().into_os_string();
+        path.push(format!(".{}.bed", strand));
+        Ok(BufWriter::new(File::create(&path)?))
+    };
+    let inner = (!args.no_combined).then_some(output);
+    let mut split = StrandSplitWriter::new(inner, split_file("plus")?, split_file("minus")?);
+    run_capped_scan(args, &mut split, checkpoint)?;
+    split.finish()?;
+    Ok(())
+}
+
+/// [`run_sorted_scan`], writing at most `--max-hits-per-contig` records of
+/// each contig.
+fn run_capped_scan<W: Write>(args: Args, output: W, checkpoint: Option<Checkpoint>) -> Result<(), Box<dyn Error>> {
+    let Some(max) = args.max_hits_per_contig else {
+        return run_sorted_scan(args, output, checkpoint);
+    };
+    // Contigs are the 1st column of BED records
+    let cap_options = [
+        (args.output_format == OutputFormat::Jsonl, "--output-format jsonl"),
+    ];
+    if let Some((_, option)) = cap_options.iter().find(|(set, _)| *set) {
+        return Err(PolyscanError::parameter(format!("{} cannot be combined with --max-hits-per-contig.", option)));
+    }
+    let mut capped = HitCapWriter::new(output, max);
+    run_sorted_scan(args, &mut capped, checkpoint)?;
+    for (contig, found, dropped) in capped.finish()? {
+        warn!("{}: {} records found, only the first {} written (--max-hits-per-contig)", contig, found, max);
+        run_stats::add_capped(CappedContig { name: contig, found, dropped });
+    }
+    Ok(())
+}
+
+/// [`run_scan`], with its output sorted as `--sort` says.
+fn run_sorted_scan<W: Write>(args: Args, output: W, checkpoint: Option<Checkpoint>) -> Result<(), Box<dyn Error>> {
+    if args.sort == SortOrder::Input {
+        run_scan(args, output, checkpoint)
+    } else {
+        let order = args.sort;
+        let mut sorted = SortedWriter::new(output, order);
+        run_scan(args, &mut sorted, checkpoint)?;
+        sorted.finish()?;
+        Ok(())
+    }
+}
 
+/// Write the `--igv-batch` script for the records of `bed`, best first,
+/// over the reference `fasta`, with its snapshots going to `snapshot_dir`
+/// (by default, next to the script).
+fn write_igv_batch(batch: &Path, snapshot_dir: Option<&Path>, fasta: &Path, bed: &Path) -> Result<(), Box<dyn Error>> {
+    let mut entries = read_bed_entries(bed)?;
+    // Stable, so ties stay in the order of the output
+    entries.sort_by(|a, b| b.score.total_cmp(&a.score));
+    let regions: Vec<SnapshotRegion> = entries.into_iter().map(|entry| SnapshotRegion { chrom: entry.chrom, start: entry.start, end: entry.end }).collect();
+    // IGV resolves relative paths against its own working directory
+    let batch = std::path::absolute(batch)?;
+    let snapshot_dir = match snapshot_dir {
+        Some(dir) => std::path::absolute(dir)?,
+        None => batch.parent().map(Path::to_path_buf).unwrap_or_default(),
+    };
+    std::fs::create_dir_all(&snapshot_dir)?;
+    let (fasta, bed) = (std::fs::canonicalize(fasta)?, std::fs::canonicalize(bed)?);
+    igv::write_batch(BufWriter::new(File::create(&batch)?), &fasta, &[bed.as_path()], &snapshot_dir, &regions)?;
+    info!("Wrote an IGV batch script with {} snapshots to {}", regions.len(), batch.display());
+    Ok(())
+}
+
+/// Write the `--stats-json` summary of a run that took `elapsed`.
+fn write_run_stats(path: &Path, elapsed: std::time::Duration) -> Result<(), Box<dyn Error>> {
+    let [plus, minus, unstranded] = run_stats::records();
+    let mut stats = serde_json::json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "contigs_scanned": run_stats::contigs(),
+        "bases_processed": run_stats::bases(),
+        "records": plus + minus + unstranded,
+        "records_per_strand": { "plus": plus, "minus": minus, "unstranded": unstranded },
+        "wall_time_seconds": elapsed.as_secs_f64(),
+        "peak_rss_bytes": run_stats::peak_rss(),
+    });
+    let checksums = run_stats::checksums();
+    if !checksums.is_empty() {
+        stats["contigs"] = checksums
+            .iter()
+            .map(|contig| serde_json::json!({ "name": contig.name, "length": contig.length, "md5": contig.md5, "sha256": contig.sha256 }))
+            .collect();
+    }
+    let capped = run_stats::capped();
+    if !capped.is_empty() {
+        stats["records_over_cap"] = capped.iter().map(|contig| contig.dropped).sum::<u64>().into();
+        stats["capped_contigs"] = capped
+            .iter()
+            .map(|contig| serde_json::json!({ "name": contig.name, "records_found": contig.found, "records_dropped": contig.dropped }))
+            .collect();
+    }
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut writer, &stats)?;
+    writeln!(writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// The options given to a scan that shape its output, which a checkpoint
+/// records so it is only resumed by a scan writing the same records: each
+/// as `id=value`, sorted, leaving out those that only change how the scan
+/// runs or what it writes besides the output (`--threads`, `--progress`,
+/// `--stats-json`, ...). Options from `--config` and `--preset` are in the
+/// matches as if given.
+fn checkpoint_options(matches: &ArgMatches) -> String {
+    const RUN_OPTIONS: &[&str] = &[
+        "config", "preset", "verbose", "quiet", "error_format", "output", "checkpoint", "resume", "threads", "progress",
+        "decompress_threads", "input_backend", "mmap", "io_strict", "io_buffer", "max_memory", "fail_if_empty", "pretty",
+        "timings", "stats_json", "contig_checksums", "igv_batch", "snapshot_dir",
+    ];
+    // The ids of options, not of the group clap makes of them
+    let command = Cli::command();
+    let is_option = |id: &str| command.get_arguments().any(|arg| arg.get_id() == id);
+    let mut options: Vec<String> = matches
+        .ids()
+        .map(|id| id.as_str())
+        .filter(|id| is_option(id) && !RUN_OPTIONS.contains(id) && matches.value_source(id) == Some(ValueSource::CommandLine))
+        .map(|id| {
+            let values: Vec<String> = match matches.try_get_raw(id) {
+                Ok(Some(values)) => values.map(|value| value.to_string_lossy().into_owned()).collect(),
+                _ => Vec::new(),
+            };
+            format!("{}={}", id, values.join(","))
+        })
+        .collect();
+    options.sort();
+    options.join(" ")
+}
+
+/// Validate the options of a scan, run it and write its output, recording
+/// completed contigs in `checkpoint` (skipping those it already has).
+fn run_scan<W: Write>(mut args: Args, output: W, checkpoint: Option<Checkpoint>) -> Result<(), Box<dyn Error>> {
+    trace!("options: {:?}", args);
+    // --ignore-masked is --case-mode upper-only; either case-only mode hides
+    // the other case, and the hidden bases are left out of the window unless
+    // --ignore-masked says otherwise
+    if args.case_mode == CaseMode::LowerOnly && args.ignore_masked.is_some() {
+        return Err(PolyscanError::parameter("--case-mode lower-only scans only the soft-masked bases; it cannot be combined with --ignore-masked."));
+    }
+    if args.ignore_masked.is_some() {
+        args.case_mode = CaseMode::UpperOnly;
+    }
+    if args.case_mode != CaseMode::Insensitive {
+        args.ignore_masked = Some(args.ignore_masked.unwrap_or(MaskedPolicy::Exclude));
+    }
+    // --combine-complements is --strand collapse, with records named by both bases
+    if args.combine_complements {
+        if !matches!(args.strand, StrandChoice::Both | StrandChoice::Collapse) {
+            return Err(PolyscanError::parameter("--combine-complements cannot be combined with --strand plus or minus."));
+        }
+        args.strand = StrandChoice::Collapse;
+    }
+    // IGV loads the output as a BED track
+    if args.igv_batch.is_some() {
+        if args.output_format == OutputFormat::Jsonl {
+            return Err(PolyscanError::parameter("--igv-batch cannot be combined with --output-format jsonl."));
+        }
+        if args.coordinates == Coordinates::OneBased {
+            return Err(PolyscanError::parameter("--igv-batch cannot be combined with --coordinates 1-based."));
+        }
+    }
+    if args.output_format == OutputFormat::Jsonl {
+        if args.pretty {
+            return Err(PolyscanError::parameter("--pretty cannot be combined with --output-format jsonl."));
+        }
+        if args.sort != SortOrder::Input {
+            return Err(PolyscanError::parameter("--sort cannot be combined with --output-format jsonl."));
+        }
+        // The schema has 0-based half-open coordinates
+        if args.coordinates == Coordinates::OneBased {
+            return Err(PolyscanError::parameter("--coordinates 1-based cannot be combined with --output-format jsonl."));
+        }
+    }
+    // Several window sizes are scanned in one pass; checks below use the smallest
+    let mut windows: Vec<usize> = args.window_size.iter().map(|&w| w as usize).collect();
+    windows.sort_unstable();
+    windows.dedup();
+    let w = windows[0];
+    let p = args.percentage;
+    let user_bases: Vec<String> = args.nucleotide.to_uppercase().split(',').map(|b| b.trim().to_string()).collect();
+
+    let targets: Vec<BaseClass> = user_bases.iter().map(|b| BaseClass::new(b.as_bytes())).collect();
+    let targets_label = targets.iter().map(BaseClass::label).collect::<Vec<_>>().join(",");
+
+    // K-mers replace the --nucleotide target
+    let mut kmers: Vec<String> = Vec::new();
+    if let Some(list) = &args.kmer {
+        kmers.extend(list.split(',').map(|k| k.trim().to_uppercase()));
+    }
+    if let Some(path) = &args.kmer_file {
+        for line in std::fs::read_to_string(path)?.lines() {
+            let line = line.trim();
+            if !line.is_empty() && !line.starts_with('#') {
+                kmers.push(line.to_uppercase());
+            }
+        }
+    }
+    if (args.kmer.is_some() || args.kmer_file.is_some()) && kmers.is_empty() {
+        return Err(PolyscanError::parameter("k-mers must be non-empty and made of A, C, G, T or U (any letters with --alphabet protein)."));
+    }
+
+    // Degenerate motifs, likewise
+    let motifs: Vec<String> = match &args.motif {
+        Some(list) => list.split(',').map(|m| m.trim().to_uppercase()).collect(),
+        None => Vec::new(),
+    };
+
+    // Position weight matrices
+    let pwms = match &args.pwm {
+        Some(path) => read_pwms(path)?,
+        None => Vec::new(),
+    };
+
+    let name_template = match &args.name_template {
+        Some(text) => match NameTemplate::parse(text) {
+            Ok(template) => Some(template),
+            Err(e) => {
+                return Err(PolyscanError::parameter(format!("invalid --name-template: {}", e)));
+            }
+        },
+        None => None,
+    };
+
+    let expr = match &args.expr {
+        Some(text) => match Expr::parse(text) {
+            Ok(expr) => Some(expr),
+            Err(e) => {
+                return Err(PolyscanError::parameter(format!("invalid --expr: {}", e)));
+            }
+        },
+        None => None,
+    };
+
+    let pair: Option<[u8; 2]> = match &args.pair {
+        Some(pair) => match pair.to_uppercase().as_bytes() {
+            &[x, y] if iupac_mask(x) != 0 && iupac_mask(y) != 0 => Some([x, y]),
+            _ => {
+                return Err(PolyscanError::parameter("--pair must be two IUPAC nucleotide codes, e.g. TA."));
+            }
+        },
+        None => None,
+    };
+
+    // Runs and repeats may be longer than the overlap between chunks
+    if args.mode != Mode::Windows && args.chunk_size.is_some() {
+        return Err(PolyscanError::parameter(format!("{} cannot be combined with --mode {}.", args.chunking(), format!("{:?}", args.mode).to_lowercase())));
+    }
+
+    let min_copies: [usize; MAX_UNIT] = match args.min_copies[..] {
+        [n] => [n; MAX_UNIT],
+        _ => match args.min_copies.clone().try_into() {
+            Ok(min_copies) => min_copies,
+            Err(_) => return Err(PolyscanError::parameter(format!("--min-copies takes one value, or one per unit size from 1 to {} bp.", MAX_UNIT))),
+        },
+    };
+
+    let telomere_motifs: Vec<String> = args.telomere_motif.split(',').map(|m| m.trim().to_uppercase()).collect();
+
+    if (args.mode == Mode::Ppt) != args.gff.is_some() {
+        return Err(PolyscanError::parameter("--mode ppt and --gff go together."));
+    }
+    let introns = match &args.gff {
+        Some(gff) => read_introns(gff)?,
+        None => Vec::new(),
+    };
+    let annotation = match &args.annotate {
+        Some(gff) => Some(read_annotation(gff)?),
+        None => None,
+    };
+    let closest_genes = match &args.closest {
+        Some(gff) => Some(read_annotation(gff)?),
+        None => None,
+    };
+
+    // Named recognition sequences, e.g. EcoRI=GAATTC
+    let sites: Vec<(String, String)> = match &args.site {
+        Some(list) => list
+            .split(',')
+            .map(|site| match site.split_once('=') {
+                Some((name, seq)) => (name.trim().to_string(), seq.trim().to_uppercase()),
+                None => (site.trim().to_uppercase(), site.trim().to_uppercase()),
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+
+    // Tile counts are nucleotide-specific
+    if args.alphabet == Alphabet::Protein && (args.tile || args.report == Report::Composition) {
+        return Err(PolyscanError::parameter("--tile and --report composition are not supported with --alphabet protein."));
+    }
+
+    // A sweep scans at its lowest threshold and tags hits with the others
+    let sweep: Vec<f64> = match &args.percentage_sweep {
+        Some(text) => {
+            let bounds: Vec<f64> = text.split(':').filter_map(|bound| bound.trim().parse().ok()).collect();
+            match bounds[..] {
+                [start, stop, step] if step > 0.0 && start <= stop => {
+                    (0..).map(|i| start + i as f64 * step).take_while(|t| *t <= stop + 1e-9).collect()
+                }
+                _ => {
+                    return Err(PolyscanError::parameter("--percentage-sweep must be START:STOP:STEP with START <= STOP and STEP > 0, e.g. 70:95:5."));
+                }
+            }
+        }
+        None => Vec::new(),
+    };
+    let p = sweep.first().copied().unwrap_or(p);
+
+    let window_weights = match args.window_weights.as_deref() {
+        None | Some("uniform") => None,
+        Some(profile) => Some(window_weights(profile, w)?),
+    };
+
+    let smooth = match args.smooth.as_deref() {
+        Some(spec) => {
+            // The track is of one target's bases, whatever the window or threshold
+            let smooth_options = [
+                (args.mode != Mode::Windows, "--mode"),
+                (args.stat != Stat::Fraction, "--stat"),
+                (args.expr.is_some(), "--expr"),
+                (args.window_weights.is_some(), "--window-weights"),
+                (args.chunk_size.is_some(), args.chunking()),
+                (targets.len() > 1, "several --nucleotide targets"),
+                // The track is of every position, so windows aren't stepped or stretched
+                (args.step != 1, "--step"),
+                (args.circular, "--circular"),
+                (args.short_contig_policy != ShortContigPolicy::Skip, "--short-contig-policy"),
+                (!kmers.is_empty(), "--kmer"),
+                (!motifs.is_empty(), "--motif"),
+                (!pwms.is_empty(), "--pwm"),
+            ];
+            if let Some((_, option)) = smooth_options.iter().find(|(set, _)| *set) {
+                return Err(PolyscanError::parameter(format!("{} cannot be combined with --smooth.", option)));
+            }
+            Some(smoothing_kernel(spec)?)
+        }
+        None => None,
+    };
+
+    // Validate percentage; below 50% both strands of a window can pass, so ask first
+    let lowest = if args.allow_low_threshold { 0.0 } else { 50.0 };
+    if sweep.iter().any(|t| !(lowest..=100.0).contains(t)) {
+        return Err(PolyscanError::parameter(format!("--percentage-sweep thresholds must be between {:.1} and 100.0 (use --allow-low-threshold to go below 50)", lowest)));
+    }
+    if !(lowest..=100.0).contains(&p) {
+        return Err(PolyscanError::parameter(format!("--percentage must be between {:.1} and 100.0 (use --allow-low-threshold to go below 50)", lowest)));
+    }
+    if !(0.0..=1.0).contains(&args.min_overlap_frac) {
+        return Err(PolyscanError::parameter("--min-overlap-frac must be between 0 and 1."));
+    }
+    if args.complement || args.unique_bases {
+        // Uncovered intervals are of the whole contigs, and the union of the records isn't hits
+        let option = if args.complement { "--complement" } else { "--unique-bases" };
+        let complement_options = [
+            (!args.region.is_empty(), "--region"),
+            (args.include_bed.is_some(), "--include-bed"),
+            (args.report_dir.is_some(), "--report-dir"),
+        ];
+        if let Some((_, other)) = complement_options.iter().find(|(set, _)| *set) {
+            return Err(PolyscanError::parameter(format!("{} cannot be combined with {}.", other, option)));
+        }
+    }
+    if args.coverage_summary.is_some() {
+        // Coverage is a fraction of whole contigs
+        let coverage_options = [
+            (!args.region.is_empty(), "--region"),
+            (args.include_bed.is_some(), "--include-bed"),
+            (args.report_dir.is_some(), "--report-dir"),
+        ];
+        if let Some((_, option)) = coverage_options.iter().find(|(set, _)| *set) {
+            return Err(PolyscanError::parameter(format!("{} cannot be combined with --coverage-summary.", option)));
+        }
+    }
+    if args.contig_report.is_some() {
+        // Contigs outside the regions aren't contigs the report could account for
+        let contig_report_options = [(!args.region.is_empty(), "--region"), (args.include_bed.is_some(), "--include-bed")];
+        if let Some((_, option)) = contig_report_options.iter().find(|(set, _)| *set) {
+            return Err(PolyscanError::parameter(format!("{} cannot be combined with --contig-report.", option)));
+        }
+    }
+    if args.trim_bed.is_some() || args.trim_tsv.is_some() {
+        let trimming = if args.trim_bed.is_some() { "--trim-bed" } else { "--trim-tsv" };
+        // Tails are trimmed off whole reads, from the records found on them
+        let trim_options = [
+            (!args.region.is_empty(), "--region"),
+            (args.include_bed.is_some(), "--include-bed"),
+            (args.chunk_size.is_some(), args.chunking()),
+        ];
+        if let Some((_, option)) = trim_options.iter().find(|(set, _)| *set) {
+            return Err(PolyscanError::parameter(format!("{} cannot be combined with {}.", option, trimming)));
+        }
+    }
+    if args.score_mode == ScoreMode::Zscore || args.pvalue.is_some() || args.fdr.is_some() {
+        let background_option = match args.score_mode {
+            ScoreMode::Zscore => "--score-mode zscore",
+            _ if args.pvalue.is_some() => "--pvalue",
+            _ => "--fdr",
+        };
+        let background_options = [
+            // Chunks would each have a background of their own
+            (args.chunk_size.is_some(), args.chunking()),
+            (args.report_dir.is_some(), "--report-dir"),
+        ];
+        if let Some((_, option)) = background_options.iter().find(|(set, _)| *set) {
+            return Err(PolyscanError::parameter(format!("{} cannot be combined with {}.", option, background_option)));
+        }
+    }
+    if let Some(fraction) = args.target_fraction {
+        if !(fraction > 0.0 && fraction < 1.0) {
+            return Err(PolyscanError::parameter("--target-fraction must be between 0 and 1."));
+        }
+        // The calibration scores plain target windows over the whole input, read a first time
+        let fraction_options = [
+            (args.fasta.as_deref() == Some("-"), "--fasta - (stdin can only be read once)"),
+            (!args.region.is_empty(), "--region"),
+            (args.include_bed.is_some(), "--include-bed"),
+            (args.mode != Mode::Windows, "--mode"),
+            (args.stat != Stat::Fraction, "--stat"),
+            (args.expr.is_some(), "--expr"),
+            (args.alphabet == Alphabet::Protein, "--alphabet protein"),
+            (user_bases.len() > 1, "several --nucleotide targets"),
+            (args.window_size.len() > 1, "several window sizes"),
+            (args.step > 1, "--step"),
+            (args.window_weights.is_some(), "--window-weights"),
+            (args.adaptive, "--adaptive"),
+            (args.seed_percentage.is_some(), "--seed-percentage"),
+            (args.all_windows, "--all-windows"),
+            (args.best_per_cluster, "--best-per-cluster"),
+            (args.fractional_iupac, "--fractional-iupac"),
+            (args.ignore_masked.is_some(), "--ignore-masked"),
+            (args.n_policy != NPolicy::CountAgainst, "--n-policy"),
+            (args.tile, "--tile"),
+            (args.report == Report::Composition, "--report composition"),
+            (args.smooth.is_some(), "--smooth"),
+            (args.kmer.is_some() || args.kmer_file.is_some(), "--kmer"),
+            (args.motif.is_some(), "--motif"),
+            (args.pwm.is_some(), "--pwm"),
+        ];
+        if let Some((_, option)) = fraction_options.iter().find(|(set, _)| *set) {
+            return Err(PolyscanError::parameter(format!("{} cannot be combined with --target-fraction.", option)));
+        }
+    }
+    // Records wait for the p-values of the whole genome, so none can be written as it is found
+    if args.fdr.is_some() && args.threads > 1 {
+        return Err(PolyscanError::parameter("--threads cannot be combined with --fdr."));
+    }
+    let slop = (args.slop_left.or(args.slop).unwrap_or(0) as usize, args.slop_right.or(args.slop).unwrap_or(0) as usize);
+    if slop != (0, 0) {
+        // Records are clamped to their contig, whose length chunks don't know
+        if args.chunk_size.is_some() {
+            return Err(PolyscanError::parameter(format!("{} cannot be combined with --slop.", args.chunking())));
+        }
+    }
+    if args.top.is_some() && args.report_dir.is_some() {
+        return Err(PolyscanError::parameter("--top cannot be combined with --report-dir."));
+    }
+    if args.all_windows {
+        // Only sliding windows have a threshold to drop
+        let all_windows_options = [
+            (args.mode != Mode::Windows, "--mode"),
+            (args.expr.is_some(), "--expr"),
+            (args.adaptive, "--adaptive"),
+            (args.seed_percentage.is_some(), "--seed-percentage"),
+            (args.min_count.is_some(), "--min-count"),
+            (!sweep.is_empty(), "--percentage-sweep"),
+            (args.trim_to_content, "--trim-to-content"),
+            (!kmers.is_empty(), "--kmer"),
+            (!motifs.is_empty(), "--motif"),
+            (!pwms.is_empty(), "--pwm"),
+        ];
+        if let Some((_, option)) = all_windows_options.iter().find(|(set, _)| *set) {
+            return Err(PolyscanError::parameter(format!("{} cannot be combined with --all-windows.", option)));
+        }
+    }
+    // Every window passes a zero threshold
+    let p = if args.all_windows { 0.0 } else { p };
+    let max_entropy = if args.all_windows { f64::INFINITY } else { args.max_entropy };
+    // Only sliding windows have offsets to skip, or can wrap around a circular contig
+    let sliding = match args.mode {
+        Mode::Windows => !args.adaptive && args.seed_percentage.is_none() && !args.tile && args.report == Report::Hits,
+        Mode::Zdna => true,
+        Mode::G4 => args.g4hunter.is_some(),
+        _ => false,
+    };
+    let clusters = match (args.best_per_cluster, args.merge, args.merge_score) {
+        (true, _, _) => Some(Reduction::Best),
+        (_, true, MergeScore::Max) => Some(Reduction::MergeMax),
+        (_, true, MergeScore::Mean) => Some(Reduction::MergeMean),
+        _ => None,
+    };
+    if clusters.is_some() {
+        // Clusters may be longer than the overlap between chunks
+        if args.chunk_size.is_some() {
+            let clustering = if args.merge { "--merge" } else { "--best-per-cluster" };
+            return Err(PolyscanError::parameter(format!("{} cannot be combined with {}.", args.chunking(), clustering)));
+        }
+    }
+    if args.min_consecutive.is_some() {
+        // Every window is reported with --all-windows
+        if args.all_windows {
+            return Err(PolyscanError::parameter("--all-windows cannot be combined with --min-consecutive."));
+        }
+    }
+    if args.circular && args.chunk_size.is_some() {
+        return Err(PolyscanError::parameter(format!("{} cannot be combined with --circular, which needs whole contigs.", args.chunking())));
+    }
+    if args.circular && (!args.region.is_empty() || args.include_bed.is_some()) {
+        return Err(PolyscanError::parameter("--circular needs whole contigs, so it cannot be combined with --region or --include-bed."));
+    }
+    let hysteresis = args.seed_percentage.zip(args.extend_percentage);
+    if let Some((seed, extend)) = hysteresis {
+        if ![seed, extend].iter().all(|percentage| (lowest..=100.0).contains(percentage)) {
+            return Err(PolyscanError::parameter(format!("--seed-percentage and --extend-percentage must be between {:.1} and 100.0 (use --allow-low-threshold to go below 50)", lowest)));
+        }
+    }
+
+    if matches!(args.on_duplicate, DuplicatePolicy::Rename | DuplicatePolicy::Merge) {
+        // A resumed scan skips contigs by their IDs in the input, and whole-contig
+        // outputs are written once per contig
+        let whole_contig_options = [
+            (args.checkpoint.is_some(), "--checkpoint"),
+            (args.on_duplicate == DuplicatePolicy::Merge && args.complement, "--complement"),
+            (args.on_duplicate == DuplicatePolicy::Merge && args.unique_bases, "--unique-bases"),
+            (args.on_duplicate == DuplicatePolicy::Merge && args.coverage_summary.is_some(), "--coverage-summary"),
+            (args.on_duplicate == DuplicatePolicy::Merge && args.occupancy.is_some(), "--occupancy"),
+        ];
+        if let Some((_, option)) = whole_contig_options.iter().find(|(set, _)| *set) {
+            let policy = if args.on_duplicate == DuplicatePolicy::Rename { "rename" } else { "merge" };
+            return Err(PolyscanError::parameter(format!("--on-duplicate {} cannot be combined with {}.", policy, option)));
+        }
+    }
+
+    if args.threads > 1 {
+        // Each contig is scanned on its own, so nothing can be gathered across contigs
+        let threads_options = [
+            (args.top.is_some(), "--top"),
+            (args.coverage_summary.is_some(), "--coverage-summary"),
+            (args.occupancy.is_some(), "--occupancy"),
+            (args.contig_report.is_some(), "--contig-report"),
+            (args.trim_bed.is_some(), "--trim-bed"),
+            (args.trim_tsv.is_some(), "--trim-tsv"),
+            (args.composition_matrix.is_some(), "--composition-matrix"),
+            (args.gene_report.is_some(), "--gene-report"),
+            (args.report_dir.is_some(), "--report-dir"),
+            (args.checkpoint.is_some(), "--checkpoint"),
+            (args.chunk_size.is_some(), args.chunking()),
+        ];
+        if let Some((_, option)) = threads_options.iter().find(|(set, _)| *set) {
+            return Err(PolyscanError::parameter(format!("{} cannot be combined with --threads.", option)));
+        }
+    }
+
+    let exclude = args.exclude_bed.as_ref().map(|bed| read_bed(bed).map(|r| IntervalSet::from_regions(&r))).transpose()?;
+    let chrom_aliases = match &args.chrom_alias {
+        Some(path) => chrom_aliases(path)?,
+        None => HashMap::new(),
+    };
+    let mut config = ScanConfig { chrom_aliases, targets, kmers, motifs, max_mismatches: args.max_mismatches,
+        pwms, pwm_cutoff: args.pwm_cutoff, mode: args.mode, min_run: args.min_run as usize, min_copies,
+        max_unit: args.max_unit as usize, max_divergence: args.max_divergence,
+        min_arm: args.min_arm as usize, max_loop: args.max_loop as usize,
+        g4hunter: args.g4hunter,
+        dust_window: args.dust_window as usize, dust_threshold: args.dust_threshold as usize,
+        stat: args.stat, max_entropy, pair, expr, telomere_motifs,
+        introns, ppt_region: args.ppt_region, sites, window: w, windows, percentage: p, flags: args.flags,
+        alphabet: args.alphabet,
+        adaptive: args.adaptive,
+        hysteresis,
+        min_count: args.min_count.map(|n| n as usize),
+        step: args.step as usize,
+        circular: args.circular,
+        sliding_windows: sliding,
+        ignore_masked: args.ignore_masked,
+        case_mode: args.case_mode,
+        n_policy: args.n_policy,
+        max_n_frac: args.max_n_frac,
+        fractional_iupac: args.fractional_iupac,
+        short_contig_policy: args.short_contig_policy,
+        strand: args.strand,
+        combine_complements: args.combine_complements,
+        trim_to_content: args.trim_to_content,
+        max_run: args.max_run,
+        min_consecutive: args.min_consecutive.unwrap_or(0) as usize,
+        clusters,
+        max_gap: args.max_gap as usize,
+        merge_any_strand: args.merge_strand == MergeStrand::Any,
+        min_length: args.min_length as usize,
+        slop,
+        window_weights,
+        score_mode: args.score_mode,
+        score_background: args.score_background,
+        score_precision: args.score_precision.map(usize::from),
+        pvalue: args.pvalue,
+        fdr: args.fdr,
+        annotation,
+        closest_genes,
+        complement: args.complement,
+        unique_bases: args.unique_bases,
+        coverage_summary: args.coverage_summary.clone(),
+        occupancy: args.occupancy.clone(),
+        contig_report: args.contig_report.clone(),
+        trim_bed: args.trim_bed.clone(),
+        trim_tsv: args.trim_tsv.clone(),
+        composition_matrix: args.composition_matrix.clone(),
+        gene_report: args.gene_report.clone(),
+        top: args.top.map(|n| n as usize),
+        top_per_contig: args.top_per_contig,
+        pair_strands: args.pair_strands,
+        sweep,
+        project_to_reference: args.project_to_reference,
+        checkpoint: checkpoint.map(Mutex::new),
+        exclude,
+        overlap_filter: args
+            .filter_overlap
+            .as_ref()
+            .map(|bed| read_bed(bed).map(|r| (IntervalSet::from_regions(&r), args.overlap_mode, args.min_overlap_frac)))
+            .transpose()?,
+        json_lines: args.output_format == OutputFormat::Jsonl,
+        one_based: args.coordinates == Coordinates::OneBased,
+        name_template,
+    };
+    config.validate()?;
+
+    // FASTA/FASTQ (plain or compressed) or .2bit, detected from the file contents
+    let fasta_path = args.fasta.expect("clap enforces --fasta unless --self-test or --manifest");
+    let p = match args.target_fraction {
+        Some(fraction) => {
+            let strands: Vec<BaseClass> = strand_targets(&config.targets, args.alphabet)
+                .into_iter()
+                .filter(|(_, strand, _)| match args.strand {
+                    StrandChoice::Plus => *strand != Strand::Minus,
+                    StrandChoice::Minus => *strand != Strand::Plus,
+                    StrandChoice::Both | StrandChoice::Collapse => true,
+                })
+                .map(|(class, _, _)| class)
+                .collect();
+            // Collapsed strands pass on the better of the two, like any pair of strands
+            let decompress_threads = args.decompress_threads.unwrap_or(args.threads) as usize;
+            let records = open_records_with(&fasta_path, args.input_format, args.reference.as_deref(), args.input_backend, decompress_threads, ReadCheck::Lenient);
+            calibrated_percentage(records?, w, &strands, fraction, args.circular, config.exclude.as_ref())?
+        }
+        None => p,
+    };
+    config.percentage = p;
+    if is_remote_path(Path::new(&fasta_path)) {
+        // A URL is streamed or fetched in ranges; there is no file to map or to count the bytes of
+        let remote_options = [(args.mmap, "--mmap"), (args.io_strict, "--io-strict")];
+        if let Some((_, option)) = remote_options.iter().find(|(set, _)| *set) {
+            return Err(PolyscanError::parameter(format!("{} cannot be combined with a remote --fasta URL.", option)));
+        }
+    }
+    let regions = match &args.include_bed {
+        Some(bed) => Some(merge_regions(&read_bed(bed)?)),
+        None if !args.region.is_empty() => Some(args.region.clone()),
+        None => None,
+    };
+    let decompress_threads = args.decompress_threads.unwrap_or(args.threads) as usize;
+    let io_check = if args.io_strict { ReadCheck::WholeFile } else { ReadCheck::Lenient };
+    // Stopping at a repeated ID part way through the scan would leave the
+    // records of the contigs before it in the output, so the IDs are checked
+    // before anything is written
+    if args.on_duplicate == DuplicatePolicy::Error && regions.is_none() {
+        if fasta_path == "-" {
+            return Err(PolyscanError::parameter("--on-duplicate error cannot be combined with --fasta - (stdin can only be read once)."));
+        }
+        let open = || match args.chunk_size {
+            Some(chunk_size) => open_chunked_records(&fasta_path, args.input_format, chunk_size as usize, config.windows[config.windows.len() - 1], decompress_threads, io_check),
+            None => open_records_with(&fasta_path, args.input_format, args.reference.as_deref(), args.input_backend, decompress_threads, io_check),
+        };
+        if let Some(id) = repeated_contig_id(&fasta_path, args.input_format, &config.chrom_aliases, open)? {
+            return Err(PolyscanError::input(format!("contig ID '{}' appears more than once in the input (--on-duplicate error).", id)));
+        }
+    }
+    let opened = match (&regions, args.chunk_size) {
+        _ if args.mmap => mapped_records(&fasta_path, regions.as_deref()).map_err(PolyscanError::reading),
+        (Some(regions), _) => region_records(&fasta_path, args.input_format, args.reference.as_deref(), regions),
+        (None, Some(chunk_size)) => open_chunked_records(&fasta_path, args.input_format, chunk_size as usize, config.windows[config.windows.len() - 1], decompress_threads, io_check),
+        (None, None) => open_records_with(&fasta_path, args.input_format, args.reference.as_deref(), args.input_backend, decompress_threads, io_check),
+    };
+    // Failing to open or parse the sequences is an input error, wherever it surfaces
+    let records: Records = Box::new(opened?.map(|record| record.map_err(PolyscanError::input)));
+    // Contigs completed before a scan was interrupted are read past
+    let records: Records = match &config.checkpoint {
+        Some(checkpoint) if args.resume => {
+            let completed = checkpoint.lock().expect("no panics while holding the checkpoint").completed_contigs();
+            Box::new(records.filter(move |record| record.as_ref().map_or(true, |record| !completed.contains(&record.id))))
+        }
+        _ => records,
+    };
+    let records = if args.timings { timed_records(records) } else { records };
+    let records = match args.progress {
+        // The whole input's length is known from a .fai, unless only some regions are read
+        true => {
+            let fai = FaiIndex::from_path(format!("{}.fai", fasta_path)).ok().filter(|_| regions.is_none());
+            with_progress(records, fai.map(|fai| fai.entries.iter().map(|entry| entry.length).sum()))
+        }
+        false => records,
+    };
+
+    if args.validate {
+        // What a window must hold to pass, for the window statistics that count bases
+        let thresholds: Vec<(usize, usize)> = match (args.mode, args.stat) {
+            (Mode::Windows, Stat::Fraction) => config
+                .windows
+                .iter()
+                .map(|&window| (window, args.min_count.map_or(((p / 100.0) * window as f64).ceil() as usize, |n| n as usize)))
+                .collect(),
+            _ => Vec::new(),
+        };
+        return validate_records(records, &thresholds, args.strict, output);
+    }
+    // Characters other than A/C/G/T/U/N are scored as no base; say so, as
+    // they often mean a corrupted file
+    let records = match args.alphabet {
+        Alphabet::Dna => audited_records(records, args.strict, args.fractional_iupac),
+        Alphabet::Protein => records,
+    };
+    // Renamed after the audit, so its warnings name contigs as the input does
+    let records = if config.chrom_aliases.is_empty() { records } else { aliased_records(records, config.chrom_aliases.clone()) };
+    // Repeated contig IDs would give records that can't be told apart; the
+    // records of a --region scan are pieces of contigs, and reads often share
+    // names. IDs are those of the output, so an aliased duplicate is renamed
+    // from its alias, and contigs given the same alias are duplicates too
+    let records = match regions {
+        Some(_) => records,
+        None => deduplicated_records(records, args.on_duplicate),
+    };
+    let records = if args.contig_checksums { checksummed_records(records) } else { records };
+    if args.mode == Mode::Skew {
+        return skew_records(records, w, output);
+    }
+    if args.mode == Mode::Methylation {
+        return methylation_records(records, w, output);
+    }
+    if args.mode == Mode::Polya {
+        return polya_records(records, w, p, trim_writer(args.trim_bed.as_deref(), args.trim_tsv.as_deref())?, output);
+    }
+    if args.mode == Mode::Dust && args.dust_output != DustOutput::Bed {
+        let hard = args.dust_output == DustOutput::HardMasked;
+        return dust_fasta_records(records, args.dust_window as usize, args.dust_threshold as usize, hard, output);
+    }
+    if args.tile {
+        return tile_records(records, w, output);
+    }
+    if let Some(kernel) = smooth {
+        return smooth_records(records, &config.targets[0], kernel, output);
+    }
+    if args.report == Report::Composition {
+        return window_composition_records(records, w, args.report_every as usize, output);
+    }
+
+    if let Some(dir) = &args.report_dir {
+        let report_label = if !config.pwms.is_empty() {
+            config.pwms.iter().map(|pwm| pwm.name.as_str()).collect::<Vec<_>>().join(",")
+        } else if !config.motifs.is_empty() {
+            config.motifs.join(",")
+        } else if !config.kmers.is_empty() {
+            config.kmers.join(",")
+        } else {
+            targets_label.clone()
+        };
+        std::fs::create_dir_all(dir)?;
+        let hits = std::io::BufWriter::new(std::fs::File::create(dir.join("hits.bed"))?);
+        let mut report = ReportCollector::new();
+        scan_records(records, &config, hits, Some(&mut report))?;
+
+        let parameters: Vec<(String, String)> = vec![
+            ("version".to_string(), env!("CARGO_PKG_VERSION").to_string()),
+            ("command".to_string(), std::env::args().collect::<Vec<_>>().join(" ")),
+            ("input".to_string(), fasta_path.clone()),
+            ("nucleotide".to_string(), targets_label.clone()),
+            ("kmers".to_string(), config.kmers.join(",")),
+            ("motifs".to_string(), config.motifs.join(",")),
+            ("max_mismatches".to_string(), config.max_mismatches.to_string()),
+            ("pwm".to_string(), args.pwm.as_ref().map(|path| path.display().to_string()).unwrap_or_default()),
+            ("pwm_cutoff".to_string(), config.pwm_cutoff.to_string()),
+            ("window_size".to_string(), config.windows.iter().map(|w| w.to_string()).collect::<Vec<_>>().join(",")),
+            ("percentage".to_string(), p.to_string()),
+            ("preset".to_string(), args.preset.and_then(|preset| preset.to_possible_value()).map(|value| value.get_name().to_string()).unwrap_or_default()),
+            ("percentage_sweep".to_string(), args.percentage_sweep.clone().unwrap_or_default()),
+            ("allow_low_threshold".to_string(), args.allow_low_threshold.to_string()),
+            ("step".to_string(), args.step.to_string()),
+            ("circular".to_string(), args.circular.to_string()),
+            ("strand".to_string(), format!("{:?}", args.strand).to_lowercase()),
+            ("chrom_alias".to_string(), args.chrom_alias.as_ref().map_or(String::new(), |path| path.display().to_string())),
+            ("n_policy".to_string(), format!("{:?}", args.n_policy).to_lowercase()),
+            ("max_n_frac".to_string(), args.max_n_frac.map_or(String::new(), |fraction| fraction.to_string())),
+            ("fractional_iupac".to_string(), args.fractional_iupac.to_string()),
+            ("short_contig_policy".to_string(), format!("{:?}", args.short_contig_policy).to_lowercase()),
+            ("trim_to_content".to_string(), args.trim_to_content.to_string()),
+            ("max_run".to_string(), args.max_run.to_string()),
+            ("min_consecutive".to_string(), args.min_consecutive.unwrap_or(0).to_string()),
+            ("all_windows".to_string(), args.all_windows.to_string()),
+            ("best_per_cluster".to_string(), args.best_per_cluster.to_string()),
+            ("merge".to_string(), if args.merge { format!("{:?}", args.merge_score).to_lowercase() } else { String::new() }),
+            ("merge_strand".to_string(), format!("{:?}", args.merge_strand).to_lowercase()),
+            ("max_gap".to_string(), args.max_gap.to_string()),
+            ("min_length".to_string(), args.min_length.to_string()),
+            ("slop".to_string(), format!("{},{}", slop.0, slop.1)),
+            ("window_weights".to_string(), args.window_weights.clone().unwrap_or_default()),
+            ("score_mode".to_string(), format!("{:?}", args.score_mode).to_lowercase()),
+            ("pvalue".to_string(), args.pvalue.map(|format| format!("{:?}", format).to_lowercase()).unwrap_or_default()),
+            ("fdr".to_string(), args.fdr.map(|rate| rate.to_string()).unwrap_or_default()),
+            ("ignore_masked".to_string(), args.ignore_masked.map(|policy| format!("{:?}", policy).to_lowercase()).unwrap_or_default()),
+            ("case_mode".to_string(), args.case_mode.to_possible_value().expect("no skipped variants").get_name().to_string()),
+            ("min_count".to_string(), args.min_count.map(|n| n.to_string()).unwrap_or_default()),
+            ("adaptive".to_string(), args.adaptive.to_string()),
+            ("seed_percentage".to_string(), args.seed_percentage.map(|p| p.to_string()).unwrap_or_default()),
+            ("extend_percentage".to_string(), args.extend_percentage.map(|p| p.to_string()).unwrap_or_default()),
+            ("mode".to_string(), format!("{:?}", args.mode).to_lowercase()),
+            ("min_run".to_string(), args.min_run.to_string()),
+            ("min_copies".to_string(), config.min_copies.map(|n| n.to_string()).join(",")),
+            ("max_unit".to_string(), config.max_unit.to_string()),
+            ("max_divergence".to_string(), config.max_divergence.to_string()),
+            ("min_arm".to_string(), config.min_arm.to_string()),
+            ("max_loop".to_string(), config.max_loop.to_string()),
+            ("g4hunter".to_string(), config.g4hunter.map(|t| t.to_string()).unwrap_or_default()),
+            ("dust_window".to_string(), config.dust_window.to_string()),
+            ("dust_threshold".to_string(), config.dust_threshold.to_string()),
+            ("stat".to_string(), format!("{:?}", config.stat).to_lowercase()),
+            ("max_entropy".to_string(), config.max_entropy.to_string()),
+            ("expr".to_string(), args.expr.clone().unwrap_or_default()),
+            ("pair".to_string(), config.pair.map(|pair| String::from_utf8_lossy(&pair).into_owned()).unwrap_or_default()),
+            ("telomere_motifs".to_string(), config.telomere_motifs.join(",")),
+            ("gff".to_string(), args.gff.as_ref().map(|path| path.display().to_string()).unwrap_or_default()),
+            ("filter_overlap".to_string(), args.filter_overlap.as_ref().map(|path| path.display().to_string()).unwrap_or_default()),
+            ("overlap_mode".to_string(), format!("{:?}", args.overlap_mode).to_lowercase()),
+            ("min_overlap_frac".to_string(), args.min_overlap_frac.to_string()),
+            ("annotate".to_string(), args.annotate.as_ref().map(|path| path.display().to_string()).unwrap_or_default()),
+            ("closest".to_string(), args.closest.as_ref().map(|path| path.display().to_string()).unwrap_or_default()),
+            ("gene_report".to_string(), args.gene_report.as_ref().map(|path| path.display().to_string()).unwrap_or_default()),
+            ("ppt_region".to_string(), config.ppt_region.to_string()),
+            ("sites".to_string(), config.sites.iter().map(|(name, seq)| format!("{}={}", name, seq)).collect::<Vec<_>>().join(",")),
+            ("project_to_reference".to_string(), args.project_to_reference.to_string()),
+            ("regions".to_string(), args.region.iter().map(|r| r.to_string()).collect::<Vec<_>>().join(",")),
+        ];
+        return report.write_bundle(dir, &report_label, &parameters);
+    }
+
+    // Write BED records to the output
+    if args.threads > 1 {
+        // Long contigs are scanned in pieces too when, as with --chunk-size, the
+        // output is the same, and the pieces don't need each other's hits
+        // (larger windows near the end of a piece would come after the next piece's smaller ones)
+        let split = args.mode == Mode::Windows
+            && config.windows.len() == 1
+            && config.clusters.is_none()
+            && !args.adaptive
+            && config.hysteresis.is_none()
+            && !args.complement
+            && !args.unique_bases
+            && !args.flags
+            && !args.circular
+            && args.score_mode == ScoreMode::Percentage
+            && args.pvalue.is_none()
+            && args.pair_strands.is_none();
+        // An indexed FASTA is read by the workers themselves, each fetching
+        // the contig ranges it scans, rather than by this thread in turn (the
+        // progress bar counts records as they are read, so it needs them read, as
+        // do --io-strict to count the bytes and --contig-checksums to hash them)
+        let input = match IndexedSource::open(&fasta_path, args.input_format, args.mmap).map_err(PolyscanError::input)? {
+            Some(fasta) if !args.progress && !args.io_strict && !args.contig_checksums => {
+                debug!("Workers fetch their contig ranges from the index of {}", fasta_path);
+                ParallelInput::Indexed(Arc::new(fasta), regions)
+            }
+            _ => ParallelInput::Records(records),
+        };
+        return scan_records_parallel(input, &config, output, args.threads as usize, split);
+    }
+    scan_records(records, &config, output, None)
+}
+
+/// The weights of `--window-weights PROFILE` for a `window`-sized window: a
+/// named profile, a comma-separated list or a file of weights.
+fn window_weights(profile: &str, window: usize) -> Result<Vec<f64>, Box<dyn Error>> {
+    let text = match profile {
+        "triangular" => return Ok(triangular(window)),
+        _ if Path::new(profile).is_file() => std::fs::read_to_string(profile)?,
+        _ => profile.replace(',', " "),
+    };
+    let weights: Vec<f64> = match text.split_whitespace().map(str::parse).collect() {
+        Ok(weights) => weights,
+        Err(_) => {
+            return Err(PolyscanError::parameter(format!(
+                "--window-weights must be 'uniform', 'triangular', a comma-separated list of weights or a file of them, not '{}'.",
+                profile
+            )));
+        }
+    };
+    if weights.len() != window {
+        return Err(PolyscanError::parameter(format!("--window-weights has {} weights for a {} bp window.", weights.len(), window)));
+    }
+    if weights.iter().any(|&weight: &f64| !weight.is_finite() || weight < 0.0) || weights.iter().sum::<f64>() <= 0.0 {
+        return Err(PolyscanError::parameter("--window-weights must be non-negative, and not all 0."));
+    }
+    Ok(weights)
+}
+
+const COMPOSITION_HEADER: &str = "#chrom\tstart\tend\tA\tC\tG\tT\tN";
+
+/// Write one composition table row.
+///
+///  columns: chrom, start, end, A, C, G, T, N
+fn write_composition_row<W: Write>(output: &mut W, chrom: &str, start: usize, end: usize, comp: &Composition) -> Result<(), Box<dyn Error>> {
+    let [a, c, g, t, n] = comp.counts();
+    writeln!(output, "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}", chrom, start, end, a, c, g, t, n)?;
+    Ok(())
+}
+
+/// Write the composition of every non-overlapping tile as a TSV table.
+fn tile_records<W: Write>(records: Records, w: usize, mut output: W) -> Result<(), Box<dyn Error>> {
+    writeln!(output, "{}", COMPOSITION_HEADER)?;
+
+    for result_record in records {
+        let mut record = result_record?;
+        if has_uracil(&record.seq) {
+            rna_to_dna(&mut record.seq);
+        }
+        tile_windows(&record.seq, w, |start, end, comp| {
+            write_composition_row(&mut output, &record.id, record.start + start, record.start + end, comp)
+        })?;
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Read every record, reporting the contig count, total length, N content,
+/// characters other than A/C/G/T/U/N, duplicate contig IDs and the `(window,
+/// bases needed)` thresholds, then a row per contig. Exits with an error
+/// after the report if IDs repeat, or (with `strict`) if there are other
+/// characters.
+fn validate_records<W: Write>(records: Records, thresholds: &[(usize, usize)], strict: bool, mut output: W) -> Result<(), Box<dyn Error>> {
+    let mut contigs: Vec<(String, usize, usize, u64)> = Vec::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut duplicates: Vec<String> = Vec::new();
+    let mut unexpected = CharacterCounts::default();
+    for result_record in records {
+        let record = result_record?;
+        let n_bases = record.seq.iter().filter(|&&b| b == b'N' || b == b'n').count();
+        if !seen.insert(record.id.clone()) && !duplicates.contains(&record.id) {
+            duplicates.push(record.id.clone());
+        }
+        let mut counts = CharacterCounts::default();
+        counts.add(&record.seq);
+        unexpected.add(&record.seq);
+        contigs.push((record.id, record.contig_len, n_bases, counts.total()));
+    }
+
+    let total: usize = contigs.iter().map(|(_, len, _, _)| len).sum();
+    let n_total: usize = contigs.iter().map(|(_, _, n, _)| n).sum();
+    writeln!(output, "# contigs: {}", contigs.len())?;
+    writeln!(output, "# total length: {} bp", total)?;
+    writeln!(output, "# N bases: {} ({:.2}%)", n_total, if total > 0 { n_total as f64 * 100.0 / total as f64 } else { 0.0 })?;
+    writeln!(output, "# other characters: {}", if unexpected.total() == 0 { "none".to_string() } else { unexpected.describe() })?;
+    writeln!(output, "# duplicate IDs: {}", if duplicates.is_empty() { "none".to_string() } else { duplicates.join(",") })?;
+    for (window, needed) in thresholds {
+        writeln!(output, "# threshold: at least {} of {} bases per window", needed, window)?;
+    }
+    writeln!(output, "contig\tlength\tn_bases\tother_chars")?;
+    for (id, len, n_bases, other) in &contigs {
+        writeln!(output, "{}\t{}\t{}\t{}", id, len, n_bases, other)?;
+    }
+    output.flush()?;
+
+    if !duplicates.is_empty() {
+        return Err(PolyscanError::input(format!("{} contig IDs appear more than once; outputs would mix their records.", duplicates.len())));
+    }
+    if strict && unexpected.total() > 0 {
+        return Err(PolyscanError::input(format!("the input holds characters other than A, C, G, T, U and N ({}) (--strict).", unexpected.describe())));
+    }
+    Ok(())
+}
+
+/// The first contig ID that repeats in the input once renamed by `aliases`,
+/// from its `.fai` if it has one and otherwise by reading through the records
+/// `open` opens.
+/// Reads from BAM/CRAM are not contigs, and only count if they are the first
+/// piece of a contig read in chunks.
+fn repeated_contig_id<F>(path: &str, format: InputFormat, aliases: &HashMap<String, String>, open: F) -> Result<Option<String>, Box<dyn Error>>
+where
+    F: FnOnce() -> Result<Records, PolyscanError>,
+{
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut repeated = |id: &String| {
+        let name = aliases.get(id).unwrap_or(id);
+        (!seen.insert(name.clone())).then(|| name.clone())
+    };
+    if let Some(indexed) = IndexedSource::open(path, format, false).map_err(PolyscanError::input)? {
+        return Ok(indexed.fai().entries.iter().find_map(|entry| repeated(&entry.name)));
+    }
+    for record in open()? {
+        let record = record.map_err(PolyscanError::input)?;
+        if record.alignment.is_none() && record.start == 0 {
+            if let Some(name) = repeated(&record.id) {
+                return Ok(Some(name));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Records with the contig IDs seen before in the input handled by `policy`
+/// (see [`DuplicatePolicy`]). A contig starts with a record at position 0;
+/// the later chunks of a streamed contig are carried along with it. Reads
+/// from BAM/CRAM are passed through.
+fn deduplicated_records(records: Records, policy: DuplicatePolicy) -> Records {
+    let mut records = records.fuse();
+    // Per contig ID: how many contigs had it, and how far their bases reach when merged
+    let mut seen: HashMap<String, (usize, usize)> = HashMap::new();
+    // The contig being read: its ID in the input, the ID it is written as and its offset
+    let mut current: Option<(String, String, usize)> = None;
+    let mut duplicates: Vec<String> = Vec::new();
+    Box::new(std::iter::from_fn(move || {
+        let mut record = match records.next() {
+            Some(Ok(record)) => record,
+            None => {
+                if policy == DuplicatePolicy::Warn && !duplicates.is_empty() {
+                    let shown = duplicates.iter().take(5).cloned().collect::<Vec<_>>().join(", ");
+                    let more = if duplicates.len() > 5 { format!(" and {} more", duplicates.len() - 5) } else { String::new() };
+                    warn!("{} contig IDs appear more than once ({}{}); their records share a name (see --on-duplicate)", duplicates.len(), shown, more);
+                    duplicates.clear();
+                }
+                return None;
+            }
+            error => return error,
+        };
+        if record.alignment.is_some() {
+            return Some(Ok(record));
+        }
+        if record.start == 0 || current.as_ref().is_none_or(|(id, _, _)| *id != record.id) {
+            let (count, reach) = seen.entry(record.id.clone()).or_default();
+            *count += 1;
+            let (name, offset) = match (*count, policy) {
+                (1, _) | (_, DuplicatePolicy::Warn) => (record.id.clone(), 0),
+                (_, DuplicatePolicy::Error) => {
+                    return Some(Err(PolyscanError::input(format!("contig ID '{}' appears more than once in the input (--on-duplicate error).", record.id))));
+                }
+                (n, DuplicatePolicy::Rename) => (format!("{}_{}", record.id, n), 0),
+                (_, DuplicatePolicy::Merge) => (record.id.clone(), *reach),
+            };
+            if *count == 2 {
+                duplicates.push(record.id.clone());
+            }
+            current = Some((record.id.clone(), name, offset));
+        }
+        let (id, name, offset) = current.as_ref().expect("set above");
+        let reach = &mut seen.get_mut(id).expect("seen above").1;
+        *reach = (*reach).max(offset + record.start + record.seq.len());
+        record.id.clone_from(name);
+        record.start += offset;
+        record.contig_len += offset;
+        Some(Ok(record))
+    }))
+}
+
+/// The `--chrom-alias` table: the output name of each contig, by its name
+/// in the input.
+fn chrom_aliases(path: &Path) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let mut aliases: HashMap<String, String> = HashMap::new();
+    for (number, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split('\t').map(str::trim);
+        let (Some(name), Some(alias)) = (fields.next().filter(|f| !f.is_empty()), fields.next().filter(|f| !f.is_empty())) else {
+            return Err(PolyscanError::input(format!("line {} of {} needs a contig name and its alias, tab-separated.", number + 1, path.display())));
+        };
+        if let Some(earlier) = aliases.insert(name.to_string(), alias.to_string()).filter(|earlier| earlier != alias) {
+            return Err(PolyscanError::input(format!("{} gives contig '{}' two aliases, '{}' and '{}'.", path.display(), name, earlier, alias)));
+        }
+    }
+    Ok(aliases)
+}
+
+/// `records`, hashing the sequence of each contig as it goes by for
+/// `--contig-checksums`. Chunks of a contig are hashed past the bases they
+/// share with the one before.
+fn checksummed_records(records: Records) -> Records {
+    let mut records = records.fuse();
+    let mut current: Option<(String, ContigHasher)> = None;
+    let finish = |(name, hasher): (String, ContigHasher)| {
+        let length = hasher.length();
+        let (md5, sha256) = hasher.finish();
+        run_stats::add_checksum(ContigChecksum { name, length, md5, sha256 });
+    };
+    Box::new(std::iter::from_fn(move || match records.next() {
+        Some(Ok(record)) => {
+            if current.as_ref().is_none_or(|(name, _)| *name != record.id) {
+                current.take().map(finish);
+                current = Some((record.id.clone(), ContigHasher::new()));
+            }
+            let (_, hasher) = current.as_mut().expect("a hasher for the record's contig");
+            let hashed = (hasher.length() as usize).saturating_sub(record.start).min(record.seq.len());
+            hasher.update(&record.seq[hashed..]);
+            Some(Ok(record))
+        }
+        None => {
+            current.take().map(finish);
+            None
+        }
+        error => error,
+    }))
+}
+
+/// Records with their contigs renamed by `aliases`, logging once at the end
+/// how many contigs (other than BAM/CRAM reads) had no alias.
+fn aliased_records(records: Records, aliases: HashMap<String, String>) -> Records {
+    let mut records = records.fuse();
+    let mut unaliased: Vec<String> = Vec::new();
+    Box::new(std::iter::from_fn(move || {
+        let mut record = match records.next() {
+            Some(Ok(record)) => record,
+            None => {
+                if !unaliased.is_empty() {
+                    let shown = unaliased.iter().take(5).cloned().collect::<Vec<_>>().join(", ");
+                    let more = if unaliased.len() > 5 { format!(" and {} more", unaliased.len() - 5) } else { String::new() };
+                    warn!("{} contigs have no --chrom-alias and keep their names ({}{})", unaliased.len(), shown, more);
+                    unaliased.clear();
+                }
+                return None;
+            }
+            error => return error,
+        };
+        if record.alignment.is_none() && !aliases.contains_key(&record.id) && unaliased.last() != Some(&record.id) {
+            unaliased.push(record.id.clone());
+        }
+        rename_record(&mut record, &aliases);
+        Some(Ok(record))
+    }))
+}
+
+/// Records passed through unchanged, warning once per contig (after its last
+/// chunk) about the characters other than A/C/G/T/U/N it holds; with
+/// `strict`, the first record holding any is an input error instead. With
+/// `fractional_iupac`, ambiguity codes are not said to be scored as no base.
+fn audited_records(records: Records, strict: bool, fractional_iupac: bool) -> Records {
+    let mut records = records.fuse();
+    // The contig being read, how far into it has been counted and what was found
+    let mut contig: Option<(String, usize, CharacterCounts)> = None;
+    let warn_about = move |id: &str, counts: &CharacterCounts| {
+        if counts.total() > 0 {
+            let scored = if fractional_iupac { "IUPAC codes scored in part" } else { "scored as no base" };
+            warn!("{}: {} characters other than A, C, G, T, U and N, {} ({})", id, counts.total(), scored, counts.describe());
+        }
+    };
+    Box::new(std::iter::from_fn(move || {
+        let next = records.next();
+        // The chunks of a contig come one after the other
+        let next_id = match &next {
+            Some(Ok(record)) => Some(record.id.as_str()),
+            _ => None,
+        };
+        if contig.as_ref().is_some_and(|(id, _, _)| Some(id.as_str()) != next_id) {
+            let (id, _, counts) = contig.take().expect("checked above");
+            warn_about(&id, &counts);
+        }
+        let Some(Ok(record)) = next else {
+            return next;
+        };
+        let (_, counted, counts) = contig.get_or_insert_with(|| (record.id.clone(), 0, CharacterCounts::default()));
+        // Chunks overlap; only the bases past the previous one are new
+        let new = &record.seq[counted.saturating_sub(record.start).min(record.seq.len())..];
+        *counted = (*counted).max(record.start + record.seq.len());
+        if strict {
+            let mut found = CharacterCounts::default();
+            found.add(new);
+            if found.total() > 0 {
+                return Some(Err(PolyscanError::input(format!(
+                    "{}: characters other than A, C, G, T, U and N ({}) (--strict).",
+                    record.id,
+                    found.describe()
+                ))));
+            }
+        } else {
+            counts.add(new);
+        }
+        Some(Ok(record))
+    }))
+}
+
+/// Write the GC skew of every tile, then the cumulative skew, as two bedGraph
+/// tracks, and report each contig's predicted origin and terminus on stderr.
+fn skew_records<W: Write>(records: Records, w: usize, mut output: W) -> Result<(), Box<dyn Error>> {
+    // Both tracks need every contig, so keep the (small) profiles until the end
+    let mut profiles: Vec<(String, usize, Vec<SkewWindow>)> = Vec::new();
+    for result_record in records {
+        let mut record = result_record?;
+        if has_uracil(&record.seq) {
+            rna_to_dna(&mut record.seq);
+        }
+        let profile = skew_profile(&record.seq, w)?;
+        if let Some((origin, terminus)) = predict_origin_terminus(&profile) {
+            info!(
+                "{}: predicted origin (cumulative skew minimum) at {}, terminus (maximum) at {}",
+                record.id,
+                record.start + origin,
+                record.start + terminus
+            );
+        }
+        profiles.push((record.id, record.start, profile));
+    }
+
+    writeln!(output, "track type=bedGraph name=\"GC skew\" description=\"(G-C)/(G+C) per {} bp window\"", w)?;
+    for (id, offset, profile) in &profiles {
+        for window in profile {
+            writeln!(output, "{}\t{}\t{}\t{:.4}", id, offset + window.start, offset + window.end, window.skew)?;
+        }
+    }
+    writeln!(output, "track type=bedGraph name=\"Cumulative GC skew\" description=\"Running sum of the GC skew\"")?;
+    for (id, offset, profile) in &profiles {
+        for window in profile {
+            writeln!(output, "{}\t{}\t{}\t{:.4}", id, offset + window.start, offset + window.end, window.cumulative)?;
+        }
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+/// The kernel of `--smooth KERNEL:BASES`.
+fn smoothing_kernel(spec: &str) -> Result<Kernel, Box<dyn Error>> {
+    let kernel = match spec.split_once(':') {
+        Some(("gaussian", sigma)) => sigma.parse().ok().filter(|&sigma: &f64| sigma.is_finite() && sigma > 0.0).map(Kernel::Gaussian),
+        Some(("box", width)) => width.parse().ok().filter(|&width: &usize| width > 0).map(Kernel::Box),
+        _ => None,
+    };
+    kernel.ok_or_else(|| {
+        PolyscanError::parameter(format!("--smooth must be 'gaussian:SIGMA' or 'box:WIDTH' with a positive number of bases, not '{}'.", spec))
+    })
+}
+
+/// Write the density of `class` along every record, smoothed with `kernel`,
+/// as a bedGraph track, with runs of positions of the same (rounded) density
+/// on one line.
+fn smooth_records<W: Write>(records: Records, class: &BaseClass, kernel: Kernel, mut output: W) -> Result<(), Box<dyn Error>> {
+    let description = match kernel {
+        Kernel::Gaussian(sigma) => format!("Gaussian-smoothed (sigma {} bp)", sigma),
+        Kernel::Box(width) => format!("{} bp moving average of the", width),
+    };
+    writeln!(output, "track type=bedGraph name=\"{} density\" description=\"{} fraction of {} bases\"", class.label(), description, class.label())?;
+
+    for result_record in records {
+        let mut record = result_record?;
+        if has_uracil(&record.seq) {
+            rna_to_dna(&mut record.seq);
+        }
+        let density = smoothed_density(&record.seq, class, kernel);
+        density_runs(&density, 3, |start, end, value| {
+            writeln!(output, "{}\t{}\t{}\t{}", record.id, record.start + start, record.start + end, value)
+        })?;
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Write the CpG, CHG and CHH densities (cytosines on either strand per base)
+/// of every tile as a TSV table.
+fn methylation_records<W: Write>(records: Records, w: usize, mut output: W) -> Result<(), Box<dyn Error>> {
+    writeln!(output, "#chrom\tstart\tend\tCpG\tCHG\tCHH")?;
+
+    for result_record in records {
+        let mut record = result_record?;
+        if has_uracil(&record.seq) {
+            rna_to_dna(&mut record.seq);
+        }
+        context_tiles(&record.seq, w, |counts| {
+            let (cpg, chg, chh) = counts.densities();
+            writeln!(output, "{}\t{}\t{}\t{:.4}\t{:.4}\t{:.4}", record.id, record.start + counts.start, record.start + counts.end, cpg, chg, chh)?;
+            Ok(())
+        })?;
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Write the poly(A) tail found at the ends of every read (see
+/// [`find_tail`]) as a TSV table, with a row for reads without one too.
+fn polya_records<W: Write>(records: Records, w: usize, p: f64, mut trim: Option<TrimWriter<BufWriter<File>>>, mut output: W) -> Result<(), Box<dyn Error>> {
+    writeln!(output, "#read\tlength\ttail\ttail_start\ttail_end\ttail_length\tpurity")?;
+
+    let (mut reads, mut lengths) = (0usize, Vec::new());
+    for result_record in records {
+        let mut record = result_record?;
+        if has_uracil(&record.seq) {
+            rna_to_dna(&mut record.seq);
+        }
+        reads += 1;
+        let tail = find_tail(&record.seq, w, p);
+        if let Some(trim) = trim.as_mut() {
+            let tails: Vec<(usize, usize)> = tail.iter().map(|tail| (tail.start, tail.end)).collect();
+            trim.add(&record.id, record.seq.len(), keep_interval(record.seq.len(), &tails, w))?;
+        }
+        match tail {
+            Some(tail) => {
+                let name = if tail.base == b'A' { "polyA" } else { "polyT" };
+                writeln!(output, "{}\t{}\t{}\t{}\t{}\t{}\t{:.2}", record.id, record.seq.len(), name, tail.start, tail.end, tail.length(), tail.purity)?;
+                lengths.push(tail.length());
+            }
+            None => writeln!(output, "{}\t{}\t.\t.\t.\t0\t.", record.id, record.seq.len())?,
+        }
+    }
+
+    if !lengths.is_empty() {
+        lengths.sort_unstable();
+        info!("{} of {} reads have a poly(A) tail, of median length {}", lengths.len(), reads, lengths[lengths.len() / 2]);
+    } else {
+        info!("None of the {} reads have a poly(A) tail", reads);
+    }
+    if let Some(trim) = trim {
+        finish_trim(trim)?;
+    }
+    output.flush()?;
+    Ok(())
+}
+
+/// Write every record as FASTA with its SDUST low-complexity intervals
+/// lowercased, or replaced by N if `hard`.
+fn dust_fasta_records<W: Write>(records: Records, window: usize, threshold: usize, hard: bool, output: W) -> Result<(), Box<dyn Error>> {
+    mask_fasta_records(records, hard, output, |record| sdust(&record.seq, window, threshold))
+}
+
+/// Write the input as FASTA with the intervals given for each record (in
+/// record coordinates) soft-masked, or hard-masked with N.
+fn mask_fasta_records<W, F>(records: Records, hard: bool, mut output: W, mut intervals_of: F) -> Result<(), Box<dyn Error>>
+where
+    W: Write,
+    F: FnMut(&SeqRecord) -> Vec<(usize, usize)>,
+{
+    for result_record in records {
+        let mut record = result_record?;
+        let intervals = intervals_of(&record);
+        let seq: &mut [u8] = &mut record.seq;
+        for (start, end) in intervals {
+            for base in &mut seq[start..end] {
+                *base = if hard { b'N' } else { base.to_ascii_lowercase() };
+            }
+        }
+
+        // Name parts of contigs (--region, --include-bed) by their coordinates
+        if record.start > 0 || seq.len() < record.contig_len {
+            writeln!(output, ">{}:{}-{}", record.id, record.start + 1, record.start + seq.len())?;
+        } else {
+            writeln!(output, ">{}", record.id)?;
+        }
+        for line in seq.chunks(FASTA_LINE_WIDTH) {
+            output.write_all(line)?;
+            output.write_all(b"\n")?;
+        }
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Bases per line of FASTA output.
+const FASTA_LINE_WIDTH: usize = 60;
+
+/// Write the composition of every `every`-th sliding window as a TSV table.
+fn window_composition_records<W: Write>(records: Records, w: usize, every: usize, mut output: W) -> Result<(), Box<dyn Error>> {
+    writeln!(output, "{}", COMPOSITION_HEADER)?;
+
+    for result_record in records {
+        let mut record = result_record?;
+        if has_uracil(&record.seq) {
+            rna_to_dna(&mut record.seq);
+        }
+        composition_windows(&record.seq, w, every, |start, end, comp| {
+            write_composition_row(&mut output, &record.id, record.start + start, record.start + end, comp)
+        })?;
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Run a command other than `scan`, writing its output to stdout.
+fn run_command(command: Command) -> Result<(), Box<dyn Error>> {
+    let stdout = std::io::stdout();
+    match command {
+        Command::Scan(_) => unreachable!("scan is run by main"),
+        Command::Merge { bed, max_gap, merge_score, merge_strand } => {
+            let reduction = match merge_score {
+                MergeScore::Max => Reduction::MergeMax,
+                MergeScore::Mean => Reduction::MergeMean,
+            };
+            merge_bed(&bed, max_gap as usize, reduction, merge_strand == MergeStrand::Any, stdout.lock())
+        }
+        Command::Stats { bed } => bed_stats(&bed, stdout.lock()),
+        Command::Mask { fasta, bed, hard } => {
+            let regions = IntervalSet::from_regions(&read_bed(&bed)?);
+            let records = open_records(&fasta, InputFormat::Auto, None)?;
+            mask_fasta_records(records, hard, stdout.lock(), |record| {
+                let start = record.start as u64;
+                regions
+                    .clipped(&record.id, start, start + record.seq.len() as u64)
+                    .into_iter()
+                    .map(|(s, e)| ((s - start) as usize, (e - start) as usize))
+                    .collect()
+            })
+        }
+        Command::Extract { fasta, bed } => extract_regions(open_records(&fasta, InputFormat::Auto, None)?, &bed, stdout.lock()),
+        Command::AnnotateVcf { fasta, vcf, min_run } => {
+            let context = SequenceContext::from_records(open_records(&fasta, InputFormat::Auto, None)?, min_run as usize)?;
+            let tagged = annotate_vcf(open_vcf(&vcf)?, &context, stdout.lock())?;
+            info!("Tagged {} variants.", tagged);
+            Ok(())
+        }
+        Command::FilterVcf { fasta, vcf, max_run, drop } => {
+            let context = SequenceContext::from_records(open_records(&fasta, InputFormat::Auto, None)?, max_run as usize + 1)?;
+            let (indels, filtered) = filter_vcf(open_vcf(&vcf)?, &context, max_run, drop, stdout.lock())?;
+            info!("Filtered {} of {} indels.", filtered, indels);
+            Ok(())
+        }
+        Command::Simulate { output, genome_size, gc, tracts, str_fraction, min_tract, max_tract, length_distribution, seed } => {
+            let params = SimulationParams {
+                genome_size: genome_size as usize,
+                gc,
+                tracts: tracts as usize,
+                str_fraction,
+                min_length: min_tract as usize,
+                max_length: max_tract as usize,
+                distribution: length_distribution,
+                seed,
+            };
+            let (seq, planted) = match simulate(&params) {
+                Ok(simulated) => simulated,
+                Err(e) => {
+                    return Err(PolyscanError::parameter(format!("{}.", e)));
+                }
+            };
+            write_simulation(&output, &seq, &planted)
+        }
+        Command::Serve { fasta, listen, max_region } => serve::run(&fasta, &listen, max_region),
+        Command::Plot { fasta, bed, outdir, format, nucleotide, window_size, percentage, bins } => {
+            let bases: Vec<String> = nucleotide.to_uppercase().split(',').map(|base| base.trim().to_string()).collect();
+            if bases.iter().any(|base| base.is_empty() || !base.bytes().all(|b| b"ACGTN".contains(&b))) {
+                return Err(PolyscanError::parameter("--nucleotide must be made of A, C, G, T or N (comma-separated for several)."));
+            }
+            if !(50.0..=100.0).contains(&percentage) {
+                return Err(PolyscanError::parameter("--percentage must be between 50.0 and 100.0"));
+            }
+            let scan = bed.is_none().then(|| ScanConfig::plain(bases.iter().map(|base| BaseClass::new(base.as_bytes())).collect(), window_size as usize, percentage));
+            plot_density(&fasta, bed.as_deref(), scan.as_ref(), &outdir, format, bins as usize)
+        }
+        Command::Diff { old, new, ignore_strand } => diff_runs(&old, &new, !ignore_strand, stdout.lock()),
+        Command::Benchmark { predictions, truth, pr_curve, roc, genome } => {
+            let roc = roc.zip(genome);
+            benchmark_predictions(&predictions, &truth, pr_curve, roc.as_ref().map(|(roc, genome)| (roc.as_path(), genome.as_path())), stdout.lock())
+        }
+        #[cfg(feature = "tui")]
+        Command::View { bed, fasta } => view::run(&bed, &fasta),
+        Command::Completions { shell } => {
+            clap_complete::generate(shell, &mut cli_command(), "polyscan", &mut stdout.lock());
+            Ok(())
+        }
+        Command::Man => Ok(clap_mangen::Man::new(cli_command()).render(&mut stdout.lock())?),
+    }
+}
+
+/// Merge the overlapping records of a BED file with the same contig, name
+/// and (unless `any_strand`) strand, writing the regions sorted per contig.
+fn merge_bed<W: Write>(bed: &Path, max_gap: usize, reduction: Reduction, any_strand: bool, output: W) -> Result<(), Box<dyn Error>> {
+    // Records as hits per contig, in order of first appearance, with their
+    // name as the scorer
+    let mut names: Vec<String> = Vec::new();
+    let mut contigs: Vec<(String, Vec<ScanHit>)> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for entry in read_bed_entries(bed)? {
+        let scorer = match names.iter().position(|name| *name == entry.name) {
+            Some(i) => i,
+            None => {
+                names.push(entry.name.clone());
+                names.len() - 1
+            }
+        };
+        let contig = *index.entry(entry.chrom.clone()).or_insert_with(|| {
+            contigs.push((entry.chrom.clone(), Vec::new()));
+            contigs.len() - 1
+        });
+        let hit = ScanHit { start: entry.start as usize, end: entry.end as usize, score: entry.score, strand: entry.strand, scorer };
+        contigs[contig].1.push(hit);
+    }
+
+    let mut bed_writer = BedWriter::new(output);
+    for (chrom, mut hits) in contigs {
+        hits.sort_by_key(|hit| (hit.start, hit.end));
+        let mut clusters = Clusters::new(reduction).with_max_gap(max_gap);
+        if any_strand {
+            clusters = clusters.with_groups((0..names.len()).collect());
+        }
+        let mut merged: Vec<ScanHit> = Vec::new();
+        for hit in hits {
+            merged.extend(clusters.push(hit));
+        }
+        merged.extend(clusters.finish());
+        for hit in merged {
+            write_bed_record(&mut bed_writer, &chrom, hit.start as u64, hit.end as u64, &names[hit.scorer], BedScore::Percentage(hit.score), hit.strand.symbol(), &[], None)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write a TSV summary of a BED file: one row per name and strand, in order
+/// of first appearance, then one for all records.
+///
+///  columns: name, strand, records, bases, covered_bases, min_length, median_length, max_length, mean_score, max_score
+fn bed_stats<W: Write>(bed: &Path, mut output: W) -> Result<(), Box<dyn Error>> {
+    let entries = read_bed_entries(bed)?;
+    let mut groups: Vec<((String, &str), Vec<&BedEntry>)> = Vec::new();
+    for entry in &entries {
+        let key = (entry.name.clone(), entry.strand.symbol());
+        match groups.iter_mut().find(|(group, _)| *group == key) {
+            Some((_, members)) => members.push(entry),
+            None => groups.push((key, vec![entry])),
+        }
+    }
+    groups.push((("all".to_string(), "."), entries.iter().collect()));
+
+    writeln!(output, "name\tstrand\trecords\tbases\tcovered_bases\tmin_length\tmedian_length\tmax_length\tmean_score\tmax_score")?;
+    for ((name, strand), members) in groups {
+        let mut lengths: Vec<u64> = members.iter().map(|entry| entry.end - entry.start).collect();
+        lengths.sort_unstable();
+        // Overlapping records cover their shared bases once
+        let regions: Vec<Region> =
+            members.iter().map(|entry| Region { chrom: entry.chrom.clone(), start: entry.start, end: Some(entry.end) }).collect();
+        let covered: u64 = merge_regions(&regions).iter().map(|region| region.end.unwrap_or(region.start) - region.start).sum();
+        let scores = members.iter().map(|entry| entry.score);
+        let (min_length, median_length, max_length, mean_score, max_score) = match lengths.len() {
+            0 => (".".to_string(), ".".to_string(), ".".to_string(), ".".to_string(), ".".to_string()),
+            n => (
+                lengths[0].to_string(),
+                lengths[n / 2].to_string(),
+                lengths[n - 1].to_string(),
+                format!("{:.2}", scores.clone().sum::<f64>() / n as f64),
+                scores.fold(f64::NEG_INFINITY, f64::max).to_string(),
+            ),
+        };
+        writeln!(
+            output,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            name,
+            strand,
+            members.len(),
+            lengths.iter().sum::<u64>(),
+            covered,
+            min_length,
+            median_length,
+            max_length,
+            mean_score,
+            max_score
+        )?;
+    }
+    Ok(())
+}
+
+/// Write the sequence of every BED record as FASTA, in the order of the
+/// contigs of the input, reverse complemented for records on the minus strand.
+fn extract_regions<W: Write>(records: Records, bed: &Path, mut output: W) -> Result<(), Box<dyn Error>> {
+    let mut by_chrom: HashMap<String, Vec<BedEntry>> = HashMap::new();
+    for entry in read_bed_entries(bed)? {
+        by_chrom.entry(entry.chrom.clone()).or_default().push(entry);
+    }
+
+    for result_record in records {
+        let record = result_record?;
+        let Some(entries) = by_chrom.remove(&record.id) else {
+            continue;
+        };
+        for entry in entries {
+            if entry.end as usize > record.seq.len() {
+                return Err(format!("{}:{}-{} lies past the end of {} ({} bases)", entry.chrom, entry.start, entry.end, record.id, record.seq.len()).into());
+            }
+            let seq = &record.seq[entry.start as usize..entry.end as usize];
+            let seq = match entry.strand {
+                Strand::Minus => reverse_complement(seq),
+                _ => seq.to_vec(),
+            };
+            // Named like regions (1-based, inclusive), with the strand and the record name
+            match entry.strand {
+                Strand::Unstranded => writeln!(output, ">{}:{}-{} {}", entry.chrom, entry.start + 1, entry.end, entry.name)?,
+                strand => writeln!(output, ">{}:{}-{}({}) {}", entry.chrom, entry.start + 1, entry.end, strand.symbol(), entry.name)?,
+            }
+            for line in seq.chunks(FASTA_LINE_WIDTH) {
+                output.write_all(line)?;
+                output.write_all(b"\n")?;
+            }
+        }
+    }
+    let missing: usize = by_chrom.values().map(Vec::len).sum();
+    if missing > 0 {
+        warn!("skipped {} records on contigs missing from the input.", missing);
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Write a hit density plot of every contig of `fasta` to `outdir/<contig>.svg` (or `.png`),
+/// from the records of `bed` or else those of scanning the FASTA with `scan`.
+fn plot_density(fasta: &Path, bed: Option<&Path>, scan: Option<&ScanConfig>, outdir: &Path, format: PlotFormat, bins: usize) -> Result<(), Box<dyn Error>> {
+    // Contig lengths in input order, gathered while the FASTA is read for the scan
+    let lengths: Rc<RefCell<Vec<(String, usize)>>> = Rc::new(RefCell::new(Vec::new()));
+    let collected = Rc::clone(&lengths);
+    let records: Records = Box::new(open_records(fasta, InputFormat::Auto, None)?.inspect(move |record| {
+        if let Ok(record) = record {
+            collected.borrow_mut().push((record.id.clone(), record.contig_len));
+        }
+    }));
+    let regions = match (bed, scan) {
+        (Some(bed), _) => {
+            records.for_each(drop);
+            read_bed(bed)?
+        }
+        (None, Some(config)) => {
+            let mut output = Vec::new();
+            scan_records(records, config, &mut output, None)?;
+            let mut regions = Vec::new();
+            for line in String::from_utf8(output)?.lines() {
+                let fields: Vec<&str> = line.split('\t').collect();
+                regions.push(Region { chrom: fields[0].to_string(), start: fields[1].parse()?, end: Some(fields[2].parse()?) });
+            }
+            regions
+        }
+        (None, None) => unreachable!("plot scans without --bed"),
+    };
+
+    // Bars show covered bases, whatever the strand or target of the records
+    let mut intervals: HashMap<String, Vec<(usize, usize)>> = HashMap::new();
+    for region in merge_regions(&regions) {
+        let end = region.end.unwrap_or(region.start);
+        intervals.entry(region.chrom).or_default().push((region.start as usize, end as usize));
+    }
+    fs::create_dir_all(outdir)?;
+    let lengths = lengths.borrow();
+    for (contig, length) in lengths.iter() {
+        let contig_intervals = intervals.get(contig).map(Vec::as_slice).unwrap_or_default();
+        // Contig names may hold characters that aren't safe in file names
+        let file_name: String = contig.chars().map(|c| if c.is_ascii_alphanumeric() || "._-".contains(c) { c } else { '_' }).collect();
+        let extension = match format {
+            PlotFormat::Svg => "svg",
+            PlotFormat::Png => "png",
+        };
+        write_density_plot(&outdir.join(format!("{}.{}", file_name, extension)), format, contig, *length, contig_intervals, bins)?;
+    }
+    info!("Plotted {} contigs into {}.", lengths.len(), outdir.display());
+    Ok(())
+}
+
+/// Write a simulated genome to `PREFIX.fa` (one contig, `sim`) and its planted
+/// tracts to `PREFIX.truth.bed`, named after their unit and scored with their
+/// length.
+fn write_simulation(prefix: &Path, seq: &[u8], planted: &[PlantedTract]) -> Result<(), Box<dyn Error>> {
+    let with_extension = |extension: &str| {
+        let mut path = prefix.as_os_str().to_owned();
+        path.push(extension);
+        PathBuf::from(path)
+    };
+    let mut fasta = BufWriter::new(File::create(with_extension(".fa"))?);
+    writeln!(fasta, ">{}", SIMULATED_CONTIG)?;
+    for line in seq.chunks(FASTA_LINE_WIDTH) {
+        fasta.write_all(line)?;
+        fasta.write_all(b"\n")?;
+    }
+    fasta.flush()?;
+
+    let mut bed_writer = BedWriter::new(BufWriter::new(File::create(with_extension(".truth.bed"))?));
+    for tract in planted {
+        let unit = String::from_utf8_lossy(&tract.unit);
+        let len = (tract.end - tract.start) as f64;
+        write_bed_record(&mut bed_writer, SIMULATED_CONTIG, tract.start as u64, tract.end as u64, &unit, BedScore::Percentage(len), "+", &[], None)?;
+    }
+    Ok(())
+}
+
+/// Name of the contig written by `polyscan simulate`.
+const SIMULATED_CONTIG: &str = "sim";
+
+/// Compare two BED outputs, writing one BED record per cluster of overlapping
+/// regions (named lost, gained or shared) and a summary on stderr.
+fn diff_runs<W: Write>(old: &Path, new: &Path, stranded: bool, output: W) -> Result<(), Box<dyn Error>> {
+    let (regions, stats) = diff_regions(read_grouped_intervals(old, stranded)?, read_grouped_intervals(new, stranded)?);
+    let mut bed_writer = BedWriter::new(output);
+    for region in &regions {
+        write_bed_record(&mut bed_writer, &region.chrom, region.start, region.end, region.change.name(), BedScore::Percentage(0.0), &region.strand, &[], None)?;
+    }
+    info!(
+        "{} lost, {} gained and {} shared regions; {} bases only in {}, {} only in {}, {} in both (Jaccard index {:.4})",
+        stats.lost,
+        stats.gained,
+        stats.shared,
+        stats.old_bases - stats.shared_bases,
+        old.display(),
+        stats.new_bases - stats.shared_bases,
+        new.display(),
+        stats.shared_bases,
+        stats.jaccard()
+    );
+    Ok(())
+}
+
+/// Write precision, recall and F1 of a BED output against a truth BED as a
+/// TSV table, for all predictions or at every score cutoff, and with `roc`
+/// a ROC curve to its file, on the genome of its `.fai` or chrom.sizes file.
+fn benchmark_predictions<W: Write>(predictions: &Path, truth: &Path, pr_curve: bool, roc: Option<(&Path, &Path)>, mut output: W) -> Result<(), Box<dyn Error>> {
+    let predictions = read_scored_bed(predictions)?;
+    // Overlapping truth intervals are one region
+    let truth = merge_regions(&read_bed(truth)?);
+    if let Some((roc, genome)) = roc {
+        let mut writer = BufWriter::new(File::create(roc)?);
+        writeln!(writer, "cutoff\tpredicted_bases\ttrue_positive_bases\tfalse_positive_bases\ttpr\tfpr\tprecision")?;
+        for point in roc_curve(&predictions, &truth, read_genome_length(genome)?) {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{:.4}\t{:.6}\t{:.4}",
+                point.cutoff,
+                point.predicted_bases,
+                point.true_positive_bases,
+                point.false_positive_bases(),
+                point.tpr(),
+                point.fpr(),
+                point.precision()
+            )?;
+        }
+        writer.flush()?;
+    }
+    let points = if pr_curve {
+        benchmark_curve(&predictions, &truth)
+    } else {
+        let lowest = predictions.iter().map(|(_, score)| *score).min_by(f64::total_cmp).unwrap_or(0.0);
+        vec![benchmark_at(&predictions, &truth, &IntervalSet::from_regions(&truth), lowest)]
+    };
+    writeln!(output, "cutoff\tpredicted\ttrue_positives\ttruth\trecovered\tprecision\trecall\tf1")?;
+    for point in points {
+        writeln!(
+            output,
+            "{}\t{}\t{}\t{}\t{}\t{:.4}\t{:.4}\t{:.4}",
+            point.cutoff,
+            point.predicted,
+            point.true_positives,
+            point.truth,
+            point.recovered,
+            point.precision(),
+            point.recall(),
+            point.f1()
+        )?;
+    }
     Ok(())
 }
@@ -0,0 +1,276 @@
+use crate::scan::{ScanHit, Strand};
+
+/// Streaming merger that coalesces overlapping or adjacent hits into maximal
+/// regions, keeping the best score.
+///
+/// Hits must be pushed in order of start position (as the scanner produces
+/// them). Plus and minus strand hits are merged separately.
+#[derive(Debug, Default)]
+pub struct Merger {
+    open: [Option<ScanHit>; 2],
+}
+
+impl Merger {
+    pub fn new() -> Self {
+        Merger::default()
+    }
+
+    fn slot(strand: Strand) -> usize {
+        match strand {
+            Strand::Plus | Strand::Unstranded => 0,
+            Strand::Minus => 1,
+        }
+    }
+
+    /// Add a hit, returning a finished region if this hit cannot extend it.
+    pub fn push(&mut self, hit: ScanHit) -> Option<ScanHit> {
+        let slot = &mut self.open[Self::slot(hit.strand)];
+        match slot {
+            Some(region) if hit.start <= region.end => {
+                region.end = region.end.max(hit.end);
+                region.score = region.score.max(hit.score);
+                None
+            }
+            _ => slot.replace(hit),
+        }
+    }
+
+    /// Flush the regions still open, in start order.
+    pub fn finish(&mut self) -> Vec<ScanHit> {
+        let mut rest: Vec<ScanHit> = self.open.iter_mut().filter_map(Option::take).collect();
+        rest.sort_by_key(|hit| hit.start);
+        rest
+    }
+}
+
+/// Merge a batch of hits (any order, any strand) into non-overlapping regions
+/// regardless of strand, keeping the best score. Merged regions are labelled
+/// with the strand of their first hit.
+pub fn merge_unstranded(hits: &[ScanHit]) -> Vec<ScanHit> {
+    let mut sorted: Vec<&ScanHit> = hits.iter().collect();
+    sorted.sort_by_key(|hit| (hit.start, hit.end));
+
+    let mut merged: Vec<ScanHit> = Vec::new();
+    for hit in sorted {
+        match merged.last_mut() {
+            Some(region) if hit.start <= region.end => {
+                region.end = region.end.max(hit.end);
+                region.score = region.score.max(hit.score);
+            }
+            _ => merged.push(hit.clone()),
+        }
+    }
+    merged
+}
+
+/// How [`Clusters`] reports a cluster of hits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reduction {
+    /// Its best hit: the highest score, then the longest, then the leftmost.
+    /// Touching hits do not overlap, so they are clustered apart.
+    Best,
+    /// The span of its hits, with their best score. Touching hits are joined.
+    MergeMax,
+    /// The span of its hits, with their mean score. Touching hits are joined.
+    MergeMean,
+}
+
+/// Streaming reducer of clusters of overlapping hits to one hit each.
+///
+/// Unlike [`Merger`], clusters are kept apart for every scorer and strand
+/// (unless scorers are grouped with [`Clusters::with_groups`]), and
+/// a cluster is closed as soon as the scan has moved past it, so regions come
+/// out in start order. Hits must be pushed in order of start position (as the
+/// scanner produces them).
+#[derive(Debug)]
+pub struct Clusters {
+    reduction: Reduction,
+    // Bases allowed between merged hits
+    max_gap: usize,
+    // Cluster group of every scorer, when scorers share clusters across strands
+    groups: Option<Vec<usize>>,
+    open: Vec<Cluster>,
+}
+
+#[derive(Debug)]
+struct Cluster {
+    // Where the cluster ends so far
+    end: usize,
+    // The best hit, or the span merged so far
+    hit: ScanHit,
+    score_sum: f64,
+    hits: usize,
+}
+
+impl Cluster {
+    fn into_hit(self, reduction: Reduction) -> ScanHit {
+        match reduction {
+            Reduction::MergeMean => ScanHit { score: self.score_sum / self.hits as f64, ..self.hit },
+            _ => self.hit,
+        }
+    }
+}
+
+impl Clusters {
+    pub fn new(reduction: Reduction) -> Self {
+        Clusters { reduction, max_gap: 0, groups: None, open: Vec::new() }
+    }
+
+    /// Also merge hits separated by at most `max_gap` bases (only when merging).
+    pub fn with_max_gap(mut self, max_gap: usize) -> Self {
+        self.max_gap = max_gap;
+        self
+    }
+
+    /// Cluster the hits of scorers in the same group (`groups[scorer]`)
+    /// together, whatever their strand; e.g. a target and its complement. A
+    /// cluster with hits on both strands is reported unstranded.
+    pub fn with_groups(mut self, groups: Vec<usize>) -> Self {
+        self.groups = Some(groups);
+        self
+    }
+
+    // Whether `hit` belongs with the hits of `cluster`
+    fn same_cluster(&self, cluster: &Cluster, hit: &ScanHit) -> bool {
+        match &self.groups {
+            Some(groups) => groups[cluster.hit.scorer] == groups[hit.scorer],
+            None => cluster.hit.scorer == hit.scorer && cluster.hit.strand == hit.strand,
+        }
+    }
+
+    // How far past its end a cluster still takes hits
+    fn reach(&self, end: usize) -> usize {
+        match self.reduction {
+            Reduction::Best => end,
+            Reduction::MergeMax | Reduction::MergeMean => end.saturating_add(1).saturating_add(self.max_gap),
+        }
+    }
+
+    /// Add a hit, returning the reduced hits of the clusters it has moved
+    /// past, in start order.
+    pub fn push(&mut self, hit: ScanHit) -> Vec<ScanHit> {
+        let mut closed = Vec::new();
+        let mut i = 0;
+        while i < self.open.len() {
+            if hit.start >= self.reach(self.open[i].end) {
+                closed.push(self.open.swap_remove(i).into_hit(self.reduction));
+            } else {
+                i += 1;
+            }
+        }
+        closed.sort_by_key(|reduced| (reduced.start, reduced.scorer));
+
+        let reduction = self.reduction;
+        match self.open.iter().position(|cluster| self.same_cluster(cluster, &hit)) {
+            Some(i) => {
+                let cluster = &mut self.open[i];
+                if cluster.hit.strand != hit.strand {
+                    cluster.hit.strand = Strand::Unstranded;
+                }
+                cluster.end = cluster.end.max(hit.end);
+                cluster.score_sum += hit.score;
+                cluster.hits += 1;
+                match reduction {
+                    Reduction::Best => {
+                        let best = &cluster.hit;
+                        let longer = hit.end - hit.start > best.end - best.start;
+                        if hit.score > best.score || (hit.score == best.score && longer) {
+                            cluster.hit = hit;
+                        }
+                    }
+                    Reduction::MergeMax | Reduction::MergeMean => {
+                        cluster.hit.end = cluster.end;
+                        cluster.hit.score = cluster.hit.score.max(hit.score);
+                    }
+                }
+            }
+            None => self.open.push(Cluster { end: hit.end, score_sum: hit.score, hits: 1, hit }),
+        }
+        closed
+    }
+
+    /// Flush the clusters still open, in start order.
+    pub fn finish(&mut self) -> Vec<ScanHit> {
+        let reduction = self.reduction;
+        let mut rest: Vec<ScanHit> = self.open.drain(..).map(|cluster| cluster.into_hit(reduction)).collect();
+        rest.sort_by_key(|hit| (hit.start, hit.scorer));
+        rest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Span = (usize, usize, f64, Strand, usize);
+
+    fn hit(start: usize, end: usize, score: f64, strand: Strand, scorer: usize) -> ScanHit {
+        ScanHit { start, end, score, strand, scorer }
+    }
+
+    fn spans(hits: &[ScanHit]) -> Vec<Span> {
+        hits.iter().map(|hit| (hit.start, hit.end, hit.score, hit.strand, hit.scorer)).collect()
+    }
+
+    fn reduce(mut clusters: Clusters, hits: &[ScanHit]) -> Vec<Span> {
+        let mut reduced = Vec::new();
+        for hit in hits {
+            reduced.extend(clusters.push(hit.clone()));
+        }
+        reduced.extend(clusters.finish());
+        spans(&reduced)
+    }
+
+    #[test]
+    fn merger_joins_overlapping_and_adjacent_hits_per_strand() {
+        let mut merger = Merger::new();
+        let mut regions = Vec::new();
+        for hit in [
+            hit(0, 10, 80.0, Strand::Plus, 0),
+            hit(2, 12, 90.0, Strand::Minus, 0),
+            hit(5, 15, 90.0, Strand::Plus, 0),
+            // Adjacent to the plus region, then a base apart from it
+            hit(15, 20, 70.0, Strand::Plus, 0),
+            hit(21, 30, 80.0, Strand::Plus, 0),
+        ] {
+            regions.extend(merger.push(hit));
+        }
+        regions.extend(merger.finish());
+        assert_eq!(spans(&regions), [(0, 20, 90.0, Strand::Plus, 0), (2, 12, 90.0, Strand::Minus, 0), (21, 30, 80.0, Strand::Plus, 0)]);
+    }
+
+    #[test]
+    fn unstranded_merging_ignores_strands() {
+        let hits = [hit(20, 30, 60.0, Strand::Plus, 0), hit(0, 10, 80.0, Strand::Minus, 0), hit(10, 15, 90.0, Strand::Plus, 1), hit(16, 18, 50.0, Strand::Plus, 0)];
+        assert_eq!(spans(&merge_unstranded(&hits)), [(0, 15, 90.0, Strand::Minus, 0), (16, 18, 50.0, Strand::Plus, 0), (20, 30, 60.0, Strand::Plus, 0)]);
+    }
+
+    #[test]
+    fn merged_clusters_join_overlapping_and_adjacent_hits() {
+        let hits = [hit(0, 10, 80.0, Strand::Plus, 0), hit(5, 15, 100.0, Strand::Plus, 0), hit(15, 20, 90.0, Strand::Plus, 0), hit(22, 30, 70.0, Strand::Plus, 0)];
+        assert_eq!(reduce(Clusters::new(Reduction::MergeMax), &hits), [(0, 20, 100.0, Strand::Plus, 0), (22, 30, 70.0, Strand::Plus, 0)]);
+        assert_eq!(reduce(Clusters::new(Reduction::MergeMean), &hits), [(0, 20, 90.0, Strand::Plus, 0), (22, 30, 70.0, Strand::Plus, 0)]);
+        // Within the gap allowed
+        assert_eq!(reduce(Clusters::new(Reduction::MergeMax).with_max_gap(2), &hits), [(0, 30, 100.0, Strand::Plus, 0)]);
+    }
+
+    #[test]
+    fn best_clusters_keep_adjacent_hits_apart() {
+        let hits = [hit(0, 10, 80.0, Strand::Plus, 0), hit(5, 15, 100.0, Strand::Plus, 0), hit(6, 16, 100.0, Strand::Plus, 0), hit(16, 20, 90.0, Strand::Plus, 0)];
+        assert_eq!(reduce(Clusters::new(Reduction::Best), &hits), [(5, 15, 100.0, Strand::Plus, 0), (16, 20, 90.0, Strand::Plus, 0)]);
+    }
+
+    #[test]
+    fn clusters_of_other_names_and_strands_are_kept_apart() {
+        let hits = [hit(0, 10, 80.0, Strand::Plus, 0), hit(2, 12, 90.0, Strand::Minus, 0), hit(4, 14, 70.0, Strand::Plus, 1), hit(8, 16, 60.0, Strand::Plus, 0)];
+        assert_eq!(
+            reduce(Clusters::new(Reduction::MergeMax), &hits),
+            [(0, 16, 80.0, Strand::Plus, 0), (2, 12, 90.0, Strand::Minus, 0), (4, 14, 70.0, Strand::Plus, 1)]
+        );
+        // Grouped, a name's strands are one cluster, reported unstranded
+        assert_eq!(
+            reduce(Clusters::new(Reduction::MergeMax).with_groups(vec![0, 1]), &hits),
+            [(0, 16, 90.0, Strand::Unstranded, 0), (4, 14, 70.0, Strand::Plus, 1)]
+        );
+    }
+}
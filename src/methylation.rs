@@ -0,0 +1,81 @@
+use std::error::Error;
+
+/// Cytosine methylation contexts (H is A, C or T).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Context {
+    CpG,
+    Chg,
+    Chh,
+}
+
+/// Context of the cytosine at `i`, on either strand: a C read forwards, or a G
+/// read backwards as the C of the minus strand. `None` for other bases, and
+/// when an N (or the sequence end) hides the context.
+pub fn context_at(seq: &[u8], i: usize) -> Option<Context> {
+    let base = |j: Option<usize>| j.and_then(|j| seq.get(j)).map(u8::to_ascii_uppercase);
+    // The two bases 3' of the cytosine, complemented on the minus strand
+    let (next, after) = match base(Some(i))? {
+        b'C' => (base(i.checked_add(1)), base(i.checked_add(2))),
+        b'G' => {
+            let complement = |nuc: Option<u8>| nuc.map(|nuc| match nuc {
+                b'A' => b'T',
+                b'C' => b'G',
+                b'G' => b'C',
+                b'T' => b'A',
+                other => other,
+            });
+            (complement(base(i.checked_sub(1))), complement(base(i.checked_sub(2))))
+        }
+        _ => return None,
+    };
+    let is_h = |nuc: u8| matches!(nuc, b'A' | b'C' | b'T');
+    match (next?, after) {
+        (b'G', _) => Some(Context::CpG),
+        (h, Some(b'G')) if is_h(h) => Some(Context::Chg),
+        (h, Some(a)) if is_h(h) && is_h(a) => Some(Context::Chh),
+        _ => None,
+    }
+}
+
+/// Cytosines in each context (both strands) in one tile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContextCounts {
+    pub start: usize,
+    pub end: usize,
+    pub cpg: usize,
+    pub chg: usize,
+    pub chh: usize,
+}
+
+impl ContextCounts {
+    /// Sites per base of the tile, as (CpG, CHG, CHH).
+    pub fn densities(&self) -> (f64, f64, f64) {
+        let len = (self.end - self.start).max(1) as f64;
+        (self.cpg as f64 / len, self.chg as f64 / len, self.chh as f64 / len)
+    }
+}
+
+/// Count the methylation contexts of consecutive non-overlapping
+/// `window`-sized tiles of `seq` (the last may be shorter).
+///
+/// A context is counted in the tile holding its cytosine, even when the rest
+/// of it lies in the next tile.
+pub fn context_tiles<F>(seq: &[u8], window: usize, mut emit: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(ContextCounts) -> Result<(), Box<dyn Error>>,
+{
+    for start in (0..seq.len()).step_by(window.max(1)) {
+        let end = (start + window.max(1)).min(seq.len());
+        let mut counts = ContextCounts { start, end, cpg: 0, chg: 0, chh: 0 };
+        for i in start..end {
+            match context_at(seq, i) {
+                Some(Context::CpG) => counts.cpg += 1,
+                Some(Context::Chg) => counts.chg += 1,
+                Some(Context::Chh) => counts.chh += 1,
+                None => {}
+            }
+        }
+        emit(counts)?;
+    }
+    Ok(())
+}
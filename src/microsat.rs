@@ -0,0 +1,303 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use crate::bases::nuc_code;
+
+/// Longest repeat unit considered a microsatellite.
+pub const MAX_UNIT: usize = 6;
+
+/// Default minimum copy numbers for units of 1-6 bp, as used by MISA.
+pub const DEFAULT_MIN_COPIES: [usize; MAX_UNIT] = [10, 6, 5, 5, 5, 5];
+
+/// A tandem repeat found by [`find_strs`] or [`find_tandem_repeats`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TandemRepeat {
+    pub start: usize,
+    pub end: usize,
+    /// The repeat unit as it first appears in the sequence
+    pub unit: Vec<u8>,
+    /// Percentage of bases matching the unit (100 for perfect repeats)
+    pub purity: f64,
+}
+
+impl TandemRepeat {
+    /// Number of copies of the unit, possibly fractional.
+    pub fn copies(&self) -> f64 {
+        (self.end - self.start) as f64 / self.unit.len() as f64
+    }
+
+    /// MISA-style label, e.g. `(AT)12` or `(CAG)7.3`.
+    pub fn label(&self) -> String {
+        let copies = self.copies();
+        let unit = String::from_utf8_lossy(&self.unit);
+        if copies.fract() == 0.0 {
+            format!("({}){}", unit, copies)
+        } else {
+            format!("({}){:.1}", unit, copies)
+        }
+    }
+}
+
+/// Whether `unit` is not itself a repeat of a shorter unit (e.g. `ATAT`, `AA`).
+fn is_primitive(unit: &[u8]) -> bool {
+    (1..unit.len()).filter(|&d| unit.len().is_multiple_of(d)).all(|d| unit.chunks(d).any(|chunk| chunk != &unit[..d]))
+}
+
+/// Find tandem repeats of 1-6 bp units with at least `min_copies[unit - 1]` copies.
+///
+/// Perfect repeats are found per unit size by comparing every base with the
+/// one a unit earlier. With `max_mismatches` above 0, each perfect repeat is
+/// then extended on both sides while no more than that many bases differ from
+/// the unit (near-perfect repeats); bases other than A/C/G/T never match.
+/// Where repeats of different unit sizes overlap, only the longest is kept,
+/// and repeats are emitted in coordinate order.
+pub fn find_strs<F>(seq: &[u8], min_copies: &[usize; MAX_UNIT], max_mismatches: usize, mut emit: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(TandemRepeat) -> Result<(), Box<dyn Error>>,
+{
+    let same = |i: usize, unit: usize| {
+        let (a, b) = (seq[i].to_ascii_uppercase(), seq[i - unit].to_ascii_uppercase());
+        a == b && matches!(a, b'A' | b'C' | b'G' | b'T')
+    };
+
+    let mut candidates: Vec<TandemRepeat> = Vec::new();
+    for unit in 1..=MAX_UNIT {
+        let mut i = unit;
+        while i < seq.len() {
+            if !same(i, unit) {
+                i += 1;
+                continue;
+            }
+            let mut end = i;
+            while end < seq.len() && same(end, unit) {
+                end += 1;
+            }
+            let start = i - unit;
+            i = end;
+
+            let repeat_unit = seq[start..start + unit].to_ascii_uppercase();
+            if (end - start) / unit < min_copies[unit - 1] || !is_primitive(&repeat_unit) {
+                continue;
+            }
+
+            // Extend past substitutions, phase-locked to the unit; a mismatch is
+            // only kept once a matching base follows it
+            let expected = |pos: usize| repeat_unit[(pos + unit - start % unit) % unit];
+            let matches = |pos: usize| seq[pos].to_ascii_uppercase() == expected(pos);
+            let (mut start, mut mismatches, mut pending) = (start, 0, 0);
+            let mut pos = end;
+            while pos < seq.len() && (matches(pos) || mismatches + pending < max_mismatches) {
+                if matches(pos) {
+                    end = pos + 1;
+                    mismatches += pending;
+                    pending = 0;
+                } else {
+                    pending += 1;
+                }
+                pos += 1;
+            }
+            pending = 0;
+            let mut pos = start;
+            while pos > 0 && (matches(pos - 1) || mismatches + pending < max_mismatches) {
+                if matches(pos - 1) {
+                    start = pos - 1;
+                    mismatches += pending;
+                    pending = 0;
+                } else {
+                    pending += 1;
+                }
+                pos -= 1;
+            }
+            i = i.max(end);
+
+            let purity = (end - start - mismatches) as f64 / (end - start) as f64 * 100.0;
+            // Name the unit in the phase the repeat starts with, as in a perfect repeat
+            let unit = (start..start + unit).map(expected).collect();
+            candidates.push(TandemRepeat { start, end, unit, purity });
+        }
+    }
+
+    for repeat in keep_longest(candidates) {
+        emit(repeat)?;
+    }
+    Ok(())
+}
+
+/// Resolve overlapping repeats in favour of the longest, in coordinate order.
+fn keep_longest(mut candidates: Vec<TandemRepeat>) -> Vec<TandemRepeat> {
+    candidates.sort_by_key(|r| (std::cmp::Reverse(r.end - r.start), r.start));
+    let mut kept: BTreeMap<usize, TandemRepeat> = BTreeMap::new();
+    for repeat in candidates {
+        let before = kept.range(..repeat.end).next_back();
+        if before.is_some_and(|(_, other)| other.end > repeat.start) {
+            continue;
+        }
+        kept.insert(repeat.start, repeat);
+    }
+    kept.into_values().collect()
+}
+
+/// Length of the k-mers whose recurrence reveals a repeat's period.
+const SEED_K: usize = 8;
+
+/// A period that recent k-mers have been recurring at.
+#[derive(Clone, Copy)]
+struct PeriodRun {
+    /// First and last k-mer start recurring at this period
+    first: usize,
+    last: usize,
+}
+
+/// Find tandem repeats with units of up to `max_unit` bases, allowing copies
+/// to diverge.
+///
+/// A fast heuristic rather than an alignment: every 8-mer is compared with its
+/// previous occurrence, and stretches where 8-mers keep recurring at the same
+/// distance (the period) become candidate repeats. Each candidate is then
+/// compared base by base with the copy before it; the purity is the
+/// percentage of matching bases, and candidates with a divergence above
+/// `max_divergence`% or fewer than `min_copies` copies (indexed by unit size,
+/// units over 6 bp use the 6 bp value) are dropped. Overlapping repeats are
+/// resolved in favour of the longest, and repeats are emitted in coordinate order.
+pub fn find_tandem_repeats<F>(
+    seq: &[u8],
+    max_unit: usize,
+    min_copies: &[usize; MAX_UNIT],
+    max_divergence: f64,
+    mut emit: F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(TandemRepeat) -> Result<(), Box<dyn Error>>,
+{
+    let mut candidates: Vec<TandemRepeat> = Vec::new();
+    let mut close = |period: usize, run: PeriodRun| {
+        let (start, end) = (run.first - period, run.last + SEED_K);
+        let copies = (end - start) / period;
+        if copies < 2 || copies < min_copies[period.min(MAX_UNIT) - 1] {
+            return;
+        }
+        let compared = end - start - period;
+        let matched = (start + period..end).filter(|&i| seq[i].eq_ignore_ascii_case(&seq[i - period])).count();
+        let purity = matched as f64 / compared as f64 * 100.0;
+        if 100.0 - purity <= max_divergence {
+            let unit = seq[start..start + period].to_ascii_uppercase();
+            candidates.push(TandemRepeat { start, end, unit, purity });
+        }
+    };
+
+    // Most recent start of every 8-mer, indexed by its 2-bit encoding
+    let mut last_seen: Vec<usize> = vec![usize::MAX; 1 << (2 * SEED_K)];
+    let mut runs: Vec<Option<PeriodRun>> = vec![None; max_unit + 1];
+    let mask = (1usize << (2 * SEED_K)) - 1;
+    let (mut kmer, mut valid) = (0usize, 0);
+
+    for (i, &nuc) in seq.iter().enumerate() {
+        let Some(code) = nuc_code(nuc) else {
+            valid = 0;
+            continue;
+        };
+        kmer = ((kmer << 2) | code) & mask;
+        valid += 1;
+        if valid < SEED_K {
+            continue;
+        }
+
+        let pos = i + 1 - SEED_K;
+        let previous = std::mem::replace(&mut last_seen[kmer], pos);
+        if previous != usize::MAX && pos - previous <= max_unit {
+            let period = pos - previous;
+            // Substitutions break up to k consecutive k-mers
+            match runs[period] {
+                Some(run) if pos - run.last <= period + SEED_K => runs[period] = Some(PeriodRun { last: pos, ..run }),
+                Some(run) => {
+                    close(period, run);
+                    runs[period] = Some(PeriodRun { first: pos, last: pos });
+                }
+                None => runs[period] = Some(PeriodRun { first: pos, last: pos }),
+            }
+        }
+
+        // Close stale runs now and then, rather than checking every period at every base
+        if pos.is_multiple_of(256) {
+            for (period, slot) in runs.iter_mut().enumerate() {
+                if slot.is_some_and(|run| pos - run.last > period + SEED_K) {
+                    close(period, slot.take().unwrap());
+                }
+            }
+        }
+    }
+    for (period, slot) in runs.iter_mut().enumerate() {
+        if let Some(run) = slot.take() {
+            close(period, run);
+        }
+    }
+
+    for repeat in keep_longest(candidates) {
+        emit(repeat)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strs(seq: &[u8], min_copies: &[usize; MAX_UNIT], max_mismatches: usize) -> Vec<(usize, usize, String, f64)> {
+        let mut found = Vec::new();
+        find_strs(seq, min_copies, max_mismatches, |repeat| {
+            found.push((repeat.start, repeat.end, repeat.label(), repeat.purity));
+            Ok(())
+        })
+        .unwrap();
+        found
+    }
+
+    fn flanked(repeat: &str) -> Vec<u8> {
+        format!("NNNNNNN{}NNNNNNN", repeat).into_bytes()
+    }
+
+    #[test]
+    fn units_of_1_to_6_bases_are_found() {
+        for unit in ["A", "CA", "CAG", "GATA", "AAGTC", "ACCTGT"] {
+            let found = strs(&flanked(&unit.repeat(10)), &DEFAULT_MIN_COPIES, 0);
+            assert_eq!(found, [(7, 7 + 10 * unit.len(), format!("({})10", unit), 100.0)], "{}", unit);
+        }
+        assert!(strs(&flanked(&"ACCTGTA".repeat(10)), &DEFAULT_MIN_COPIES, 0).is_empty());
+    }
+
+    #[test]
+    fn a_period_2_repeat_is_not_also_a_period_4_or_6_repeat() {
+        assert_eq!(strs(&flanked(&"AT".repeat(12)), &[10, 2, 2, 2, 2, 2], 0), [(7, 31, "(AT)12".to_string(), 100.0)]);
+        assert_eq!(strs(b"ATATATATATAT", &[10, 2, 2, 2, 2, 2], 0), [(0, 12, "(AT)6".to_string(), 100.0)]);
+    }
+
+    #[test]
+    fn repeats_need_min_copies() {
+        assert!(strs(&flanked(&"CA".repeat(5)), &DEFAULT_MIN_COPIES, 0).is_empty());
+        assert_eq!(strs(&flanked(&"CA".repeat(6)), &DEFAULT_MIN_COPIES, 0), [(7, 19, "(CA)6".to_string(), 100.0)]);
+        assert!(strs(&flanked(&"A".repeat(9)), &DEFAULT_MIN_COPIES, 0).is_empty());
+    }
+
+    #[test]
+    fn partial_copies_are_counted() {
+        assert_eq!(strs(&flanked("CAGCAGCAGCAGCAGCA"), &DEFAULT_MIN_COPIES, 0), [(7, 24, "(CAG)5.7".to_string(), 100.0)]);
+    }
+
+    #[test]
+    fn substitutions_are_bridged_with_max_mismatches() {
+        let seq = flanked(&format!("{}CT{}", "CA".repeat(6), "CA".repeat(3)));
+        assert_eq!(strs(&seq, &DEFAULT_MIN_COPIES, 0), [(7, 20, "(CA)6.5".to_string(), 100.0)]);
+        let found = strs(&seq, &DEFAULT_MIN_COPIES, 1);
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].0, found[0].1, found[0].2.as_str()), (7, 27, "(CA)10"));
+        assert_eq!(found[0].3, 95.0);
+    }
+
+    #[test]
+    fn overlapping_repeats_keep_the_longest() {
+        let seq = flanked(&format!("{}{}", "A".repeat(12), "AG".repeat(8)));
+        let found = strs(&seq, &DEFAULT_MIN_COPIES, 0);
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].0, found[0].2.as_str()), (19, "(AG)8"));
+    }
+}
@@ -0,0 +1,77 @@
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+use memmap2::Mmap;
+
+use crate::faidx::{definition_line, with_suffix, FaiEntry, FaiIndex, Region};
+use crate::input::{IndexedSource, Records, Sequence};
+
+/// An uncompressed, `.fai`-indexed FASTA mapped into memory.
+///
+/// Sequence ranges that sit on a single line (e.g. unwrapped FASTA) are
+/// borrowed from the mapping without copying; ranges spanning line breaks
+/// are copied with the breaks removed.
+pub struct MappedFasta {
+    map: Arc<Mmap>,
+    pub fai: FaiIndex,
+}
+
+impl MappedFasta {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let fai_path = with_suffix(path, ".fai");
+        if !fai_path.exists() {
+            return Err(format!("--mmap needs a .fai index next to {}", path.display()).into());
+        }
+
+        let file = File::open(path)?;
+        // SAFETY: the mapping is read-only; like any mmap user we assume the
+        // file is not truncated or rewritten while polyscan runs.
+        let map = unsafe { Mmap::map(&file)? };
+        if map.starts_with(&[0x1f, 0x8b]) {
+            return Err("--mmap needs an uncompressed FASTA".into());
+        }
+
+        Ok(MappedFasta { map: Arc::new(map), fai: FaiIndex::from_path(fai_path)? })
+    }
+
+    /// Bases `[start, end)` of the contig described by `entry`.
+    pub fn fetch(&self, entry: &FaiEntry, start: u64, end: u64) -> Result<Sequence, Box<dyn Error>> {
+        let end = end.min(entry.length);
+        if start >= end {
+            return Ok(Sequence::default());
+        }
+
+        let first = entry.position_offset(start) as usize;
+        let last = entry.position_offset(end - 1) as usize + 1;
+        if last > self.map.len() {
+            return Err(format!("contig '{}' runs past the end of the FASTA; is the .fai stale?", entry.name).into());
+        }
+
+        if start / entry.line_bases == (end - 1) / entry.line_bases {
+            // One line: scan in place
+            return Ok(Sequence::Mapped { map: Arc::clone(&self.map), range: first..last });
+        }
+        let seq = self.map[first..last].iter().copied().filter(|&b| b != b'\n' && b != b'\r').collect::<Vec<u8>>();
+        Ok(seq.into())
+    }
+
+    /// The definition line (name and description, without the `>`) of the
+    /// contig at `position` in the index.
+    pub fn definition(&self, position: usize) -> Result<String, Box<dyn Error>> {
+        let range = self.fai.header_range(position);
+        let range = range.start.min(self.map.len() as u64) as usize..range.end.min(self.map.len() as u64) as usize;
+        Ok(definition_line(&self.map[range]))
+    }
+}
+
+/// Read every contig (or only `regions`, in file order) of a memory-mapped FASTA.
+pub fn mapped_records<P: AsRef<Path>>(path: P, regions: Option<&[Region]>) -> Result<Records, Box<dyn Error>> {
+    let fasta = IndexedSource::Mapped(MappedFasta::open(path)?);
+    match regions {
+        Some(regions) => fasta.region_records(regions),
+        None => Ok(fasta.contig_records()),
+    }
+}
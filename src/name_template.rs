@@ -0,0 +1,115 @@
+use std::error::Error;
+
+/// A parsed `--name-template`, e.g. `{base}{strand}_{percent}_{length}`,
+/// filled in for each record to give its name column.
+///
+/// Placeholders are `{base}` (the target as given, e.g. `A` or `GC`),
+/// `{strand}` (`+`, `-` or `.`), `{percent}` (the score column: the
+/// percentage rounded up), `{length}`, `{chrom}`, `{start}` and `{end}`.
+/// `{{` and `}}` stand for literal braces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Base,
+    Strand,
+    Percent,
+    Length,
+    Chrom,
+    Start,
+    End,
+}
+
+/// What a record's name is made of; `start` and `end` as written (so
+/// 1-based with `--coordinates 1-based`), `length` in bases.
+#[derive(Debug, Clone, Copy)]
+pub struct NameFields<'a> {
+    pub base: &'a str,
+    pub strand: &'a str,
+    pub score: f64,
+    pub length: u64,
+    pub chrom: &'a str,
+    pub start: u64,
+    pub end: u64,
+}
+
+impl NameTemplate {
+    pub fn parse(text: &str) -> Result<NameTemplate, Box<dyn Error>> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("unclosed placeholder '{{{}'", name).into()),
+                        }
+                    }
+                    let part = match name.as_str() {
+                        "base" => Part::Base,
+                        "strand" => Part::Strand,
+                        "percent" => Part::Percent,
+                        "length" => Part::Length,
+                        "chrom" => Part::Chrom,
+                        "start" => Part::Start,
+                        "end" => Part::End,
+                        _ => {
+                            return Err(format!(
+                                "unknown placeholder '{{{}}}' (expected base, strand, percent, length, chrom, start or end)",
+                                name
+                            )
+                            .into())
+                        }
+                    };
+                    if !literal.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut literal)));
+                    }
+                    parts.push(part);
+                }
+                '}' => return Err("unmatched '}' (write '}}' for a brace)".into()),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Text(literal));
+        }
+        if parts.is_empty() {
+            return Err("the template is empty".into());
+        }
+        Ok(NameTemplate { parts })
+    }
+
+    /// The name of a record.
+    pub fn fill(&self, fields: &NameFields) -> String {
+        let mut name = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => name.push_str(text),
+                Part::Base => name.push_str(fields.base),
+                Part::Strand => name.push_str(fields.strand),
+                Part::Percent => name.push_str(&(fields.score.ceil() as u64).to_string()),
+                Part::Length => name.push_str(&fields.length.to_string()),
+                Part::Chrom => name.push_str(fields.chrom),
+                Part::Start => name.push_str(&fields.start.to_string()),
+                Part::End => name.push_str(&fields.end.to_string()),
+            }
+        }
+        name
+    }
+}
@@ -0,0 +1,103 @@
+use std::ops::Range;
+
+use crate::bases::nuc_code;
+
+const BASES: [u8; 4] = *b"ACGT";
+
+// The four bases of every packed byte, lowest bits first
+const UNPACKED: [[u8; 4]; 256] = {
+    let mut table = [[0; 4]; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut i = 0;
+        while i < 4 {
+            table[byte][i] = BASES[(byte >> (2 * i)) & 3];
+            i += 1;
+        }
+        byte += 1;
+    }
+    table
+};
+
+/// A sequence held in memory at 2 bits per base, for contigs that must wait
+/// in memory before they are scanned.
+///
+/// Uppercase A/C/G/T are packed four to a byte. Lowercase a/c/g/t are packed
+/// too, with the soft-masked runs kept apart, and any other byte (N, IUPAC
+/// codes, U) is kept in a list of runs, so a gap of Ns costs one entry.
+/// [`PackedSequence::unpack`] gives back the exact bytes.
+#[derive(Debug, Clone, Default)]
+pub struct PackedSequence {
+    len: usize,
+    codes: Vec<u8>,
+    /// Sorted runs of lowercase a/c/g/t
+    lowercase: Vec<Range<usize>>,
+    /// Sorted runs of one other byte, which the codes hold as A
+    others: Vec<(Range<usize>, u8)>,
+}
+
+impl PackedSequence {
+    pub fn from_seq(seq: &[u8]) -> Self {
+        let mut codes = vec![0u8; seq.len().div_ceil(4)];
+        let mut lowercase: Vec<Range<usize>> = Vec::new();
+        let mut others: Vec<(Range<usize>, u8)> = Vec::new();
+        for (i, &nuc) in seq.iter().enumerate() {
+            match nuc_code(nuc).filter(|_| !matches!(nuc, b'U' | b'u')) {
+                Some(code) => {
+                    codes[i / 4] |= (code as u8) << (2 * (i % 4));
+                    if nuc.is_ascii_lowercase() {
+                        match lowercase.last_mut() {
+                            Some(run) if run.end == i => run.end += 1,
+                            _ => lowercase.push(i..i + 1),
+                        }
+                    }
+                }
+                None => match others.last_mut() {
+                    Some((run, byte)) if run.end == i && *byte == nuc => run.end += 1,
+                    _ => others.push((i..i + 1, nuc)),
+                },
+            }
+        }
+        PackedSequence { len: seq.len(), codes, lowercase, others }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Bytes of memory held (roughly a quarter of the sequence length).
+    pub fn heap_bytes(&self) -> usize {
+        self.codes.capacity()
+            + self.lowercase.capacity() * std::mem::size_of::<Range<usize>>()
+            + self.others.capacity() * std::mem::size_of::<(Range<usize>, u8)>()
+    }
+
+    /// The bases in `range`, as they were before packing.
+    pub fn unpack(&self, range: Range<usize>) -> Vec<u8> {
+        let range = range.start.min(self.len)..range.end.min(self.len);
+        if range.is_empty() {
+            return Vec::new();
+        }
+        // Whole packed bytes, then trimmed to the range
+        let first = range.start / 4 * 4;
+        let mut seq: Vec<u8> = self.codes[range.start / 4..range.end.div_ceil(4)].iter().flat_map(|&byte| UNPACKED[byte as usize]).collect();
+        seq.truncate(range.end - first);
+        seq.drain(..range.start - first);
+
+        let from = self.lowercase.partition_point(|run| run.end <= range.start);
+        for run in self.lowercase[from..].iter().take_while(|run| run.start < range.end) {
+            let run = run.start.max(range.start)..run.end.min(range.end);
+            seq[run.start - range.start..run.end - range.start].make_ascii_lowercase();
+        }
+        let from = self.others.partition_point(|(run, _)| run.end <= range.start);
+        for (run, byte) in self.others[from..].iter().take_while(|(run, _)| run.start < range.end) {
+            let run = run.start.max(range.start)..run.end.min(range.end);
+            seq[run.start - range.start..run.end - range.start].fill(*byte);
+        }
+        seq
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::error::Error;
+
+/// An inverted repeat: two arms that are reverse complements of each other,
+/// separated by a loop (a perfect palindrome such as `GAATTC` has no loop).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvertedRepeat {
+    /// Start of the left arm
+    pub start: usize,
+    pub arm: usize,
+    pub loop_len: usize,
+    /// Arm positions that are not complementary
+    pub mismatches: usize,
+}
+
+impl InvertedRepeat {
+    pub fn left_arm(&self) -> (usize, usize) {
+        (self.start, self.start + self.arm)
+    }
+
+    pub fn right_arm(&self) -> (usize, usize) {
+        let start = self.start + self.arm + self.loop_len;
+        (start, start + self.arm)
+    }
+
+    /// Percentage of arm positions that pair up.
+    pub fn identity(&self) -> f64 {
+        (self.arm - self.mismatches) as f64 / self.arm as f64 * 100.0
+    }
+}
+
+fn pairs(a: u8, b: u8) -> bool {
+    matches!(
+        (a.to_ascii_uppercase(), b.to_ascii_uppercase()),
+        (b'A', b'T') | (b'T', b'A') | (b'C', b'G') | (b'G', b'C')
+    )
+}
+
+/// Find inverted repeats with arms of at least `min_arm` bases and loops of
+/// up to `max_loop` bases, in order of their left arm.
+///
+/// Arms are grown outwards from the loop, tolerating up to `max_mismatches`
+/// unpaired positions (never at the arms' ends). Each inverted repeat is
+/// reported once, with the longest arms (and so the shortest loop) it can have,
+/// and not at all if both its arms lie within the arms of another: stretches
+/// of a stem pairing out of register are part of that stem, not hairpins of
+/// their own. Hairpins within the loop of another are still reported.
+pub fn find_inverted_repeats<F>(
+    seq: &[u8],
+    min_arm: usize,
+    max_loop: usize,
+    max_mismatches: usize,
+    mut emit: F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(InvertedRepeat) -> Result<(), Box<dyn Error>>,
+{
+    let min_arm = min_arm.max(1);
+    let mut found = Vec::new();
+    for center in 1..seq.len() {
+        for loop_len in 0..=max_loop {
+            let right = center + loop_len;
+            if right >= seq.len() {
+                break;
+            }
+            if !pairs(seq[center - 1], seq[right]) {
+                continue;
+            }
+            if loop_len >= 2 && pairs(seq[center], seq[right - 1]) {
+                continue;
+            }
+
+            // Grow the arms outwards; a mismatch is only kept once a pair follows it
+            let (mut arm, mut mismatches, mut pending) = (1, 0, 0);
+            let mut offset = 1;
+            while offset < center && right + offset < seq.len() {
+                if pairs(seq[center - 1 - offset], seq[right + offset]) {
+                    arm = offset + 1;
+                    mismatches += pending;
+                    pending = 0;
+                } else if mismatches + pending < max_mismatches {
+                    pending += 1;
+                } else {
+                    break;
+                }
+                offset += 1;
+            }
+
+            if arm >= min_arm {
+                found.push(InvertedRepeat { start: center - arm, arm, loop_len, mismatches });
+            }
+        }
+    }
+
+    // Mismatches let one stem be split into arms and loop in several ways;
+    // keep the longest arms for each outer span
+    found.sort_by_key(|ir| (ir.start, ir.right_arm().1, std::cmp::Reverse(ir.arm), ir.mismatches));
+    found.dedup_by_key(|ir| (ir.start, ir.right_arm().1));
+
+    // The arms of an inverted repeat within another's end their left arm
+    // between the end of its own and the start of its right arm, so at most
+    // a loop apart
+    let mut by_left_end: HashMap<usize, Vec<&InvertedRepeat>> = HashMap::new();
+    for ir in &found {
+        by_left_end.entry(ir.left_arm().1).or_default().push(ir);
+    }
+    let within = |ir: &InvertedRepeat, outer: &InvertedRepeat| {
+        outer != ir && outer.start <= ir.start && outer.right_arm().0 <= ir.right_arm().0 && outer.right_arm().1 >= ir.right_arm().1
+    };
+    let nested: Vec<bool> = found
+        .iter()
+        .map(|ir| (ir.left_arm().1..=ir.right_arm().0).filter_map(|end| by_left_end.get(&end)).flatten().any(|outer| within(ir, outer)))
+        .collect();
+    for (ir, nested) in found.into_iter().zip(nested) {
+        if !nested {
+            emit(ir)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inverted_repeats(seq: &[u8], min_arm: usize, max_loop: usize, max_mismatches: usize) -> Vec<InvertedRepeat> {
+        let mut found = Vec::new();
+        find_inverted_repeats(seq, min_arm, max_loop, max_mismatches, |ir| {
+            found.push(ir);
+            Ok(())
+        })
+        .unwrap();
+        found
+    }
+
+    #[test]
+    fn stretches_of_a_stem_are_not_hairpins_of_their_own() {
+        // With a mismatch allowed, bases 0-6 and 8-14 also pair with parts of
+        // the right arm out of register
+        let seq = b"GACAGTTAGACCGTACGTTTGTACGGTCTAACTGTC";
+        assert_eq!(inverted_repeats(seq, 6, 20, 1), [InvertedRepeat { start: 0, arm: 16, loop_len: 4, mismatches: 0 }]);
+    }
+
+    #[test]
+    fn hairpins_in_a_loop_are_kept() {
+        // An 8-base stem around an 18-base loop holding a 6-base stem of its own
+        let seq = b"TTTTTGCATCCAGACGTTGCTTTTGCAACGCCTGGATGCTTTTT";
+        let found = inverted_repeats(seq, 6, 20, 0);
+        assert_eq!(found.iter().map(|ir| (ir.start, ir.arm, ir.loop_len)).collect::<Vec<_>>(), [(5, 8, 18), (14, 6, 4)]);
+    }
+}
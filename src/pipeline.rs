@@ -0,0 +1,2361 @@
+//! The record pipeline behind `polyscan scan`: a [`ScanConfig`] describes
+//! everything a scan does on top of the target windows of a [`Scanner`]
+//! (other modes and statistics, filters, clusters, scores, annotations and
+//! the side outputs), and [`scan_records`] runs it over a stream of records,
+//! writing BED (or JSON Lines) as it goes. Front ends parse their options
+//! into a `ScanConfig`, check it with [`ScanConfig::validate`] and hand the
+//! records over, so the command line and `polyscan serve` scan alike.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use log::{debug, info, warn};
+use rayon::prelude::*;
+
+use crate::background::{fdr_cutoff, log10_binomial_tail, z_score, Background};
+use crate::bed::{BedWriter, IntervalSet, JsonField};
+use crate::checkpoint::Checkpoint;
+use crate::chunked::piece_ranges;
+use crate::coverage::CoverageIndex;
+use crate::dinuc::{dinucleotide_windows, DinucleotideDensity};
+use crate::dust::sdust;
+use crate::error::PolyscanError;
+use crate::expr::Expr;
+use crate::faidx::Region;
+use crate::flags::{window_flags, GapIndex, HitFlags};
+use crate::g4::{find_g4_motifs, g4hunter_windows};
+use crate::gff::{Annotation, GeneBases, GeneContext, Intron};
+use crate::input::{IndexedSource, Records, SeqRecord, Sequence};
+use crate::kmer::{iupac_mask, motif_sites, reverse_complement, reverse_complement_motif, scan_kmers, KmerDensity};
+use crate::merge::{Clusters, Reduction};
+use crate::microsat::{find_strs, find_tandem_repeats, DEFAULT_MIN_COPIES, MAX_UNIT};
+use crate::name_template::{NameFields, NameTemplate};
+use crate::packed::PackedSequence;
+use crate::palindrome::find_inverted_repeats;
+use crate::pwm::{scan_pwms, Pwm, PwmScorer};
+use crate::report::ReportCollector;
+use crate::run_stats;
+use crate::runs::{find_runs, longest_run};
+use crate::adaptive::{adaptive_scan, hysteresis_scan};
+use crate::scan::{hide_soft_masked, hide_unmasked, has_uracil, rna_to_dna, scan_queries, scan_windows, Alphabet, BaseClass, BaseFraction, Composition, Entropy, ScanHit, Query, Steps, Strand, WindowScorer};
+use crate::scanner::{collapse_strands, MaskedPolicy, NPolicy, ScanParams, Scanner, StrandChoice};
+use crate::telomere::{end_position, find_telomeric_blocks};
+use crate::timings;
+use crate::top::TopK;
+use crate::trim::{keep_interval, TrimWriter};
+use crate::weighted::weighted_windows;
+use crate::zdna::alternation_windows;
+
+/// Detection modes for `--mode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum Mode {
+    /// Sliding windows above a threshold
+    Windows,
+    /// Maximal exact runs of the target
+    Runs,
+    /// Short tandem repeats (microsatellites) of 1-6 bp units
+    Str,
+    /// Approximate tandem repeats with longer units
+    Tandem,
+    /// Inverted repeats (palindromes, hairpins)
+    Palindrome,
+    /// G-quadruplex motifs
+    G4,
+    /// GC skew and cumulative skew per tile, as bedGraph
+    Skew,
+    /// Low-complexity intervals (SDUST)
+    Dust,
+    /// Blocks of telomeric repeats
+    Telomere,
+    /// Windows of alternating purines and pyrimidines (Z-DNA)
+    Zdna,
+    /// Polypyrimidine tracts at the 3' end of introns
+    Ppt,
+    /// Restriction enzyme sites
+    Sites,
+    /// Assembly gaps (exact runs of N)
+    Gaps,
+    /// CpG/CHG/CHH context densities
+    Methylation,
+    /// Poly(A) tail length at the ends of each read
+    Polya,
+}
+
+/// Window statistics for `--stat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum Stat {
+    /// Fraction of the target base or class
+    Fraction,
+    /// Shannon entropy of the window's bases
+    Entropy,
+    /// Density of a dinucleotide
+    Dinuc,
+}
+
+/// Score column of records, for `--score-mode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum ScoreMode {
+    /// Percentage of target bases
+    Percentage,
+    /// Standard deviations above the background composition
+    Zscore,
+}
+
+/// Format of the `--pvalue` column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum PvalueFormat {
+    /// The p-value itself
+    Raw,
+    /// -log10 of the p-value
+    NegLog10,
+}
+
+/// Background composition for `--score-mode zscore` and `--pvalue`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum ScoreBackground {
+    /// The whole contig
+    Contig,
+    /// The megabase around the window
+    Local,
+}
+
+/// What `--filter-overlap` does with overlapping records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum OverlapMode {
+    /// Drop them
+    Remove,
+    /// Keep only them
+    Keep,
+}
+
+/// Bases scanned by case, for soft-masked references.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum CaseMode {
+    /// Every base, whatever its case
+    Insensitive,
+    /// Uppercase (unmasked) bases only
+    UpperOnly,
+    /// Lowercase (soft-masked) bases only
+    LowerOnly,
+}
+
+/// What to do with a contig shorter than the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum ShortContigPolicy {
+    /// Leave it out, as it has no window
+    Skip,
+    /// Evaluate the whole contig as a single window
+    Whole,
+    /// Leave it out, with a warning
+    Warn,
+}
+
+/// Settings shared by every contig in a scan.
+pub struct ScanConfig {
+    /// Output names of contigs, by their names in the input, for the
+    /// contigs workers fetch from an index themselves
+    pub chrom_aliases: HashMap<String, String>,
+    /// User-chosen bases or classes, all scanned in the same pass (the complement
+    /// is scanned on the minus strand unless the class is its own complement, e.g. GC)
+    pub targets: Vec<BaseClass>,
+    /// When non-empty, score k-mer density instead of the base targets
+    pub kmers: Vec<String>,
+    /// Likewise for degenerate (IUPAC) motifs, allowing `max_mismatches` per occurrence
+    pub motifs: Vec<String>,
+    pub max_mismatches: usize,
+    /// When non-empty, report windows by their best site for these matrices
+    pub pwms: Vec<Pwm>,
+    pub pwm_cutoff: f64,
+    /// Fixed windows, or exact runs of at least `min_run` bases
+    pub mode: Mode,
+    pub min_run: usize,
+    /// Minimum copy number per unit size (1-6 bp) for `--mode str`
+    pub min_copies: [usize; MAX_UNIT],
+    /// Longest unit and copy divergence (%) for `--mode tandem`
+    pub max_unit: usize,
+    pub max_divergence: f64,
+    /// Shortest arm and longest loop for `--mode palindrome`
+    pub min_arm: usize,
+    pub max_loop: usize,
+    /// Score `--mode g4` windows by G4Hunter instead of matching the motif
+    pub g4hunter: Option<f64>,
+    /// SDUST window and score threshold for `--mode dust`
+    pub dust_window: usize,
+    pub dust_threshold: usize,
+    /// Window statistic, its threshold for `--stat entropy` and its
+    /// dinucleotide for `--stat dinuc`
+    pub stat: Stat,
+    pub max_entropy: f64,
+    pub pair: Option<[u8; 2]>,
+    /// `--expr` predicate, replacing the target fraction
+    pub expr: Option<Expr>,
+    /// Repeat units for `--mode telomere`
+    pub telomere_motifs: Vec<String>,
+    /// Introns whose last `ppt_region` bases `--mode ppt` scans
+    pub introns: Vec<Intron>,
+    pub ppt_region: u64,
+    /// Named recognition sequences for `--mode sites`
+    pub sites: Vec<(String, String)>,
+    /// Window size, and every size of a multi-scale scan (just `window` otherwise)
+    pub window: usize,
+    pub windows: Vec<usize>,
+    pub percentage: f64,
+    /// Proteins are scanned on a single, unstranded pass
+    pub alphabet: Alphabet,
+    /// Append a caveat-flags column
+    pub flags: bool,
+    /// Report maximal intervals grown from seed windows instead of fixed windows
+    pub adaptive: bool,
+    /// Seed and extend percentages for hysteresis thresholding
+    pub hysteresis: Option<(f64, f64)>,
+    /// Count of target bases required instead of `percentage`
+    pub min_count: Option<usize>,
+    /// Report only sliding windows starting at multiples of this contig offset
+    pub step: usize,
+    /// Wrap windows around the origin of every contig (`--circular`), and
+    /// whether the scan is one of fixed windows that can wrap at all
+    pub circular: bool,
+    pub sliding_windows: bool,
+    /// Hide soft-masked bases, and whether they still count against the target
+    pub ignore_masked: Option<MaskedPolicy>,
+    /// Which bases are hidden before scanning: lowercase, uppercase or none
+    pub case_mode: CaseMode,
+    /// How Ns affect the target fraction
+    pub n_policy: NPolicy,
+    /// Drop windows with more than this fraction of Ns
+    pub max_n_frac: Option<f64>,
+    /// Count IUPAC ambiguity codes in part toward the target
+    pub fractional_iupac: bool,
+    /// Contigs shorter than the window
+    pub short_contig_policy: ShortContigPolicy,
+    /// Strands to report
+    pub strand: StrandChoice,
+    /// Name collapsed records by the target and its complement, e.g. A/T
+    pub combine_complements: bool,
+    /// Shrink windows to their first and last target base
+    pub trim_to_content: bool,
+    /// Add a column with the longest target run in each record
+    pub max_run: bool,
+    /// Drop windows without a run of this many target bases (0 keeps them all)
+    pub min_consecutive: usize,
+    /// Reduce clusters of overlapping windows to their best one, or merge them
+    pub clusters: Option<Reduction>,
+    /// Bases allowed between merged regions
+    pub max_gap: usize,
+    /// Merge both strands of a target together
+    pub merge_any_strand: bool,
+    /// Drop merged regions shorter than this
+    pub min_length: usize,
+    /// Bases to extend every record by on its left and right sides
+    pub slop: (usize, usize),
+    /// Weight of each position in the window, when not uniform
+    pub window_weights: Option<Vec<f64>>,
+    /// Score records by their percentage of target bases, or by their z-score against `score_background`
+    pub score_mode: ScoreMode,
+    pub score_background: ScoreBackground,
+    /// Decimals of scores, instead of percentages rounded up and z-scores to two decimals
+    pub score_precision: Option<usize>,
+    /// Add a column with the binomial p-value of each record against `score_background`
+    pub pvalue: Option<PvalueFormat>,
+    /// Only report records significant at this false discovery rate, over every window evaluated
+    pub fdr: Option<f64>,
+    /// Report the parts of contigs without hits instead of the hits
+    pub complement: bool,
+    /// Report the union of the hits, per contig, instead of the hits
+    pub unique_bases: bool,
+    /// Genes and exons to annotate records with
+    pub annotation: Option<Annotation>,
+    /// Genes to find the closest of for every record
+    pub closest_genes: Option<Annotation>,
+    /// Write hit coverage per contig and genome-wide to this file
+    pub coverage_summary: Option<PathBuf>,
+    /// Write the support depth of the union of the records to this file
+    pub occupancy: Option<PathBuf>,
+    /// Write every contig and what became of it to this file
+    pub contig_report: Option<PathBuf>,
+    /// Write the part of every read to keep, once terminal tails are trimmed, as BED to this file
+    pub trim_bed: Option<PathBuf>,
+    /// Write the trimmed bases and keep-coordinates of every read to this file
+    pub trim_tsv: Option<PathBuf>,
+    /// Write the base composition of every record to this file
+    pub composition_matrix: Option<PathBuf>,
+    /// Write a per-gene summary of the records to this file (needs `annotation`)
+    pub gene_report: Option<PathBuf>,
+    /// Keep only this many of the best-scoring records
+    pub top: Option<usize>,
+    /// Apply `top` to each contig rather than the whole input
+    pub top_per_contig: bool,
+    /// Pair plus- and minus-strand records this far apart into BEDPE records
+    pub pair_strands: Option<u64>,
+    /// `--percentage-sweep` thresholds, ascending; `percentage` is the first
+    pub sweep: Vec<f64>,
+    /// Report read hits in reference coordinates (BAM/CRAM input)
+    pub project_to_reference: bool,
+    /// Record each completed contig here (the output is flushed first)
+    pub checkpoint: Option<Mutex<Checkpoint>>,
+    /// Drop hits overlapping these intervals
+    pub exclude: Option<IntervalSet>,
+    /// Intervals records are kept or dropped by, and the fraction of a record they must cover
+    pub overlap_filter: Option<(IntervalSet, OverlapMode, f64)>,
+    /// Write records as JSON Lines instead of BED
+    pub json_lines: bool,
+    /// Write 1-based closed coordinates instead of 0-based half-open ones
+    pub one_based: bool,
+    /// Names of the records, instead of their target
+    pub name_template: Option<NameTemplate>,
+}
+
+impl ScanConfig {
+    /// The library scan the target windows of this one start from; the other
+    /// options are applied to its hits by [`scan_records`].
+    pub fn scan_params(&self) -> ScanParams {
+        ScanParams {
+            targets: self.targets.clone(),
+            window: self.window,
+            percentage: self.percentage,
+            min_count: self.min_count,
+            alphabet: self.alphabet,
+            strand: self.strand,
+            n_policy: self.n_policy,
+            ignore_masked: self.ignore_masked,
+            step: self.step,
+            fractional_iupac: self.fractional_iupac,
+        }
+    }
+
+    /// A [`PolyscanError::Parameter`] naming the options (as they are called
+    /// on the command line) that don't go together, or the first value that
+    /// doesn't describe a scan. Options of the front end itself (inputs,
+    /// regions, threads, table outputs) are for it to check.
+    pub fn validate(&self) -> Result<(), PolyscanError> {
+        let w = self.window;
+        let conflict = |options: &[(bool, &str)], option: &str| match options.iter().find(|(set, _)| *set) {
+            Some((_, other)) => Err(PolyscanError::Parameter(format!("{} cannot be combined with {}.", other, option))),
+            None => Ok(()),
+        };
+        let parameter = |message: &str| Err(PolyscanError::Parameter(message.to_string()));
+        if self.windows.is_empty() || self.windows.contains(&0) || self.step == 0 {
+            return parameter("the window size and step must be at least 1.");
+        }
+
+        // Each target is one of {A,C,G,T,U,N}, or several counted together as
+        // a class (e.g. GC). Proteins take any residue letters.
+        let valid = self.targets.iter().all(|target| {
+            !target.letters().is_empty()
+                && target.letters().iter().all(|&c| match self.alphabet {
+                    Alphabet::Dna => matches!(c, b'A' | b'C' | b'G' | b'T' | b'U' | b'N'),
+                    Alphabet::Protein => c.is_ascii_alphabetic(),
+                })
+        });
+        if self.targets.is_empty() || !valid {
+            return parameter(match self.alphabet {
+                Alphabet::Dna => "--nucleotide must be one of A, C, G, T, U, or N, or a class of them such as GC or AT.",
+                Alphabet::Protein => "--nucleotide must be residue letters with --alphabet protein.",
+            });
+        }
+        let n_target = self.targets.iter().any(|target| target.contains(b'N'));
+
+        // K-mers, motifs and matrices replace the targets
+        let valid_kmer = |kmer: &String| {
+            !kmer.is_empty()
+                && kmer.chars().all(|c| match self.alphabet {
+                    Alphabet::Dna => matches!(c, 'A' | 'C' | 'G' | 'T' | 'U'),
+                    Alphabet::Protein => c.is_ascii_alphabetic(),
+                })
+        };
+        if !self.kmers.iter().all(valid_kmer) {
+            return parameter("k-mers must be non-empty and made of A, C, G, T or U (any letters with --alphabet protein).");
+        }
+        if self.kmers.iter().any(|kmer| kmer.len() > w) {
+            return parameter("k-mers cannot be longer than --window-size.");
+        }
+        if self.motifs.iter().any(|motif| motif.is_empty() || !motif.bytes().all(|code| iupac_mask(code) != 0)) {
+            return parameter("--motif must be made of IUPAC nucleotide codes (A, C, G, T, U, R, Y, S, W, K, M, B, D, H, V, N).");
+        }
+        if !self.motifs.is_empty() && self.alphabet == Alphabet::Protein {
+            return parameter("--motif cannot be combined with --alphabet protein.");
+        }
+        if self.motifs.iter().any(|motif| motif.len() > w) {
+            return parameter("motifs cannot be longer than --window-size.");
+        }
+        if self.motifs.iter().any(|motif| self.max_mismatches >= motif.len()) {
+            return parameter("--max-mismatches must be smaller than the motif length.");
+        }
+        if !self.pwms.is_empty() && self.alphabet == Alphabet::Protein {
+            return parameter("--pwm cannot be combined with --alphabet protein.");
+        }
+        if !(0.0..=100.0).contains(&self.pwm_cutoff) {
+            return parameter("--pwm-cutoff must be between 0 and 100.");
+        }
+        if self.pwms.iter().any(|pwm| pwm.len() > w) {
+            return parameter("matrices cannot be longer than --window-size.");
+        }
+        let (kmers, motifs, pwms) = (!self.kmers.is_empty(), !self.motifs.is_empty(), !self.pwms.is_empty());
+        let windows_mode = self.mode == Mode::Windows;
+        let (fraction, expr, seeded) = (self.stat == Stat::Fraction, self.expr.is_some(), self.hysteresis.is_some());
+
+        // Entropy, dinucleotides and expressions replace the target fraction
+        if !fraction || expr {
+            let stat = if expr { "--expr".to_string() } else { format!("--stat {:?}", self.stat).to_lowercase() };
+            let fraction_options = [
+                (!windows_mode, "--mode"),
+                (expr && !fraction, "--stat"),
+                (self.adaptive, "--adaptive"),
+                (kmers, "--kmer"),
+                (motifs, "--motif"),
+                (pwms, "--pwm"),
+            ];
+            conflict(&fraction_options, &stat)?;
+        }
+        if (self.stat == Stat::Dinuc) != self.pair.is_some() {
+            return parameter("--stat dinuc and --pair go together.");
+        }
+        if self.stat == Stat::Dinuc && self.alphabet == Alphabet::Protein {
+            return parameter("--stat dinuc is for nucleotide sequences.");
+        }
+        if self.max_entropy < 0.0 {
+            return parameter("--max-entropy cannot be negative.");
+        }
+
+        // Other modes replace the sliding-window scan altogether
+        if !windows_mode {
+            let mode = format!("--mode {:?}", self.mode).to_lowercase();
+            conflict(&[(self.adaptive, "--adaptive"), (kmers, "--kmer"), (motifs, "--motif"), (pwms, "--pwm")], &mode)?;
+        }
+        if self.max_mismatches > 0 && !motifs && !matches!(self.mode, Mode::Str | Mode::Palindrome) {
+            return parameter("--max-mismatches needs --motif, --mode str or --mode palindrome.");
+        }
+        if !(0.0..=100.0).contains(&self.max_divergence) {
+            return parameter("--max-divergence must be between 0 and 100.");
+        }
+        if self.g4hunter.is_some_and(|threshold| threshold <= 0.0 || threshold > 4.0) || (self.g4hunter.is_some() && self.mode != Mode::G4) {
+            return parameter("--g4hunter needs --mode g4 and a threshold above 0 and at most 4.");
+        }
+        if !matches!(self.mode, Mode::Windows | Mode::Runs) && self.alphabet == Alphabet::Protein {
+            return Err(PolyscanError::Parameter(format!("--mode {} is not supported with --alphabet protein.", format!("{:?}", self.mode).to_lowercase())));
+        }
+        if self.telomere_motifs.iter().any(|m| m.is_empty() || !m.chars().all(|c| matches!(c, 'A' | 'C' | 'G' | 'T'))) {
+            return parameter("--telomere-motif must be made of A, C, G and T.");
+        }
+        if (self.mode == Mode::Sites) == self.sites.is_empty() {
+            return parameter("--mode sites and --site go together.");
+        }
+        if self.sites.iter().any(|(_, seq)| seq.is_empty() || !seq.bytes().all(|code| iupac_mask(code) != 0)) {
+            return parameter("--site sequences must be made of IUPAC nucleotide codes.");
+        }
+        if self.alphabet == Alphabet::Protein && self.flags {
+            return parameter("--flags is not supported with --alphabet protein.");
+        }
+
+        // A sweep scans at its lowest threshold and tags hits with the others
+        if !self.sweep.is_empty() {
+            let sweep_options = [
+                (!windows_mode, "--mode"),
+                (!fraction, "--stat"),
+                (expr, "--expr"),
+                (self.adaptive, "--adaptive"),
+                (seeded, "--seed-percentage"),
+                (self.min_count.is_some(), "--min-count"),
+                (pwms, "--pwm"),
+            ];
+            conflict(&sweep_options, "--percentage-sweep")?;
+        }
+        if self.window_weights.is_some() {
+            // Weights are counted along the target's strand, so each strand must be a scorer of its own
+            let weights_options = [
+                (!windows_mode, "--mode"),
+                (!fraction, "--stat"),
+                (expr, "--expr"),
+                (self.adaptive, "--adaptive"),
+                (seeded, "--seed-percentage"),
+                (self.min_count.is_some(), "--min-count"),
+                (!self.sweep.is_empty(), "--percentage-sweep"),
+                (self.windows.len() > 1, "several window sizes"),
+                (self.strand == StrandChoice::Collapse, "--strand collapse"),
+                (self.fractional_iupac, "--fractional-iupac"),
+                (self.ignore_masked.is_some(), "--ignore-masked"),
+                (self.n_policy != NPolicy::CountAgainst, "--n-policy"),
+                (self.score_mode == ScoreMode::Zscore, "--score-mode zscore"),
+                (self.pvalue.is_some(), "--pvalue"),
+                (self.fdr.is_some(), "--fdr"),
+                (kmers, "--kmer"),
+                (motifs, "--motif"),
+                (pwms, "--pwm"),
+            ];
+            conflict(&weights_options, "--window-weights")?;
+        }
+
+        if self.complement || self.unique_bases {
+            // Neither the uncovered intervals nor the union of the records are hits
+            let complement_options = [
+                (self.project_to_reference, "--project-to-reference"),
+                (self.top.is_some(), "--top"),
+                (self.flags, "--flags"),
+                (self.composition_matrix.is_some(), "--composition-matrix"),
+                (self.gene_report.is_some(), "--gene-report"),
+            ];
+            conflict(&complement_options, if self.complement { "--complement" } else { "--unique-bases" })?;
+        }
+        if self.composition_matrix.is_some() && self.top.is_some() {
+            return parameter("--composition-matrix cannot be combined with --top.");
+        }
+        if self.gene_report.is_some() && self.top.is_some() {
+            return parameter("--gene-report cannot be combined with --top.");
+        }
+        if self.coverage_summary.is_some() || self.contig_report.is_some() {
+            // Coverage is a fraction of whole contigs, and reads aren't contigs a report could account for
+            let output = if self.coverage_summary.is_some() { "--coverage-summary" } else { "--contig-report" };
+            conflict(&[(self.project_to_reference, "--project-to-reference")], output)?;
+        }
+        if self.occupancy.is_some() {
+            // Records are tallied by the contig they were found on, as they are found
+            let occupancy_options = [
+                (self.project_to_reference, "--project-to-reference"),
+                (self.top.is_some(), "--top"),
+                (self.checkpoint.is_some(), "--checkpoint"),
+            ];
+            conflict(&occupancy_options, "--occupancy")?;
+        }
+        if self.trim_bed.is_some() || self.trim_tsv.is_some() {
+            // Tails are trimmed off whole reads, from the records found on them
+            let trimming = if self.trim_bed.is_some() { "--trim-bed" } else { "--trim-tsv" };
+            conflict(&[(self.circular, "--circular"), (self.project_to_reference, "--project-to-reference")], trimming)?;
+        }
+        if self.score_mode == ScoreMode::Zscore || self.pvalue.is_some() || self.fdr.is_some() {
+            let background_option = match self.score_mode {
+                ScoreMode::Zscore => "--score-mode zscore",
+                _ if self.pvalue.is_some() => "--pvalue",
+                _ => "--fdr",
+            };
+            // Z-scores and p-values are of a target's fraction of the window, against its fraction of the contig
+            let background_options = [
+                (!windows_mode, "--mode"),
+                (!fraction, "--stat"),
+                (expr, "--expr"),
+                (self.alphabet == Alphabet::Protein, "--alphabet protein"),
+                (self.strand == StrandChoice::Collapse, "--strand collapse"),
+                (self.fractional_iupac, "--fractional-iupac"),
+                (self.ignore_masked.is_some(), "--ignore-masked"),
+                (matches!(self.clusters, Some(Reduction::MergeMax | Reduction::MergeMean)), "--merge"),
+                (kmers, "--kmer"),
+                (motifs, "--motif"),
+                (pwms, "--pwm"),
+            ];
+            conflict(&background_options, background_option)?;
+        } else if self.score_background != ScoreBackground::Contig {
+            return parameter("--score-background only applies to --score-mode zscore, --pvalue and --fdr.");
+        }
+        if self.score_precision.is_some() {
+            // Other modes have scores of their own, mostly whole numbers
+            conflict(&[(!windows_mode, "--mode")], "--score-precision")?;
+        }
+        if let Some(rate) = self.fdr {
+            if !(rate > 0.0 && rate <= 1.0) {
+                return parameter("--fdr must be greater than 0 and at most 1.");
+            }
+            // Records wait for the p-values of the whole genome, so nothing can be written or tallied as it is found
+            let fdr_options = [
+                (self.top.is_some(), "--top"),
+                (self.complement, "--complement"),
+                (self.unique_bases, "--unique-bases"),
+                (self.coverage_summary.is_some(), "--coverage-summary"),
+                (self.occupancy.is_some(), "--occupancy"),
+                (self.contig_report.is_some(), "--contig-report"),
+                (self.composition_matrix.is_some(), "--composition-matrix"),
+                (self.gene_report.is_some(), "--gene-report"),
+                (self.trim_bed.is_some(), "--trim-bed"),
+                (self.trim_tsv.is_some(), "--trim-tsv"),
+                (self.checkpoint.is_some(), "--checkpoint"),
+            ];
+            conflict(&fdr_options, "--fdr")?;
+        }
+        if self.slop != (0, 0) {
+            // Records are clamped to their contig, whose length projected reads don't know
+            conflict(&[(self.project_to_reference, "--project-to-reference")], "--slop")?;
+        }
+        if self.top == Some(0) {
+            return parameter("--top must be at least 1.");
+        }
+        if let Some(min_count) = self.min_count {
+            if min_count > w {
+                return parameter("--min-count cannot be larger than --window-size.");
+            }
+            // Only plain target windows have a count to compare against
+            let count_options = [
+                (!windows_mode, "--mode"),
+                (!fraction, "--stat"),
+                (expr, "--expr"),
+                (self.adaptive, "--adaptive"),
+                (seeded, "--seed-percentage"),
+                (kmers, "--kmer"),
+                (motifs, "--motif"),
+                (pwms, "--pwm"),
+            ];
+            conflict(&count_options, "--min-count")?;
+        }
+        if let Some(reduction) = self.clusters {
+            // Clusters are built from windows as they slide past, one scale at a time
+            let merge = reduction != Reduction::Best;
+            let cluster_options = [
+                (!self.sliding_windows, "--mode"),
+                (self.windows.len() > 1, "several window sizes"),
+                (self.step > 1, "--step"),
+                // Merged regions are not windows a threshold can be read off
+                (merge && !self.sweep.is_empty(), "--percentage-sweep"),
+            ];
+            // Trimming and runs look for one strand's bases
+            if self.merge_any_strand && (self.trim_to_content || self.max_run) {
+                return parameter("--trim-to-content and --max-run cannot be combined with --merge-strand any.");
+            }
+            conflict(&cluster_options, if merge { "--merge" } else { "--best-per-cluster" })?;
+        }
+        if self.strand == StrandChoice::Collapse {
+            // Only target windows pair a plus and a minus scorer up
+            let collapse_options = [
+                (!windows_mode, "--mode"),
+                (self.stat == Stat::Dinuc, "--stat dinuc"),
+                (self.adaptive, "--adaptive"),
+                (seeded, "--seed-percentage"),
+                (self.windows.len() > 1, "several window sizes"),
+                (kmers, "--kmer"),
+                (motifs, "--motif"),
+                (pwms, "--pwm"),
+            ];
+            conflict(&collapse_options, "--strand collapse")?;
+        }
+        // Only target windows have bases to trim to, or runs of them
+        let target_options = [
+            (!windows_mode, "--mode"),
+            (!fraction, "--stat"),
+            (expr, "--expr"),
+            (kmers, "--kmer"),
+            (motifs, "--motif"),
+            (pwms, "--pwm"),
+        ];
+        if self.trim_to_content {
+            conflict(&target_options, "--trim-to-content")?;
+            conflict(&[(self.adaptive, "--adaptive"), (seeded, "--seed-percentage")], "--trim-to-content")?;
+        }
+        if self.max_run {
+            conflict(&target_options, "--max-run")?;
+        }
+        if self.min_consecutive > 0 {
+            // As for --max-run
+            conflict(&target_options, "--min-consecutive")?;
+            conflict(&[(self.merge_any_strand, "--merge-strand any")], "--min-consecutive")?;
+        }
+        if let Some(fraction) = self.max_n_frac {
+            if !(0.0..=1.0).contains(&fraction) {
+                return parameter("--max-n-frac must be between 0 and 1.");
+            }
+            // Other modes find features, not windows, and N is a residue of proteins
+            let max_n_options = [
+                (self.alphabet == Alphabet::Protein, "--alphabet protein"),
+                (n_target, "an N --nucleotide target"),
+                (!windows_mode, "--mode"),
+            ];
+            conflict(&max_n_options, "--max-n-frac")?;
+        }
+        if self.n_policy != NPolicy::CountAgainst {
+            // Only the target fraction has a threshold for Ns to sway
+            let n_options = [
+                (self.alphabet == Alphabet::Protein, "--alphabet protein"),
+                (n_target, "an N --nucleotide target"),
+                (!windows_mode, "--mode"),
+                (!fraction, "--stat"),
+                (expr, "--expr"),
+                (self.adaptive, "--adaptive"),
+                (seeded, "--seed-percentage"),
+                (kmers, "--kmer"),
+                (motifs, "--motif"),
+                (pwms, "--pwm"),
+            ];
+            conflict(&n_options, "--n-policy")?;
+        }
+        if self.short_contig_policy == ShortContigPolicy::Whole {
+            // A whole contig is a window of its own size, so only single-size windows can stretch to it
+            let whole_options = [
+                (!windows_mode, "--mode"),
+                (self.stat == Stat::Dinuc, "--stat dinuc"),
+                (self.adaptive, "--adaptive"),
+                (seeded, "--seed-percentage"),
+                (self.windows.len() > 1, "several --window-size values"),
+                (kmers, "--kmer"),
+                (motifs, "--motif"),
+                (pwms, "--pwm"),
+            ];
+            conflict(&whole_options, "--short-contig-policy whole")?;
+        }
+        if self.fractional_iupac {
+            // Only the target fraction counts bases, and only nucleotides are ambiguous
+            let iupac_options = [
+                (self.alphabet == Alphabet::Protein, "--alphabet protein"),
+                (!windows_mode, "--mode"),
+                (!fraction, "--stat"),
+                (expr, "--expr"),
+                (self.adaptive, "--adaptive"),
+                (seeded, "--seed-percentage"),
+                (kmers, "--kmer"),
+                (motifs, "--motif"),
+                (pwms, "--pwm"),
+            ];
+            conflict(&iupac_options, "--fractional-iupac")?;
+        }
+        // Only sliding windows have offsets to skip, or can wrap around a circular contig
+        if self.circular && !self.sliding_windows {
+            return parameter("--circular only applies to sliding windows (--mode windows, zdna, or g4 with --g4hunter), not to --adaptive, --seed-percentage, --tile, --report composition or other modes.");
+        }
+        if self.step > 1 && !self.sliding_windows {
+            return parameter("--step only applies to sliding windows (--mode windows, zdna, or g4 with --g4hunter), not to --adaptive, --seed-percentage, --tile, --report composition or other modes.");
+        }
+        // Only the plain window scan has a window size to vary
+        if self.windows.len() > 1 {
+            let scale_options = [
+                (!windows_mode, "--mode"),
+                (self.stat == Stat::Dinuc, "--stat dinuc"),
+                (self.adaptive, "--adaptive"),
+                (seeded, "--seed-percentage"),
+                (self.min_count.is_some(), "--min-count"),
+                (kmers, "--kmer"),
+                (motifs, "--motif"),
+                (pwms, "--pwm"),
+            ];
+            conflict(&scale_options, "several window sizes")?;
+        }
+        if let Some((seed, extend)) = self.hysteresis {
+            if extend > seed {
+                return parameter("--extend-percentage cannot be higher than --seed-percentage.");
+            }
+            let seed_options = [
+                (!windows_mode, "--mode"),
+                (!fraction, "--stat"),
+                (expr, "--expr"),
+                (self.adaptive, "--adaptive"),
+                (kmers, "--kmer"),
+                (motifs, "--motif"),
+                (pwms, "--pwm"),
+            ];
+            conflict(&seed_options, "--seed-percentage")?;
+        }
+        if self.pair_strands.is_some() {
+            // Pairs are made from the stranded BED records of a contig, and written as BEDPE
+            let pair_options = [
+                (!windows_mode, "--mode"),
+                (self.strand != StrandChoice::Both, "--strand"),
+                (self.json_lines, "--output-format jsonl"),
+                (self.one_based, "--coordinates 1-based"),
+                (self.top.is_some(), "--top"),
+                (self.fdr.is_some(), "--fdr"),
+                (self.complement, "--complement"),
+                (self.unique_bases, "--unique-bases"),
+            ];
+            conflict(&pair_options, "--pair-strands")?;
+        }
+        Ok(())
+    }
+
+    /// A sliding-window scan for `targets` at `percentage`, with every other
+    /// option at its default.
+    pub fn plain(targets: Vec<BaseClass>, window: usize, percentage: f64) -> Self {
+        ScanConfig {
+            chrom_aliases: HashMap::new(),
+            targets,
+            kmers: Vec::new(),
+            motifs: Vec::new(),
+            max_mismatches: 0,
+            pwms: Vec::new(),
+            pwm_cutoff: 80.0,
+            mode: Mode::Windows,
+            min_run: 8,
+            min_copies: DEFAULT_MIN_COPIES,
+            max_unit: 100,
+            max_divergence: 10.0,
+            min_arm: 10,
+            max_loop: 20,
+            g4hunter: None,
+            dust_window: 64,
+            dust_threshold: 20,
+            stat: Stat::Fraction,
+            max_entropy: 1.5,
+            pair: None,
+            expr: None,
+            telomere_motifs: vec!["TTAGGG".to_string()],
+            introns: Vec::new(),
+            ppt_region: 50,
+            sites: Vec::new(),
+            window,
+            windows: vec![window],
+            percentage,
+            flags: false,
+            alphabet: Alphabet::Dna,
+            adaptive: false,
+            hysteresis: None,
+            min_count: None,
+            step: 1,
+            sweep: Vec::new(),
+            circular: false,
+            sliding_windows: true,
+            ignore_masked: None,
+            case_mode: CaseMode::Insensitive,
+            n_policy: NPolicy::CountAgainst,
+            max_n_frac: None,
+            fractional_iupac: false,
+            short_contig_policy: ShortContigPolicy::Skip,
+            strand: StrandChoice::Both,
+            combine_complements: false,
+            trim_to_content: false,
+            max_run: false,
+            min_consecutive: 0,
+            clusters: None,
+            max_gap: 0,
+            merge_any_strand: false,
+            min_length: 0,
+            slop: (0, 0),
+            window_weights: None,
+            score_mode: ScoreMode::Percentage,
+            score_background: ScoreBackground::Contig,
+            score_precision: None,
+            pvalue: None,
+            fdr: None,
+            complement: false,
+            unique_bases: false,
+            annotation: None,
+            closest_genes: None,
+            coverage_summary: None,
+            occupancy: None,
+            contig_report: None,
+            trim_bed: None,
+            trim_tsv: None,
+            composition_matrix: None,
+            gene_report: None,
+            top: None,
+            top_per_contig: false,
+            pair_strands: None,
+            project_to_reference: false,
+            checkpoint: None,
+            exclude: None,
+            overlap_filter: None,
+            json_lines: false,
+            one_based: false,
+            name_template: None,
+        }
+    }
+}
+
+/// A BED record held back until it is known to be among the `--top` ones.
+struct HeldRecord {
+    chrom: String,
+    start: u64,
+    end: u64,
+    name: String,
+    score: BedScore,
+    strand_symbol: &'static str,
+    extra_columns: Vec<String>,
+    flags: Option<String>,
+}
+
+impl HeldRecord {
+    fn write<W: Write>(&self, writer: &mut BedWriter<W>) -> Result<(), Box<dyn Error>> {
+        write_bed_record(
+            writer,
+            &self.chrom,
+            self.start,
+            self.end,
+            &self.name,
+            self.score,
+            self.strand_symbol,
+            &self.extra_columns,
+            self.flags.as_deref(),
+        )
+    }
+}
+
+/// Write the held records whose p-value is significant at false discovery
+/// rate `rate` over `tests` tests, by the Benjamini-Hochberg procedure, in
+/// the order they were found.
+///
+/// Windows that didn't reach the threshold were tested but not held; their
+/// p-values are taken to be higher than those of the records held.
+fn write_significant<W: Write>(writer: &mut BedWriter<W>, held: Vec<(f64, Vec<HeldRecord>)>, tests: u64, rate: f64) -> Result<(), Box<dyn Error>> {
+    let tests = tests.max(held.len() as u64);
+    let log10_p: Vec<f64> = held.iter().map(|(log10_p, _)| *log10_p).collect();
+    let cutoff = fdr_cutoff(&log10_p, tests, rate);
+
+    let mut significant = 0;
+    if let Some(cutoff) = cutoff {
+        for (_, records) in held.iter().filter(|(log10_p, _)| *log10_p <= cutoff) {
+            significant += 1;
+            for record in records {
+                record.write(writer)?;
+            }
+        }
+    }
+    match cutoff {
+        Some(cutoff) => info!("{} of {} records are significant at --fdr {} over {} windows (p <= {:.3e})", significant, held.len(), rate, tests, 10f64.powf(cutoff)),
+        None => info!("None of {} records are significant at --fdr {} over {} windows", held.len(), rate, tests),
+    }
+    Ok(())
+}
+
+/// The pairs of a plus- and a minus-strand record among the records of a
+/// contig, sorted by position, with at most `distance` bases from the end of
+/// the first to the start of the second (overlapping records are not
+/// paired); the upstream record of each pair comes first.
+fn opposite_strand_pairs(records: &[HeldRecord], distance: u64) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    for (i, first) in records.iter().enumerate() {
+        // Sorted by start, so the records after the first too far away are too
+        for (j, second) in records.iter().enumerate().skip(i + 1).take_while(|(_, second)| second.start <= first.end.saturating_add(distance)) {
+            let opposite = matches!((first.strand_symbol, second.strand_symbol), ("+", "-") | ("-", "+"));
+            if opposite && second.start >= first.end {
+                pairs.push((i, j));
+            }
+        }
+    }
+    pairs
+}
+
+/// Write the BEDPE records of the `--pair-strands` pairs of a contig's
+/// records.
+///
+///  columns: chrom1, start1, end1, chrom2, start2, end2, name, score, strand1, strand2, distance
+///
+///  - name => the names of both records, upstream first, e.g. A,A
+///  - score => the lower score of the two, as in the BED records
+///  - distance => bases between the two records
+fn write_strand_pairs<W: Write>(writer: &mut BedWriter<W>, mut records: Vec<HeldRecord>, distance: u64) -> Result<(), Box<dyn Error>> {
+    records.sort_by_key(|record| (record.start, record.end));
+    for (i, j) in opposite_strand_pairs(&records, distance) {
+        let (first, second) = (&records[i], &records[j]);
+        run_stats::add_record(".");
+        writer
+            .record(&first.chrom, first.start, first.end)
+            .column(&second.chrom)
+            .int_column(second.start)
+            .int_column(second.end)
+            .column(&format!("{},{}", first.name, second.name));
+        // A pair is only as strong as its weaker arm
+        match if first.score.value() <= second.score.value() { first.score } else { second.score } {
+            BedScore::Percentage(percentage) => writer.int_column(percentage.ceil() as u64),
+            BedScore::Decimal(score, decimals) => writer.column(&format!("{:.*}", decimals, score)),
+        };
+        writer.column(first.strand_symbol).column(second.strand_symbol).int_column(second.start - first.end);
+        writer.finish()?;
+    }
+    Ok(())
+}
+
+/// A writer of the records of a scan with `config`: BED, or JSON Lines with
+/// a field for each column the options add.
+fn record_writer<W: Write>(output: W, config: &ScanConfig) -> BedWriter<W> {
+    if !config.json_lines {
+        let writer = BedWriter::new(output);
+        return if config.one_based { writer.one_based() } else { writer };
+    }
+    // The same columns as write_bed_record, in the same order
+    let mut fields = vec![JsonField::Text("name"), JsonField::Number("score"), JsonField::Text("strand")];
+    if config.windows.len() > 1 {
+        fields.push(JsonField::Number("window_size"));
+    }
+    if !config.sweep.is_empty() {
+        fields.push(JsonField::Number("threshold"));
+    }
+    if config.max_run {
+        fields.push(JsonField::Number("max_run"));
+    }
+    match config.pvalue {
+        Some(PvalueFormat::Raw) => fields.push(JsonField::Number("pvalue")),
+        Some(PvalueFormat::NegLog10) => fields.push(JsonField::Number("neg_log10_pvalue")),
+        None => (),
+    }
+    if config.annotation.is_some() {
+        fields.extend([JsonField::Text("gene_context"), JsonField::OptionalText("genes")]);
+    }
+    if config.closest_genes.is_some() {
+        fields.extend([JsonField::OptionalText("closest_gene"), JsonField::Number("distance")]);
+    }
+    if config.flags {
+        fields.push(JsonField::OptionalText("flags"));
+    }
+    BedWriter::json_lines(output, fields)
+}
+
+/// The score column of a record.
+#[derive(Debug, Clone, Copy)]
+pub enum BedScore {
+    /// A percentage (or other positive score), rounded up
+    Percentage(f64),
+    /// A z-score or unrounded percentage, to this many decimals
+    Decimal(f64, usize),
+}
+
+impl BedScore {
+    fn value(&self) -> f64 {
+        match *self {
+            BedScore::Percentage(score) | BedScore::Decimal(score, _) => score,
+        }
+    }
+}
+
+/// Write a BED record.
+///
+///  columns: chrom, start, end, name, score, strand[, window size][, threshold][, max run][, p-value][, gene context, genes][, closest gene, distance][, flags]
+///
+///  - name => user base (or class)
+///  - score => integer (rounded up) percentage, or z-score with --score-mode zscore (either to --score-precision decimals)
+///  - strand => plus or minus
+///  - window size => only when scanning several window sizes
+///  - threshold => highest --percentage-sweep threshold met (only with a sweep)
+///  - max run => longest run of the target inside the record (only with --max-run)
+///  - p-value => binomial p-value against the background composition, or its -log10 (only with --pvalue)
+///  - gene context, genes => exon/intron/intergenic and overlapping gene IDs (only with --annotate)
+///  - closest gene, distance => nearest gene ID and signed distance to it (only with --closest)
+///  - flags => comma-separated caveat tags (only with --flags)
+#[allow(clippy::too_many_arguments)]
+pub fn write_bed_record<W: Write>(
+    writer: &mut BedWriter<W>,
+    chrom: &str,
+    start: u64,
+    end: u64,
+    name: &str,        // user-chosen base (not the complement)
+    score: BedScore,
+    strand_symbol: &str,    // e.g. "+"
+    extra_columns: &[String],
+    flags: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    run_stats::add_record(strand_symbol);
+    // columns 1..3, then name => user base, score => rounded up percentage (or z-score), strand
+    writer.record(chrom, start, end).column(name);
+    match score {
+        BedScore::Percentage(percentage) => writer.int_column(percentage.ceil() as u64),
+        BedScore::Decimal(score, decimals) => writer.column(&format!("{:.*}", decimals, score)),
+    };
+    writer.column(strand_symbol);
+
+    // window size and sweep threshold, then flags
+    for column in extra_columns {
+        writer.column(column);
+    }
+    if let Some(flags) = flags {
+        writer.column(flags);
+    }
+
+    writer.finish()?;
+    Ok(())
+}
+
+/// Scan every sequence record and write passing windows as BED.
+pub fn scan_records<W: Write>(
+    records: Records,
+    config: &ScanConfig,
+    mut output: W,
+    mut report: Option<&mut ReportCollector>,
+) -> Result<(), Box<dyn Error>> {
+    let w = config.window;
+    let p = config.percentage;
+
+    // The library's Scanner finds the scorers for each user base (plus strand) + complement
+    // (minus strand), as it does for its own scans; the options below add to them.
+    // Proteins and self-complementary targets (N, GC, AT) get a single unstranded scorer,
+    // as both strands would report the same windows.
+    // Every scorer runs against the same rolling composition, in one pass.
+    let scanner = Scanner::new(config.scan_params())?;
+    let strands = scanner.strands();
+    let strand_scorers = scanner.fractions();
+    let collapsed = if config.strand == StrandChoice::Collapse { collapse_strands(strand_scorers, strands, config.combine_complements) } else { Vec::new() };
+    let entropy = Entropy::new(config.max_entropy, config.alphabet);
+    let scorers: Vec<(&dyn WindowScorer, Strand)> = match config.stat {
+        // An expression is a single predicate over both strands
+        Stat::Fraction if config.expr.is_some() => {
+            vec![(config.expr.as_ref().unwrap() as &dyn WindowScorer, Strand::Unstranded)]
+        }
+        Stat::Fraction if config.strand == StrandChoice::Collapse => {
+            collapsed.iter().map(|(either, _)| (either as &dyn WindowScorer, Strand::Unstranded)).collect()
+        }
+        Stat::Fraction => strand_scorers
+            .iter()
+            .zip(strands)
+            .map(|(scorer, (_, strand, _))| (scorer as &dyn WindowScorer, *strand))
+            .collect(),
+        // Entropy is the same on both strands, whatever the target
+        Stat::Entropy => vec![(&entropy as &dyn WindowScorer, Strand::Unstranded)],
+        Stat::Dinuc => Vec::new(),
+    };
+
+    // A dinucleotide is scanned with its reverse complement on the minus
+    // strand, unless it is its own reverse complement (e.g. TA)
+    let mut dinuc_scorers: Vec<(DinucleotideDensity, Strand)> = Vec::new();
+    let mut dinuc_labels: Vec<String> = Vec::new();
+    if let Some(pair) = config.pair {
+        let label = String::from_utf8_lossy(&pair).into_owned();
+        let revcomp = reverse_complement_motif(&pair);
+        if revcomp == pair {
+            dinuc_scorers.push((DinucleotideDensity::new(pair, p, w), Strand::Unstranded));
+            dinuc_labels.push(label);
+        } else {
+            dinuc_scorers.push((DinucleotideDensity::new(pair, p, w), Strand::Plus));
+            dinuc_scorers.push((DinucleotideDensity::new([revcomp[0], revcomp[1]], p, w), Strand::Minus));
+            dinuc_labels.extend([label.clone(), label]);
+        }
+    }
+    let dinuc_scorers: Vec<(&DinucleotideDensity, Strand)> = dinuc_scorers.iter().map(|(scorer, strand)| (scorer, *strand)).collect();
+
+    // Each scale of a multi-scale scan needs its own target counts (entropy
+    // and expressions work on fractions, so they fit every scale)
+    let scales: Vec<(usize, Option<Vec<BaseFraction>>)> = config
+        .windows
+        .iter()
+        .map(|&scale| {
+            let fractions = (config.stat == Stat::Fraction && config.expr.is_none()).then(|| scanner.fractions_for(scale, None));
+            (scale, fractions)
+        })
+        .collect();
+
+    // K-mers and motifs are scanned with their reverse complement on the minus
+    // strand, unless they are their own reverse complement (e.g. AATT)
+    let mut kmer_scorers: Vec<(KmerDensity, Strand)> = Vec::new();
+    let mut kmer_labels: Vec<String> = Vec::new();
+    for kmer in &config.kmers {
+        let scan_kmer: Vec<u8> = match config.alphabet {
+            Alphabet::Dna => kmer.bytes().map(|nuc| if nuc == b'U' { b'T' } else { nuc }).collect(),
+            Alphabet::Protein => kmer.bytes().collect(),
+        };
+        let revcomp = reverse_complement(&scan_kmer);
+        if config.alphabet == Alphabet::Protein || revcomp == scan_kmer {
+            kmer_scorers.push((KmerDensity::new(&scan_kmer, p, w), Strand::Unstranded));
+            kmer_labels.push(kmer.clone());
+        } else {
+            kmer_scorers.push((KmerDensity::new(&scan_kmer, p, w), Strand::Plus));
+            kmer_scorers.push((KmerDensity::new(&revcomp, p, w), Strand::Minus));
+            kmer_labels.extend([kmer.clone(), kmer.clone()]);
+        }
+    }
+    for motif in &config.motifs {
+        let revcomp = reverse_complement_motif(motif.as_bytes());
+        if revcomp == motif.as_bytes() {
+            kmer_scorers.push((KmerDensity::motif(motif.as_bytes(), config.max_mismatches, p, w), Strand::Unstranded));
+            kmer_labels.push(motif.clone());
+        } else {
+            kmer_scorers.push((KmerDensity::motif(motif.as_bytes(), config.max_mismatches, p, w), Strand::Plus));
+            kmer_scorers.push((KmerDensity::motif(&revcomp, config.max_mismatches, p, w), Strand::Minus));
+            kmer_labels.extend([motif.clone(), motif.clone()]);
+        }
+    }
+    let kmer_scorers: Vec<(&KmerDensity, Strand)> = kmer_scorers.iter().map(|(scorer, strand)| (scorer, *strand)).collect();
+
+    // Matrices are always scanned on both strands
+    let pwm_scorers: Vec<(PwmScorer, Strand)> = config
+        .pwms
+        .iter()
+        .flat_map(|pwm| {
+            [
+                (PwmScorer::new(pwm.clone(), config.pwm_cutoff), Strand::Plus),
+                (PwmScorer::new(pwm.reverse_complement(), config.pwm_cutoff), Strand::Minus),
+            ]
+        })
+        .collect();
+    let pwm_scorers: Vec<(&PwmScorer, Strand)> = pwm_scorers.iter().map(|(scorer, strand)| (scorer, *strand)).collect();
+
+    // Name column for each scorer index
+    let labels: Vec<String> = if config.mode == Mode::Zdna {
+        vec!["zdna".to_string()]
+    } else if config.mode == Mode::G4 {
+        vec!["G4".to_string()]
+    } else if !pwm_scorers.is_empty() {
+        pwm_scorers.iter().map(|(scorer, _)| scorer.pwm().name.clone()).collect()
+    } else if !kmer_scorers.is_empty() {
+        kmer_labels
+    } else if config.stat == Stat::Entropy {
+        vec!["entropy".to_string()]
+    } else if config.stat == Stat::Dinuc {
+        dinuc_labels
+    } else if config.expr.is_some() {
+        vec!["expr".to_string()]
+    } else if config.strand == StrandChoice::Collapse {
+        collapsed.iter().map(|(_, label)| label.clone()).collect()
+    } else {
+        strands.iter().map(|(_, _, label)| label.clone()).collect()
+    };
+
+    // Target bases behind each scorer, for --trim-to-content and --max-run; a
+    // collapsed scorer takes both strands' letters, and --n-policy count-for adds N
+    let mut target_letters: Vec<Vec<u8>> = Vec::new();
+    for (class, strand, _) in strands {
+        match target_letters.last_mut() {
+            Some(letters) if config.strand == StrandChoice::Collapse && *strand == Strand::Minus => {
+                letters.extend_from_slice(class.letters());
+            }
+            _ => target_letters.push(class.letters().to_vec()),
+        }
+    }
+    if config.n_policy == NPolicy::CountFor {
+        target_letters.iter_mut().for_each(|letters| letters.push(b'N'));
+    }
+    let target_classes: Vec<BaseClass> = target_letters.iter().map(|letters| BaseClass::new(letters)).collect();
+
+    // A target's scorers share its label, whatever the strand; --merge-strand any
+    // merges the windows of each label together
+    let merge_groups: Vec<usize> =
+        labels.iter().map(|label| labels.iter().position(|other| other == label).unwrap()).collect();
+
+    // Introns per contig, in coordinate order, and the classes making up a
+    // polypyrimidine tract on either strand
+    let mut introns_by_chrom: HashMap<&str, Vec<&Intron>> = HashMap::new();
+    for intron in &config.introns {
+        introns_by_chrom.entry(intron.chrom.as_str()).or_default().push(intron);
+    }
+    for introns in introns_by_chrom.values_mut() {
+        introns.sort_by_key(|intron| intron.start);
+    }
+    let pyrimidines = BaseFraction::for_class(&BaseClass::new(b"CT"), p, w);
+    let purines = BaseFraction::for_class(&BaseClass::new(b"AG"), p, w);
+
+    // Prepare a BED writer
+    // Borrowed, so the writer can be dropped to flush it at each checkpoint
+    let mut bed_writer = record_writer(&mut output, config);
+    // Inverted repeats are numbered so their two arms can be paired up
+    let mut inverted_repeats = 0;
+    // With --top, records are held back until their contig (or the input) is done;
+    // a hit split across the origin of a circular contig is held as one
+    let mut top: Option<TopK<Vec<HeldRecord>>> = config.top.map(TopK::new);
+    let mut top_contig: Option<String> = None;
+    // With --pair-strands, the records of the current contig, paired once it is done
+    let mut strand_held: Vec<HeldRecord> = Vec::new();
+    let mut strand_contig: Option<String> = None;
+    // With --fdr, every record (by its log10 p-value) and the number of windows evaluated
+    let mut fdr_held: Vec<(f64, Vec<HeldRecord>)> = Vec::new();
+    let mut fdr_tests: u64 = 0;
+    // With --complement, --unique-bases, --coverage-summary or --occupancy, the records so far on the current contig
+    let track_coverage = config.complement || config.unique_bases || config.coverage_summary.is_some() || config.occupancy.is_some();
+    let mut covered_contig: Option<(String, u64)> = None;
+    let mut covered = CoverageIndex::new();
+    let mut coverage_summary = match &config.coverage_summary {
+        Some(path) => Some(CoverageSummary::create(path)?),
+        None => None,
+    };
+    let mut occupancy = match &config.occupancy {
+        Some(path) => Some(BufWriter::new(File::create(path)?)),
+        None => None,
+    };
+    let mut contig_report = match &config.contig_report {
+        Some(path) => Some(ContigReport::create(path)?),
+        None => None,
+    };
+    let mut trim = trim_writer(config.trim_bed.as_deref(), config.trim_tsv.as_deref())?;
+    let mut composition_matrix = match &config.composition_matrix {
+        Some(path) => {
+            let mut matrix = BufWriter::new(File::create(path)?);
+            writeln!(matrix, "{}", COMPOSITION_MATRIX_HEADER)?;
+            Some(matrix)
+        }
+        None => None,
+    };
+    // With --gene-report, the records overlapping each gene so far, by contig and gene ID
+    let mut gene_tallies: HashMap<(String, String), GeneTally> = HashMap::new();
+
+    // For each contig
+    let scan_started = std::time::Instant::now();
+    let (mut scanned_records, mut total_hits) = (0, 0);
+    let mut checkpoint_contig: Option<String> = None;
+    for result_record in records {
+        let mut record = result_record?;
+        // A contig is complete once the next one starts (chunks of a contig come one after the other)
+        if let Some(checkpoint) = &config.checkpoint {
+            if checkpoint_contig.as_ref().is_some_and(|id| *id != record.id) {
+                drop(bed_writer);
+                output.flush()?;
+                checkpoint.lock().expect("no panics while holding the checkpoint").complete(checkpoint_contig.as_deref().expect("checked above"))?;
+                bed_writer = record_writer(&mut output, config);
+            }
+            checkpoint_contig = Some(record.id.clone());
+        }
+        let record_started = std::time::Instant::now();
+        let (mut record_hits, mut record_excluded) = (0, 0);
+        // With --trim-bed or --trim-tsv, the records written on this read
+        let mut record_intervals: Vec<(usize, usize)> = Vec::new();
+        scanned_records += 1;
+        if track_coverage {
+            // The length of a streamed contig is only known with its last chunk
+            match covered_contig.as_mut() {
+                Some((id, len)) if *id == record.id => *len = record.contig_len as u64,
+                _ => {
+                    if let Some((id, len)) = covered_contig.replace((record.id.clone(), record.contig_len as u64)) {
+                        let covered = std::mem::take(&mut covered);
+                        finish_coverage(config, &mut bed_writer, coverage_summary.as_mut(), occupancy.as_mut(), &id, covered, len)?;
+                    }
+                }
+            }
+        }
+        if let Some(top) = top.as_mut() {
+            // Chunks and regions of a contig come one after the other
+            if config.top_per_contig && top_contig.as_deref() != Some(record.id.as_str()) {
+                for held in top.drain_in_order().into_iter().flatten() {
+                    held.write(&mut bed_writer)?;
+                }
+                top_contig = Some(record.id.clone());
+            }
+        }
+        if let Some(distance) = config.pair_strands {
+            // Chunks and regions of a contig come one after the other
+            if strand_contig.as_deref() != Some(record.id.as_str()) {
+                write_strand_pairs(&mut bed_writer, std::mem::take(&mut strand_held), distance)?;
+                strand_contig = Some(record.id.clone());
+            }
+        }
+        // Only rewrite when needed, so memory-mapped sequences aren't copied
+        if config.alphabet == Alphabet::Dna && has_uracil(&record.seq) {
+            rna_to_dna(&mut record.seq);
+        }
+        match config.case_mode {
+            CaseMode::Insensitive => (),
+            CaseMode::UpperOnly => hide_soft_masked(&mut record.seq),
+            CaseMode::LowerOnly => hide_unmasked(&mut record.seq),
+        }
+        let contig_id = &record.id;
+        // A whole contig shorter than the window has none, unless it is taken as one
+        let windowed = matches!(config.mode, Mode::Windows | Mode::Zdna | Mode::Ppt) || (config.mode == Mode::G4 && config.g4hunter.is_some());
+        let short_contig = windowed
+            && record.seq.len() < w && record.start == 0 && record.seq.len() == record.contig_len && record.alignment.is_none();
+        let whole_contig = short_contig && !record.seq.is_empty() && config.short_contig_policy == ShortContigPolicy::Whole;
+        // Windows of a circular contig run on past its end into a copy of its
+        // start; this needs the whole contig
+        let circular = config.sliding_windows
+            && (config.circular || record.circular)
+            && !record.chunked
+            && record.start == 0
+            && record.seq.len() == record.contig_len
+            && record.alignment.is_none()
+            && !whole_contig;
+        let wrapped: Vec<u8>;
+        let seq: &[u8] = if circular {
+            let overhang = (config.windows[config.windows.len() - 1] - 1).min(record.seq.len());
+            wrapped = [&record.seq[..], &record.seq[..overhang]].concat();
+            &wrapped
+        } else {
+            &record.seq
+        };
+        let gaps = if config.flags { GapIndex::from_seq(seq) } else { GapIndex::default() };
+        if let Some(report) = report.as_deref_mut() {
+            if !config.project_to_reference {
+                report.add_contig(contig_id, record.contig_len);
+            }
+        }
+
+        // With --score-mode zscore, --pvalue or --fdr, the composition of each target on this contig
+        let backgrounds: Vec<Background> = if config.score_mode == ScoreMode::Zscore || config.pvalue.is_some() || config.fdr.is_some() {
+            target_classes.iter().map(|class| Background::new(seq, class)).collect()
+        } else {
+            Vec::new()
+        };
+
+        // Steps count from the contig start, so chunks and regions line up
+        let steps = Steps::new(config.step, record.start);
+
+        // Overlapping windows often trim to the same tract; it is reported once per scorer
+        let mut last_trimmed: Vec<Option<(usize, usize)>> = vec![None; target_classes.len()];
+
+        // We STILL label minus-strand records with the user's base, but mark strand="-"
+        let mut write_named_hit = |hit: ScanHit, label: &str| {
+            // Unstranded hits belong to either strand
+            let unwanted = match config.strand {
+                StrandChoice::Plus => Strand::Minus,
+                StrandChoice::Minus => Strand::Plus,
+                _ => Strand::Unstranded,
+            };
+            if hit.strand == unwanted && unwanted != Strand::Unstranded {
+                return Ok(());
+            }
+            // Windows starting in the copy were already seen at the contig start
+            if circular && hit.start >= record.contig_len {
+                return Ok(());
+            }
+            let window_len = hit.end - hit.start;
+            let scale = (config.windows.len() > 1).then_some(window_len);
+            let flags = config.flags.then(|| {
+                let mut flags = window_flags(seq, &gaps, hit.start, hit.end, record.start, record.contig_len, scale.unwrap_or(w));
+                // Fixed windows always fit inside one chunk, but adaptive and
+                // hysteresis regions can run into its edges
+                let cut_left = hit.start == 0 && record.start > 0;
+                let cut_right = hit.end == seq.len() && record.start + seq.len() < record.contig_len;
+                if (config.adaptive || config.hysteresis.is_some()) && record.chunked && (cut_left || cut_right) {
+                    flags.set(HitFlags::TRUNCATED_BY_CHUNKING);
+                }
+                if circular {
+                    flags.clear(HitFlags::NEAR_CONTIG_END);
+                }
+                flags.to_tags()
+            });
+            // Trim to the target bases; the score stays that of the whole window
+            let hit = if config.trim_to_content {
+                let class = &target_classes[hit.scorer];
+                let is_target = |&nuc: &u8| class.contains(nuc);
+                let window = &seq[hit.start..hit.end];
+                let (Some(first), Some(last)) = (window.iter().position(is_target), window.iter().rposition(is_target)) else {
+                    return Ok(());
+                };
+                let trimmed = (hit.start + first, hit.start + last + 1);
+                if last_trimmed[hit.scorer].replace(trimmed) == Some(trimmed) {
+                    return Ok(());
+                }
+                ScanHit { start: trimmed.0, end: trimmed.1, ..hit }
+            } else {
+                hit
+            };
+            if hit.end - hit.start < config.min_length {
+                return Ok(());
+            }
+            // Project read coordinates onto the reference if asked to
+            let (chrom, start, end) = if config.project_to_reference {
+                let projected = record.alignment.as_ref().and_then(|aln| {
+                    aln.project(hit.start, hit.end).map(|(start, end)| (aln.reference.as_str(), start, end))
+                });
+                match projected {
+                    Some(projected) => projected,
+                    None => return Ok(()),
+                }
+            } else {
+                (contig_id.as_str(), record.start + hit.start, record.start + hit.end)
+            };
+            // A window across the origin of a circular contig is written in two pieces
+            let pieces = if circular && end > record.contig_len {
+                vec![(start, record.contig_len), (0, end - record.contig_len)]
+            } else {
+                vec![(start, end)]
+            };
+            let excluded = |&(start, end): &(usize, usize)| {
+                config.exclude.as_ref().is_some_and(|exclude| exclude.overlaps(chrom, start as u64, end as u64))
+            };
+            if pieces.iter().any(excluded) {
+                record_excluded += 1;
+                return Ok(());
+            }
+            if let Some((intervals, mode, min_fraction)) = &config.overlap_filter {
+                let covered: u64 = pieces.iter().map(|&(start, end)| intervals.overlap_len(chrom, start as u64, end as u64)).sum();
+                let len: usize = pieces.iter().map(|&(start, end)| end - start).sum();
+                let fraction = covered as f64 / len as f64;
+                let overlaps = covered > 0 && fraction >= *min_fraction;
+                if overlaps != (*mode == OverlapMode::Keep) {
+                    record_excluded += 1;
+                    return Ok(());
+                }
+            }
+            // Pad the record after filtering, so the padding alone never drops it
+            let mut pieces = pieces;
+            if config.slop != (0, 0) {
+                let last = pieces.len() - 1;
+                pieces[0].0 = pieces[0].0.saturating_sub(config.slop.0);
+                pieces[last].1 = pieces[last].1.saturating_add(config.slop.1).min(record.contig_len);
+            }
+            if let Some(report) = report.as_deref_mut() {
+                for &(start, end) in &pieces {
+                    report.add_hit(chrom, &ScanHit { start, end, ..hit.clone() });
+                }
+            }
+
+            let mut extra_columns = Vec::new();
+            if let Some(scale) = scale {
+                extra_columns.push(scale.to_string());
+            }
+            // Hits pass the lowest threshold; tag them with the highest one they
+            // meet, rounding each to a count the way the scorers do
+            let len = window_len as f64;
+            let count = (hit.score / 100.0 * len).round();
+            if let Some(threshold) = config.sweep.iter().rev().find(|&&t| count >= ((t / 100.0) * len).ceil()) {
+                extra_columns.push(threshold.to_string());
+            }
+            if config.max_run {
+                extra_columns.push(longest_run(&seq[hit.start..hit.end], &target_classes[hit.scorer]).to_string());
+            }
+            // Z-scores and p-values are of the whole window, even if trimmed
+            let background = backgrounds.get(hit.scorer).map(|background| match config.score_background {
+                ScoreBackground::Contig => background.contig(),
+                ScoreBackground::Local => background.local(hit.start, hit.end),
+            });
+            let log10_p = background.map(|background| log10_binomial_tail(count as usize, window_len, background));
+            if let (Some(format), Some(log10)) = (config.pvalue, log10_p) {
+                extra_columns.push(match format {
+                    PvalueFormat::Raw => format!("{:.3e}", 10f64.powf(log10)),
+                    // Adding 0 turns -0 (p = 1) into 0
+                    PvalueFormat::NegLog10 => format!("{:.2}", -log10 + 0.0),
+                });
+            }
+            // From here on the score is the z-score, if asked for
+            let (hit, score) = match (config.score_mode, background) {
+                (ScoreMode::Zscore, Some(background)) => {
+                    let z = z_score(hit.score, window_len, background);
+                    (ScanHit { score: z, ..hit }, BedScore::Decimal(z, config.score_precision.unwrap_or(2)))
+                }
+                _ => {
+                    let score = match config.score_precision {
+                        Some(decimals) => BedScore::Decimal(hit.score, decimals),
+                        None => BedScore::Percentage(hit.score),
+                    };
+                    (hit, score)
+                }
+            };
+            if let Some(annotation) = &config.annotation {
+                // A hit across the origin of a circular contig takes the genes of both pieces
+                let mut context = GeneContext::Intergenic;
+                let mut genes: Vec<&str> = Vec::new();
+                for &(start, end) in &pieces {
+                    let (piece_context, piece_genes) = annotation.annotate(chrom, start as u64, end as u64);
+                    context = context.max(piece_context);
+                    for gene in piece_genes {
+                        if !genes.contains(&gene) {
+                            genes.push(gene);
+                        }
+                    }
+                }
+                extra_columns.push(context.name().to_string());
+                extra_columns.push(if genes.is_empty() { ".".to_string() } else { genes.join(",") });
+            }
+            if let Some(genes) = &config.closest_genes {
+                // A hit across the origin of a circular contig is measured from its first piece
+                let (start, end) = pieces[0];
+                match genes.closest(chrom, start as u64, end as u64) {
+                    Some((gene, distance)) => extra_columns.extend([gene.to_string(), distance.to_string()]),
+                    None => extra_columns.extend([".".to_string(), ".".to_string()]),
+                }
+            }
+
+            record_hits += pieces.len();
+            if trim.is_some() {
+                record_intervals.push((hit.start, hit.end));
+            }
+            if track_coverage {
+                for &(start, end) in &pieces {
+                    covered.insert(start as u64, end as u64, hit.score, label);
+                }
+            }
+            if config.complement || config.unique_bases {
+                return Ok(());
+            }
+            // With --name-template, each piece is named after its own coordinates
+            let names: Vec<String> = pieces
+                .iter()
+                .map(|&(start, end)| match &config.name_template {
+                    Some(template) => template.fill(&NameFields {
+                        base: label,
+                        strand: hit.strand.symbol(),
+                        score: hit.score,
+                        length: (end - start) as u64,
+                        chrom,
+                        start: start as u64 + config.one_based as u64,
+                        end: end as u64,
+                    }),
+                    None => label.to_string(),
+                })
+                .collect();
+            if top.is_some() || config.fdr.is_some() {
+                let held = pieces
+                    .into_iter()
+                    .zip(names)
+                    .map(|((start, end), name)| HeldRecord {
+                        chrom: chrom.to_string(),
+                        start: start as u64,
+                        end: end as u64,
+                        name,
+                        score,
+                        strand_symbol: hit.strand.symbol(),
+                        extra_columns: extra_columns.clone(),
+                        flags: flags.clone(),
+                    })
+                    .collect();
+                match top.as_mut() {
+                    Some(top) => top.push(hit.score, held),
+                    None => fdr_held.push((log10_p.expect("--fdr scores against a background"), held)),
+                }
+                return Ok(());
+            }
+            if config.pair_strands.is_some() {
+                strand_held.extend(pieces.into_iter().zip(names).map(|((start, end), name)| HeldRecord {
+                    chrom: chrom.to_string(),
+                    start: start as u64,
+                    end: end as u64,
+                    name,
+                    score,
+                    strand_symbol: hit.strand.symbol(),
+                    extra_columns: Vec::new(),
+                    flags: None,
+                }));
+                return Ok(());
+            }
+            if config.gene_report.is_some() {
+                if let Some(annotation) = &config.annotation {
+                    // A hit across the origin of a circular contig counts once per gene
+                    let mut overlapped: Vec<(&str, GeneBases)> = Vec::new();
+                    for &(start, end) in &pieces {
+                        for (gene, bases) in annotation.gene_bases(chrom, start as u64, end as u64) {
+                            match overlapped.iter_mut().find(|(seen, _)| *seen == gene) {
+                                Some((_, total)) => {
+                                    total.cds += bases.cds;
+                                    total.utr += bases.utr;
+                                    total.intron += bases.intron;
+                                }
+                                None => overlapped.push((gene, bases)),
+                            }
+                        }
+                    }
+                    for (gene, bases) in overlapped {
+                        gene_tallies.entry((chrom.to_string(), gene.to_string())).or_default().add(&bases, hit.score);
+                    }
+                }
+            }
+            if let Some(matrix) = composition_matrix.as_mut() {
+                // The pieces of a hit across the origin of a circular contig split its sequence there
+                let slices = match pieces.len() {
+                    1 => vec![(hit.start, hit.end)],
+                    _ => vec![(hit.start, record.contig_len), (record.contig_len, hit.end)],
+                };
+                for ((&(start, end), (from, to)), name) in pieces.iter().zip(slices).zip(&names) {
+                    let comp = Composition::from_seq(&seq[from..to]);
+                    write_composition_matrix_row(matrix, chrom, start, end, name, hit.strand.symbol(), &comp)?;
+                }
+            }
+            for ((start, end), name) in pieces.into_iter().zip(&names) {
+                write_bed_record(
+                    &mut bed_writer,
+                    chrom,
+                    start as u64,
+                    end as u64,
+                    name,
+                    score,
+                    hit.strand.symbol(),
+                    &extra_columns,
+                    flags.as_deref(),
+                )?;
+            }
+            Ok(())
+        };
+        // With --best-per-cluster or --merge, windows wait until their cluster is closed
+        let mut clusters = config.clusters.map(|reduction| {
+            let clusters = Clusters::new(reduction).with_max_gap(config.max_gap);
+            if config.merge_any_strand {
+                clusters.with_groups(merge_groups.clone())
+            } else {
+                clusters
+            }
+        });
+        let mut write_hit = |hit: ScanHit| {
+            // Windows are dropped before they can join a cluster
+            if config.min_consecutive > 0 && longest_run(&seq[hit.start..hit.end], &target_classes[hit.scorer]) < config.min_consecutive {
+                return Ok(());
+            }
+            if let Some(max_n_frac) = config.max_n_frac {
+                let n_bases = seq[hit.start..hit.end].iter().filter(|&&nuc| nuc.eq_ignore_ascii_case(&b'N')).count();
+                if n_bases as f64 > max_n_frac * (hit.end - hit.start) as f64 {
+                    return Ok(());
+                }
+            }
+            if let Some(clusters) = clusters.as_mut() {
+                for reduced in clusters.push(hit) {
+                    let label = &labels[reduced.scorer];
+                    write_named_hit(reduced, label)?;
+                }
+                return Ok(());
+            }
+            let label = &labels[hit.scorer];
+            write_named_hit(hit, label)
+        };
+
+        if config.mode == Mode::Str {
+            // Repeats are named by their unit and copy number, e.g. (AT)12
+            find_strs(seq, &config.min_copies, config.max_mismatches, |repeat| {
+                let hit = ScanHit { start: repeat.start, end: repeat.end, score: repeat.purity, strand: Strand::Unstranded, scorer: 0 };
+                write_named_hit(hit, &repeat.label())
+            })?;
+        } else if config.mode == Mode::Tandem {
+            find_tandem_repeats(seq, config.max_unit, &config.min_copies, config.max_divergence, |repeat| {
+                let hit = ScanHit { start: repeat.start, end: repeat.end, score: repeat.purity, strand: Strand::Unstranded, scorer: 0 };
+                write_named_hit(hit, &repeat.label())
+            })?;
+        } else if config.mode == Mode::Palindrome {
+            find_inverted_repeats(seq, config.min_arm, config.max_loop, config.max_mismatches, |ir| {
+                inverted_repeats += 1;
+                let label = format!("IR{}:arm={},loop={}", inverted_repeats, ir.arm, ir.loop_len);
+                let score = ir.identity();
+                let ((left_start, left_end), (right_start, right_end)) = (ir.left_arm(), ir.right_arm());
+                write_named_hit(ScanHit { start: left_start, end: left_end, score, strand: Strand::Plus, scorer: 0 }, &label)?;
+                write_named_hit(ScanHit { start: right_start, end: right_end, score, strand: Strand::Minus, scorer: 0 }, &label)
+            })?;
+        } else if config.mode == Mode::G4 {
+            // G4Hunter windows slide, so they can be clustered like target windows
+            match config.g4hunter {
+                Some(threshold) => g4hunter_windows(seq, w, steps, threshold, write_hit)?,
+                None => find_g4_motifs(seq, |hit| write_named_hit(hit, "G4"))?,
+            }
+        } else if config.mode == Mode::Dust {
+            for (start, end) in sdust(seq, config.dust_window, config.dust_threshold) {
+                let hit = ScanHit { start, end, score: (end - start) as f64, strand: Strand::Unstranded, scorer: 0 };
+                write_named_hit(hit, "dust")?;
+            }
+        } else if config.mode == Mode::Telomere {
+            // Name blocks by unit, copies and the contig end they sit at, e.g. (TTAGGG)523:end
+            let mut blocks = Vec::new();
+            for motif in &config.telomere_motifs {
+                let min_copies = config.min_copies[motif.len().min(MAX_UNIT) - 1];
+                find_telomeric_blocks(seq, motif.as_bytes(), min_copies, |block| {
+                    blocks.push((motif, block));
+                    Ok(())
+                })?;
+            }
+            blocks.sort_by_key(|(_, block)| block.start);
+            for (motif, block) in blocks {
+                let position = end_position(record.start + block.start, record.start + block.end, record.contig_len);
+                let label = format!("({}){}:{}", motif, block.copies, position);
+                let hit = ScanHit { start: block.start, end: block.end, score: block.purity, strand: block.strand, scorer: 0 };
+                write_named_hit(hit, &label)?;
+            }
+        } else if config.mode == Mode::Zdna {
+            alternation_windows(seq, w, steps, p, write_hit)?;
+        } else if config.mode == Mode::Ppt {
+            // C/T-rich windows on the intron's strand (A/G-rich on the plus strand for minus-strand introns)
+            let (contig_start, contig_end) = (record.start as u64, (record.start + seq.len()) as u64);
+            for intron in introns_by_chrom.get(contig_id.as_str()).into_iter().flatten() {
+                let (start, end) = intron.three_prime_end(config.ppt_region);
+                let (start, end) = (start.max(contig_start), end.min(contig_end));
+                if start >= end {
+                    continue;
+                }
+                let offset = (start - contig_start) as usize;
+                let region = &seq[offset..(end - contig_start) as usize];
+                let scorer: [(&dyn WindowScorer, Strand); 1] = match intron.strand {
+                    Strand::Minus => [(&purines, Strand::Minus)],
+                    _ => [(&pyrimidines, Strand::Plus)],
+                };
+                scan_windows(region, w, steps.skip(offset), &scorer, |hit| {
+                    write_named_hit(ScanHit { start: hit.start + offset, end: hit.end + offset, ..hit }, &intron.id)
+                })?;
+            }
+        } else if config.mode == Mode::Sites {
+            // Palindromic sites (most type II enzymes) are reported once, others per strand
+            let mut hits = Vec::new();
+            for (i, (_, site)) in config.sites.iter().enumerate() {
+                let revcomp = reverse_complement_motif(site.as_bytes());
+                let strands = if revcomp == site.as_bytes() {
+                    vec![(site.as_bytes().to_vec(), Strand::Unstranded)]
+                } else {
+                    vec![(site.as_bytes().to_vec(), Strand::Plus), (revcomp, Strand::Minus)]
+                };
+                for (pattern, strand) in strands {
+                    for pos in motif_sites(seq, &pattern) {
+                        hits.push(ScanHit { start: pos, end: pos + pattern.len(), score: 0.0, strand, scorer: i });
+                    }
+                }
+            }
+            hits.sort_by_key(|hit| (hit.start, hit.scorer, hit.strand == Strand::Minus));
+            for hit in hits {
+                let name = &config.sites[hit.scorer].0;
+                write_named_hit(hit, name)?;
+            }
+        } else if config.mode == Mode::Gaps {
+            // Assembly gaps are runs of N, whatever the target
+            find_runs(seq, &BaseClass::new(b"N"), config.min_run, Strand::Unstranded, |hit| write_named_hit(hit, "gap"))?;
+        } else if config.mode == Mode::Runs {
+            // Exact runs on each strand (and target), in coordinate order
+            let mut hits = Vec::new();
+            for (i, (class, strand, _)) in strands.iter().enumerate() {
+                find_runs(seq, class, config.min_run, *strand, |hit| {
+                    hits.push(ScanHit { scorer: i, ..hit });
+                    Ok(())
+                })?;
+            }
+            hits.sort_by_key(|hit| (hit.start, hit.scorer));
+            for hit in hits {
+                write_hit(hit)?;
+            }
+        } else if config.adaptive {
+            // Extend seeds on each strand (and target), then report them all in coordinate order
+            let mut hits = Vec::new();
+            for (i, (class, strand, _)) in strands.iter().enumerate() {
+                adaptive_scan(seq, w, p, class, *strand, |hit| {
+                    hits.push(ScanHit { scorer: i, ..hit });
+                    Ok(())
+                })?;
+            }
+            hits.sort_by_key(|hit| (hit.start, hit.scorer));
+            for hit in hits {
+                write_hit(hit)?;
+            }
+        } else if let Some((seed, extend)) = config.hysteresis {
+            // Seed and extend on each strand (and target), then report them all in coordinate order
+            let mut hits = Vec::new();
+            for (i, (class, strand, _)) in strands.iter().enumerate() {
+                hysteresis_scan(seq, w, seed, extend, class, *strand, |hit| {
+                    hits.push(ScanHit { scorer: i, ..hit });
+                    Ok(())
+                })?;
+            }
+            hits.sort_by_key(|hit| (hit.start, hit.scorer));
+            for hit in hits {
+                write_hit(hit)?;
+            }
+        } else if let Some(weights) = &config.window_weights {
+            let targets: Vec<(&BaseClass, Strand)> = target_classes.iter().zip(strands).map(|(class, (_, strand, _))| (class, *strand)).collect();
+            weighted_windows(seq, weights, steps, p, &targets, write_hit)?;
+        } else if !pwm_scorers.is_empty() {
+            scan_pwms(seq, w, steps, &pwm_scorers, write_hit)?;
+        } else if !kmer_scorers.is_empty() {
+            scan_kmers(seq, w, steps, &kmer_scorers, write_hit)?;
+        } else if !dinuc_scorers.is_empty() {
+            dinucleotide_windows(seq, w, steps, &dinuc_scorers, write_hit)?;
+        } else if config.windows.len() > 1 {
+            // Every scale in one pass, in coordinate order. Chunks overlap by
+            // the largest window, so smaller windows starting in that overlap
+            // were already reported with the previous chunk.
+            let largest = config.windows[config.windows.len() - 1];
+            let queries: Vec<Query> = scales
+                .iter()
+                .flat_map(|(scale, fractions)| {
+                    let scale_scorers: Vec<(&dyn WindowScorer, Strand)> = match fractions {
+                        Some(fractions) => {
+                            fractions.iter().zip(strands).map(|(scorer, (_, strand, _))| (scorer as &dyn WindowScorer, *strand)).collect()
+                        }
+                        None => scorers.clone(),
+                    };
+                    scale_scorers.into_iter().map(|(scorer, strand)| Query { window: *scale, scorer, strand })
+                })
+                .collect();
+            // Every scale has the same scorers, so hits map back to theirs by position
+            let per_scale = queries.len() / scales.len();
+            let seen = |scale: usize| if record.chunked && record.start > 0 { largest - scale } else { 0 };
+            scan_queries(seq, &queries, steps, |hit| {
+                let hit = ScanHit { scorer: hit.scorer % per_scale, ..hit };
+                if hit.start < seen(hit.end - hit.start) { Ok(()) } else { write_hit(hit) }
+            })?;
+        } else if whole_contig {
+            // The contig is a single window of its own length, which the
+            // target fractions take their threshold of (entropy and
+            // expressions work on fractions, so they fit it as they are)
+            let len = seq.len();
+            let whole_fractions = scanner.fractions_for(len, config.min_count);
+            let whole_collapsed = collapse_strands(&whole_fractions, strands, config.combine_complements);
+            let whole_scorers: Vec<(&dyn WindowScorer, Strand)> = match config.stat {
+                Stat::Fraction if config.expr.is_none() && config.strand == StrandChoice::Collapse => {
+                    whole_collapsed.iter().map(|(either, _)| (either as &dyn WindowScorer, Strand::Unstranded)).collect()
+                }
+                Stat::Fraction if config.expr.is_none() => {
+                    whole_fractions.iter().zip(strands).map(|(scorer, (_, strand, _))| (scorer as &dyn WindowScorer, *strand)).collect()
+                }
+                _ => scorers.clone(),
+            };
+            scan_windows(seq, len, steps, &whole_scorers, write_hit)?;
+        } else {
+            // Contigs shorter than the window yield no hits.
+            scan_windows(seq, w, steps, &scorers, write_hit)?;
+        }
+        for reduced in clusters.iter_mut().flat_map(Clusters::finish) {
+            let label = &labels[reduced.scorer];
+            write_named_hit(reduced, label)?;
+        }
+        if short_contig && config.short_contig_policy == ShortContigPolicy::Warn {
+            warn!("{}: skipped, {} bp is shorter than the {} bp window", record.id, record.seq.len(), w);
+        } else if record.seq.len() < w && !whole_contig {
+            debug!("{}: skipped, {} bp is shorter than the window", record.id, record.seq.len());
+        } else {
+            let (start, end) = (record.start + 1, record.start + record.seq.len());
+            debug!("{}:{}-{}: scanned in {:.2?}, {} hits", record.id, start, end, record_started.elapsed(), record_hits);
+            timings::add_contig(&record.id, record.seq.len(), record_started.elapsed());
+        }
+        // Chunks and pieces of a contig repeat the last bases of the one before
+        let overlap = if record.chunked && record.start > 0 { config.windows[config.windows.len() - 1] - 1 } else { 0 };
+        run_stats::add_bases(&record.id, record.seq.len().saturating_sub(overlap));
+        if config.fdr.is_some() {
+            // Every window start (on the step) is a test, for every target and strand
+            let starts = |size: usize| if circular { record.seq.len() } else { (record.seq.len() + 1).saturating_sub(size) };
+            let tests: usize = if whole_contig { 1 } else { config.windows.iter().map(|&size| starts(size).div_ceil(config.step)).sum() };
+            fdr_tests += (tests * target_classes.len()) as u64;
+        }
+        if let Some(contig_report) = contig_report.as_mut() {
+            contig_report.add(&record, record_hits as u64, record_excluded, short_contig && !whole_contig)?;
+        }
+        if let Some(trim) = trim.as_mut() {
+            let anchor = config.windows[config.windows.len() - 1];
+            trim.add(&record.id, record.seq.len(), keep_interval(record.seq.len(), &record_intervals, anchor))?;
+        }
+        total_hits += record_hits;
+    }
+    debug!("Scanned {} records in {:.2?}: {} hits", scanned_records, scan_started.elapsed(), total_hits);
+    if let Some(top) = top.as_mut() {
+        for held in top.drain_in_order().into_iter().flatten() {
+            held.write(&mut bed_writer)?;
+        }
+    }
+    if let Some(rate) = config.fdr {
+        write_significant(&mut bed_writer, fdr_held, fdr_tests, rate)?;
+    }
+    if let Some(distance) = config.pair_strands {
+        write_strand_pairs(&mut bed_writer, strand_held, distance)?;
+    }
+    if let Some((id, len)) = covered_contig {
+        finish_coverage(config, &mut bed_writer, coverage_summary.as_mut(), occupancy.as_mut(), &id, covered, len)?;
+    }
+    if let Some(summary) = coverage_summary {
+        summary.finish()?;
+    }
+    if let Some(mut occupancy) = occupancy {
+        occupancy.flush()?;
+    }
+    if let Some(contig_report) = contig_report {
+        contig_report.finish()?;
+    }
+    if let Some(trim) = trim {
+        finish_trim(trim)?;
+    }
+    if let Some(mut matrix) = composition_matrix {
+        matrix.flush()?;
+    }
+    if let (Some(path), Some(annotation)) = (&config.gene_report, &config.annotation) {
+        write_gene_report(path, annotation, &gene_tallies)?;
+    }
+    if let (Some(checkpoint), Some(contig)) = (&config.checkpoint, checkpoint_contig) {
+        drop(bed_writer);
+        output.flush()?;
+        checkpoint.lock().expect("no panics while holding the checkpoint").complete(&contig)?;
+    }
+
+    Ok(())
+}
+
+/// The records overlapping one gene, for `--gene-report`.
+#[derive(Debug, Default)]
+struct GeneTally {
+    hits: u64,
+    bases: GeneBases,
+    max_score: Option<f64>,
+}
+
+impl GeneTally {
+    fn add(&mut self, bases: &GeneBases, score: f64) {
+        self.hits += 1;
+        self.bases.cds += bases.cds;
+        self.bases.utr += bases.utr;
+        self.bases.intron += bases.intron;
+        self.max_score = Some(self.max_score.map_or(score, |max| max.max(score)));
+    }
+}
+
+/// Write a row per gene of the annotation, by contig name and position, with
+/// the records tallied against it.
+///
+///  columns: gene, chrom, start, end, hits, cds_bases, utr_bases, intron_bases, max_score ('.' without hits)
+fn write_gene_report(path: &Path, annotation: &Annotation, tallies: &HashMap<(String, String), GeneTally>) -> Result<(), Box<dyn Error>> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "gene\tchrom\tstart\tend\thits\tcds_bases\tutr_bases\tintron_bases\tmax_score")?;
+    let no_hits = GeneTally::default();
+    for (chrom, start, end, gene) in annotation.genes() {
+        let tally = tallies.get(&(chrom.to_string(), gene.to_string())).unwrap_or(&no_hits);
+        let max_score = tally.max_score.map_or(".".to_string(), |score| score.to_string());
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            gene, chrom, start, end, tally.hits, tally.bases.cds, tally.bases.utr, tally.bases.intron, max_score
+        )?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Bases covered by hits, per contig and genome-wide, for `--coverage-summary`.
+struct CoverageSummary {
+    writer: BufWriter<File>,
+    length: u64,
+    covered: u64,
+}
+
+impl CoverageSummary {
+    fn create(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "contig\tlength\tcovered_bases\tcovered_fraction")?;
+        Ok(CoverageSummary { writer, length: 0, covered: 0 })
+    }
+
+    fn add_contig(&mut self, name: &str, length: u64, covered: u64) -> Result<(), Box<dyn Error>> {
+        self.length += length;
+        self.covered += covered;
+        write_coverage_row(&mut self.writer, name, length, covered)
+    }
+
+    /// Write the genome-wide row.
+    fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        write_coverage_row(&mut self.writer, "genome", self.length, self.covered)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Every contig scanned and what became of it, for `--contig-report`.
+struct ContigReport {
+    writer: BufWriter<File>,
+    // The contig being scanned (its chunks come one after the other)
+    current: Option<ContigTally>,
+    contigs: u64,
+    without_records: u64,
+}
+
+#[derive(Debug)]
+struct ContigTally {
+    name: String,
+    length: u64,
+    records: u64,
+    excluded: u64,
+    too_short: bool,
+}
+
+impl ContigTally {
+    fn status(&self) -> &'static str {
+        if self.length == 0 {
+            "empty"
+        } else if self.too_short {
+            "shorter-than-window"
+        } else if self.records > 0 {
+            "reported"
+        } else if self.excluded > 0 {
+            "excluded"
+        } else {
+            "no-hits"
+        }
+    }
+}
+
+impl ContigReport {
+    fn create(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "contig\tlength\trecords\tstatus")?;
+        Ok(ContigReport { writer, current: None, contigs: 0, without_records: 0 })
+    }
+
+    /// Tally a scanned record: its records written, windows excluded, and
+    /// whether it was a contig too short for the window.
+    fn add(&mut self, record: &SeqRecord, records: u64, excluded: u64, too_short: bool) -> Result<(), Box<dyn Error>> {
+        // A contig starts again at 0; later chunks (or merged duplicates) carry on
+        let same = self.current.as_ref().is_some_and(|tally| tally.name == record.id) && record.start > 0;
+        if !same {
+            self.write_current()?;
+            self.current = Some(ContigTally { name: record.id.clone(), length: 0, records: 0, excluded: 0, too_short: false });
+        }
+        let tally = self.current.as_mut().expect("set above");
+        // The length of a streamed contig is only known with its last chunk
+        tally.length = record.contig_len as u64;
+        tally.records += records;
+        tally.excluded += excluded;
+        tally.too_short |= too_short;
+        Ok(())
+    }
+
+    fn write_current(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(tally) = self.current.take() {
+            self.contigs += 1;
+            if tally.records == 0 {
+                self.without_records += 1;
+            }
+            writeln!(self.writer, "{}\t{}\t{}\t{}", tally.name, tally.length, tally.records, tally.status())?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        self.write_current()?;
+        self.writer.flush()?;
+        info!("{} of {} contigs have no records (see --contig-report)", self.without_records, self.contigs);
+        Ok(())
+    }
+}
+
+fn write_coverage_row<W: Write>(writer: &mut W, name: &str, length: u64, covered: u64) -> Result<(), Box<dyn Error>> {
+    let fraction = if length > 0 { covered as f64 / length as f64 } else { 0.0 };
+    writeln!(writer, "{}\t{}\t{}\t{:.6}", name, length, covered, fraction)?;
+    Ok(())
+}
+
+/// Once a contig is done, write the intervals not covered by any record (as
+/// BED3, with --complement) or their union (as BED6, with --unique-bases),
+/// its line of the coverage summary and its support depth (with
+/// --occupancy).
+fn finish_coverage<W: Write>(
+    config: &ScanConfig,
+    writer: &mut BedWriter<W>,
+    summary: Option<&mut CoverageSummary>,
+    occupancy: Option<&mut BufWriter<File>>,
+    chrom: &str,
+    mut covered: CoverageIndex,
+    contig_len: u64,
+) -> Result<(), Box<dyn Error>> {
+    if config.unique_bases {
+        for interval in covered.union() {
+            let score = match config.score_precision {
+                Some(decimals) => BedScore::Decimal(interval.max_score, decimals),
+                None => BedScore::Percentage(interval.max_score),
+            };
+            write_bed_record(writer, chrom, interval.start, interval.end, &interval.names.join(","), score, ".", &[], None)?;
+        }
+    }
+    if let Some(occupancy) = occupancy {
+        for (start, end, depth) in covered.depth() {
+            writeln!(occupancy, "{}\t{}\t{}\t{}", chrom, start, end, depth)?;
+        }
+    }
+    let uncovered = covered.complement(contig_len);
+    if let Some(summary) = summary {
+        let uncovered_bases: u64 = uncovered.iter().map(|(start, end)| end - start).sum();
+        summary.add_contig(chrom, contig_len, contig_len - uncovered_bases)?;
+    }
+    if config.complement {
+        for (start, end) in uncovered {
+            writer.record(chrom, start, end).finish()?;
+        }
+    }
+    Ok(())
+}
+
+const COMPOSITION_MATRIX_HEADER: &str = "#chrom\tstart\tend\tname\tstrand\tA\tC\tG\tT\tN\tGC";
+
+/// Write one `--composition-matrix` row.
+///
+///  columns: chrom, start, end, name, strand, A, C, G, T, N, GC% of A/C/G/T ('.' if none)
+fn write_composition_matrix_row<W: Write>(
+    output: &mut W,
+    chrom: &str,
+    start: usize,
+    end: usize,
+    name: &str,
+    strand_symbol: &str,
+    comp: &Composition,
+) -> Result<(), Box<dyn Error>> {
+    let [a, c, g, t, n] = comp.counts();
+    let gc = match a + c + g + t {
+        0 => ".".to_string(),
+        bases => format!("{:.2}", (g + c) as f64 / bases as f64 * 100.0),
+    };
+    writeln!(output, "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}", chrom, start, end, name, strand_symbol, a, c, g, t, n, gc)?;
+    Ok(())
+}
+
+// Pieces per thread in each batch of a parallel scan, so threads finishing
+// short contigs early find more to do while memory stays bounded
+pub const PIECES_PER_THREAD: usize = 4;
+
+// Shortest piece a contig is split into for a parallel scan
+const MIN_PIECE: usize = 1_000_000;
+
+/// Where the records of a parallel scan come from.
+pub enum ParallelInput {
+    /// Read in turn by the writing thread
+    Records(Records),
+    /// Fetched by the workers from an indexed FASTA, only the given regions
+    /// (in file order) or every contig whole
+    Indexed(Arc<IndexedSource>, Option<Vec<Region>>),
+}
+
+// Contig ranges of an indexed FASTA to scan, in turn: the index position of
+// the contig, the bases and whether the contig is circular
+type IndexedSpans = Box<dyn Iterator<Item = Result<(usize, Range<u64>, bool), Box<dyn Error>>>>;
+
+/// Scan records on `threads` worker threads, writing each record's output in
+/// input order. Every record is scanned on its own, so options gathering
+/// records across contigs must not be set. With `split`, contigs are also
+/// cut into up to `threads` overlapping pieces (of at least a million
+/// bases), so a few huge chromosomes keep every thread busy.
+///
+/// Pieces are scanned a batch at a time on a rayon pool of `threads`
+/// threads, each into a buffer of its own, and this thread writes a batch's
+/// buffers in input order, so the output never depends on the number of
+/// threads or on which finishes first. Pieces of an indexed FASTA are
+/// batched as contig ranges, so the workers also read (and decompress) the
+/// input in parallel.
+pub fn scan_records_parallel<W: Write>(input: ParallelInput, config: &ScanConfig, mut output: W, threads: usize, split: bool) -> Result<(), Box<dyn Error>> {
+    let largest = config.windows[config.windows.len() - 1];
+    let limit = threads * PIECES_PER_THREAD;
+    // Circular contigs wrap around, and reads keep their alignment whole
+    let ranges = move |len: usize, whole: bool| -> Vec<Range<usize>> {
+        match split && whole {
+            true => piece_ranges(len, len.div_ceil(threads).max(MIN_PIECE), largest),
+            false => std::iter::once(0..len).collect(),
+        }
+    };
+    // The pieces of each record in turn
+    let mut pieces: Box<dyn Iterator<Item = Result<Vec<QueuedPiece>, Box<dyn Error>>>> = match input {
+        ParallelInput::Records(records) => Box::new(records.map(move |record| {
+            let record = record?;
+            let ranges = ranges(record.seq.len(), !record.circular && record.alignment.is_none());
+            Ok(QueuedPiece::queue(record, ranges))
+        })),
+        ParallelInput::Indexed(fasta, regions) => {
+            let spans: IndexedSpans = match regions {
+                Some(regions) => {
+                    let (fasta, regions) = (Arc::clone(&fasta), fasta.sorted_regions(&regions));
+                    Box::new(regions.into_iter().map(move |region| fasta.span(&region).map(|(position, range)| (position, range, false))))
+                }
+                None => {
+                    let fasta = Arc::clone(&fasta);
+                    Box::new((0..fasta.fai().entries.len()).map(move |position| {
+                        Ok((position, 0..fasta.fai().entries[position].length, fasta.is_circular(position)?))
+                    }))
+                }
+            };
+            Box::new(spans.map(move |span| {
+                let (position, span, circular) = span.map_err(PolyscanError::input)?;
+                let ranges = ranges((span.end - span.start) as usize, !circular);
+                let whole = ranges.len() == 1;
+                Ok(ranges
+                    .into_iter()
+                    .map(|range| QueuedPiece::Indexed {
+                        fasta: Arc::clone(&fasta),
+                        position,
+                        range: span.start + range.start as u64..span.start + range.end as u64,
+                        circular,
+                        whole,
+                    })
+                    .collect())
+            }))
+        }
+    };
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build()?;
+    loop {
+        // Take pieces up to the limit; a contig's pieces go in together
+        let mut batch: Vec<QueuedPiece> = Vec::with_capacity(limit + threads);
+        let mut read_error: Option<Box<dyn Error>> = None;
+        while batch.len() < limit {
+            match pieces.next() {
+                Some(Ok(record_pieces)) => batch.extend(record_pieces),
+                Some(Err(e)) => {
+                    // Reported once the records before it are written
+                    read_error = Some(e);
+                    break;
+                }
+                None => break,
+            }
+        }
+        if batch.is_empty() {
+            return read_error.map_or(Ok(()), Err);
+        }
+        // Errors come back as text (and whether they are input errors, to exit with the same code)
+        let outputs: Vec<Result<Vec<u8>, (bool, String)>> = pool.install(|| {
+            batch
+                .into_par_iter()
+                .map(|piece| {
+                    let mut buffer = Vec::new();
+                    // Records read in turn were renamed as they were read
+                    let indexed = matches!(piece, QueuedPiece::Indexed { .. });
+                    piece
+                        .into_record()
+                        .map(|mut record| {
+                            if indexed {
+                                rename_record(&mut record, &config.chrom_aliases);
+                            }
+                            record
+                        })
+                        .map_err(PolyscanError::input)
+                        .and_then(|record| scan_records(Box::new(std::iter::once(Ok(record))), config, &mut buffer, None))
+                        .map(|()| buffer)
+                        .map_err(|e| (matches!(e.downcast_ref::<PolyscanError>(), Some(PolyscanError::Input(_))), e.to_string()))
+                })
+                .collect()
+        });
+        for result in outputs {
+            match result {
+                Ok(buffer) => output.write_all(&buffer)?,
+                Err((true, e)) => return Err(Box::new(PolyscanError::Input(e))),
+                Err((false, e)) => return Err(e.into()),
+            }
+        }
+        if let Some(e) = read_error {
+            return Err(e);
+        }
+        output.flush()?;
+    }
+}
+
+/// A piece of a record waiting for a thread of a parallel scan. Read-in
+/// sequences wait packed at 2 bits per base, so a batch of large contigs
+/// takes about a quarter of the memory; mapped ones are already on disk, and
+/// indexed ones are only fetched by the thread scanning them.
+enum QueuedPiece {
+    Mapped(SeqRecord),
+    Packed { record: Arc<SeqRecord>, packed: Arc<PackedSequence>, range: Range<usize>, whole: bool },
+    Indexed { fasta: Arc<IndexedSource>, position: usize, range: Range<u64>, circular: bool, whole: bool },
+}
+
+impl QueuedPiece {
+    /// Queue the pieces of `record` in `ranges` of its sequence (one range
+    /// for the whole record).
+    fn queue(mut record: SeqRecord, ranges: Vec<Range<usize>>) -> Vec<QueuedPiece> {
+        let whole = ranges.len() == 1;
+        if let Sequence::Mapped { .. } = record.seq {
+            return ranges.into_iter().map(|range| QueuedPiece::Mapped(piece_record(&record, record.seq.slice(range.clone()), range.start, whole))).collect();
+        }
+        let packed = Arc::new(PackedSequence::from_seq(&record.seq));
+        record.seq = Sequence::default();
+        let record = Arc::new(record);
+        ranges.into_iter().map(|range| QueuedPiece::Packed { record: Arc::clone(&record), packed: Arc::clone(&packed), range, whole }).collect()
+    }
+
+    /// The piece as a record to scan, unpacking or fetching its bases.
+    fn into_record(self) -> Result<SeqRecord, Box<dyn Error>> {
+        match self {
+            QueuedPiece::Mapped(record) => Ok(record),
+            QueuedPiece::Packed { record, packed, range, whole } => Ok(piece_record(&record, packed.unpack(range.clone()).into(), range.start, whole)),
+            QueuedPiece::Indexed { fasta, position, range, circular, whole } => {
+                let started = std::time::Instant::now();
+                let mut record = fasta.fetch_record(position, range, circular)?;
+                timings::add_reading(started.elapsed());
+                record.chunked = !whole;
+                Ok(record)
+            }
+        }
+    }
+}
+
+// The piece of `record` holding the bases `seq` from `offset` on; a piece
+// short of the whole record is a chunk of it, as with split_record
+fn piece_record(record: &SeqRecord, seq: Sequence, offset: usize, whole: bool) -> SeqRecord {
+    SeqRecord {
+        id: record.id.clone(),
+        seq,
+        start: record.start + offset,
+        contig_len: record.contig_len,
+        alignment: record.alignment.clone(),
+        chunked: record.chunked || !whole,
+        circular: record.circular,
+    }
+}
+
+// Give a record (and the reference it is aligned to) its output name
+pub fn rename_record(record: &mut SeqRecord, aliases: &HashMap<String, String>) {
+    if let Some(alias) = aliases.get(&record.id) {
+        record.id.clone_from(alias);
+    }
+    if let Some(alignment) = record.alignment.as_mut() {
+        if let Some(alias) = aliases.get(&alignment.reference) {
+            alignment.reference.clone_from(alias);
+        }
+    }
+}
+
+/// Open the `--trim-bed` and `--trim-tsv` outputs, if either is asked for.
+pub fn trim_writer(bed: Option<&Path>, tsv: Option<&Path>) -> Result<Option<TrimWriter<BufWriter<File>>>, Box<dyn Error>> {
+    if bed.is_none() && tsv.is_none() {
+        return Ok(None);
+    }
+    let create = |path: &Path| File::create(path).map(BufWriter::new);
+    Ok(Some(TrimWriter::new(bed.map(create).transpose()?, tsv.map(create).transpose()?)?))
+}
+
+pub fn finish_trim(trim: TrimWriter<BufWriter<File>>) -> Result<(), Box<dyn Error>> {
+    let (reads, trimmed) = trim.finish()?;
+    info!("{} of {} reads have a tail to trim", trimmed, reads);
+    Ok(())
+}
@@ -0,0 +1,108 @@
+//! Hit density plots of whole contigs, drawn with plotters to SVG or PNG
+//! files, for a quick look at where records cluster along an assembly.
+
+use std::error::Error;
+use std::path::Path;
+
+use plotters::coord::Shift;
+use plotters::prelude::*;
+
+use crate::svg::binned_coverage;
+
+const SIZE: (u32, u32) = (800, 160);
+const BAR: RGBColor = RGBColor(0x3b, 0x6e, 0xa5);
+const FRAME: RGBColor = RGBColor(0x99, 0x99, 0x99);
+
+/// Image formats of [`write_density_plot`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum PlotFormat {
+    /// Scalable vector graphics, with the contig name and axis labels
+    #[default]
+    Svg,
+    /// PNG images of the bars in a frame, without text
+    Png,
+}
+
+/// Plot the density of `intervals` (which must not overlap each other) along
+/// `contig`, `length` bases long, to `path`: `bins` bars, each as high as the
+/// fraction of its bases covered.
+///
+/// plotters has no font to rasterize text with here, so PNG plots leave out
+/// the contig name and axis labels.
+pub fn write_density_plot(
+    path: &Path,
+    format: PlotFormat,
+    contig: &str,
+    length: usize,
+    intervals: &[(usize, usize)],
+    bins: usize,
+) -> Result<(), Box<dyn Error>> {
+    match format {
+        PlotFormat::Svg => draw_density(SVGBackend::new(path, SIZE).into_drawing_area(), Some(contig), length, intervals, bins),
+        PlotFormat::Png => draw_density(BitMapBackend::new(path, SIZE).into_drawing_area(), None, length, intervals, bins),
+    }
+}
+
+// Draw the plot on `root`, titled with `contig` and with labelled axes when
+// given one, or else in a bare frame
+fn draw_density<DB: DrawingBackend>(
+    root: DrawingArea<DB, Shift>,
+    contig: Option<&str>,
+    length: usize,
+    intervals: &[(usize, usize)],
+    bins: usize,
+) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let density = binned_coverage(length, intervals, bins);
+    let bin_start = |b: usize| (b * length / bins) as u64;
+
+    root.fill(&WHITE)?;
+    let mut builder = ChartBuilder::on(&root);
+    builder.margin(10);
+    if let Some(contig) = contig {
+        builder.caption(contig, ("sans-serif", 14)).x_label_area_size(30).y_label_area_size(40);
+    }
+    let x_range = 0..length.max(1) as u64;
+    let mut chart = builder.build_cartesian_2d(x_range.clone(), 0.0..1.0)?;
+    if contig.is_some() {
+        chart.configure_mesh().disable_mesh().x_desc("bp").y_labels(3).draw()?;
+    }
+    let bars = density.iter().enumerate().filter(|(_, value)| **value > 0.0);
+    chart.draw_series(bars.map(|(b, &value)| Rectangle::new([(bin_start(b), 0.0), (bin_start(b + 1), value)], BAR.filled())))?;
+    if contig.is_none() {
+        chart.plotting_area().draw(&Rectangle::new([(x_range.start, 0.0), (x_range.end, 1.0)], FRAME))?;
+    }
+    root.present()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plot(format: PlotFormat, name: &str) -> Vec<u8> {
+        let path = std::env::temp_dir().join(format!("polyscan-plot-{}-{}", std::process::id(), name));
+        write_density_plot(&path, format, "chr1", 1000, &[(0, 500)], 10).unwrap();
+        let image = std::fs::read(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        image
+    }
+
+    #[test]
+    fn svg_plots_are_titled_with_the_contig() {
+        let svg = String::from_utf8(plot(PlotFormat::Svg, "chr1.svg")).unwrap();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("chr1"));
+        // The bars of the covered half, in the bar color
+        assert_eq!(svg.matches("#3B6EA5").count(), 5, "{}", svg);
+    }
+
+    #[test]
+    fn png_plots_are_pngs() {
+        let png = plot(PlotFormat::Png, "chr1.png");
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    }
+}
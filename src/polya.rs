@@ -0,0 +1,146 @@
+//! Poly(A) tail lengths at the ends of reads, for `--mode polya`.
+
+use crate::scan::BaseClass;
+
+/// The poly(A) tail of a read: A bases at its 3' end, or T bases at its 5'
+/// end when the read is of the other strand.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tail {
+    /// `b'A'` for a poly(A) tail at the 3' end, `b'T'` for a poly(T) head at the 5' end
+    pub base: u8,
+    pub start: usize,
+    pub end: usize,
+    /// Percentage of the tail's bases that are `base`
+    pub purity: f64,
+}
+
+impl Tail {
+    pub fn length(&self) -> usize {
+        self.end - self.start
+    }
+}
+
+/// Estimate the poly(A) tail of a read: the longer of a poly(A) tract at its
+/// 3' end and a poly(T) tract at its 5' end, or `None` if it has neither.
+///
+/// A read has a tail if the `window` bases at its end are at least
+/// `percentage`% A (or T). The tail is then the highest-scoring stretch
+/// anchored at that end, scoring each tail base +1 and every other base
+/// `-percentage / (100 - percentage)` (-4 at 80%), so stretches break even at
+/// exactly `percentage`% purity. The tail therefore stops where the read turns
+/// mostly impure, instead of carrying a pure seed over the bases upstream of
+/// it. Impure bases at the very end are trimmed, so a tail may stop a few
+/// bases short of the end.
+///
+/// ```
+/// # use polyscan::polya::find_tail;
+/// let tail = find_tail(b"ACGTACGTCCAAAAAAAAAAAAAAAA", 10, 80.0).unwrap();
+/// assert_eq!((tail.base, tail.length()), (b'A', 16));
+/// ```
+pub fn find_tail(seq: &[u8], window: usize, percentage: f64) -> Option<Tail> {
+    let poly_a = end_tract(seq, window, percentage, b'A', true);
+    let poly_t = end_tract(seq, window, percentage, b'T', false);
+    match (poly_a, poly_t) {
+        (Some(a), Some(t)) if t.length() > a.length() => Some(t),
+        (Some(a), _) => Some(a),
+        (None, t) => t,
+    }
+}
+
+// The tract of `base` anchored at the end (`three_prime`) or start of `seq`
+fn end_tract(seq: &[u8], window: usize, percentage: f64, base: u8, three_prime: bool) -> Option<Tail> {
+    if window == 0 || seq.len() < window {
+        return None;
+    }
+    let class = BaseClass::new(&[base]);
+    let seed = if three_prime { seq.len() - window } else { 0 };
+    let count = seq[seed..seed + window].iter().filter(|&&nuc| class.contains(nuc)).count();
+    if (count as f64 / window as f64) * 100.0 < percentage {
+        return None;
+    }
+
+    // Every other base costs as much as `percentage / (100 - percentage)`
+    // tail bases, or more than the whole read at 100%
+    let penalty = if percentage >= 100.0 { seq.len() as f64 + 1.0 } else { percentage / (100.0 - percentage) };
+    let (mut score, mut best, mut inner) = (0.0, 0.0, None);
+    for i in 0..seq.len() {
+        let pos = if three_prime { seq.len() - 1 - i } else { i };
+        score += if class.contains(seq[pos]) { 1.0 } else { -penalty };
+        // Ties keep the shorter tail
+        if score > best {
+            (best, inner) = (score, Some(pos));
+        }
+    }
+    let inner = inner?;
+    // The inner boundary falls on a tail base already; trim the outer one
+    let (mut start, mut end) = if three_prime { (inner, seq.len()) } else { (0, inner + 1) };
+    if three_prime {
+        while !class.contains(seq[end - 1]) {
+            end -= 1;
+        }
+    } else {
+        while !class.contains(seq[start]) {
+            start += 1;
+        }
+    }
+    let count = seq[start..end].iter().filter(|&&nuc| class.contains(nuc)).count();
+    let purity = (count as f64 / (end - start) as f64) * 100.0;
+    Some(Tail { base, start, end, purity })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulate::Rng;
+
+    // `len` random bases, none of them `base`, so the flank can't extend a tail
+    fn flank(rng: &mut Rng, len: usize, base: u8) -> Vec<u8> {
+        let others: Vec<u8> = b"ACGT".iter().copied().filter(|&other| other != base).collect();
+        (0..len).map(|_| others[rng.below(others.len())]).collect()
+    }
+
+    #[test]
+    fn tails_stop_at_random_flanks() {
+        let mut rng = Rng::new(7);
+        for _ in 0..200 {
+            let mut read = flank(&mut rng, 100, b'A');
+            read.extend([b'A'; 60]);
+            let tail = find_tail(&read, 10, 80.0).unwrap();
+            assert_eq!((tail.base, tail.start, tail.end), (b'A', 100, 160), "{}", String::from_utf8_lossy(&read));
+        }
+    }
+
+    #[test]
+    fn heads_stop_at_random_flanks() {
+        let mut rng = Rng::new(11);
+        for _ in 0..200 {
+            let mut read = vec![b'T'; 40];
+            read.extend(flank(&mut rng, 100, b'T'));
+            let tail = find_tail(&read, 10, 80.0).unwrap();
+            assert_eq!((tail.base, tail.start, tail.end), (b'T', 0, 40), "{}", String::from_utf8_lossy(&read));
+        }
+    }
+
+    #[test]
+    fn tails_stay_near_their_length_past_any_flank() {
+        // A flank may end in A by chance, which no estimate can tell from the tail
+        let mut rng = Rng::new(3);
+        let mut lengths = Vec::new();
+        for _ in 0..500 {
+            let mut read: Vec<u8> = (0..100).map(|_| b"ACGT"[rng.below(4)]).collect();
+            read.extend([b'A'; 60]);
+            lengths.push(find_tail(&read, 10, 80.0).unwrap().length());
+        }
+        lengths.sort_unstable();
+        assert_eq!(lengths[lengths.len() / 2], 60);
+        assert!(lengths[lengths.len() - 1] < 70, "{:?}", lengths);
+    }
+
+    #[test]
+    fn impure_tails_and_ends() {
+        // One C in the tail is carried over, the CG past it is trimmed
+        assert_eq!(find_tail(b"ACGTACGTAAAAAAAAAACAAAAAAAAAACG", 10, 80.0).map(|tail| (tail.start, tail.end)), Some((8, 29)));
+        assert_eq!(find_tail(b"ACGTACGTAAAAAAAAAAAAAAAAAAAA", 10, 100.0).map(|tail| tail.length()), Some(20));
+        assert_eq!(find_tail(b"AAAAAAAAAAAACGTACGTACGTCC", 10, 80.0), None);
+    }
+}
@@ -0,0 +1,133 @@
+//! Human-friendly tables for the terminal, for `--pretty`: aligned columns,
+//! thousands separators, scores in color and a closing summary.
+
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+
+// ANSI escapes for the best, middle and lowest third of the scores
+const HIGH: &str = "\x1b[1;31m";
+const MIDDLE: &str = "\x1b[33m";
+const LOW: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+/// Holds back every line written to it until [`PrettyWriter::finish`], then
+/// writes the tab-separated ones as a table: columns padded to a common
+/// width (numbers to the right), positions with thousands separators, and,
+/// with `color`, BED records colored by their score within the range of
+/// scores written. Lines that are not tab-separated (such as comments) are
+/// written first, as they are; a summary line comes last.
+///
+/// The whole output is held in memory.
+pub struct PrettyWriter<W: Write> {
+    inner: W,
+    color: bool,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> PrettyWriter<W> {
+    pub fn new(inner: W, color: bool) -> Self {
+        PrettyWriter { inner, color, buf: Vec::new() }
+    }
+
+    /// Write the table of the lines held so far and flush the underlying writer.
+    pub fn finish(&mut self) -> io::Result<()> {
+        let buf = std::mem::take(&mut self.buf);
+        let text = String::from_utf8_lossy(&buf);
+        let (rows, others): (Vec<&str>, Vec<&str>) = text.lines().partition(|line| line.contains('\t'));
+        for line in others {
+            writeln!(self.inner, "{}", line)?;
+        }
+
+        let rows: Vec<Vec<String>> = rows.iter().map(|row| row.split('\t').enumerate().map(|(i, cell)| pretty_cell(i, cell)).collect()).collect();
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let width = |i: usize| rows.iter().filter_map(|row| row.get(i)).map(|cell| cell.chars().count()).max().unwrap_or(0);
+        let widths: Vec<usize> = (0..columns).map(width).collect();
+        // Column headers start with #
+        let data = || rows.iter().filter(|row| !row[0].starts_with('#'));
+        // A column is right-aligned when every value in it is a number
+        let numeric: Vec<bool> = (0..columns).map(|i| data().filter_map(|row| row.get(i)).all(|cell| is_number(cell))).collect();
+
+        let scores: Vec<Option<f64>> = rows.iter().map(|row| bed_score(row)).collect();
+        let (low, high) = scores.iter().flatten().fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), &score| (low.min(score), high.max(score)));
+        for (row, score) in rows.iter().zip(&scores) {
+            let cells: Vec<String> = row
+                .iter()
+                .enumerate()
+                .map(|(i, cell)| if numeric[i] { format!("{:>1$}", cell, widths[i]) } else { format!("{:<1$}", cell, widths[i]) })
+                .collect();
+            let line = cells.join("  ");
+            let line = line.trim_end();
+            match score.filter(|_| self.color && high > low) {
+                Some(score) => {
+                    let tier = (score - low) / (high - low);
+                    let style = if tier >= 2.0 / 3.0 {
+                        HIGH
+                    } else if tier >= 1.0 / 3.0 {
+                        MIDDLE
+                    } else {
+                        LOW
+                    };
+                    writeln!(self.inner, "{}{}{}", style, line, RESET)?;
+                }
+                None => writeln!(self.inner, "{}", line)?,
+            }
+        }
+
+        let records = data().count();
+        if records > 0 {
+            let contigs: BTreeSet<&str> = data().map(|row| row[0].as_str()).collect();
+            let plural = |n: usize| if n == 1 { "" } else { "s" };
+            let mut summary =
+                format!("{} record{} on {} contig{}", with_separators(&records.to_string()), plural(records), contigs.len(), plural(contigs.len()));
+            if high >= low {
+                summary.push_str(&format!(", scores {} to {}", low, high));
+            }
+            writeln!(self.inner, "{}", summary)?;
+        }
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Write for PrettyWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    // Nothing is written before `finish`
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Positions (the second and third columns) get thousands separators
+fn pretty_cell(column: usize, cell: &str) -> String {
+    match column {
+        1 | 2 if !cell.is_empty() && cell.bytes().all(|c| c.is_ascii_digit()) => with_separators(cell),
+        _ => cell.to_string(),
+    }
+}
+
+/// `digits` with a comma between every group of three, e.g. 1,234,567.
+pub fn with_separators(digits: &str) -> String {
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    grouped
+}
+
+fn is_number(cell: &str) -> bool {
+    cell.replace(',', "").parse::<f64>().is_ok()
+}
+
+// The score of a BED6 record (a numeric fifth column before a strand)
+fn bed_score(row: &[String]) -> Option<f64> {
+    match row {
+        [_, _, _, _, score, strand, ..] if matches!(strand.as_str(), "+" | "-" | ".") => score.parse().ok(),
+        _ => None,
+    }
+}
@@ -0,0 +1,65 @@
+use indicatif::{ProgressBar, ProgressStyle};
+
+use crate::input::{Records, SeqRecord};
+
+/// Records passed through unchanged while a progress bar on stderr counts the
+/// bases of those already scanned.
+///
+/// The bar advances as each record is finished with (each contig, or each
+/// chunk of a streamed contig) and shows the contig being scanned. With the
+/// `total` bases known (e.g. from a `.fai`), it also shows how far along the
+/// scan is and an ETA; otherwise it only counts.
+struct ProgressRecords {
+    records: Records,
+    bar: ProgressBar,
+    /// Contig of the last record and how far into it the records have reached
+    last: Option<(String, usize)>,
+    /// Bases of the last record, counted once the next one is asked for
+    pending: u64,
+}
+
+impl Iterator for ProgressRecords {
+    type Item = <Records as Iterator>::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.bar.inc(self.pending);
+        self.pending = 0;
+        let Some(result) = self.records.next() else {
+            self.bar.finish_with_message("done");
+            return None;
+        };
+        if let Ok(record) = &result {
+            self.pending = new_bases(&self.last, record);
+            self.last = Some((record.id.clone(), record.start + record.seq.len()));
+            self.bar.set_message(record.id.clone());
+        }
+        Some(result)
+    }
+}
+
+// Bases of `record` not already counted: chunks of a contig overlap the
+// previous chunk by a window
+fn new_bases(last: &Option<(String, usize)>, record: &SeqRecord) -> u64 {
+    let end = record.start + record.seq.len();
+    match last {
+        Some((id, reached)) if *id == record.id && record.chunked => end.saturating_sub(*reached) as u64,
+        _ => record.seq.len() as u64,
+    }
+}
+
+/// Show the progress of scanning `records` on stderr, out of `total` bases
+/// when known. Nothing is drawn when stderr isn't a terminal.
+pub fn with_progress(records: Records, total: Option<u64>) -> Records {
+    let bar = match total {
+        Some(total) => ProgressBar::new(total).with_style(
+            ProgressStyle::with_template("{spinner} {msg:12} [{elapsed_precise}] [{wide_bar}] {human_pos}/{human_len} bp (ETA {eta})")
+                .expect("valid template")
+                .progress_chars("=> "),
+        ),
+        None => ProgressBar::no_length()
+            .with_style(ProgressStyle::with_template("{spinner} {msg:12} [{elapsed_precise}] {human_pos} bp").expect("valid template")),
+    };
+    // Keep ticking through long contigs, so the scan doesn't look hung
+    bar.enable_steady_tick(std::time::Duration::from_millis(200));
+    Box::new(ProgressRecords { records, bar, last: None, pending: 0 })
+}
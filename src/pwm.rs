@@ -0,0 +1,350 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::bases::nuc_code;
+use crate::scan::{ScanHit, Steps, Strand};
+
+/// Pseudo-frequency added to every matrix cell, so bases never seen at a
+/// position are penalised rather than ruled out.
+const PSEUDO_FREQUENCY: f64 = 0.01;
+
+/// A position weight matrix: per-position log2-odds scores for A, C, G and T
+/// against a uniform background.
+#[derive(Debug, Clone)]
+pub struct Pwm {
+    pub name: String,
+    weights: Vec<[f64; 4]>,
+}
+
+impl Pwm {
+    /// Build from per-position counts or probabilities (columns A, C, G, T).
+    pub fn from_counts(name: &str, counts: &[[f64; 4]]) -> Self {
+        let weights = counts
+            .iter()
+            .map(|column| {
+                let total: f64 = column.iter().sum();
+                column.map(|count| {
+                    let frequency = if total > 0.0 { count / total } else { 0.25 };
+                    let frequency = (frequency + PSEUDO_FREQUENCY) / (1.0 + 4.0 * PSEUDO_FREQUENCY);
+                    (frequency / 0.25).log2()
+                })
+            })
+            .collect();
+        Pwm { name: name.to_string(), weights }
+    }
+
+    /// Motif length.
+    pub fn len(&self) -> usize {
+        self.weights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.weights.is_empty()
+    }
+
+    /// The matrix for the opposite strand: positions reversed, A<->T and C<->G swapped.
+    pub fn reverse_complement(&self) -> Self {
+        let weights = self.weights.iter().rev().map(|&[a, c, g, t]| [t, g, c, a]).collect();
+        Pwm { name: self.name.clone(), weights }
+    }
+
+    fn min_score(&self) -> f64 {
+        self.weights.iter().map(|column| column.iter().copied().fold(f64::INFINITY, f64::min)).sum()
+    }
+
+    fn max_score(&self) -> f64 {
+        self.weights.iter().map(|column| column.iter().copied().fold(f64::NEG_INFINITY, f64::max)).sum()
+    }
+
+    /// Raw score of the site starting at `pos`, or `None` if it holds a base
+    /// other than A/C/G/T/U.
+    fn site_score(&self, seq: &[u8], pos: usize) -> Option<f64> {
+        let mut score = 0.0;
+        for (column, &nuc) in self.weights.iter().zip(&seq[pos..pos + self.len()]) {
+            score += column[nuc_code(nuc)?];
+        }
+        Some(score)
+    }
+}
+
+/// Scores windows by their best PWM site, as a relative score: 0 for the
+/// worst possible site and 100 for the consensus.
+#[derive(Debug, Clone)]
+pub struct PwmScorer {
+    pwm: Pwm,
+    min: f64,
+    max: f64,
+    cutoff: f64,
+}
+
+impl PwmScorer {
+    /// Passes windows holding a site with a relative score of at least `cutoff` (0-100).
+    pub fn new(pwm: Pwm, cutoff: f64) -> Self {
+        PwmScorer { min: pwm.min_score(), max: pwm.max_score(), pwm, cutoff }
+    }
+
+    pub fn pwm(&self) -> &Pwm {
+        &self.pwm
+    }
+
+    fn relative_score(&self, score: f64) -> f64 {
+        if self.max > self.min {
+            (score - self.min) / (self.max - self.min) * 100.0
+        } else {
+            100.0
+        }
+    }
+}
+
+/// Read the matrices of a JASPAR (`>ID NAME` followed by `A [ ... ]` rows, or
+/// bare count rows) or MEME (`MOTIF` / `letter-probability matrix:`) file.
+pub fn read_pwms<P: AsRef<Path>>(path: P) -> Result<Vec<Pwm>, Box<dyn Error>> {
+    let path = path.as_ref();
+    let lines: Vec<String> = BufReader::new(File::open(path)?).lines().collect::<Result<_, _>>()?;
+
+    let pwms = if lines.iter().any(|line| line.starts_with("MOTIF")) {
+        parse_meme(&lines)
+    } else {
+        parse_jaspar(&lines)
+    }
+    .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    if pwms.is_empty() {
+        return Err(format!("{}: no matrices found", path.display()).into());
+    }
+    Ok(pwms)
+}
+
+fn parse_numbers(text: &str) -> Result<Vec<f64>, String> {
+    text.split_whitespace()
+        .map(|v| v.parse::<f64>().map_err(|_| format!("invalid matrix value '{}'", v)))
+        .collect()
+}
+
+fn parse_jaspar(lines: &[String]) -> Result<Vec<Pwm>, String> {
+    fn finish(name: &str, rows: &[Vec<f64>]) -> Result<Pwm, String> {
+        if rows.len() != 4 || rows.iter().any(|row| row.len() != rows[0].len()) || rows[0].is_empty() {
+            return Err(format!("matrix '{}' needs 4 rows (A, C, G, T) of equal length", name));
+        }
+        let counts: Vec<[f64; 4]> = (0..rows[0].len()).map(|i| [rows[0][i], rows[1][i], rows[2][i], rows[3][i]]).collect();
+        Ok(Pwm::from_counts(name, &counts))
+    }
+
+    let mut pwms = Vec::new();
+    let mut name: Option<String> = None;
+    let mut rows: Vec<Vec<f64>> = Vec::new();
+    for line in lines {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('>') {
+            if let Some(name) = name.take() {
+                pwms.push(finish(&name, &rows)?);
+            }
+            // Prefer the readable name (e.g. "Arnt") over the matrix ID
+            let fields: Vec<&str> = header.split_whitespace().collect();
+            name = Some(fields.get(1).or(fields.first()).unwrap_or(&"pwm").to_string());
+            rows.clear();
+            continue;
+        }
+
+        // "A  [ 4 19 0 ]" or plain "4 19 0"; rows are taken in A, C, G, T order
+        let values = line.trim_start_matches(|c: char| c.is_ascii_alphabetic()).replace(['[', ']'], " ");
+        rows.push(parse_numbers(&values)?);
+        if name.is_none() {
+            name = Some("pwm".to_string());
+        }
+    }
+    if let Some(name) = name {
+        pwms.push(finish(&name, &rows)?);
+    }
+    Ok(pwms)
+}
+
+fn parse_meme(lines: &[String]) -> Result<Vec<Pwm>, String> {
+    let mut pwms = Vec::new();
+    let mut lines = lines.iter().map(|line| line.trim()).peekable();
+    while let Some(line) = lines.next() {
+        let Some(header) = line.strip_prefix("MOTIF") else {
+            continue;
+        };
+        let fields: Vec<&str> = header.split_whitespace().collect();
+        let name = fields.get(1).or(fields.first()).unwrap_or(&"pwm").to_string();
+
+        // Skip to the probability matrix, then read rows until a non-numeric line
+        for line in lines.by_ref() {
+            if line.starts_with("letter-probability matrix") {
+                break;
+            }
+        }
+        let mut counts: Vec<[f64; 4]> = Vec::new();
+        while let Some(line) = lines.peek() {
+            if line.is_empty() {
+                lines.next();
+                if counts.is_empty() {
+                    continue;
+                }
+                break;
+            }
+            let Ok(row) = parse_numbers(line) else {
+                break;
+            };
+            if row.len() != 4 {
+                return Err(format!("matrix '{}' rows need 4 columns (A, C, G, T)", name));
+            }
+            counts.push([row[0], row[1], row[2], row[3]]);
+            lines.next();
+        }
+        if counts.is_empty() {
+            return Err(format!("matrix '{}' has no rows", name));
+        }
+        pwms.push(Pwm::from_counts(&name, &counts));
+    }
+    Ok(pwms)
+}
+
+/// Slide a `window`-sized window across `seq` and call `emit` for every
+/// (window, matrix) pair whose best site passes the cutoff, among the windows
+/// starting on `steps`.
+///
+/// Sites must lie wholly inside the window; the hit score is the best site's
+/// relative score. Like [`scan_windows`](crate::scan::scan_windows), hits for
+/// the same window are emitted in scorer order and carry the scorer's index.
+pub fn scan_pwms<F>(
+    seq: &[u8],
+    window: usize,
+    steps: Steps,
+    scorers: &[(&PwmScorer, Strand)],
+    mut emit: F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(ScanHit) -> Result<(), Box<dyn Error>>,
+{
+    if window == 0 || seq.len() < window {
+        return Ok(());
+    }
+
+    // Per scorer: candidate sites (position, relative score) with decreasing
+    // scores, so the front is always the window's best site
+    let mut best: Vec<VecDeque<(usize, f64)>> = scorers.iter().map(|_| VecDeque::new()).collect();
+    let push_site = |best: &mut VecDeque<(usize, f64)>, scorer: &PwmScorer, pos: usize| {
+        if let Some(score) = scorer.pwm.site_score(seq, pos) {
+            let score = scorer.relative_score(score);
+            while best.back().is_some_and(|&(_, s)| s <= score) {
+                best.pop_back();
+            }
+            best.push_back((pos, score));
+        }
+    };
+
+    for start in 0..=(seq.len() - window) {
+        let end = start + window;
+        for (index, ((scorer, strand), best)) in scorers.iter().zip(best.iter_mut()).enumerate() {
+            let k = scorer.pwm.len();
+            if k == 0 || k > window {
+                continue;
+            }
+
+            if start == 0 {
+                for pos in 0..=(window - k) {
+                    push_site(best, scorer, pos);
+                }
+            } else {
+                push_site(best, scorer, end - k);
+                while best.front().is_some_and(|&(pos, _)| pos < start) {
+                    best.pop_front();
+                }
+            }
+
+            if let Some(&(_, score)) = best.front().filter(|_| steps.contains(start)) {
+                if score >= scorer.cutoff {
+                    emit(ScanHit { start, end, score, strand: *strand, scorer: index })?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(String::from).collect()
+    }
+
+    // Consensus TATA: A at the second and fourth positions, T at the others
+    const JASPAR: &str = ">MA0001.1 TBP\n\
+                          A [ 0 10 0 9 ]\n\
+                          C [ 0 0 0 1 ]\n\
+                          G [ 0 0 0 0 ]\n\
+                          T [ 10 0 10 0 ]\n";
+
+    fn best_scores(seq: &[u8], pwm: Pwm, window: usize) -> Vec<(usize, f64)> {
+        let scorer = PwmScorer::new(pwm, 0.0);
+        let mut hits = Vec::new();
+        scan_pwms(seq, window, Steps::EVERY_BASE, &[(&scorer, Strand::Plus)], |hit| {
+            hits.push((hit.start, hit.score));
+            Ok(())
+        })
+        .unwrap();
+        hits
+    }
+
+    #[test]
+    fn jaspar_matrices_are_parsed() {
+        let pwms = parse_jaspar(&lines(&format!("{}\n>MA0002.1\n1 2\n3 4\n5 6\n7 8\n", JASPAR))).unwrap();
+        assert_eq!(pwms.iter().map(|pwm| (pwm.name.as_str(), pwm.len())).collect::<Vec<_>>(), [("TBP", 4), ("MA0002.1", 2)]);
+        // Bare rows without a header
+        let pwms = parse_jaspar(&lines("1 0\n0 1\n0 0\n0 0\n")).unwrap();
+        assert_eq!((pwms[0].name.as_str(), pwms[0].len()), ("pwm", 2));
+    }
+
+    #[test]
+    fn meme_matrices_are_parsed() {
+        let meme = "MEME version 4\n\nALPHABET= ACGT\n\n\
+                    MOTIF MA0001.1 TBP\n\
+                    letter-probability matrix: alength= 4 w= 3 nsites= 10\n\
+                    0.0 0.0 0.0 1.0\n\
+                    1.0 0.0 0.0 0.0\n\
+                    0.25 0.25 0.25 0.25\n\n\
+                    URL http://example.org\n";
+        let pwms = parse_meme(&lines(meme)).unwrap();
+        assert_eq!(pwms.iter().map(|pwm| (pwm.name.as_str(), pwm.len())).collect::<Vec<_>>(), [("TBP", 3)]);
+    }
+
+    #[test]
+    fn malformed_matrices_are_rejected() {
+        let error = |text: &str| parse_jaspar(&lines(text)).unwrap_err();
+        assert_eq!(error(">m\nA [ 1 2 ]\nC [ 1 x ]\nG [ 1 2 ]\nT [ 1 2 ]\n"), "invalid matrix value 'x'");
+        assert_eq!(error(">m\nA [ 1 2 ]\nC [ 1 2 ]\nG [ 1 2 ]\n"), "matrix 'm' needs 4 rows (A, C, G, T) of equal length");
+        assert_eq!(error(">m\nA [ 1 2 ]\nC [ 1 2 ]\nG [ 1 ]\nT [ 1 2 ]\n"), "matrix 'm' needs 4 rows (A, C, G, T) of equal length");
+        assert_eq!(error(">m\n>n\n1\n1\n1\n1\n"), "matrix 'm' needs 4 rows (A, C, G, T) of equal length");
+        let meme = "MOTIF m\nletter-probability matrix:\n0.5 0.5 0.0\n";
+        assert_eq!(parse_meme(&lines(meme)).unwrap_err(), "matrix 'm' rows need 4 columns (A, C, G, T)");
+        assert_eq!(parse_meme(&lines("MOTIF m\nletter-probability matrix:\n\n")).unwrap_err(), "matrix 'm' has no rows");
+    }
+
+    #[test]
+    fn the_consensus_scores_100_and_the_worst_site_0() {
+        let pwm = parse_jaspar(&lines(JASPAR)).unwrap().remove(0);
+        assert_eq!(best_scores(b"TATA", pwm.clone(), 4), [(0, 100.0)]);
+        assert_eq!(best_scores(b"GCGG", pwm.clone(), 4), [(0, 0.0)]);
+        // The window's best site counts, wherever it lies in the window
+        let hits = best_scores(b"GGTATAGG", pwm.clone(), 6);
+        assert_eq!(hits.iter().map(|&(start, score)| (start, score == 100.0)).collect::<Vec<_>>(), [(0, true), (1, true), (2, true)]);
+        // TATC is second best, and sites with N are not sites
+        let second = best_scores(b"TATC", pwm.clone(), 4)[0].1;
+        assert!(0.0 < second && second < 100.0);
+        assert!(best_scores(b"TANA", pwm.clone(), 4).is_empty());
+        // The reverse complement matrix finds the consensus on the other strand
+        assert_eq!(best_scores(b"TATA", pwm.reverse_complement(), 4), [(0, 100.0)]);
+        assert!((best_scores(b"GATA", pwm.reverse_complement(), 4)[0].1 - second).abs() < 1e-9);
+    }
+}
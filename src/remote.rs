@@ -0,0 +1,104 @@
+//! Reading input over HTTP(S) and from S3, for `--fasta https://...` and
+//! `--fasta s3://...`.
+//!
+//! Remote files are streamed as they download, through the same
+//! decompression as local ones. With a `.fai` index next to an uncompressed
+//! FASTA, regions are fetched with range requests instead.
+
+use std::error::Error;
+use std::io::Read;
+use std::ops::Range;
+
+use crate::faidx::{definition_line, FaiEntry, FaiIndex};
+
+/// Is `path` a URL to read over the network rather than a local file?
+pub fn is_remote(path: &str) -> bool {
+    ["http://", "https://", "s3://"].iter().any(|scheme| path.starts_with(scheme))
+}
+
+/// The HTTPS URL of `path`: `s3://BUCKET/KEY` becomes a request to the
+/// bucket's endpoint (`AWS_ENDPOINT_URL` if set, for S3-compatible stores),
+/// other URLs are left as they are.
+///
+/// Requests are not signed, so S3 objects must be public or reached through
+/// a presigned `https://` URL.
+///
+/// ```
+/// # use polyscan::remote::object_url;
+/// # if std::env::var_os("AWS_ENDPOINT_URL").is_none() {
+/// assert_eq!(object_url("s3://refs/hg38.fa.gz"), "https://refs.s3.amazonaws.com/hg38.fa.gz");
+/// # }
+/// ```
+pub fn object_url(path: &str) -> String {
+    match path.strip_prefix("s3://").and_then(|rest| rest.split_once('/')) {
+        Some((bucket, key)) => match std::env::var("AWS_ENDPOINT_URL") {
+            // S3-compatible stores (MinIO, Ceph) take the bucket in the path
+            Ok(endpoint) if !endpoint.is_empty() => format!("{}/{}/{}", endpoint.trim_end_matches('/'), bucket, key),
+            _ => format!("https://{}.s3.amazonaws.com/{}", bucket, key),
+        },
+        None => path.to_string(),
+    }
+}
+
+/// Stream the object at `path` (a URL, see [`object_url`]) as it downloads.
+pub fn open_remote(path: &str) -> Result<Box<dyn Read + Send>, Box<dyn Error>> {
+    let response = ureq::get(&object_url(path)).call().map_err(|e| format!("could not fetch {}: {}", path, e))?;
+    Ok(Box::new(response.into_body().into_reader()))
+}
+
+/// Bytes `range` of the object at `path`, by a range request.
+pub fn fetch_range(path: &str, range: Range<u64>) -> Result<Vec<u8>, Box<dyn Error>> {
+    if range.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut response = ureq::get(&object_url(path))
+        .header("Range", format!("bytes={}-{}", range.start, range.end - 1))
+        .call()
+        .map_err(|e| format!("could not fetch {}: {}", path, e))?;
+    // A server ignoring the range would send the whole object
+    if response.status() != 206 {
+        return Err(format!("{} does not support range requests", path).into());
+    }
+    let bytes = response.body_mut().with_config().limit(u64::MAX).read_to_vec()?;
+    if bytes.len() as u64 != range.end - range.start {
+        return Err(format!("{} sent {} bytes for a range of {}", path, bytes.len(), range.end - range.start).into());
+    }
+    Ok(bytes)
+}
+
+/// A remote uncompressed FASTA with a `.fai` index next to it, whose
+/// sequences are fetched by range requests.
+pub struct RemoteFasta {
+    path: String,
+    pub fai: FaiIndex,
+}
+
+impl RemoteFasta {
+    /// Fetch the `.fai` of the FASTA at `path`, or return `None` if there is
+    /// none or the FASTA is compressed (a bgzip file would need its `.gzi` too).
+    pub fn open(path: &str) -> Result<Option<Self>, Box<dyn Error>> {
+        if [".gz", ".bgz", ".zst", ".xz", ".bz2"].iter().any(|extension| path.ends_with(extension)) {
+            return Ok(None);
+        }
+        let fai = match ureq::get(&object_url(&format!("{}.fai", path))).call() {
+            Ok(mut response) => response.body_mut().with_config().limit(u64::MAX).read_to_string()?,
+            Err(ureq::Error::StatusCode(404 | 403)) => return Ok(None),
+            Err(e) => return Err(format!("could not fetch {}.fai: {}", path, e).into()),
+        };
+        Ok(Some(RemoteFasta { path: path.to_string(), fai: FaiIndex::from_text(&fai)? }))
+    }
+
+    /// The definition line of the contig at `position`, without the `>`.
+    pub fn definition(&self, position: usize) -> Result<String, Box<dyn Error>> {
+        Ok(definition_line(&fetch_range(&self.path, self.fai.header_range(position))?))
+    }
+
+    /// The bases `start..end` of `entry`, fetched in one request.
+    pub fn fetch(&self, entry: &FaiEntry, start: u64, end: u64) -> Result<Vec<u8>, Box<dyn Error>> {
+        let end = end.min(entry.length);
+        let start = start.min(end);
+        let mut bases = fetch_range(&self.path, entry.position_offset(start)..entry.position_offset(end))?;
+        bases.retain(|c| !c.is_ascii_whitespace());
+        Ok(bases)
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::bigwig::write_bigwig;
+use crate::merge::{merge_unstranded, Merger};
+use crate::scan::{ScanHit, Strand};
+use crate::svg::{density_svg, escape};
+
+// Contigs plotted in the HTML overview, and bars per plot.
+const MAX_PLOTS: usize = 50;
+const PLOT_BINS: usize = 200;
+
+/// Hits collected for one contig.
+#[derive(Debug, Default)]
+struct ContigReport {
+    name: String,
+    length: usize,
+    plus_windows: usize,
+    minus_windows: usize,
+    merger: Merger,
+    merged: Vec<ScanHit>,
+}
+
+/// Collects hits during a scan and writes the `--report-dir` bundle:
+/// merged regions, a bigWig track, a per-contig summary, the run parameters
+/// and an HTML overview with density plots.
+#[derive(Debug, Default)]
+pub struct ReportCollector {
+    contigs: Vec<ContigReport>,
+    index: HashMap<String, usize>,
+}
+
+impl ReportCollector {
+    pub fn new() -> Self {
+        ReportCollector::default()
+    }
+
+    fn contig(&mut self, name: &str) -> &mut ContigReport {
+        let i = match self.index.get(name) {
+            Some(&i) => i,
+            None => {
+                self.contigs.push(ContigReport { name: name.to_string(), ..Default::default() });
+                self.index.insert(name.to_string(), self.contigs.len() - 1);
+                self.contigs.len() - 1
+            }
+        };
+        &mut self.contigs[i]
+    }
+
+    /// Register a contig (so contigs without hits still appear in the summary).
+    pub fn add_contig(&mut self, name: &str, length: usize) {
+        let contig = self.contig(name);
+        contig.length = contig.length.max(length);
+    }
+
+    /// Record a hit on `chrom`, in contig coordinates.
+    pub fn add_hit(&mut self, chrom: &str, hit: &ScanHit) {
+        let contig = self.contig(chrom);
+        contig.length = contig.length.max(hit.end);
+        match hit.strand {
+            Strand::Plus | Strand::Unstranded => contig.plus_windows += 1,
+            Strand::Minus => contig.minus_windows += 1,
+        }
+        if let Some(region) = contig.merger.push(hit.clone()) {
+            contig.merged.push(region);
+        }
+    }
+
+    /// Write the bundle into `dir`. `name` labels merged regions (the target base).
+    pub fn write_bundle(mut self, dir: &Path, name: &str, parameters: &[(String, String)]) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(dir)?;
+
+        for contig in &mut self.contigs {
+            let rest = contig.merger.finish();
+            contig.merged.extend(rest);
+            // Re-merge each strand in case hits arrived out of order (e.g. projected reads)
+            let mut merged = Vec::new();
+            for strand in [Strand::Plus, Strand::Minus, Strand::Unstranded] {
+                let mut strand_hits: Vec<ScanHit> = contig.merged.iter().filter(|h| h.strand == strand).cloned().collect();
+                strand_hits.sort_by_key(|h| h.start);
+                let mut merger = Merger::new();
+                for hit in strand_hits {
+                    merged.extend(merger.push(hit));
+                }
+                merged.extend(merger.finish());
+            }
+            merged.sort_by_key(|h| (h.start, h.strand == Strand::Minus));
+            contig.merged = merged;
+        }
+
+        // Merged regions, per strand
+        let mut bed = BufWriter::new(File::create(dir.join("merged.bed"))?);
+        for contig in &self.contigs {
+            for hit in &contig.merged {
+                writeln!(
+                    bed,
+                    "{}\t{}\t{}\t{}\t{}\t{}",
+                    contig.name,
+                    hit.start,
+                    hit.end,
+                    name,
+                    hit.score.ceil() as u64,
+                    hit.strand.symbol()
+                )?;
+            }
+        }
+        bed.flush()?;
+
+        // Strand-collapsed coverage for the bigWig track and plots
+        let unstranded: Vec<Vec<ScanHit>> = self.contigs.iter().map(|c| merge_unstranded(&c.merged)).collect();
+
+        let chrom_sizes: Vec<(String, u32)> =
+            self.contigs.iter().map(|c| (c.name.clone(), c.length as u32)).collect();
+        let bedgraph: Vec<(String, u32, u32, f32)> = self
+            .contigs
+            .iter()
+            .zip(&unstranded)
+            .flat_map(|(c, hits)| hits.iter().map(|h| (c.name.clone(), h.start as u32, h.end as u32, h.score as f32)))
+            .collect();
+        write_bigwig(dir.join("density.bw"), &chrom_sizes, &bedgraph)?;
+
+        // Per-contig summary
+        let mut summary_rows = Vec::new();
+        let mut summary = BufWriter::new(File::create(dir.join("summary.tsv"))?);
+        writeln!(
+            summary,
+            "contig\tlength\tplus_windows\tminus_windows\tmerged_regions\tcovered_bases\tcovered_fraction"
+        )?;
+        for (contig, hits) in self.contigs.iter().zip(&unstranded) {
+            let covered: usize = hits.iter().map(|h| h.end - h.start).sum();
+            let fraction = if contig.length > 0 { covered as f64 / contig.length as f64 } else { 0.0 };
+            let row = vec![
+                contig.name.clone(),
+                contig.length.to_string(),
+                contig.plus_windows.to_string(),
+                contig.minus_windows.to_string(),
+                contig.merged.len().to_string(),
+                covered.to_string(),
+                format!("{:.6}", fraction),
+            ];
+            writeln!(summary, "{}", row.join("\t"))?;
+            summary_rows.push(row);
+        }
+        summary.flush()?;
+
+        // Parameter manifest
+        let mut manifest = BufWriter::new(File::create(dir.join("parameters.tsv"))?);
+        writeln!(manifest, "parameter\tvalue")?;
+        for (key, value) in parameters {
+            writeln!(manifest, "{}\t{}", key, value)?;
+        }
+        manifest.flush()?;
+
+        // HTML overview
+        let mut html = String::new();
+        html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>polyscan report</title>\n");
+        html.push_str("<style>body{font-family:sans-serif;margin:2em}table{border-collapse:collapse}td,th{border:1px solid #ccc;padding:2px 8px;text-align:left}</style>\n");
+        html.push_str("</head>\n<body>\n<h1>polyscan report</h1>\n<h2>Parameters</h2>\n<table>\n");
+        for (key, value) in parameters {
+            let _ = writeln!(html, "<tr><th>{}</th><td>{}</td></tr>", escape(key), escape(value));
+        }
+        html.push_str("</table>\n<h2>Summary</h2>\n<table>\n<tr><th>contig</th><th>length</th><th>plus windows</th><th>minus windows</th><th>merged regions</th><th>covered bases</th><th>covered fraction</th></tr>\n");
+        for row in &summary_rows {
+            html.push_str("<tr>");
+            for cell in row {
+                let _ = write!(html, "<td>{}</td>", escape(cell));
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</table>\n<h2>ScanHit density</h2>\n");
+        for (contig, hits) in self.contigs.iter().zip(&unstranded).take(MAX_PLOTS) {
+            let intervals: Vec<(usize, usize)> = hits.iter().map(|h| (h.start, h.end)).collect();
+            html.push_str(&density_svg(&contig.name, contig.length, &intervals, PLOT_BINS));
+        }
+        if self.contigs.len() > MAX_PLOTS {
+            let _ = writeln!(html, "<p>Only the first {} of {} contigs are plotted.</p>", MAX_PLOTS, self.contigs.len());
+        }
+        html.push_str("</body>\n</html>\n");
+        fs::write(dir.join("report.html"), html)?;
+
+        Ok(())
+    }
+}
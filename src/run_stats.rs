@@ -0,0 +1,124 @@
+//! What a scan got through, for `--stats-json`: contigs, bases and records
+//! per strand, plus the peak memory of the process.
+
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+// Counters are process-wide, like the timers of `--timings`, so the workers
+// of a parallel scan add to them wherever they run; nothing is counted until
+// `enable` is called.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static BASES: AtomicU64 = AtomicU64::new(0);
+// Records written on the plus and minus strands, and unstranded
+static RECORDS: [AtomicU64; 3] = [AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0)];
+// Contigs seen, by ID; chunks, pieces and regions of a contig count once
+static CONTIGS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+// Checksums of the contigs read, in input order, with --contig-checksums
+static CHECKSUMS: Mutex<Vec<ContigChecksum>> = Mutex::new(Vec::new());
+// Contigs with more records than --max-hits-per-contig lets through
+static CAPPED: Mutex<Vec<CappedContig>> = Mutex::new(Vec::new());
+
+/// The checksums of a contig's sequence, for `--contig-checksums`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContigChecksum {
+    pub name: String,
+    pub length: u64,
+    /// MD5 of the uppercased sequence, as in the `M5` tag of SAM `@SQ` lines
+    pub md5: String,
+    pub sha256: String,
+}
+
+/// A contig that went over `--max-hits-per-contig`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CappedContig {
+    pub name: String,
+    /// Records found on the contig, written or not
+    pub found: u64,
+    /// Records found past the cap, and left out
+    pub dropped: u64,
+}
+
+/// Start counting.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Count `bases` new bases of `contig` (leaving out any overlap with a
+/// previous chunk of it).
+pub fn add_bases(contig: &str, bases: usize) {
+    if !enabled() {
+        return;
+    }
+    BASES.fetch_add(bases as u64, Ordering::Relaxed);
+    let mut contigs = CONTIGS.lock().expect("no panics while holding the contigs seen");
+    if !contigs.contains(contig) {
+        contigs.insert(contig.to_string());
+    }
+}
+
+/// Count a record written on the strand of `symbol` ("+", "-" or ".").
+pub fn add_record(symbol: &str) {
+    if !enabled() {
+        return;
+    }
+    let strand = match symbol {
+        "+" => 0,
+        "-" => 1,
+        _ => 2,
+    };
+    RECORDS[strand].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Record the checksums of a contig.
+pub fn add_checksum(checksum: ContigChecksum) {
+    if enabled() {
+        CHECKSUMS.lock().expect("no panics while holding the checksums").push(checksum);
+    }
+}
+
+/// The checksums of the contigs read, in input order.
+pub fn checksums() -> Vec<ContigChecksum> {
+    CHECKSUMS.lock().expect("no panics while holding the checksums").clone()
+}
+
+/// Record a contig that went over the cap.
+pub fn add_capped(contig: CappedContig) {
+    if enabled() {
+        CAPPED.lock().expect("no panics while holding the capped contigs").push(contig);
+    }
+}
+
+/// The contigs that went over the cap, in the order of the output.
+pub fn capped() -> Vec<CappedContig> {
+    CAPPED.lock().expect("no panics while holding the capped contigs").clone()
+}
+
+/// Number of distinct contigs scanned.
+pub fn contigs() -> u64 {
+    CONTIGS.lock().expect("no panics while holding the contigs seen").len() as u64
+}
+
+/// Bases scanned.
+pub fn bases() -> u64 {
+    BASES.load(Ordering::Relaxed)
+}
+
+/// Records written on the plus strand, the minus strand and unstranded.
+pub fn records() -> [u64; 3] {
+    RECORDS.each_ref().map(|count| count.load(Ordering::Relaxed))
+}
+
+/// The most memory the process has held at once (its peak resident set
+/// size), in bytes, where the system reports it (Linux); `None` elsewhere.
+pub fn peak_rss() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    // e.g. "VmHWM:     12345 kB"
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
@@ -0,0 +1,60 @@
+use std::error::Error;
+
+use crate::scan::{BaseClass, ScanHit, Strand};
+
+/// Call `emit` for every maximal run of `target` letters that is at least
+/// `min_run` long.
+///
+/// Unlike windows, runs are reported with their exact boundaries; the hit
+/// score is the run length.
+pub fn find_runs<F>(seq: &[u8], target: &BaseClass, min_run: usize, strand: Strand, mut emit: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(ScanHit) -> Result<(), Box<dyn Error>>,
+{
+    let mut run_start = None;
+    // One past the end, so a run reaching the end of the sequence is closed too
+    for i in 0..=seq.len() {
+        let in_run = i < seq.len() && target.contains(seq[i]);
+        match (in_run, run_start) {
+            (true, None) => run_start = Some(i),
+            (false, Some(start)) => {
+                if i - start >= min_run.max(1) {
+                    emit(ScanHit { start, end: i, score: (i - start) as f64, strand, scorer: 0 })?;
+                }
+                run_start = None;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Length of the longest uninterrupted run of `target` letters in `seq`.
+pub fn longest_run(seq: &[u8], target: &BaseClass) -> usize {
+    let mut longest = 0;
+    let mut current = 0;
+    for &nuc in seq {
+        current = if target.contains(nuc) { current + 1 } else { 0 };
+        longest = longest.max(current);
+    }
+    longest
+}
+
+/// Every maximal run of a single nucleotide (A, C, G or T, whatever the case)
+/// at least `min_run` long, as `(start, end, base)` in order; the base is
+/// uppercase.
+pub fn homopolymers(seq: &[u8], min_run: usize) -> Vec<(usize, usize, u8)> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    for i in 1..=seq.len() {
+        if i < seq.len() && seq[i].eq_ignore_ascii_case(&seq[start]) {
+            continue;
+        }
+        let base = seq[start].to_ascii_uppercase();
+        if i - start >= min_run.max(1) && matches!(base, b'A' | b'C' | b'G' | b'T') {
+            runs.push((start, i, base));
+        }
+        start = i;
+    }
+    runs
+}
@@ -0,0 +1,903 @@
+use std::error::Error;
+
+pub use crate::bases::letter_index;
+use crate::bases::{iupac_mask, is_plain_acgt, nuc_code, PLAIN_CHUNK};
+
+/// Map a nucleotide to its slot in the [A,C,G,T,N] frequency array.
+pub fn nuc_to_index(nuc: u8) -> Option<usize> {
+    match nuc {
+        b'N' | b'n' => Some(4),
+        _ => nuc_code(nuc),
+    }
+}
+
+/// Sequence alphabet being scanned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "kebab-case"))]
+pub enum Alphabet {
+    /// Nucleotides; the complement is scanned on the minus strand
+    #[default]
+    Dna,
+    /// Amino acids; any residue letter can be targeted and there is no strand
+    Protein,
+}
+
+/// Complement of a single (uppercase) nucleotide.
+pub fn complement_char(c: char) -> char {
+    match c {
+        'A' => 'T',
+        'T' | 'U' => 'A',
+        'C' => 'G',
+        'G' => 'C',
+        'N' => 'N',
+        _ => 'N', // fallback
+    }
+}
+
+/// One or more letters counted together as a window's target: a single base
+/// such as `A`, or a class such as `GC` whose combined fraction is scored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+// Serialized as its label, e.g. "GC"
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(from = "String", into = "String"))]
+pub struct BaseClass {
+    // Uppercase and deduplicated, in the order given
+    letters: Vec<u8>,
+}
+
+impl BaseClass {
+    pub fn new(letters: &[u8]) -> Self {
+        let mut unique: Vec<u8> = Vec::with_capacity(letters.len());
+        for letter in letters.iter().map(u8::to_ascii_uppercase) {
+            if !unique.contains(&letter) {
+                unique.push(letter);
+            }
+        }
+        BaseClass { letters: unique }
+    }
+
+    pub fn letters(&self) -> &[u8] {
+        &self.letters
+    }
+
+    /// The letters as written, e.g. "GC".
+    pub fn label(&self) -> String {
+        String::from_utf8_lossy(&self.letters).into_owned()
+    }
+
+    pub fn contains(&self, letter: u8) -> bool {
+        self.letters.contains(&letter.to_ascii_uppercase())
+    }
+
+    /// The class of complementary nucleotides.
+    pub fn complement(&self) -> BaseClass {
+        let letters: Vec<u8> = self.letters.iter().map(|&nuc| complement_char(nuc as char) as u8).collect();
+        BaseClass::new(&letters)
+    }
+
+    /// Whether the class equals its own complement (e.g. `GC`, `AT`), so
+    /// both strands would report the same windows.
+    pub fn is_self_complementary(&self) -> bool {
+        let complement = self.complement();
+        complement.letters.len() == self.letters.len() && complement.letters.iter().all(|&nuc| self.contains(nuc))
+    }
+}
+
+impl From<String> for BaseClass {
+    fn from(label: String) -> Self {
+        BaseClass::new(label.as_bytes())
+    }
+}
+
+impl From<BaseClass> for String {
+    fn from(class: BaseClass) -> Self {
+        class.label()
+    }
+}
+
+/// Whether `seq` contains any `U`/`u`.
+pub fn has_uracil(seq: &[u8]) -> bool {
+    seq.iter().any(|&nuc| nuc == b'U' || nuc == b'u')
+}
+
+/// Placeholder for bases taken out of the scan (e.g. soft-masked ones): not a
+/// letter, so it is never counted and never matches a target.
+pub const HIDDEN: u8 = b'.';
+
+/// Replace soft-masked (lowercase) letters by [`HIDDEN`] in place.
+pub fn hide_soft_masked(seq: &mut [u8]) {
+    for nuc in seq.iter_mut() {
+        if nuc.is_ascii_lowercase() {
+            *nuc = HIDDEN;
+        }
+    }
+}
+
+/// Replace unmasked (uppercase) letters by [`HIDDEN`] in place, to scan only
+/// the soft-masked bases.
+pub fn hide_unmasked(seq: &mut [u8]) {
+    for nuc in seq.iter_mut() {
+        if nuc.is_ascii_uppercase() {
+            *nuc = HIDDEN;
+        }
+    }
+}
+
+/// Rewrite RNA uracil as thymine in place (`U`→`T`, `u`→`t`), so transcript
+/// sequences are counted like DNA.
+pub fn rna_to_dna(seq: &mut [u8]) {
+    for nuc in seq.iter_mut() {
+        match *nuc {
+            b'U' => *nuc = b'T',
+            b'u' => *nuc = b't',
+            _ => (),
+        }
+    }
+}
+
+/// Strand a window was reported on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+// Serialized as its BED symbol
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Strand {
+    #[cfg_attr(feature = "serde", serde(rename = "+"))]
+    Plus,
+    #[cfg_attr(feature = "serde", serde(rename = "-"))]
+    Minus,
+    /// No strand (protein sequences)
+    #[cfg_attr(feature = "serde", serde(rename = "."))]
+    Unstranded,
+}
+
+impl Strand {
+    /// BED strand symbol ("+" or "-").
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Strand::Plus => "+",
+            Strand::Minus => "-",
+            Strand::Unstranded => ".",
+        }
+    }
+}
+
+/// Rolling composition of the current window.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Composition {
+    /// Counts of each letter A..Z (case-insensitive), so both nucleotides and
+    /// amino acids can be counted; other characters are not counted.
+    pub letters: [usize; 26],
+    /// Window length in bases.
+    pub len: usize,
+}
+
+impl Composition {
+    /// Composition of a whole slice.
+    pub fn from_seq(seq: &[u8]) -> Self {
+        let mut comp = Composition { letters: [0; 26], len: seq.len() };
+        for &nuc in seq {
+            comp.add(nuc);
+        }
+        comp
+    }
+
+    /// Number of occurrences of `letter` in the window (0 for non-letters).
+    pub fn count(&self, letter: u8) -> usize {
+        letter_index(letter).map_or(0, |i| self.letters[i])
+    }
+
+    /// Counts of [A,C,G,T,N].
+    pub fn counts(&self) -> [usize; 5] {
+        [b'A', b'C', b'G', b'T', b'N'].map(|nuc| self.count(nuc))
+    }
+
+    fn add(&mut self, letter: u8) {
+        if let Some(i) = letter_index(letter) {
+            self.letters[i] += 1;
+        }
+    }
+
+    fn remove(&mut self, letter: u8) {
+        if let Some(i) = letter_index(letter) {
+            self.letters[i] = self.letters[i].saturating_sub(1);
+        }
+    }
+}
+
+/// Decides whether a window passes and what score it gets.
+///
+/// Implement this to plug a custom statistic into polyscan's rolling window
+/// and output machinery.
+pub trait WindowScorer {
+    /// Score the window described by `comp`, or return `None` if it does not pass.
+    fn score(&self, comp: &Composition) -> Option<f64>;
+
+    /// The letters (uppercase) this scorer counts and how many of them a
+    /// window needs, when that is all it looks at and it scores a passing
+    /// window by their percentage of the window's bases. [`scan_windows`]
+    /// then counts them with a match bitmap instead of a full composition.
+    fn plain_count(&self) -> Option<(Vec<u8>, usize)> {
+        None
+    }
+
+    /// Like [`WindowScorer::plain_count`], for windows holding only
+    /// uppercase A/C/G/T (see [`is_plain_acgt`]): settings about soft-masked
+    /// bases, Ns or IUPAC codes make no difference there, so more scorers
+    /// can be counted with a match bitmap over such stretches.
+    fn plain_acgt_count(&self) -> Option<(Vec<u8>, usize)> {
+        self.plain_count()
+    }
+}
+
+/// Any closure scoring a window from its counts is a scorer, so custom
+/// statistics (entropy, skew, model scores, ...) can reuse the rolling window
+/// and its output without a type of their own; see [`scan_windows_by`].
+impl<F> WindowScorer for F
+where
+    F: Fn(&Composition) -> Option<f64>,
+{
+    fn score(&self, comp: &Composition) -> Option<f64> {
+        self(comp)
+    }
+}
+
+/// Default scorer: percentage of a single nucleotide (or residue), or of a
+/// class of them combined, within the window.
+#[derive(Debug, Clone)]
+pub struct BaseFraction {
+    indices: Vec<usize>,
+    threshold_count: usize,
+    /// `None` for a fixed count (see [`BaseFraction::with_min_count`])
+    percentage: Option<f64>,
+    /// Compute the percentage over the window's letters only, leaving out
+    /// [`HIDDEN`] bases and other non-letters
+    letters_only: bool,
+    /// Letters also left out of the window size (e.g. N)
+    ignored: Vec<usize>,
+    /// Letters that make a window fail outright (e.g. N)
+    rejected: Vec<usize>,
+    /// Letters counted as part of the target, in sixths of a base (see
+    /// [`BaseFraction::fractional_iupac`])
+    partial: Vec<(usize, usize)>,
+}
+
+impl BaseFraction {
+    /// Passes windows where `nuc` (any letter) makes up at least `percentage`% of `window` bases.
+    pub fn new(nuc: u8, percentage: f64, window: usize) -> Self {
+        BaseFraction::for_class(&BaseClass::new(&[nuc]), percentage, window)
+    }
+
+    /// Passes windows where the letters of `class` together make up at least
+    /// `percentage`% of `window` bases.
+    pub fn for_class(class: &BaseClass, percentage: f64, window: usize) -> Self {
+        // The minimum count needed in a window to be considered "passing"
+        let threshold_count = ((percentage / 100.0) * (window as f64)).ceil() as usize;
+        BaseFraction {
+            indices: class.letters().iter().map(|&l| letter_index(l).expect("BaseFraction requires letters")).collect(),
+            threshold_count,
+            percentage: Some(percentage),
+            letters_only: false,
+            ignored: Vec::new(),
+            rejected: Vec::new(),
+            partial: Vec::new(),
+        }
+    }
+
+    /// Leave non-letters (such as [`HIDDEN`] bases) out of the window size the
+    /// percentage is taken of; windows without any letters never pass.
+    pub fn letters_only(self) -> Self {
+        BaseFraction { letters_only: true, ..self }
+    }
+
+    /// Leave `letter` out of the window size the percentage is taken of, like
+    /// a non-letter with [`BaseFraction::letters_only`].
+    pub fn ignoring(mut self, letter: u8) -> Self {
+        self.ignored.extend(letter_index(letter));
+        self
+    }
+
+    /// Count `letter` as part of the target.
+    pub fn counting(mut self, letter: u8) -> Self {
+        let index = letter_index(letter).expect("BaseFraction requires letters");
+        if !self.indices.contains(&index) {
+            self.indices.push(index);
+        }
+        self
+    }
+
+    /// Never pass a window containing `letter`.
+    pub fn rejecting(mut self, letter: u8) -> Self {
+        self.rejected.extend(letter_index(letter));
+        self
+    }
+
+    /// Count IUPAC ambiguity codes (other than N) as the share of the bases
+    /// they stand for that are targets, e.g. W (A or T) as half an A, or S
+    /// (C or G) as a whole GC base. For nucleotide targets only.
+    pub fn fractional_iupac(mut self) -> Self {
+        let target = self.indices.iter().filter_map(|&i| nuc_code(b'A' + i as u8)).fold(0u8, |mask, code| mask | 1 << code);
+        for code in b"RYSWKMBDHV" {
+            let index = letter_index(*code).expect("IUPAC codes are letters");
+            let mask = iupac_mask(*code);
+            // 6 is a multiple of every ambiguity code's base count (2 or 3)
+            let sixths = 6 * (mask & target).count_ones() as usize / mask.count_ones() as usize;
+            if sixths > 0 && !self.indices.contains(&index) {
+                self.partial.push((index, sixths));
+            }
+        }
+        self
+    }
+
+    /// Passes windows holding at least `min_count` letters of `class`,
+    /// whatever the window size.
+    pub fn with_min_count(class: &BaseClass, min_count: usize) -> Self {
+        BaseFraction { threshold_count: min_count, percentage: None, ..BaseFraction::for_class(class, 0.0, 0) }
+    }
+}
+
+impl WindowScorer for BaseFraction {
+    fn score(&self, comp: &Composition) -> Option<f64> {
+        if self.rejected.iter().any(|&i| comp.letters[i] > 0) {
+            return None;
+        }
+        let count: usize = self.indices.iter().map(|&i| comp.letters[i]).sum();
+        if !self.partial.is_empty() {
+            return self.score_partial(comp, count);
+        }
+        if self.letters_only || !self.ignored.is_empty() {
+            let window = if self.letters_only { comp.letters.iter().sum() } else { comp.len };
+            let total = window - self.ignored.iter().map(|&i| comp.letters[i]).sum::<usize>();
+            let needed = match self.percentage {
+                Some(percentage) => ((percentage / 100.0) * (total as f64)).ceil() as usize,
+                None => self.threshold_count,
+            };
+            return (total > 0 && count >= needed).then(|| (count as f64 / total as f64) * 100.0);
+        }
+        if count >= self.threshold_count {
+            Some((count as f64 / comp.len as f64) * 100.0)
+        } else {
+            None
+        }
+    }
+
+    fn plain_count(&self) -> Option<(Vec<u8>, usize)> {
+        let plain = !self.letters_only && self.ignored.is_empty() && self.rejected.is_empty() && self.partial.is_empty();
+        plain.then(|| (self.indices.iter().map(|&i| b'A' + i as u8).collect(), self.threshold_count))
+    }
+
+    fn plain_acgt_count(&self) -> Option<(Vec<u8>, usize)> {
+        // Every base is a letter and none is left out, unless it is one of A/C/G/T
+        let acgt = |i: &usize| b"ACGT".contains(&(b'A' + *i as u8));
+        let plain = !self.ignored.iter().any(acgt) && !self.rejected.iter().any(acgt);
+        plain.then(|| (self.indices.iter().map(|&i| b'A' + i as u8).collect(), self.threshold_count))
+    }
+}
+
+impl BaseFraction {
+    // score() with ambiguity codes counted in part: everything is counted in
+    // sixths of a base, so the threshold is still met exactly
+    fn score_partial(&self, comp: &Composition, count: usize) -> Option<f64> {
+        let sixths = 6 * count + self.partial.iter().map(|&(i, sixths)| sixths * comp.letters[i]).sum::<usize>();
+        let window = if self.letters_only { comp.letters.iter().sum() } else { comp.len };
+        let total = window - self.ignored.iter().map(|&i| comp.letters[i]).sum::<usize>();
+        let needed = match self.percentage {
+            Some(percentage) => ((percentage / 100.0) * (total as f64) * 6.0).ceil() as usize,
+            None => 6 * self.threshold_count,
+        };
+        (total > 0 && sixths >= needed).then(|| (sixths as f64 / 6.0 / total as f64) * 100.0)
+    }
+}
+
+/// Passes windows that any of its scorers passes, with the best of their
+/// scores; e.g. a target on either strand.
+pub struct AnyOf<'a> {
+    scorers: Vec<&'a dyn WindowScorer>,
+}
+
+impl<'a> AnyOf<'a> {
+    pub fn new(scorers: Vec<&'a dyn WindowScorer>) -> Self {
+        AnyOf { scorers }
+    }
+
+    pub fn push(&mut self, scorer: &'a dyn WindowScorer) {
+        self.scorers.push(scorer);
+    }
+}
+
+impl WindowScorer for AnyOf<'_> {
+    fn score(&self, comp: &Composition) -> Option<f64> {
+        self.scorers.iter().filter_map(|scorer| scorer.score(comp)).max_by(f64::total_cmp)
+    }
+}
+
+/// Scores windows by the Shannon entropy of their letters, passing windows
+/// that are at least as repetitive as `max_bits` allows.
+///
+/// Entropy is computed over the window's letters other than N (or X, for
+/// proteins), so it reaches 2 bits for an even mix of A/C/G/T and catches
+/// low-complexity windows not dominated by one base, e.g. `(AT)n` at 1 bit.
+/// The score is the entropy in bits times 100.
+#[derive(Debug, Clone)]
+pub struct Entropy {
+    max_bits: f64,
+    ignored: usize,
+}
+
+impl Entropy {
+    pub fn new(max_bits: f64, alphabet: Alphabet) -> Self {
+        let ignored = match alphabet {
+            Alphabet::Dna => b'N',
+            Alphabet::Protein => b'X',
+        };
+        Entropy { max_bits, ignored: letter_index(ignored).unwrap() }
+    }
+}
+
+impl WindowScorer for Entropy {
+    fn score(&self, comp: &Composition) -> Option<f64> {
+        let counted = || comp.letters.iter().enumerate().filter(|&(i, &n)| i != self.ignored && n > 0).map(|(_, &n)| n as f64);
+        let total: f64 = counted().sum();
+        if total == 0.0 {
+            return None;
+        }
+        let bits: f64 = counted().map(|n| -(n / total) * (n / total).log2()).sum();
+        (bits <= self.max_bits).then_some(bits * 100.0)
+    }
+}
+
+/// A passing window, in 0-based half-open coordinates, as the scans of this
+/// module find it; a [`Hit`](crate::Hit) names its contig and target too.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScanHit {
+    pub start: usize,
+    pub end: usize,
+    pub score: f64,
+    pub strand: Strand,
+    /// Index of the scorer that produced the hit, in the list given to
+    /// [`scan_windows`] (or of the query, with [`scan_queries`]).
+    pub scorer: usize,
+}
+
+/// The window starts a sliding scan evaluates: every `step`-th base, counted
+/// from the start of the contig the scanned sequence was taken from, so the
+/// chunks and regions of a contig share its grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Steps {
+    step: usize,
+    phase: usize,
+}
+
+impl Steps {
+    /// Every window start.
+    pub const EVERY_BASE: Steps = Steps { step: 1, phase: 0 };
+
+    /// Every `step`-th start of a sequence taken from `offset` bases into its contig.
+    pub fn new(step: usize, offset: usize) -> Self {
+        Steps { step, phase: offset % step }
+    }
+
+    /// Whether windows starting at `start` are evaluated.
+    pub fn contains(&self, start: usize) -> bool {
+        (self.phase + start).is_multiple_of(self.step)
+    }
+
+    /// The same grid, for the part of the sequence from `offset` on.
+    pub fn skip(&self, offset: usize) -> Self {
+        Steps::new(self.step, self.phase + offset)
+    }
+}
+
+/// Slide a `window`-sized window across `seq` and call `emit` for every
+/// (window, scorer) pair that passes, among the windows starting on `steps`.
+///
+/// Scorers are evaluated in the order given, so hits for the same window are
+/// emitted in that order. Sequences shorter than the window produce no hits.
+pub fn scan_windows<F>(
+    seq: &[u8],
+    window: usize,
+    steps: Steps,
+    scorers: &[(&dyn WindowScorer, Strand)],
+    mut emit: F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(ScanHit) -> Result<(), Box<dyn Error>>,
+{
+    if window == 0 || seq.len() < window {
+        return Ok(());
+    }
+    if let Some(counts) = scorers.iter().map(|(scorer, _)| scorer.plain_count()).collect::<Option<Vec<_>>>() {
+        return scan_counts(seq, window, steps, &counts, scorers, emit);
+    }
+    let acgt_counts = scorers.iter().map(|(scorer, _)| scorer.plain_acgt_count()).collect::<Option<Vec<_>>>();
+
+    // Windows are taken a stretch of starts at a time: those of a stretch
+    // holding only uppercase A/C/G/T are counted off a match bitmap when the
+    // scorers allow, and the others by the rolling composition
+    let last = seq.len() - window;
+    let mut comp: Option<Composition> = None;
+    let mut from = 0;
+    while from <= last {
+        let to = (from + PLAIN_CHUNK).min(last + 1);
+        if let Some(counts) = acgt_counts.as_ref().filter(|_| is_plain_acgt(&seq[from..to + window - 1])) {
+            scan_counts(&seq[from..to + window - 1], window, steps.skip(from), counts, scorers, |hit| emit(ScanHit { start: hit.start + from, end: hit.end + from, ..hit }))?;
+            comp = None;
+            from = to;
+            continue;
+        }
+        for start in from..to {
+            match comp.as_mut() {
+                Some(comp) => {
+                    comp.remove(seq[start - 1]);
+                    comp.add(seq[start + window - 1]);
+                }
+                None => comp = Some(Composition::from_seq(&seq[start..start + window])),
+            }
+            if !steps.contains(start) {
+                continue;
+            }
+            let comp = comp.as_ref().expect("the composition was just taken");
+
+            for (index, (scorer, strand)) in scorers.iter().enumerate() {
+                if let Some(score) = scorer.score(comp) {
+                    emit(ScanHit { start, end: start + window, score, strand: *strand, scorer: index })?;
+                }
+            }
+        }
+        from = to;
+    }
+
+    Ok(())
+}
+
+/// [`scan_windows`] with a single statistic computed by `score` from the
+/// counts of each window, e.g. `|comp| (comp.count(b'G') > comp.count(b'C')).then_some(1.0)`.
+/// Hits are unstranded.
+pub fn scan_windows_by<S, F>(seq: &[u8], window: usize, score: S, emit: F) -> Result<(), Box<dyn Error>>
+where
+    S: Fn(&Composition) -> Option<f64>,
+    F: FnMut(ScanHit) -> Result<(), Box<dyn Error>>,
+{
+    scan_windows(seq, window, Steps::EVERY_BASE, &[(&score as &dyn WindowScorer, Strand::Unstranded)], emit)
+}
+
+/// A scorer evaluated on windows of its own size by [`scan_queries`].
+#[derive(Clone, Copy)]
+pub struct Query<'a> {
+    pub window: usize,
+    pub scorer: &'a dyn WindowScorer,
+    pub strand: Strand,
+}
+
+/// Evaluate several queries, of any mix of window sizes, in a single pass
+/// over `seq`: one rolling composition per window size, shared by the
+/// queries of that size, slides along with the others.
+///
+/// Hits come in coordinate order (by start, then in the order of the
+/// queries), with [`ScanHit::scorer`] the index of the query. Queries of a single
+/// window size are scanned as by [`scan_windows`], and like it only windows
+/// starting on `steps` are evaluated.
+pub fn scan_queries<F>(seq: &[u8], queries: &[Query], steps: Steps, mut emit: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(ScanHit) -> Result<(), Box<dyn Error>>,
+{
+    let mut windows: Vec<usize> = queries.iter().map(|query| query.window).filter(|&window| window > 0).collect();
+    windows.sort_unstable();
+    windows.dedup();
+    if let [window] = windows[..] {
+        let scorers: Vec<(&dyn WindowScorer, Strand)> =
+            queries.iter().filter(|query| query.window == window).map(|query| (query.scorer, query.strand)).collect();
+        // Map the hits back to the queries, in case some had no window
+        let indices: Vec<usize> = (0..queries.len()).filter(|&i| queries[i].window == window).collect();
+        return scan_windows(seq, window, steps, &scorers, |hit| emit(ScanHit { scorer: indices[hit.scorer], ..hit }));
+    }
+
+    let queries: Vec<Query> = queries.iter().map(|query| if query.window > seq.len() { Query { window: 0, ..*query } } else { *query }).collect();
+    if let Some(counts) = queries.iter().map(|query| query.scorer.plain_count()).collect::<Option<Vec<_>>>() {
+        return scan_query_counts(seq, &queries, steps, &counts, emit);
+    }
+
+    // The composition of each window size, which its queries read
+    let mut states: Vec<(usize, Composition)> =
+        windows.iter().filter(|&&window| window <= seq.len()).map(|&window| (window, Composition::from_seq(&seq[..window]))).collect();
+    let state_of: Vec<Option<usize>> = queries.iter().map(|query| states.iter().position(|&(window, _)| window == query.window)).collect();
+
+    for start in 0..seq.len() {
+        for (window, comp) in states.iter_mut() {
+            if start > 0 && start + *window <= seq.len() {
+                comp.remove(seq[start - 1]);
+                comp.add(seq[start + *window - 1]);
+            }
+        }
+        let mut any = false;
+        for (index, (query, state)) in queries.iter().zip(&state_of).enumerate() {
+            let Some((window, comp)) = state.map(|state| &states[state]).filter(|(window, _)| start + window <= seq.len()) else {
+                continue;
+            };
+            any = true;
+            if !steps.contains(start) {
+                continue;
+            }
+            if let Some(score) = query.scorer.score(comp) {
+                emit(ScanHit { start, end: start + window, score, strand: query.strand, scorer: index })?;
+            }
+        }
+        if !any {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+// scan_queries for queries that only count letters: one match bitmap per
+// target, shared by the window sizes, and a running count per query
+fn scan_query_counts<F>(seq: &[u8], queries: &[Query], steps: Steps, counts: &[(Vec<u8>, usize)], mut emit: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(ScanHit) -> Result<(), Box<dyn Error>>,
+{
+    let mut targets: Vec<&[u8]> = counts.iter().map(|(letters, _)| letters.as_slice()).collect();
+    targets.sort_unstable();
+    targets.dedup();
+    let bitmaps: Vec<Vec<u64>> = targets.iter().map(|letters| match_bitmap(seq, letters)).collect();
+    let bitmap_of: Vec<&[u64]> = counts.iter().map(|(letters, _)| bitmaps[targets.binary_search(&letters.as_slice()).expect("every target has a bitmap")].as_slice()).collect();
+    let mut running: Vec<usize> = queries.iter().zip(&bitmap_of).map(|(query, bitmap)| count_bits(bitmap, 0, query.window)).collect();
+    let bit = |bitmap: &[u64], i: usize| (bitmap[i / 64] >> (i % 64)) as usize & 1;
+
+    for start in 0..seq.len() {
+        let mut any = false;
+        for (index, (query, (count, (_, needed)))) in queries.iter().zip(running.iter_mut().zip(counts)).enumerate() {
+            let window = query.window;
+            if window == 0 || start + window > seq.len() {
+                continue;
+            }
+            any = true;
+            if start > 0 {
+                *count = *count + bit(bitmap_of[index], start + window - 1) - bit(bitmap_of[index], start - 1);
+            }
+            if *count >= *needed && steps.contains(start) {
+                let score = (*count as f64 / window as f64) * 100.0;
+                emit(ScanHit { start, end: start + window, score, strand: query.strand, scorer: index })?;
+            }
+        }
+        if !any {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+// scan_windows for scorers that only count letters (see WindowScorer::plain_count):
+// each keeps a running count of its letters, read off a match bitmap of the sequence
+fn scan_counts<F>(
+    seq: &[u8],
+    window: usize,
+    steps: Steps,
+    counts: &[(Vec<u8>, usize)],
+    scorers: &[(&dyn WindowScorer, Strand)],
+    mut emit: F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(ScanHit) -> Result<(), Box<dyn Error>>,
+{
+    let bitmaps: Vec<Vec<u64>> = counts.iter().map(|(letters, _)| match_bitmap(seq, letters)).collect();
+    let mut running: Vec<usize> = bitmaps.iter().map(|bitmap| count_bits(bitmap, 0, window)).collect();
+    let bit = |bitmap: &[u64], i: usize| (bitmap[i / 64] >> (i % 64)) as usize & 1;
+
+    for start in 0..=(seq.len() - window) {
+        if start > 0 {
+            for (count, bitmap) in running.iter_mut().zip(&bitmaps) {
+                *count = *count + bit(bitmap, start + window - 1) - bit(bitmap, start - 1);
+            }
+        }
+        if !steps.contains(start) {
+            continue;
+        }
+
+        for (index, ((count, (_, needed)), (_, strand))) in running.iter().zip(counts).zip(scorers).enumerate() {
+            if count >= needed {
+                let score = (*count as f64 / window as f64) * 100.0;
+                emit(ScanHit { start, end: start + window, score, strand: *strand, scorer: index })?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Bitmap of the positions of `seq` holding any of `letters` (uppercase;
+/// either case matches), 64 positions to a word starting from the lowest bit.
+///
+/// Whole 64-base blocks are compared 32 bytes at a time with AVX2 where the
+/// CPU has it, and a byte at a time otherwise.
+pub fn match_bitmap(seq: &[u8], letters: &[u8]) -> Vec<u64> {
+    // `| 0x20` lowercases letters, and only a letter's two cases can land on a lowercase letter
+    let lower: Vec<u8> = letters.iter().map(|&letter| letter.to_ascii_lowercase()).collect();
+    #[cfg(target_arch = "x86_64")]
+    let avx2 = std::arch::is_x86_feature_detected!("avx2");
+    seq.chunks(64)
+        .map(|block| {
+            #[cfg(target_arch = "x86_64")]
+            if let (true, Ok(block)) = (avx2, <&[u8; 64]>::try_from(block)) {
+                // SAFETY: the CPU supports AVX2, checked above
+                return unsafe { block_mask_avx2(block, &lower) };
+            }
+            block_mask(block, &lower)
+        })
+        .collect()
+}
+
+fn block_mask(block: &[u8], lower: &[u8]) -> u64 {
+    block.iter().enumerate().fold(0, |bits, (i, &c)| bits | (u64::from(lower.contains(&(c | 0x20))) << i))
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn block_mask_avx2(block: &[u8; 64], lower: &[u8]) -> u64 {
+    use std::arch::x86_64::*;
+    let case = _mm256_set1_epi8(0x20);
+    let mut halves = [0u64; 2];
+    for (half, bits) in halves.iter_mut().enumerate() {
+        let bytes = _mm256_or_si256(_mm256_loadu_si256(block.as_ptr().add(half * 32) as *const __m256i), case);
+        let mut matched = _mm256_setzero_si256();
+        for &letter in lower {
+            matched = _mm256_or_si256(matched, _mm256_cmpeq_epi8(bytes, _mm256_set1_epi8(letter as i8)));
+        }
+        *bits = _mm256_movemask_epi8(matched) as u32 as u64;
+    }
+    halves[0] | (halves[1] << 32)
+}
+
+/// Number of set bits of `bitmap` (from [`match_bitmap`]) in positions `start..end`.
+pub fn count_bits(bitmap: &[u64], start: usize, end: usize) -> usize {
+    if start >= end {
+        return 0;
+    }
+    let (first, last) = (start / 64, (end - 1) / 64);
+    let head = !0u64 << (start % 64);
+    let tail = !0u64 >> (63 - (end - 1) % 64);
+    if first == last {
+        return (bitmap[first] & head & tail).count_ones() as usize;
+    }
+    let middle: u32 = bitmap[first + 1..last].iter().map(|word| word.count_ones()).sum();
+    ((bitmap[first] & head).count_ones() + middle + (bitmap[last] & tail).count_ones()) as usize
+}
+
+/// Partition `seq` into consecutive non-overlapping tiles of `window` bases
+/// (the last tile may be shorter) and call `emit` with each tile's
+/// coordinates and composition.
+pub fn tile_windows<F>(seq: &[u8], window: usize, mut emit: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(usize, usize, &Composition) -> Result<(), Box<dyn Error>>,
+{
+    if window == 0 {
+        return Ok(());
+    }
+    for (i, tile) in seq.chunks(window).enumerate() {
+        let start = i * window;
+        emit(start, start + tile.len(), &Composition::from_seq(tile))?;
+    }
+    Ok(())
+}
+
+/// Slide a `window`-sized window across `seq` one base at a time and call
+/// `emit` with the coordinates and composition of every `every`-th window,
+/// starting with the first. Sequences shorter than the window yield nothing.
+pub fn composition_windows<F>(seq: &[u8], window: usize, every: usize, mut emit: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(usize, usize, &Composition) -> Result<(), Box<dyn Error>>,
+{
+    if window == 0 || seq.len() < window {
+        return Ok(());
+    }
+    let every = every.max(1);
+
+    let mut comp = Composition::from_seq(&seq[0..window]);
+    for start in 0..=(seq.len() - window) {
+        if start > 0 {
+            comp.remove(seq[start - 1]);
+            comp.add(seq[start + window - 1]);
+        }
+        if start % every == 0 {
+            emit(start, start + window, &comp)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Bases of either case, IUPAC codes and bytes that aren't nucleotides at all
+    const POOL: &[u8] = b"ACGTUNacgtunRYSWKMBDHVryswkmbdhv-.*@`{[\x00\x7f\x80\xc1\xe1\xff";
+
+    // A xorshift generator, enough to vary the blocks deterministically
+    fn random_block(state: &mut u64) -> [u8; 64] {
+        let mut block = [0u8; 64];
+        for byte in block.iter_mut() {
+            *state ^= *state << 13;
+            *state ^= *state >> 7;
+            *state ^= *state << 17;
+            // Every other byte from the pool, the rest anything at all
+            *byte = if *state & 1 == 0 { POOL[(*state >> 8) as usize % POOL.len()] } else { (*state >> 8) as u8 };
+        }
+        block
+    }
+
+    #[test]
+    fn avx2_block_mask_matches_scalar() {
+        #[cfg(target_arch = "x86_64")]
+        if std::arch::is_x86_feature_detected!("avx2") {
+            let mut state = 0x9e37_79b9_7f4a_7c15;
+            for letters in [&b"A"[..], b"GC", b"N", b"RY", b"ACGT", b"BDHV"] {
+                let lower: Vec<u8> = letters.iter().map(|&letter| letter.to_ascii_lowercase()).collect();
+                for _ in 0..1000 {
+                    let block = random_block(&mut state);
+                    // SAFETY: the CPU supports AVX2, checked above
+                    let avx2 = unsafe { block_mask_avx2(&block, &lower) };
+                    assert_eq!(avx2, block_mask(&block, &lower), "{:?} in {:?}", letters, block);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn match_bitmap_spans_blocks() {
+        let mut state = 0x2545_f491_4f6c_dd1d;
+        let seq: Vec<u8> = (0..3).flat_map(|_| random_block(&mut state)).take(150).collect();
+        let bitmap = match_bitmap(&seq, b"AT");
+        assert_eq!(bitmap.len(), 3);
+        for (i, c) in seq.iter().enumerate() {
+            let expected = matches!(c, b'A' | b'T' | b'a' | b't');
+            assert_eq!((bitmap[i / 64] >> (i % 64)) & 1 == 1, expected, "position {} ({})", i, c);
+        }
+    }
+
+    #[test]
+    fn only_windows_on_the_steps_are_scored() {
+        let mut state = 0x853c_49e6_748f_ea9b;
+        let mut seq: Vec<u8> = (0..4).flat_map(|_| random_block(&mut state)).map(|c| b"ACGT"[c as usize % 4]).collect();
+        seq[100..110].fill(b'N');
+        // Counted off the whole bitmap, off the bitmap of plain stretches, and by the rolling composition
+        let a = BaseFraction::new(b'A', 25.0, 8);
+        let a_letters = BaseFraction::new(b'A', 25.0, 8).letters_only();
+        let more_g = |comp: &Composition| (comp.count(b'G') > comp.count(b'C')).then_some(1.0);
+        // A piece taken 5 bases into its contig, stepping by 3 from the contig start
+        let steps = Steps::new(3, 5);
+        let on_steps = |hits: Vec<ScanHit>| -> Vec<ScanHit> { hits.into_iter().filter(|hit| (hit.start + 5) % 3 == 0).collect() };
+
+        let windows = |steps: Steps, scorers: &[(&dyn WindowScorer, Strand)]| {
+            let mut hits = Vec::new();
+            scan_windows(&seq, 8, steps, scorers, |hit| {
+                hits.push(hit);
+                Ok(())
+            })
+            .unwrap();
+            hits
+        };
+        for scorers in [&[(&a as &dyn WindowScorer, Strand::Plus)][..], &[(&a_letters, Strand::Plus)], &[(&a, Strand::Plus), (&more_g, Strand::Unstranded)]] {
+            let expected = on_steps(windows(Steps::EVERY_BASE, scorers));
+            assert!(!expected.is_empty());
+            assert_eq!(windows(steps, scorers), expected);
+        }
+
+        let queries = |steps: Steps, queries: &[Query]| {
+            let mut hits = Vec::new();
+            scan_queries(&seq, queries, steps, |hit| {
+                hits.push(hit);
+                Ok(())
+            })
+            .unwrap();
+            hits
+        };
+        let a6 = BaseFraction::new(b'A', 25.0, 6);
+        let counted = [Query { window: 6, scorer: &a6, strand: Strand::Plus }, Query { window: 8, scorer: &a, strand: Strand::Plus }];
+        let composed = [Query { window: 6, scorer: &a6, strand: Strand::Plus }, Query { window: 8, scorer: &more_g, strand: Strand::Unstranded }];
+        for scales in [&counted, &composed] {
+            let expected = on_steps(queries(Steps::EVERY_BASE, scales));
+            assert!(!expected.is_empty());
+            assert_eq!(queries(steps, scales), expected);
+        }
+    }
+}
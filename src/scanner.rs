@@ -0,0 +1,524 @@
+//! Window scanning for embedding polyscan in other tools: describe a scan
+//! with [`ScanParams`], build a [`Scanner`] from it and get the passing
+//! windows of sequences, records or whole files back as [`Hit`]s, without
+//! shelling out to the command line and parsing its BED. Hits can be
+//! collected, or taken lazily one at a time (see [`scan_windows`]).
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::error::Error;
+#[cfg(feature = "io")]
+use std::path::Path;
+
+use crate::bases::PLAIN_CHUNK;
+use crate::error::PolyscanError;
+#[cfg(feature = "io")]
+use crate::input::{open_records, InputFormat, SeqRecord};
+use crate::scan::{self, has_uracil, hide_soft_masked, rna_to_dna, Alphabet, AnyOf, BaseClass, BaseFraction, Strand, WindowScorer};
+use crate::sink::{write_hits, OutputSink};
+
+/// How soft-masked (lowercase) bases are treated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "kebab-case"))]
+pub enum MaskedPolicy {
+    /// Leave them out of the window: percentages are of the unmasked bases
+    Exclude,
+    /// Count them as bases that never match the target
+    NonMatching,
+}
+
+/// How ambiguous bases (N) affect the target fraction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "kebab-case"))]
+pub enum NPolicy {
+    /// Leave Ns out of the window: percentages are of the other bases
+    Ignore,
+    /// Count Ns as bases that never match the target
+    #[default]
+    CountAgainst,
+    /// Count Ns as target bases
+    CountFor,
+    /// Never report a window containing an N
+    SkipWindow,
+}
+
+/// Strands reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "kebab-case"))]
+pub enum StrandChoice {
+    /// Both strands, as separate records
+    #[default]
+    Both,
+    /// Only the plus strand (and unstranded hits)
+    Plus,
+    /// Only the minus strand (and unstranded hits)
+    Minus,
+    /// One unstranded record when either strand passes
+    Collapse,
+}
+
+/// What a [`Scanner`] looks for, as the options of the same names do on the
+/// command line. The defaults are those of the command line too.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScanParams {
+    /// Bases (or classes of bases, e.g. `GC`) scanned in the same pass
+    pub targets: Vec<BaseClass>,
+    pub window: usize,
+    /// Percentage of a window the target must make up
+    pub percentage: f64,
+    /// Instead of `percentage`, the number of target bases a window needs
+    pub min_count: Option<usize>,
+    pub alphabet: Alphabet,
+    pub strand: StrandChoice,
+    pub n_policy: NPolicy,
+    pub ignore_masked: Option<MaskedPolicy>,
+    /// Only windows starting at a multiple of this from the contig start
+    pub step: usize,
+    /// Count ambiguity codes (R, Y, N, ...) as the fraction of their bases
+    /// that are targets, as `--fractional-iupac` does
+    pub fractional_iupac: bool,
+}
+
+impl Default for ScanParams {
+    fn default() -> Self {
+        ScanParams {
+            targets: vec![BaseClass::new(b"A")],
+            window: 10,
+            percentage: 80.0,
+            min_count: None,
+            alphabet: Alphabet::Dna,
+            strand: StrandChoice::Both,
+            n_policy: NPolicy::CountAgainst,
+            ignore_masked: None,
+            step: 1,
+            fractional_iupac: false,
+        }
+    }
+}
+
+impl ScanParams {
+    /// A [`ScanParamsBuilder`] starting from the defaults.
+    pub fn builder() -> ScanParamsBuilder {
+        ScanParamsBuilder::default()
+    }
+
+    /// A [`PolyscanError::Parameter`] if the parameters don't describe a scan
+    /// (no targets, a zero window or step, a percentage outside 0-100 or a
+    /// minimum count larger than the window).
+    pub fn validate(&self) -> Result<(), PolyscanError> {
+        if self.targets.is_empty() {
+            return Err(PolyscanError::Parameter("no targets to scan".into()));
+        }
+        if let Some(target) = self.targets.iter().find(|target| target.letters().is_empty() || !target.letters().iter().all(u8::is_ascii_alphabetic)) {
+            return Err(PolyscanError::Parameter(format!("invalid target '{}': targets are letters", target.label())));
+        }
+        if self.window == 0 || self.step == 0 {
+            return Err(PolyscanError::Parameter("the window size and step must be at least 1".into()));
+        }
+        if self.min_count.is_none() && !(0.0..=100.0).contains(&self.percentage) {
+            return Err(PolyscanError::Parameter(format!("invalid percentage {}: it must be between 0 and 100", self.percentage)));
+        }
+        if let Some(min_count) = self.min_count.filter(|&min_count| min_count > self.window) {
+            return Err(PolyscanError::Parameter(format!("invalid minimum count {}: it can't be larger than the window ({})", min_count, self.window)));
+        }
+        Ok(())
+    }
+}
+
+/// Builds [`ScanParams`] one option at a time, checking them in
+/// [`ScanParamsBuilder::build`]:
+///
+/// ```
+/// # use polyscan::scanner::StrandChoice;
+/// # use polyscan::ScanParams;
+/// # fn main() -> Result<(), polyscan::error::PolyscanError> {
+/// let params = ScanParams::builder().window(15).percentage(85.0).base("A").strands(StrandChoice::Both).build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ScanParamsBuilder {
+    params: ScanParams,
+    // Targets given so far; the default target (A) is used if there are none
+    targets: Vec<BaseClass>,
+}
+
+impl ScanParamsBuilder {
+    /// Scan for `letters` (a base such as `A`, or a class such as `GC`), as
+    /// well as the targets already given.
+    pub fn base(mut self, letters: &str) -> Self {
+        self.targets.push(BaseClass::new(letters.as_bytes()));
+        self
+    }
+
+    /// Scan for `targets` (as well as the targets already given).
+    pub fn targets(mut self, targets: impl IntoIterator<Item = BaseClass>) -> Self {
+        self.targets.extend(targets);
+        self
+    }
+
+    pub fn window(mut self, window: usize) -> Self {
+        self.params.window = window;
+        self
+    }
+
+    pub fn percentage(mut self, percentage: f64) -> Self {
+        self.params.percentage = percentage;
+        self
+    }
+
+    pub fn min_count(mut self, min_count: usize) -> Self {
+        self.params.min_count = Some(min_count);
+        self
+    }
+
+    pub fn alphabet(mut self, alphabet: Alphabet) -> Self {
+        self.params.alphabet = alphabet;
+        self
+    }
+
+    pub fn strands(mut self, strand: StrandChoice) -> Self {
+        self.params.strand = strand;
+        self
+    }
+
+    pub fn n_policy(mut self, n_policy: NPolicy) -> Self {
+        self.params.n_policy = n_policy;
+        self
+    }
+
+    pub fn ignore_masked(mut self, policy: MaskedPolicy) -> Self {
+        self.params.ignore_masked = Some(policy);
+        self
+    }
+
+    pub fn step(mut self, step: usize) -> Self {
+        self.params.step = step;
+        self
+    }
+
+    pub fn fractional_iupac(mut self, fractional_iupac: bool) -> Self {
+        self.params.fractional_iupac = fractional_iupac;
+        self
+    }
+
+    /// The parameters, or why they don't describe a scan.
+    pub fn build(self) -> Result<ScanParams, PolyscanError> {
+        let mut params = self.params;
+        if !self.targets.is_empty() {
+            params.targets = self.targets;
+        }
+        params.validate()?;
+        Ok(params)
+    }
+}
+
+/// A passing window, in 0-based half-open contig coordinates.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Hit {
+    pub chrom: String,
+    pub start: usize,
+    pub end: usize,
+    /// The target as given (e.g. `A` on both strands, or `GC`)
+    pub target: String,
+    /// Percentage of the window made up by the target
+    pub score: f64,
+    pub strand: Strand,
+}
+
+/// The scorer of each target on each strand it is scanned on, with the
+/// target's label: the complement is scanned on the minus strand, unless the
+/// target is its own complement (N, GC, AT) or a protein residue, which get a
+/// single unstranded scorer. U is scanned as T, since RNA is read as DNA.
+pub fn strand_targets(targets: &[BaseClass], alphabet: Alphabet) -> Vec<(BaseClass, Strand, String)> {
+    let mut strands = Vec::new();
+    for target in targets {
+        let label = target.label();
+        let scan_class = match alphabet {
+            Alphabet::Dna => {
+                let letters: Vec<u8> = target.letters().iter().map(|&nuc| if nuc == b'U' { b'T' } else { nuc }).collect();
+                BaseClass::new(&letters)
+            }
+            Alphabet::Protein => target.clone(),
+        };
+        match alphabet {
+            Alphabet::Dna if scan_class.is_self_complementary() => strands.push((scan_class, Strand::Unstranded, label)),
+            Alphabet::Dna => {
+                let complement = scan_class.complement();
+                strands.push((scan_class, Strand::Plus, label.clone()));
+                strands.push((complement, Strand::Minus, label));
+            }
+            Alphabet::Protein => strands.push((scan_class, Strand::Unstranded, label)),
+        }
+    }
+    strands
+}
+
+/// Pair each target's minus-strand scorer with the plus-strand one before it,
+/// for [`StrandChoice::Collapse`]: one scorer passing where either strand
+/// does, labelled with the target (e.g. `A`), or with the target and its
+/// complement (e.g. `A/T`) if `name_both`. Unstranded targets are left alone.
+pub fn collapse_strands<'a>(scorers: &'a [BaseFraction], strands: &[(BaseClass, Strand, String)], name_both: bool) -> Vec<(AnyOf<'a>, String)> {
+    let mut collapsed: Vec<(AnyOf, String)> = Vec::new();
+    for (scorer, (class, strand, label)) in scorers.iter().zip(strands) {
+        match collapsed.last_mut() {
+            Some((either, either_label)) if *strand == Strand::Minus => {
+                either.push(scorer);
+                if name_both {
+                    either_label.push('/');
+                    either_label.push_str(&class.label());
+                }
+            }
+            _ => collapsed.push((AnyOf::new(vec![scorer]), label.clone())),
+        }
+    }
+    collapsed
+}
+
+/// Apply the masked-base and N policies to a target fraction scorer.
+pub fn configure_fraction(scorer: BaseFraction, ignore_masked: Option<MaskedPolicy>, n_policy: NPolicy) -> BaseFraction {
+    let scorer = if ignore_masked == Some(MaskedPolicy::Exclude) { scorer.letters_only() } else { scorer };
+    match n_policy {
+        NPolicy::Ignore => scorer.ignoring(b'N'),
+        NPolicy::CountAgainst => scorer,
+        NPolicy::CountFor => scorer.counting(b'N'),
+        NPolicy::SkipWindow => scorer.rejecting(b'N'),
+    }
+}
+
+/// Scans sequences for the windows passing its [`ScanParams`].
+#[derive(Debug, Clone)]
+pub struct Scanner {
+    params: ScanParams,
+    strands: Vec<(BaseClass, Strand, String)>,
+    fractions: Vec<BaseFraction>,
+    // With StrandChoice::Collapse, the scorer of each target on either strand
+    collapsed: Vec<EitherStrand>,
+}
+
+// The fractions of a target on both strands as one scorer, passing where
+// either does, for StrandChoice::Collapse
+#[derive(Debug, Clone)]
+struct EitherStrand {
+    fractions: Vec<BaseFraction>,
+    label: String,
+}
+
+impl WindowScorer for EitherStrand {
+    fn score(&self, comp: &scan::Composition) -> Option<f64> {
+        self.fractions.iter().filter_map(|fraction| fraction.score(comp)).max_by(f64::total_cmp)
+    }
+}
+
+impl Scanner {
+    /// A scanner for `params`, or an error if they don't describe a scan.
+    pub fn new(params: ScanParams) -> Result<Self, PolyscanError> {
+        params.validate()?;
+
+        let strands = strand_targets(&params.targets, params.alphabet);
+        let mut scanner = Scanner { params, strands, fractions: Vec::new(), collapsed: Vec::new() };
+        scanner.fractions = scanner.fractions_for(scanner.params.window, scanner.params.min_count);
+        if scanner.params.strand == StrandChoice::Collapse {
+            // A minus-strand target follows its plus strand, as in collapse_strands
+            for (fraction, (_, strand, label)) in scanner.fractions.iter().zip(&scanner.strands) {
+                match scanner.collapsed.last_mut() {
+                    Some(either) if *strand == Strand::Minus => either.fractions.push(fraction.clone()),
+                    _ => scanner.collapsed.push(EitherStrand { fractions: vec![fraction.clone()], label: label.clone() }),
+                }
+            }
+        }
+        Ok(scanner)
+    }
+
+    pub fn params(&self) -> &ScanParams {
+        &self.params
+    }
+
+    /// The targets scanned on each strand, with their labels (see
+    /// [`strand_targets`]).
+    pub fn strands(&self) -> &[(BaseClass, Strand, String)] {
+        &self.strands
+    }
+
+    /// The target fraction scorer of each of [`Scanner::strands`].
+    pub fn fractions(&self) -> &[BaseFraction] {
+        &self.fractions
+    }
+
+    /// [`Scanner::fractions`] for windows of another size, needing
+    /// `min_count` target bases if given and the percentage otherwise; for
+    /// scans at several window sizes, or of whole contigs as one window.
+    pub fn fractions_for(&self, window: usize, min_count: Option<usize>) -> Vec<BaseFraction> {
+        self.strands
+            .iter()
+            .map(|(class, _, _)| {
+                let scorer = match min_count {
+                    Some(min_count) => BaseFraction::with_min_count(class, min_count),
+                    None => BaseFraction::for_class(class, self.params.percentage, window),
+                };
+                let scorer = configure_fraction(scorer, self.params.ignore_masked, self.params.n_policy);
+                if self.params.fractional_iupac {
+                    scorer.fractional_iupac()
+                } else {
+                    scorer
+                }
+            })
+            .collect()
+    }
+
+    /// The passing windows of `seq`, the bases of `chrom` from its start.
+    pub fn scan_seq(&self, chrom: &str, seq: &[u8]) -> Result<Vec<Hit>, PolyscanError> {
+        Ok(self.hits(chrom, seq).collect())
+    }
+
+    /// The passing windows of `record`, in contig coordinates (a region or
+    /// chunk starts part way into its contig).
+    #[cfg(feature = "io")]
+    pub fn scan_record(&self, record: &SeqRecord) -> Result<Vec<Hit>, PolyscanError> {
+        Ok(self.record_hits(record).collect())
+    }
+
+    /// The passing windows of every record of a sequence file (FASTA or
+    /// FASTQ, optionally compressed, .2bit, BAM or CRAM without a reference).
+    #[cfg(feature = "io")]
+    pub fn scan_file<P: AsRef<Path>>(&self, path: P) -> Result<Vec<Hit>, PolyscanError> {
+        let mut hits = Vec::new();
+        for record in open_records(path, InputFormat::Auto, None)? {
+            hits.extend(self.record_hits(&record.map_err(PolyscanError::reading)?));
+        }
+        Ok(hits)
+    }
+
+    /// Write the passing windows of every record of a sequence file to
+    /// `sink` as they are found, then finish it.
+    #[cfg(feature = "io")]
+    pub fn scan_file_into<P: AsRef<Path>, S: OutputSink + ?Sized>(&self, path: P, sink: &mut S) -> Result<(), Box<dyn Error>> {
+        for record in open_records(path, InputFormat::Auto, None)? {
+            let record = record?;
+            for hit in self.record_hits(&record) {
+                sink.write_hit(&hit)?;
+            }
+        }
+        sink.finish()
+    }
+
+    /// Write the passing windows of `seq`, the bases of `chrom` from its
+    /// start, to `sink` as they are found, then finish it.
+    pub fn scan_seq_into<S: OutputSink + ?Sized>(&self, chrom: &str, seq: &[u8], sink: &mut S) -> Result<(), Box<dyn Error>> {
+        write_hits(self.hits(chrom, seq), sink)
+    }
+
+    /// The passing windows of `seq`, the bases of `chrom` from its start, as
+    /// they are found: stopping early leaves the rest of `seq` unscanned.
+    pub fn hits<'a>(&'a self, chrom: &'a str, seq: &'a [u8]) -> WindowHits<'a> {
+        WindowHits::new(Cow::Borrowed(self), chrom, self.prepared(seq), 0)
+    }
+
+    /// [`Scanner::hits`] for `record`, in contig coordinates.
+    #[cfg(feature = "io")]
+    pub fn record_hits<'a>(&'a self, record: &'a SeqRecord) -> WindowHits<'a> {
+        WindowHits::new(Cow::Borrowed(self), &record.id, self.prepared(&record.seq), record.start)
+    }
+
+    // `seq` as scanned: RNA read as DNA, and soft-masked bases hidden if asked
+    // to; only copied when a base has to be rewritten
+    fn prepared<'a>(&self, seq: &'a [u8]) -> Cow<'a, [u8]> {
+        let mut seq = Cow::Borrowed(seq);
+        if self.params.alphabet == Alphabet::Dna && has_uracil(&seq) {
+            rna_to_dna(seq.to_mut());
+        }
+        if self.params.ignore_masked.is_some() {
+            hide_soft_masked(seq.to_mut());
+        }
+        seq
+    }
+
+    // Add the hits of the windows of `seq`, the bases of `chrom` from
+    // `offset`, to `hits`
+    fn scan_into(&self, chrom: &str, seq: &[u8], offset: usize, hits: &mut VecDeque<Hit>) {
+        let scorers: Vec<(&dyn WindowScorer, Strand, &str)> = match self.params.strand {
+            StrandChoice::Collapse => self.collapsed.iter().map(|either| (either as &dyn WindowScorer, Strand::Unstranded, either.label.as_str())).collect(),
+            _ => self.fractions.iter().zip(&self.strands).map(|(scorer, (_, strand, label))| (scorer as &dyn WindowScorer, *strand, label.as_str())).collect(),
+        };
+        // Unstranded hits belong to either strand
+        let unwanted = match self.params.strand {
+            StrandChoice::Plus => Some(Strand::Minus),
+            StrandChoice::Minus => Some(Strand::Plus),
+            _ => None,
+        };
+
+        let window_scorers: Vec<(&dyn WindowScorer, Strand)> = scorers.iter().map(|&(scorer, strand, _)| (scorer, strand)).collect();
+        // Steps count from the contig start
+        let steps = scan::Steps::new(self.params.step, offset);
+        let scanned = scan::scan_windows(seq, self.params.window, steps, &window_scorers, |hit| {
+            if Some(hit.strand) != unwanted {
+                hits.push_back(Hit {
+                    chrom: chrom.to_string(),
+                    start: offset + hit.start,
+                    end: offset + hit.end,
+                    target: scorers[hit.scorer].2.to_string(),
+                    score: hit.score,
+                    strand: hit.strand,
+                });
+            }
+            Ok(())
+        });
+        scanned.expect("collecting hits never fails");
+    }
+}
+
+/// The passing windows of a sequence, found a stretch of window starts at a
+/// time as they are asked for; see [`Scanner::hits`] and [`scan_windows`].
+pub struct WindowHits<'a> {
+    scanner: Cow<'a, Scanner>,
+    chrom: &'a str,
+    seq: Cow<'a, [u8]>,
+    /// Contig position of `seq[0]`
+    offset: usize,
+    /// The first window start not scanned yet
+    next: usize,
+    /// Hits of the stretch scanned last, not yet taken
+    pending: VecDeque<Hit>,
+}
+
+impl<'a> WindowHits<'a> {
+    fn new(scanner: Cow<'a, Scanner>, chrom: &'a str, seq: Cow<'a, [u8]>, offset: usize) -> Self {
+        WindowHits { scanner, chrom, seq, offset, next: 0, pending: VecDeque::new() }
+    }
+}
+
+impl Iterator for WindowHits<'_> {
+    type Item = Hit;
+
+    fn next(&mut self) -> Option<Hit> {
+        let window = self.scanner.params.window;
+        loop {
+            if let Some(hit) = self.pending.pop_front() {
+                return Some(hit);
+            }
+            if self.seq.len() < window || self.next > self.seq.len() - window {
+                return None;
+            }
+            // Stretches line up with those checked for uppercase A/C/G/T
+            let end = (self.next + PLAIN_CHUNK).min(self.seq.len() - window + 1);
+            self.scanner.scan_into(self.chrom, &self.seq[self.next..end + window - 1], self.offset + self.next, &mut self.pending);
+            self.next = end;
+        }
+    }
+}
+
+/// The passing windows of `seq` under `params`, found lazily as they are
+/// asked for, so callers can stop early or feed them into their own sinks.
+/// Hits have an empty `chrom` and coordinates from the start of `seq`; use
+/// [`Scanner::hits`] to name the sequence. Errors if `params` don't describe
+/// a scan (see [`Scanner::new`]).
+pub fn scan_windows<'a>(seq: &'a [u8], params: &ScanParams) -> Result<WindowHits<'a>, PolyscanError> {
+    let scanner = Scanner::new(params.clone())?;
+    let seq = scanner.prepared(seq);
+    Ok(WindowHits::new(Cow::Owned(scanner), "", seq, 0))
+}
@@ -0,0 +1,152 @@
+use std::error::Error;
+use std::io::Write;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use polyscan::input::{fasta_records, open_records_with, InputBackend, InputFormat, ReadCheck};
+use polyscan::scan::BaseClass;
+
+use polyscan::pipeline::{scan_records, ScanConfig};
+
+use crate::{aliased_records, deduplicated_records, DuplicatePolicy};
+
+/// A built-in scan with its known-good BED output.
+struct Case {
+    name: &'static str,
+    fasta: &'static str,
+    base: char,
+    window: usize,
+    percentage: f64,
+    expected: &'static str,
+}
+
+const CASES: &[Case] = &[
+    Case {
+        name: "plus-strand polyA tract",
+        fasta: ">s1\nAAAAAAAAAACGT\n",
+        base: 'A',
+        window: 10,
+        percentage: 80.0,
+        expected: "s1\t0\t10\tA\t100\t+\n\
+                   s1\t1\t11\tA\t90\t+\n\
+                   s1\t2\t12\tA\t80\t+\n",
+    },
+    Case {
+        name: "minus-strand tract with rounded-up scores",
+        fasta: ">s2\nGGTTTT\nTTTTGG\n",
+        base: 'A',
+        window: 8,
+        percentage: 75.0,
+        expected: "s2\t0\t8\tA\t75\t-\n\
+                   s2\t1\t9\tA\t88\t-\n\
+                   s2\t2\t10\tA\t100\t-\n\
+                   s2\t3\t11\tA\t88\t-\n\
+                   s2\t4\t12\tA\t75\t-\n",
+    },
+    Case {
+        name: "short contigs skipped, N reported once, unstranded",
+        fasta: ">s3\nAAA\n>s4 description\naaaaaNNNNN\n",
+        base: 'N',
+        window: 5,
+        percentage: 100.0,
+        expected: "s4\t5\t10\tN\t100\t.\n",
+    },
+    Case {
+        name: "soft-masked tract across a 64-base block",
+        fasta: ">s5\nGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGGG\naaaaaaaaaaaaGGGGGGGG\n",
+        base: 'A',
+        window: 10,
+        percentage: 100.0,
+        expected: "s5\t60\t70\tA\t100\t+\n\
+                   s5\t61\t71\tA\t100\t+\n\
+                   s5\t62\t72\tA\t100\t+\n",
+    },
+];
+
+// Contigs of a gzip file made by concatenating one per member, as `cat a.gz b.gz` does
+const MEMBERS: &[&str] = &[">m1\nACGTACGT\n", ">m2\nAAAAAAAA\n", ">m3\nTTTTTTTT\n"];
+
+/// Read `MEMBERS` back from one file of concatenated gzip members with every
+/// parser, checking that no member after the first is dropped.
+fn concatenated_gzip() -> Result<bool, Box<dyn Error>> {
+    let path = std::env::temp_dir().join(format!("polyscan-self-test-{}.fa.gz", std::process::id()));
+    let mut file = std::fs::File::create(&path)?;
+    for member in MEMBERS {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(member.as_bytes())?;
+        file.write_all(&encoder.finish()?)?;
+    }
+    drop(file);
+
+    let mut passed = true;
+    for backend in [InputBackend::Noodles, InputBackend::Needletail] {
+        let ids: Result<Vec<String>, _> = open_records_with(&path, InputFormat::Auto, None, backend, 1, ReadCheck::WholeFile)?.map(|record| record.map(|record| record.id)).collect();
+        match ids {
+            Ok(ids) if ids == ["m1", "m2", "m3"] => {}
+            other => {
+                eprintln!("--- expected m1, m2, m3 with {:?}, got {:?}", backend, other);
+                passed = false;
+            }
+        }
+    }
+    std::fs::remove_file(&path)?;
+    Ok(passed)
+}
+
+/// Run every built-in case, report results on stderr, and fail if any output differs.
+// Contigs renamed by --chrom-alias, then told apart by --on-duplicate rename
+const DUPLICATES: &str = ">x\nACGT\n>y\nACGT\n>x\nACGT\n>z\nACGT\n";
+
+/// Rename `DUPLICATES` as a scan does, checking that a repeated contig is
+/// renamed from its alias and that contigs given one alias are duplicates.
+fn aliased_duplicates() -> Result<bool, Box<dyn Error>> {
+    let aliases = [("x", "chrX"), ("y", "chrY"), ("z", "chrX")].iter().map(|(name, alias)| (name.to_string(), alias.to_string())).collect();
+    let records = deduplicated_records(aliased_records(fasta_records(DUPLICATES.as_bytes()), aliases), DuplicatePolicy::Rename);
+    let ids = records.map(|record| record.map(|record| record.id)).collect::<Result<Vec<String>, _>>()?;
+    if ids != ["chrX", "chrY", "chrX_2", "chrX_3"] {
+        eprintln!("--- expected chrX, chrY, chrX_2, chrX_3, got {:?}", ids);
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+pub fn run() -> Result<(), Box<dyn Error>> {
+    let mut failures = 0;
+
+    for case in CASES {
+        let mut output = Vec::new();
+        let config = ScanConfig::plain(vec![BaseClass::new(&[case.base as u8])], case.window, case.percentage);
+        scan_records(fasta_records(case.fasta.as_bytes()), &config, &mut output, None)?;
+        let output = String::from_utf8(output)?;
+
+        if output == case.expected {
+            eprintln!("ok      {}", case.name);
+        } else {
+            failures += 1;
+            eprintln!("FAILED  {}", case.name);
+            eprintln!("--- expected\n{}--- got\n{}", case.expected, output);
+        }
+    }
+
+    if concatenated_gzip()? {
+        eprintln!("ok      concatenated gzip members");
+    } else {
+        failures += 1;
+        eprintln!("FAILED  concatenated gzip members");
+    }
+
+    if aliased_duplicates()? {
+        eprintln!("ok      aliased duplicate contigs");
+    } else {
+        failures += 1;
+        eprintln!("FAILED  aliased duplicate contigs");
+    }
+
+    let total = CASES.len() + 2;
+    if failures > 0 {
+        return Err(format!("{} of {} self-test cases failed.", failures, total).into());
+    }
+
+    eprintln!("All {} self-test cases passed.", total);
+    Ok(())
+}
@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+
+use polyscan::error::PolyscanError;
+use polyscan::faidx::{IndexedFasta, Region};
+use polyscan::input::{Records, SeqRecord};
+use polyscan::scan::BaseClass;
+
+use polyscan::pipeline::{scan_records, ScanConfig};
+
+/// Scan parameters used when a request leaves them out.
+const DEFAULT_BASE: &str = "A";
+const DEFAULT_WINDOW: usize = 10;
+const DEFAULT_PERCENTAGE: f64 = 80.0;
+
+/// Connections answered at once, and accepted ones allowed to wait for a
+/// worker; past both, a connection gets a 503 straight away.
+const WORKERS: usize = 16;
+const QUEUED_CONNECTIONS: usize = 64;
+/// How long a client may take to send its whole request, or to take the
+/// response.
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longest request or header line, and most header lines, read per request.
+const MAX_LINE: usize = 8192;
+const MAX_HEADERS: usize = 100;
+
+/// Answer `GET /scan?region=chr1:1-100000&base=A&w=10&p=80` requests on
+/// `address` with the BED output of scanning that region of `fasta`, which
+/// must be indexed. Connections are served by a fixed pool of `WORKERS`
+/// threads; runs until the process is stopped.
+pub fn run(fasta: &Path, address: &str, max_region: u64) -> Result<(), Box<dyn Error>> {
+    let Some(indexed) = IndexedFasta::open(fasta)? else {
+        return Err(PolyscanError::input("polyscan serve needs an indexed FASTA (plain with a .fai, or bgzip with .fai and .gzi; see samtools faidx)."));
+    };
+    let indexed = Arc::new(indexed);
+    let listener = TcpListener::bind(address)?;
+    info!("Serving {} on http://{}/scan", fasta.display(), listener.local_addr()?);
+
+    // Accepted connections go to the workers through a bounded queue, so a
+    // flood of clients can't spawn threads without limit
+    let (sender, receiver) = mpsc::sync_channel::<TcpStream>(QUEUED_CONNECTIONS);
+    let receiver = Arc::new(Mutex::new(receiver));
+    for _ in 0..WORKERS {
+        let (receiver, indexed) = (Arc::clone(&receiver), Arc::clone(&indexed));
+        std::thread::spawn(move || work(&receiver, &indexed, max_region));
+    }
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("failed to accept a connection: {}", e);
+                continue;
+            }
+        };
+        if let Err(e) = stream.set_read_timeout(Some(IO_TIMEOUT)).and_then(|_| stream.set_write_timeout(Some(IO_TIMEOUT))) {
+            warn!("failed to set a connection's timeouts: {}", e);
+            continue;
+        }
+        match sender.try_send(stream) {
+            Ok(()) => {}
+            Err(TrySendError::Full(mut stream)) => {
+                if let Err(e) = respond(&mut stream, "503 Service Unavailable", "too many requests in flight, try again\n") {
+                    warn!("failed to turn a connection away: {}", e);
+                }
+            }
+            Err(TrySendError::Disconnected(_)) => return Err("the request workers have stopped".into()),
+        }
+    }
+    Ok(())
+}
+
+// Answer connections from the queue until it is closed
+fn work(receiver: &Mutex<Receiver<TcpStream>>, indexed: &IndexedFasta, max_region: u64) {
+    loop {
+        // The lock is only held while waiting, not while answering
+        let stream = match receiver.lock() {
+            Ok(receiver) => receiver.recv(),
+            Err(_) => return,
+        };
+        let Ok(stream) = stream else { return };
+        if let Err(e) = handle(stream, indexed, max_region) {
+            warn!("failed to answer a request: {}", e);
+        }
+    }
+}
+
+// Read one line of at most MAX_LINE bytes into `line`; false if it was
+// longer (or the client stopped mid-line at the limit)
+fn read_line_limited<R: BufRead>(reader: &mut R, line: &mut String) -> std::io::Result<bool> {
+    let read = reader.by_ref().take(MAX_LINE as u64).read_line(line)?;
+    Ok(read < MAX_LINE || line.ends_with('\n'))
+}
+
+// The request side of a connection, refusing to read past `deadline`: each
+// read may only wait for what's left of it, so a client sending a byte at a
+// time can't hold a worker for longer
+struct DeadlineReader {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(std::io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buf)
+    }
+}
+
+// Read one request and write its response
+fn handle(mut stream: TcpStream, indexed: &IndexedFasta, max_region: u64) -> Result<(), Box<dyn Error>> {
+    let mut reader = BufReader::new(DeadlineReader { stream: stream.try_clone()?, deadline: Instant::now() + IO_TIMEOUT });
+    let request_line = match read_request(&mut reader) {
+        Ok(Ok(request_line)) => request_line,
+        Ok(Err((status, message))) => return respond(&mut stream, status, &message),
+        Err(e) if matches!(e.kind(), std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock) => {
+            return respond(&mut stream, "408 Request Timeout", &format!("the request took longer than {} seconds to send\n", IO_TIMEOUT.as_secs()));
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    if method != "GET" {
+        return respond(&mut stream, "405 Method Not Allowed", "only GET is supported\n");
+    }
+    if path != "/scan" {
+        return respond(&mut stream, "404 Not Found", "the only endpoint is /scan\n");
+    }
+    match scan_query(&parse_query(query), indexed, max_region) {
+        Ok(bed) => respond(&mut stream, "200 OK", &bed),
+        Err(message) => respond(&mut stream, "400 Bad Request", &format!("{}\n", message)),
+    }
+}
+
+// The request line, after reading the headers up to the blank line, or the
+// status and message to refuse the request with
+fn read_request<R: BufRead>(reader: &mut R) -> std::io::Result<Result<String, (&'static str, String)>> {
+    let mut request_line = String::new();
+    if !read_line_limited(reader, &mut request_line)? {
+        return Ok(Err(("414 URI Too Long", format!("the request line is longer than {} bytes\n", MAX_LINE))));
+    }
+    // Headers are not needed, but must be read before answering
+    let mut header = String::new();
+    let mut headers = 0;
+    loop {
+        let complete = read_line_limited(reader, &mut header)?;
+        if complete && header.trim_end().is_empty() {
+            return Ok(Ok(request_line));
+        }
+        if !complete || headers == MAX_HEADERS {
+            return Ok(Err(("431 Request Header Fields Too Large", format!("headers are limited to {} lines of up to {} bytes\n", MAX_HEADERS, MAX_LINE))));
+        }
+        header.clear();
+        headers += 1;
+    }
+}
+
+fn respond(stream: &mut TcpStream, status: &str, body: &str) -> Result<(), Box<dyn Error>> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()?;
+    Ok(())
+}
+
+// Query parameters, percent-decoded ('+' is a space)
+fn parse_query(query: &str) -> HashMap<String, String> {
+    let decode = |s: &str| {
+        let bytes = s.as_bytes();
+        let mut decoded = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok()).and_then(|h| u8::from_str_radix(h, 16).ok());
+            match (bytes[i], hex) {
+                (b'%', Some(byte)) => {
+                    decoded.push(byte);
+                    i += 3;
+                }
+                (b'+', _) => {
+                    decoded.push(b' ');
+                    i += 1;
+                }
+                (byte, _) => {
+                    decoded.push(byte);
+                    i += 1;
+                }
+            }
+        }
+        String::from_utf8_lossy(&decoded).into_owned()
+    };
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(key), decode(value))
+        })
+        .collect()
+}
+
+// Scan the requested region, returning the BED output or what's wrong with the request
+fn scan_query(params: &HashMap<String, String>, indexed: &IndexedFasta, max_region: u64) -> Result<String, String> {
+    let region: Region = params.get("region").ok_or("missing region, e.g. region=chr1:1-100000")?.parse()?;
+    let contig_len = indexed.fai.get(&region.chrom).ok_or_else(|| format!("unknown contig '{}'", region.chrom))?.length;
+    let end = region.span(contig_len)?.end;
+    if end - region.start > max_region {
+        return Err(format!("regions are limited to {} bases", max_region));
+    }
+
+    let bases = params.get("base").map_or(DEFAULT_BASE, String::as_str).to_uppercase();
+    let targets: Vec<BaseClass> = bases.split(',').map(|base| BaseClass::new(base.trim().as_bytes())).collect();
+    if bases.split(',').any(|base| base.trim().is_empty() || !base.trim().bytes().all(|b| b"ACGTN".contains(&b))) {
+        return Err("base must be made of A, C, G, T or N (comma-separated for several)".to_string());
+    }
+    let window = match params.get("w") {
+        Some(w) => w.parse::<usize>().ok().filter(|&w| w > 0).ok_or("w must be a positive integer")?,
+        None => DEFAULT_WINDOW,
+    };
+    let percentage = match params.get("p") {
+        Some(p) => p.parse::<f64>().ok().filter(|p| (50.0..=100.0).contains(p)).ok_or("p must be between 50 and 100")?,
+        None => DEFAULT_PERCENTAGE,
+    };
+
+    let config = ScanConfig::plain(targets, window, percentage);
+    config.validate().map_err(|e| e.to_string())?;
+
+    let seq = indexed.fetch(&region.chrom, region.start, end).map_err(|e| e.to_string())?;
+    let record = SeqRecord { id: region.chrom.clone(), seq: seq.into(), start: region.start as usize, contig_len: contig_len as usize, ..Default::default() };
+    let records: Records = Box::new(std::iter::once(Ok(record)));
+    let mut bed = Vec::new();
+    scan_records(records, &config, &mut bed, None).map_err(|e| e.to_string())?;
+    String::from_utf8(bed).map_err(|e| e.to_string())
+}
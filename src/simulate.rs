@@ -0,0 +1,158 @@
+/// How the lengths of planted tracts are drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum LengthDistribution {
+    /// Every length between the shortest and the longest equally likely
+    Uniform,
+    /// Short tracts more likely, each extra base less so, with the mean
+    /// halfway between the shortest and the longest (before truncation)
+    Geometric,
+}
+
+/// Parameters of a simulated genome.
+#[derive(Debug, Clone)]
+pub struct SimulationParams {
+    pub genome_size: usize,
+    /// Expected G+C fraction of the background sequence
+    pub gc: f64,
+    pub tracts: usize,
+    /// Fraction of the tracts that are STRs rather than homopolymers
+    pub str_fraction: f64,
+    pub min_length: usize,
+    pub max_length: usize,
+    pub distribution: LengthDistribution,
+    pub seed: u64,
+}
+
+/// A tract planted in the simulated sequence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlantedTract {
+    pub start: usize,
+    pub end: usize,
+    /// The repeated unit: one base for homopolymers, 2-6 for STRs
+    pub unit: Vec<u8>,
+}
+
+/// SplitMix64, a small seedable generator, so simulations are reproducible
+/// without a dependency.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform in `[0, n)`; `n` must be positive.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+const BASES: [u8; 4] = *b"ACGT";
+const SHORTEST_STR_UNIT: usize = 2;
+const LONGEST_STR_UNIT: usize = 6;
+
+// A background base, G or C with probability `gc`
+fn random_base(rng: &mut Rng, gc: f64) -> u8 {
+    let strong = rng.next_f64() < gc;
+    match (strong, rng.below(2)) {
+        (true, 0) => b'G',
+        (true, _) => b'C',
+        (false, 0) => b'A',
+        (false, _) => b'T',
+    }
+}
+
+fn tract_length(rng: &mut Rng, params: &SimulationParams) -> usize {
+    let span = params.max_length - params.min_length;
+    match params.distribution {
+        LengthDistribution::Uniform => params.min_length + rng.below(span + 1),
+        LengthDistribution::Geometric => {
+            // Each extra base with probability q, for a mean of span / 2 extra
+            // bases; lengths past the longest are drawn again
+            let mean_extra = span as f64 / 2.0;
+            let q = mean_extra / (mean_extra + 1.0);
+            loop {
+                let mut extra = 0;
+                while extra <= span && rng.next_f64() < q {
+                    extra += 1;
+                }
+                if extra <= span {
+                    return params.min_length + extra;
+                }
+            }
+        }
+    }
+}
+
+// A unit that isn't itself a repeat of a shorter one (e.g. not ATAT), so the
+// planted tract is an STR of exactly that unit
+fn str_unit(rng: &mut Rng) -> Vec<u8> {
+    loop {
+        let len = SHORTEST_STR_UNIT + rng.below(LONGEST_STR_UNIT - SHORTEST_STR_UNIT + 1);
+        let unit: Vec<u8> = (0..len).map(|_| BASES[rng.below(4)]).collect();
+        let periodic = (1..len).any(|period| len.is_multiple_of(period) && unit.iter().skip(period).zip(&unit).all(|(a, b)| a == b));
+        if !periodic {
+            return unit;
+        }
+    }
+}
+
+/// Simulate a genome of random background sequence with homopolymers and
+/// STRs planted at known positions, returned in order.
+///
+/// The genome is cut into as many equal slots as there are tracts, and each
+/// tract is placed at a random offset within its slot, so tracts never touch.
+/// The bases flanking a tract are chosen so as not to extend it. Runs arising
+/// by chance in the background are not reported.
+pub fn simulate(params: &SimulationParams) -> Result<(Vec<u8>, Vec<PlantedTract>), String> {
+    if params.min_length == 0 || params.min_length > params.max_length {
+        return Err("tract lengths must be at least 1, with the shortest no longer than the longest".to_string());
+    }
+    if !(0.0..=1.0).contains(&params.gc) || !(0.0..=1.0).contains(&params.str_fraction) {
+        return Err("GC content and STR fraction must be between 0 and 1".to_string());
+    }
+    // Room for the longest tract and a flanking base on either side
+    let slot = params.genome_size.checked_div(params.tracts).unwrap_or(params.genome_size);
+    if params.tracts > 0 && slot < params.max_length + 2 {
+        return Err(format!("a {} base genome is too small for {} tracts of up to {} bases", params.genome_size, params.tracts, params.max_length));
+    }
+
+    let mut rng = Rng::new(params.seed);
+    let mut seq: Vec<u8> = (0..params.genome_size).map(|_| random_base(&mut rng, params.gc)).collect();
+    let mut tracts = Vec::with_capacity(params.tracts);
+    for i in 0..params.tracts {
+        let len = tract_length(&mut rng, params);
+        let unit = if rng.next_f64() < params.str_fraction { str_unit(&mut rng) } else { vec![BASES[rng.below(4)]] };
+        let start = i * slot + 1 + rng.below(slot - len - 1);
+        let end = start + len;
+        for (offset, base) in seq[start..end].iter_mut().enumerate() {
+            *base = unit[offset % unit.len()];
+        }
+        // The base before must not continue the unit backwards, nor the base after forwards
+        let before = unit[unit.len() - 1];
+        let after = unit[len % unit.len()];
+        while seq[start - 1] == before {
+            seq[start - 1] = random_base(&mut rng, params.gc);
+        }
+        while end < seq.len() && seq[end] == after {
+            seq[end] = random_base(&mut rng, params.gc);
+        }
+        tracts.push(PlantedTract { start, end, unit });
+    }
+    Ok((seq, tracts))
+}
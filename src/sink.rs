@@ -0,0 +1,55 @@
+//! Where a scan's hits go. An [`OutputSink`] takes the hits of a scan one at
+//! a time, so an embedding tool can stream them into its own store (a
+//! database, a channel) instead of collecting them first; the BED writer and
+//! `Vec<Hit>` are sinks too.
+
+use std::error::Error;
+
+use crate::scanner::Hit;
+
+/// Takes the hits of a scan, in the order they are found.
+pub trait OutputSink {
+    /// Take one hit.
+    fn write_hit(&mut self, hit: &Hit) -> Result<(), Box<dyn Error>>;
+
+    /// Called once after the last hit, e.g. to flush or commit what was
+    /// written. Does nothing by default.
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+impl OutputSink for Vec<Hit> {
+    fn write_hit(&mut self, hit: &Hit) -> Result<(), Box<dyn Error>> {
+        self.push(hit.clone());
+        Ok(())
+    }
+}
+
+impl<S: OutputSink + ?Sized> OutputSink for &mut S {
+    fn write_hit(&mut self, hit: &Hit) -> Result<(), Box<dyn Error>> {
+        (**self).write_hit(hit)
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        (**self).finish()
+    }
+}
+
+impl<S: OutputSink + ?Sized> OutputSink for Box<S> {
+    fn write_hit(&mut self, hit: &Hit) -> Result<(), Box<dyn Error>> {
+        (**self).write_hit(hit)
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        (**self).finish()
+    }
+}
+
+/// Write `hits` to `sink`, then finish it.
+pub fn write_hits<S: OutputSink + ?Sized>(hits: impl IntoIterator<Item = Hit>, sink: &mut S) -> Result<(), Box<dyn Error>> {
+    for hit in hits {
+        sink.write_hit(&hit)?;
+    }
+    sink.finish()
+}
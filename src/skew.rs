@@ -0,0 +1,45 @@
+use std::error::Error;
+
+use crate::scan::{tile_windows, Composition};
+
+/// GC skew `(G - C) / (G + C)` of a composition; 0 when it has neither G nor C.
+pub fn gc_skew(comp: &Composition) -> f64 {
+    let (g, c) = (comp.count(b'G') as f64, comp.count(b'C') as f64);
+    if g + c == 0.0 {
+        0.0
+    } else {
+        (g - c) / (g + c)
+    }
+}
+
+/// GC skew of one tile, with the running sum of the skews up to and including it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SkewWindow {
+    pub start: usize,
+    pub end: usize,
+    pub skew: f64,
+    pub cumulative: f64,
+}
+
+/// GC skew of consecutive non-overlapping `window`-sized tiles of `seq` (the
+/// last may be shorter) and the cumulative skew curve.
+pub fn skew_profile(seq: &[u8], window: usize) -> Result<Vec<SkewWindow>, Box<dyn Error>> {
+    let mut profile = Vec::with_capacity(seq.len() / window.max(1) + 1);
+    let mut cumulative = 0.0;
+    tile_windows(seq, window, |start, end, comp| {
+        let skew = gc_skew(comp);
+        cumulative += skew;
+        profile.push(SkewWindow { start, end, skew, cumulative });
+        Ok(())
+    })?;
+    Ok(profile)
+}
+
+/// Predicted replication origin and terminus of a circular bacterial
+/// chromosome: the ends of the tiles where the cumulative skew reaches its
+/// minimum (oriC) and maximum (ter). `None` for an empty profile.
+pub fn predict_origin_terminus(profile: &[SkewWindow]) -> Option<(usize, usize)> {
+    let min = profile.iter().min_by(|a, b| a.cumulative.total_cmp(&b.cumulative))?;
+    let max = profile.iter().max_by(|a, b| a.cumulative.total_cmp(&b.cumulative))?;
+    Some((min.end, max.end))
+}
@@ -0,0 +1,91 @@
+//! Kernel-smoothed density of target bases along a sequence, for `--smooth`.
+
+use crate::scan::BaseClass;
+
+/// A smoothing kernel over positions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kernel {
+    /// Gaussian with this standard deviation in bases
+    Gaussian(f64),
+    /// Moving average over this many bases, centred on each position
+    Box(usize),
+}
+
+impl Kernel {
+    // Radii of the box passes making up the kernel. Three passes of a box of
+    // radius r have a variance of r(r + 1), close enough to a Gaussian's shape
+    fn passes(&self) -> Vec<usize> {
+        match *self {
+            Kernel::Gaussian(sigma) => {
+                let radius = (((1.0 + 4.0 * sigma * sigma).sqrt() - 1.0) / 2.0).round() as usize;
+                vec![radius; 3]
+            }
+            Kernel::Box(width) => vec![width / 2],
+        }
+    }
+}
+
+/// The fraction of bases in `class` around every position of `seq`, smoothed
+/// with `kernel`, from 0 to 1.
+///
+/// The Gaussian is approximated by three box passes, so it reaches about
+/// 3.5 standard deviations either side. Near the ends of the sequence each
+/// pass averages over the bases there are, so the density isn't pulled down
+/// towards 0. N bases count as bases not in the class.
+///
+/// ```
+/// # use polyscan::scan::BaseClass;
+/// # use polyscan::smooth::{smoothed_density, Kernel};
+/// let density = smoothed_density(b"CCCCAAAACCCC", &BaseClass::new(b"A"), Kernel::Box(3));
+/// assert_eq!(density[5], 1.0);
+/// ```
+pub fn smoothed_density(seq: &[u8], class: &BaseClass, kernel: Kernel) -> Vec<f32> {
+    let mut density: Vec<f32> = seq.iter().map(|&nuc| class.contains(nuc) as u8 as f32).collect();
+    for radius in kernel.passes() {
+        box_pass(&mut density, radius);
+    }
+    density
+}
+
+// Replace every value with the mean of those within `radius` of it, in place.
+// The values that have gone out of the back of the window are already
+// overwritten, so keep the originals in a ring
+fn box_pass(values: &mut [f32], radius: usize) {
+    let len = values.len();
+    if radius == 0 || len == 0 {
+        return;
+    }
+    let slots = radius + 1;
+    let mut ring = vec![0.0f32; slots];
+    let mut sum: f64 = values[..radius.min(len)].iter().map(|&v| v as f64).sum();
+    for i in 0..len {
+        if i + radius < len {
+            sum += values[i + radius] as f64;
+        }
+        if i > radius {
+            sum -= ring[(i - radius - 1) % slots] as f64;
+        }
+        ring[i % slots] = values[i];
+        let (from, to) = (i.saturating_sub(radius), (i + radius).min(len - 1));
+        values[i] = (sum / (to - from + 1) as f64) as f32;
+    }
+}
+
+/// Call `emit` with every run of positions `[start, end)` whose density
+/// rounds to the same value at `decimals` decimal places, and that value, so
+/// a per-base track collapses to as few bedGraph lines as it can.
+pub fn density_runs<F, E>(density: &[f32], decimals: usize, mut emit: F) -> Result<(), E>
+where
+    F: FnMut(usize, usize, f64) -> Result<(), E>,
+{
+    let scale = 10f64.powi(decimals as i32);
+    let rounded = |value: f32| (value as f64 * scale).round() / scale;
+    let mut start = 0;
+    for i in 1..=density.len() {
+        if i == density.len() || rounded(density[i]) != rounded(density[start]) {
+            emit(start, i, rounded(density[start]))?;
+            start = i;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,127 @@
+//! Sorting tab-separated output (BED and the other tables starting with
+//! chrom, start and end) by contig and position, for `--sort`.
+
+use std::cmp::Ordering;
+use std::io::{self, Write};
+
+/// Order of the records of a scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "kebab-case"))]
+pub enum SortOrder {
+    /// Contigs in the order of the input, records as they are found
+    #[default]
+    Input,
+    /// Contigs by name, byte by byte (chr1, chr10, chr2), as `sort -k1,1`
+    Lexicographic,
+    /// Contigs by name with runs of digits compared as numbers (chr1, chr2,
+    /// chr10), as `sort -V`
+    Natural,
+}
+
+impl SortOrder {
+    /// Compare two contig names.
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        match self {
+            SortOrder::Input => Ordering::Equal,
+            SortOrder::Lexicographic => a.cmp(b),
+            SortOrder::Natural => natural_cmp(a, b),
+        }
+    }
+}
+
+/// Compare `a` and `b` with each run of digits taken as a number, so chr2
+/// comes before chr10 and contig_9 before contig_10. Other characters
+/// compare byte by byte; numbers equal but for leading zeros (chr01, chr1)
+/// fall back to comparing the names whole.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (x, y) = (a.as_bytes(), b.as_bytes());
+    let (mut i, mut j) = (0, 0);
+    while i < x.len() && j < y.len() {
+        if x[i].is_ascii_digit() && y[j].is_ascii_digit() {
+            let (start_x, start_y) = (i, j);
+            while i < x.len() && x[i].is_ascii_digit() {
+                i += 1;
+            }
+            while j < y.len() && y[j].is_ascii_digit() {
+                j += 1;
+            }
+            let trim = |digits: &[u8]| -> usize { digits.iter().take_while(|&&d| d == b'0').count() };
+            let (digits_x, digits_y) = (&x[start_x..i], &y[start_y..j]);
+            let (digits_x, digits_y) = (&digits_x[trim(digits_x)..], &digits_y[trim(digits_y)..]);
+            // Without leading zeros, a longer number is larger
+            let order = digits_x.len().cmp(&digits_y.len()).then_with(|| digits_x.cmp(digits_y));
+            if order != Ordering::Equal {
+                return order;
+            }
+        } else {
+            if x[i] != y[j] {
+                return x[i].cmp(&y[j]);
+            }
+            i += 1;
+            j += 1;
+        }
+    }
+    (x.len() - i).cmp(&(y.len() - j)).then_with(|| a.cmp(b))
+}
+
+/// Holds back every line written to it until [`SortedWriter::finish`],
+/// then writes them sorted by their first three tab-separated columns:
+/// contig in `order`, then start and end as numbers. Lines without
+/// coordinates (headers) come first; lines with the same ones keep the
+/// order they were written in.
+///
+/// The whole output is held in memory.
+pub struct SortedWriter<W: Write> {
+    inner: W,
+    order: SortOrder,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> SortedWriter<W> {
+    pub fn new(inner: W, order: SortOrder) -> Self {
+        SortedWriter { inner, order, buf: Vec::new() }
+    }
+
+    /// Write the lines held so far, sorted, and flush the underlying writer.
+    pub fn finish(&mut self) -> io::Result<()> {
+        let buf = std::mem::take(&mut self.buf);
+        let mut lines: Vec<(Option<Coordinates>, &[u8])> =
+            buf.split_inclusive(|&c| c == b'\n').map(|line| (coordinates(line), line)).collect();
+        if self.order != SortOrder::Input {
+            let order = self.order;
+            lines.sort_by(|(a, _), (b, _)| match (a, b) {
+                (Some(a), Some(b)) => order.compare(a.0, b.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)),
+                (a, b) => a.is_some().cmp(&b.is_some()),
+            });
+        }
+        for (_, line) in lines {
+            self.inner.write_all(line)?;
+        }
+        self.inner.flush()
+    }
+}
+
+// The contig, start and end a line starts with
+type Coordinates<'a> = (&'a str, u64, u64);
+
+fn coordinates(line: &[u8]) -> Option<Coordinates<'_>> {
+    let line = std::str::from_utf8(line).ok()?;
+    let mut columns = line.trim_end_matches(['\n', '\r']).split('\t');
+    let chrom = columns.next()?;
+    let start = columns.next()?.parse().ok()?;
+    let end = columns.next()?.parse().ok()?;
+    Some((chrom, start, end))
+}
+
+impl<W: Write> Write for SortedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    // Nothing is written before `finish`
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
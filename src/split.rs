@@ -0,0 +1,84 @@
+//! Splitting BED output into a file per strand, for `--split-by strand`.
+
+use std::io::{self, Write};
+
+/// What to split the output by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "kebab-case"))]
+pub enum SplitBy {
+    /// Plus- and minus-strand records to files of their own
+    Strand,
+}
+
+/// Passes BED lines on to `inner` (unless it is `None`) and copies each
+/// line to `plus` or `minus` by its strand column, the 6th.
+///
+/// Unstranded records (`.`) belong to either strand, so they go to both, as
+/// do header and track lines (those with fewer than six columns).
+pub struct StrandSplitWriter<W: Write, S: Write> {
+    inner: Option<W>,
+    plus: S,
+    minus: S,
+    // The start of a line not yet ended
+    partial: Vec<u8>,
+}
+
+impl<W: Write, S: Write> StrandSplitWriter<W, S> {
+    pub fn new(inner: Option<W>, plus: S, minus: S) -> Self {
+        StrandSplitWriter { inner, plus, minus, partial: Vec::new() }
+    }
+
+    /// Write out any unfinished last line and flush every output.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if !self.partial.is_empty() {
+            let line = std::mem::take(&mut self.partial);
+            self.split_line(&line)?;
+        }
+        if let Some(inner) = self.inner.as_mut() {
+            inner.flush()?;
+        }
+        self.plus.flush()?;
+        self.minus.flush()
+    }
+
+    fn split_line(&mut self, line: &[u8]) -> io::Result<()> {
+        match line.split(|&c| c == b'\t').nth(5).map(|strand| strand.trim_ascii_end()) {
+            Some(b"+") => self.plus.write_all(line),
+            Some(b"-") => self.minus.write_all(line),
+            _ => {
+                self.plus.write_all(line)?;
+                self.minus.write_all(line)
+            }
+        }
+    }
+}
+
+impl<W: Write, S: Write> Write for StrandSplitWriter<W, S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.write_all(buf)?;
+        }
+        let mut rest = buf;
+        while let Some(end) = rest.iter().position(|&c| c == b'\n') {
+            let (line, next) = rest.split_at(end + 1);
+            if self.partial.is_empty() {
+                self.split_line(line)?;
+            } else {
+                let mut whole = std::mem::take(&mut self.partial);
+                whole.extend_from_slice(line);
+                self.split_line(&whole)?;
+            }
+            rest = next;
+        }
+        self.partial.extend_from_slice(rest);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.flush()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,88 @@
+use std::fmt::Write;
+
+const WIDTH: f64 = 800.0;
+const HEIGHT: f64 = 120.0;
+const MARGIN: f64 = 30.0;
+
+/// Escape text for inclusion in SVG/HTML.
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Fraction of each of `bins` equal-width bins along `length` bases covered by
+/// `intervals` (which must not overlap each other).
+pub fn binned_coverage(length: usize, intervals: &[(usize, usize)], bins: usize) -> Vec<f64> {
+    let mut covered = vec![0usize; bins];
+    if length == 0 || bins == 0 {
+        return vec![0.0; bins];
+    }
+    let bin_start = |b: usize| b * length / bins;
+
+    for &(start, end) in intervals {
+        let (start, end) = (start.min(length), end.min(length));
+        if start >= end {
+            continue;
+        }
+        let first = start * bins / length;
+        let last = ((end - 1) * bins / length).min(bins - 1);
+        for (b, slot) in covered.iter_mut().enumerate().take(last + 1).skip(first) {
+            let lo = start.max(bin_start(b));
+            let hi = end.min(bin_start(b + 1));
+            *slot += hi.saturating_sub(lo);
+        }
+    }
+
+    covered
+        .iter()
+        .enumerate()
+        .map(|(b, &c)| {
+            let size = bin_start(b + 1) - bin_start(b);
+            if size == 0 { 0.0 } else { c as f64 / size as f64 }
+        })
+        .collect()
+}
+
+/// Render a bar plot of hit density along one sequence as a standalone SVG.
+///
+/// Each bar shows the fraction of its bin covered by `intervals`.
+pub fn density_svg(title: &str, length: usize, intervals: &[(usize, usize)], bins: usize) -> String {
+    let density = binned_coverage(length, intervals, bins);
+    let plot_width = WIDTH - 2.0 * MARGIN;
+    let plot_height = HEIGHT - 2.0 * MARGIN;
+    let bar_width = plot_width / bins.max(1) as f64;
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}" font-family="sans-serif" font-size="11">"#
+    );
+    let _ = writeln!(svg, r#"<text x="{MARGIN}" y="{}">{}</text>"#, MARGIN - 10.0, escape(title));
+    let _ = writeln!(
+        svg,
+        r##"<rect x="{MARGIN}" y="{MARGIN}" width="{plot_width}" height="{plot_height}" fill="none" stroke="#999"/>"##
+    );
+    for (b, value) in density.iter().enumerate() {
+        if *value <= 0.0 {
+            continue;
+        }
+        let height = value * plot_height;
+        let _ = writeln!(
+            svg,
+            r##"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="#3b6ea5"/>"##,
+            MARGIN + b as f64 * bar_width,
+            MARGIN + plot_height - height,
+            bar_width,
+            height
+        );
+    }
+    let _ = writeln!(svg, r#"<text x="{MARGIN}" y="{}">0</text>"#, HEIGHT - 12.0);
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="{}" text-anchor="end">{} bp</text>"#,
+        WIDTH - MARGIN,
+        HEIGHT - 12.0,
+        length
+    );
+    svg.push_str("</svg>\n");
+    svg
+}
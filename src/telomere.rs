@@ -0,0 +1,97 @@
+use std::error::Error;
+
+use crate::kmer::reverse_complement;
+use crate::scan::Strand;
+
+/// Default telomeric repeat (vertebrates); plants mostly use `TTTAGGG`.
+pub const DEFAULT_MOTIF: &str = "TTAGGG";
+
+/// Blocks starting or ending this close to a contig end are reported as
+/// telomeres of that end; others are interstitial.
+pub const END_DISTANCE: usize = 5000;
+
+/// Where a block covering `start..end` of a contig of `contig_len` bases
+/// sits: `start` or `end`, for the nearer contig end if it is within
+/// [`END_DISTANCE`], or else `interstitial`. On contigs shorter than twice
+/// that, a block is near both ends and goes with the nearer one.
+pub fn end_position(start: usize, end: usize, contig_len: usize) -> &'static str {
+    let (to_start, to_end) = (start, contig_len.saturating_sub(end));
+    match to_start.min(to_end) {
+        distance if distance > END_DISTANCE => "interstitial",
+        _ if to_start <= to_end => "start",
+        _ => "end",
+    }
+}
+
+/// A contiguous array of telomeric repeats.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TelomereBlock {
+    pub start: usize,
+    pub end: usize,
+    /// Occurrences of the motif in the block
+    pub copies: usize,
+    /// Percentage of the block's bases inside an occurrence
+    pub purity: f64,
+    /// `+` for the motif itself (e.g. TTAGGG), `-` for its reverse complement (CCCTAA)
+    pub strand: Strand,
+}
+
+/// Find blocks of tandem `motif` copies (and, on the minus strand, of its
+/// reverse complement) with at least `min_copies` copies, in coordinate order.
+///
+/// Occurrences separated by at most one motif length of other sequence are
+/// joined into one block, so the odd sequencing error or variant repeat does
+/// not split a telomere.
+pub fn find_telomeric_blocks<F>(seq: &[u8], motif: &[u8], min_copies: usize, mut emit: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(TelomereBlock) -> Result<(), Box<dyn Error>>,
+{
+    let k = motif.len();
+    if k == 0 || seq.len() < k {
+        return Ok(());
+    }
+
+    let revcomp = reverse_complement(motif);
+    let mut strands = vec![(motif.to_vec(), Strand::Plus)];
+    if revcomp != motif {
+        strands.push((revcomp, Strand::Minus));
+    }
+
+    let mut blocks = Vec::new();
+    for (pattern, strand) in strands {
+        // Current block as (start, end, copies, bases inside an occurrence)
+        let mut current: Option<(usize, usize, usize, usize)> = None;
+        let mut close = |(start, end, copies, covered): (usize, usize, usize, usize)| {
+            if copies >= min_copies.max(1) {
+                let purity = covered as f64 / (end - start) as f64 * 100.0;
+                blocks.push(TelomereBlock { start, end, copies, purity, strand });
+            }
+        };
+        for pos in 0..=(seq.len() - k) {
+            if !seq[pos..pos + k].eq_ignore_ascii_case(&pattern) {
+                continue;
+            }
+            current = match current {
+                // Only the bases past the previous occurrence are new
+                Some((start, end, copies, covered)) if pos <= end + k => {
+                    Some((start, pos + k, copies + 1, covered + (pos + k - end.max(pos))))
+                }
+                previous => {
+                    if let Some(block) = previous {
+                        close(block);
+                    }
+                    Some((pos, pos + k, 1, k))
+                }
+            };
+        }
+        if let Some(block) = current {
+            close(block);
+        }
+    }
+
+    blocks.sort_by_key(|block| (block.start, block.strand == Strand::Minus));
+    for block in blocks {
+        emit(block)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,191 @@
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::input::Records;
+
+// Where the time of a scan went, for `--timings`. Timers are process-wide,
+// so readers, writers and the scan loop can add to them wherever they run;
+// nothing is timed until `enable` is called.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static DECOMPRESSION: AtomicU64 = AtomicU64::new(0);
+static READING: AtomicU64 = AtomicU64::new(0);
+static WRITING: AtomicU64 = AtomicU64::new(0);
+// Bases and time spent on each contig, in the order they were first scanned
+static CONTIGS: Mutex<Vec<ContigTiming>> = Mutex::new(Vec::new());
+
+/// Time spent scanning (and writing the records of) one contig.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContigTiming {
+    pub contig: String,
+    pub bases: usize,
+    pub elapsed: Duration,
+}
+
+impl ContigTiming {
+    /// Megabases scanned per second.
+    pub fn throughput(&self) -> f64 {
+        self.bases as f64 / 1e6 / self.elapsed.as_secs_f64().max(1e-9)
+    }
+}
+
+/// Start timing.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+fn add(timer: &AtomicU64, elapsed: Duration) {
+    timer.fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+}
+
+fn total(timer: &AtomicU64) -> Duration {
+    Duration::from_nanos(timer.load(Ordering::Relaxed))
+}
+
+/// Time spent reading the (decompressed) input stream, including disk reads.
+pub fn decompression() -> Duration {
+    total(&DECOMPRESSION)
+}
+
+/// Time spent getting records, decompression included.
+pub fn reading() -> Duration {
+    total(&READING)
+}
+
+/// Time spent writing the output.
+pub fn writing() -> Duration {
+    total(&WRITING)
+}
+
+/// Add `elapsed` to the time spent getting records, for records fetched
+/// outside [`timed_records`] (e.g. by the threads of a parallel scan).
+pub fn add_reading(elapsed: Duration) {
+    if enabled() {
+        add(&READING, elapsed);
+    }
+}
+
+/// Add `elapsed` on `bases` of `contig`; chunks and pieces of a contig add up.
+pub fn add_contig(contig: &str, bases: usize, elapsed: Duration) {
+    if !enabled() {
+        return;
+    }
+    let mut contigs = CONTIGS.lock().expect("no panics while holding the contig timings");
+    match contigs.iter_mut().find(|timing| timing.contig == contig) {
+        Some(timing) => {
+            timing.bases += bases;
+            timing.elapsed += elapsed;
+        }
+        None => contigs.push(ContigTiming { contig: contig.to_string(), bases, elapsed }),
+    }
+}
+
+/// The contigs timed so far.
+pub fn contigs() -> Vec<ContigTiming> {
+    CONTIGS.lock().expect("no panics while holding the contig timings").clone()
+}
+
+/// Reader timing its reads as decompression (when timing is enabled).
+pub struct TimedReader<R: Read> {
+    inner: R,
+}
+
+impl<R: Read> TimedReader<R> {
+    pub fn new(inner: R) -> Self {
+        TimedReader { inner }
+    }
+}
+
+impl<R: Read> Read for TimedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !enabled() {
+            return self.inner.read(buf);
+        }
+        let started = Instant::now();
+        let result = self.inner.read(buf);
+        add(&DECOMPRESSION, started.elapsed());
+        result
+    }
+}
+
+/// Writer timing its writes and flushes.
+pub struct TimedWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> TimedWriter<W> {
+    pub fn new(inner: W) -> Self {
+        TimedWriter { inner }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for TimedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let started = Instant::now();
+        let result = self.inner.write(buf);
+        add(&WRITING, started.elapsed());
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let started = Instant::now();
+        let result = self.inner.flush();
+        add(&WRITING, started.elapsed());
+        result
+    }
+}
+
+/// Records passed through unchanged, timing how long each takes to get.
+pub fn timed_records(mut records: Records) -> Records {
+    Box::new(std::iter::from_fn(move || {
+        let started = Instant::now();
+        let record = records.next();
+        add(&READING, started.elapsed());
+        record
+    }))
+}
+
+/// Per-contig breakdown kept in the report, the slowest first
+const REPORTED_CONTIGS: usize = 20;
+
+/// The breakdown of a scan that took `elapsed` in all, one line per stage
+/// followed by the throughput of the slowest contigs.
+pub fn report(elapsed: Duration) -> Vec<String> {
+    let (decompression, reading, writing) = (decompression(), reading(), writing());
+    // Decompression happens while records are read, and records are written while scanning
+    let parsing = reading.saturating_sub(decompression);
+    let scanning = elapsed.saturating_sub(reading).saturating_sub(writing);
+    let mut lines = vec!["Timings:".to_string()];
+    for (stage, time) in [("decompression", decompression), ("parsing", parsing), ("scanning", scanning), ("writing", writing), ("total", elapsed)] {
+        lines.push(format!("  {:<14} {:>10.3}s", stage, time.as_secs_f64()));
+    }
+
+    let mut contigs = contigs();
+    let bases: usize = contigs.iter().map(|timing| timing.bases).sum();
+    if bases > 0 {
+        lines.push(format!("  {:<14} {:>10.2} Mb/s over {} contigs", "throughput", bases as f64 / 1e6 / elapsed.as_secs_f64().max(1e-9), contigs.len()));
+    }
+    contigs.sort_by_key(|timing| std::cmp::Reverse(timing.elapsed));
+    for timing in contigs.iter().take(REPORTED_CONTIGS) {
+        lines.push(format!(
+            "  {:<14} {:>10.3}s {:>10.2} Mb {:>8.2} Mb/s",
+            timing.contig,
+            timing.elapsed.as_secs_f64(),
+            timing.bases as f64 / 1e6,
+            timing.throughput()
+        ));
+    }
+    if contigs.len() > REPORTED_CONTIGS {
+        lines.push(format!("  ({} faster contigs not shown)", contigs.len() - REPORTED_CONTIGS));
+    }
+    lines
+}
@@ -0,0 +1,68 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+/// Keeps the `capacity` highest-scoring items pushed to it, in a bounded
+/// min-heap, so memory stays proportional to `capacity` rather than to the
+/// number of items seen.
+///
+/// Ties are broken in favour of the item pushed first.
+#[derive(Debug)]
+pub struct TopK<T> {
+    capacity: usize,
+    heap: BinaryHeap<Reverse<Ranked<T>>>,
+    pushed: u64,
+}
+
+#[derive(Debug)]
+struct Ranked<T> {
+    score: f64,
+    order: u64,
+    item: T,
+}
+
+impl<T> Ord for Ranked<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Higher scores rank higher, then earlier pushes
+        self.score.total_cmp(&other.score).then_with(|| other.order.cmp(&self.order))
+    }
+}
+
+impl<T> PartialOrd for Ranked<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> PartialEq for Ranked<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Ranked<T> {}
+
+impl<T> TopK<T> {
+    pub fn new(capacity: usize) -> Self {
+        TopK { capacity, heap: BinaryHeap::with_capacity(capacity + 1), pushed: 0 }
+    }
+
+    /// Offer an item, evicting the lowest-ranked one if the heap is full.
+    pub fn push(&mut self, score: f64, item: T) {
+        let ranked = Ranked { score, order: self.pushed, item };
+        self.pushed += 1;
+        if self.heap.len() < self.capacity {
+            self.heap.push(Reverse(ranked));
+        } else if self.heap.peek().is_some_and(|Reverse(lowest)| ranked > *lowest) {
+            self.heap.pop();
+            self.heap.push(Reverse(ranked));
+        }
+    }
+
+    /// Take the kept items in the order they were pushed, leaving the heap
+    /// empty for reuse.
+    pub fn drain_in_order(&mut self) -> Vec<T> {
+        let mut kept: Vec<Ranked<T>> = self.heap.drain().map(|Reverse(ranked)| ranked).collect();
+        kept.sort_by_key(|ranked| ranked.order);
+        kept.into_iter().map(|ranked| ranked.item).collect()
+    }
+}
@@ -0,0 +1,91 @@
+//! Per-read keep-coordinates once terminal tails are trimmed off, for
+//! `--trim-bed` and `--trim-tsv`.
+
+use std::io::{self, Write};
+
+/// The part of a read to keep, `[start, end)`, once the tails among `hits`
+/// (intervals in read coordinates) are removed from both ends of a read of
+/// `len` bases.
+///
+/// A tail is a hit starting within the first `anchor` bases of the read (or
+/// ending within its last `anchor` bases), along with any hits overlapping
+/// or abutting it further in, so runs of overlapping windows are trimmed as
+/// one. A hit near both ends of a short read is a tail of the nearer one.
+/// Reads that are tail from end to end keep nothing (`start == end`).
+///
+/// ```
+/// # use polyscan::trim::keep_interval;
+/// assert_eq!(keep_interval(100, &[(0, 12), (10, 20), (90, 100)], 10), (20, 90));
+/// ```
+pub fn keep_interval(len: usize, hits: &[(usize, usize)], anchor: usize) -> (usize, usize) {
+    let mut by_start = hits.to_vec();
+    by_start.sort_unstable();
+    let mut start = 0;
+    for &(hit_start, hit_end) in &by_start {
+        let anchored = hit_start < anchor && hit_start <= len - hit_end.min(len);
+        if anchored || (start > 0 && hit_start <= start) {
+            start = start.max(hit_end);
+        }
+    }
+
+    let mut by_end = hits.to_vec();
+    by_end.sort_unstable_by_key(|&(hit_start, hit_end)| std::cmp::Reverse((hit_end, hit_start)));
+    let mut end = len;
+    for &(hit_start, hit_end) in &by_end {
+        let anchored = hit_end + anchor > len && len - hit_end.min(len) < hit_start;
+        if anchored || (end < len && hit_end >= end) {
+            end = end.min(hit_start);
+        }
+    }
+
+    let start = start.min(len);
+    (start, end.max(start))
+}
+
+/// Writes the keep-coordinates of every read as BED (for `seqkit subseq
+/// --bed`) and/or as a TSV table with the bases trimmed off each end.
+///
+/// Reads that keep nothing are left out of the BED, which would otherwise
+/// hold empty intervals, but are listed in the table.
+pub struct TrimWriter<W: Write> {
+    bed: Option<W>,
+    tsv: Option<W>,
+    reads: u64,
+    trimmed: u64,
+}
+
+impl<W: Write> TrimWriter<W> {
+    pub fn new(bed: Option<W>, mut tsv: Option<W>) -> io::Result<Self> {
+        if let Some(tsv) = tsv.as_mut() {
+            writeln!(tsv, "#read\tlength\tkeep_start\tkeep_end\ttrimmed_5p\ttrimmed_3p")?;
+        }
+        Ok(TrimWriter { bed, tsv, reads: 0, trimmed: 0 })
+    }
+
+    /// Write the keep-coordinates `[start, end)` of `read`, of `len` bases.
+    pub fn add(&mut self, read: &str, len: usize, (start, end): (usize, usize)) -> io::Result<()> {
+        self.reads += 1;
+        if start > 0 || end < len {
+            self.trimmed += 1;
+        }
+        if let Some(bed) = self.bed.as_mut().filter(|_| end > start) {
+            writeln!(bed, "{}\t{}\t{}", read, start, end)?;
+        }
+        if let Some(tsv) = self.tsv.as_mut() {
+            writeln!(tsv, "{}\t{}\t{}\t{}\t{}\t{}", read, len, start, end, start, len - end)?;
+        }
+        Ok(())
+    }
+
+    /// Flush both outputs and return how many reads were seen and how many had
+    /// something trimmed.
+    pub fn finish(mut self) -> io::Result<(u64, u64)> {
+        if let Some(bed) = self.bed.as_mut() {
+            bed.flush()?;
+        }
+        if let Some(tsv) = self.tsv.as_mut() {
+            tsv.flush()?;
+        }
+        Ok((self.reads, self.trimmed))
+    }
+}
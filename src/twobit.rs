@@ -0,0 +1,251 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::input::SeqRecord;
+
+/// Signature at the start of every .2bit file (as read in the file's own byte order).
+pub const TWOBIT_SIGNATURE: u32 = 0x1A41_2743;
+
+// Packed bases, two bits each, most significant bits first.
+const PACKED_BASES: [u8; 4] = [b'T', b'C', b'A', b'G'];
+
+// Number of packed bytes decoded per read.
+const BLOCK_BYTES: usize = 1 << 16;
+
+/// Reader for UCSC .2bit files, decoding one sequence at a time.
+///
+/// The index is read up front; each call to `next` seeks to the next sequence
+/// and unpacks it block by block, restoring N runs and soft-masked (lowercase)
+/// regions.
+pub struct TwoBitReader {
+    reader: BufReader<File>,
+    swap: bool,
+    index: Vec<(String, u64)>,
+    next: usize,
+}
+
+impl TwoBitReader {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut word = [0u8; 4];
+        reader.read_exact(&mut word)?;
+        let swap = if u32::from_le_bytes(word) == TWOBIT_SIGNATURE {
+            false
+        } else if u32::from_be_bytes(word) == TWOBIT_SIGNATURE {
+            true
+        } else {
+            return Err("not a .2bit file (bad signature)".into());
+        };
+
+        let mut this = TwoBitReader { reader, swap, index: Vec::new(), next: 0 };
+        let version = this.read_u32()?;
+        if version > 1 {
+            return Err(format!("unsupported .2bit version {}", version).into());
+        }
+        let seq_count = this.read_u32()?;
+        let _reserved = this.read_u32()?;
+
+        for _ in 0..seq_count {
+            let mut name_len = [0u8; 1];
+            this.reader.read_exact(&mut name_len)?;
+            let mut name = vec![0u8; name_len[0] as usize];
+            this.reader.read_exact(&mut name)?;
+            // Version 1 files use 64-bit offsets
+            let offset = if version == 1 { this.read_u64()? } else { this.read_u32()? as u64 };
+            this.index.push((String::from_utf8(name)?, offset));
+        }
+
+        Ok(this)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, Box<dyn Error>> {
+        let mut word = [0u8; 4];
+        self.reader.read_exact(&mut word)?;
+        Ok(if self.swap { u32::from_be_bytes(word) } else { u32::from_le_bytes(word) })
+    }
+
+    fn read_u64(&mut self) -> Result<u64, Box<dyn Error>> {
+        let mut word = [0u8; 8];
+        self.reader.read_exact(&mut word)?;
+        Ok(if self.swap { u64::from_be_bytes(word) } else { u64::from_le_bytes(word) })
+    }
+
+    /// Read a block list (count, starts[], sizes[]) as (start, size) pairs.
+    fn read_blocks(&mut self) -> Result<Vec<(usize, usize)>, Box<dyn Error>> {
+        let count = self.read_u32()? as usize;
+        let starts = (0..count).map(|_| self.read_u32()).collect::<Result<Vec<_>, _>>()?;
+        let sizes = (0..count).map(|_| self.read_u32()).collect::<Result<Vec<_>, _>>()?;
+        Ok(starts.into_iter().zip(sizes).map(|(s, l)| (s as usize, l as usize)).collect())
+    }
+
+    fn read_record(&mut self, id: String, offset: u64) -> Result<SeqRecord, Box<dyn Error>> {
+        self.reader.seek(SeekFrom::Start(offset))?;
+
+        let dna_size = self.read_u32()? as usize;
+        let n_blocks = self.read_blocks()?;
+        let mask_blocks = self.read_blocks()?;
+        let _reserved = self.read_u32()?;
+
+        // Unpack the 2-bit bases block by block
+        let mut seq = Vec::with_capacity(dna_size);
+        let mut packed = vec![0u8; BLOCK_BYTES];
+        let mut remaining = dna_size.div_ceil(4);
+        while remaining > 0 {
+            let n = remaining.min(BLOCK_BYTES);
+            self.reader.read_exact(&mut packed[..n])?;
+            for &byte in &packed[..n] {
+                for shift in [6, 4, 2, 0] {
+                    seq.push(PACKED_BASES[((byte >> shift) & 0b11) as usize]);
+                }
+            }
+            remaining -= n;
+        }
+        seq.truncate(dna_size);
+
+        for (start, size) in n_blocks {
+            let end = (start + size).min(dna_size);
+            seq[start.min(end)..end].fill(b'N');
+        }
+        for (start, size) in mask_blocks {
+            let end = (start + size).min(dna_size);
+            seq[start.min(end)..end].make_ascii_lowercase();
+        }
+
+        Ok(SeqRecord { id, seq: seq.into(), contig_len: dna_size, ..Default::default() })
+    }
+}
+
+impl Iterator for TwoBitReader {
+    type Item = Result<SeqRecord, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (id, offset) = self.index.get(self.next)?.clone();
+        self.next += 1;
+        Some(self.read_record(id, offset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Runs of bases matching `in_run`, as (start, size)
+    fn runs(seq: &[u8], in_run: impl Fn(u8) -> bool) -> Vec<(u32, u32)> {
+        let mut runs: Vec<(u32, u32)> = Vec::new();
+        for (i, &c) in seq.iter().enumerate() {
+            if !in_run(c) {
+                continue;
+            }
+            match runs.last_mut() {
+                Some((start, size)) if (*start + *size) as usize == i => *size += 1,
+                _ => runs.push((i as u32, 1)),
+            }
+        }
+        runs
+    }
+
+    // A .2bit file of `seqs` (of A, C, G, T and N in either case), in either byte order and version
+    fn two_bit(seqs: &[(&str, &[u8])], big_endian: bool, version: u32) -> Vec<u8> {
+        let word = |value: u32| if big_endian { value.to_be_bytes() } else { value.to_le_bytes() };
+        let mut records: Vec<Vec<u8>> = Vec::new();
+        for (_, seq) in seqs {
+            let mut record = word(seq.len() as u32).to_vec();
+            for blocks in [runs(seq, |c| c.eq_ignore_ascii_case(&b'N')), runs(seq, |c| c.is_ascii_lowercase())] {
+                record.extend(word(blocks.len() as u32));
+                record.extend(blocks.iter().flat_map(|&(start, _)| word(start)));
+                record.extend(blocks.iter().flat_map(|&(_, size)| word(size)));
+            }
+            record.extend(word(0));
+            for bases in seq.chunks(4) {
+                let mut byte = 0u8;
+                for i in 0..4 {
+                    let code = match bases.get(i).map(u8::to_ascii_uppercase) {
+                        Some(b'C') => 1,
+                        Some(b'A') => 2,
+                        Some(b'G') => 3,
+                        _ => 0,
+                    };
+                    byte |= code << (6 - 2 * i);
+                }
+                record.push(byte);
+            }
+            records.push(record);
+        }
+        let offset_size = if version == 1 { 8 } else { 4 };
+        let mut offset = 16 + seqs.iter().map(|(name, _)| 1 + name.len() + offset_size).sum::<usize>();
+        let mut file: Vec<u8> = [word(TWOBIT_SIGNATURE), word(version), word(seqs.len() as u32), word(0)].concat();
+        for ((name, _), record) in seqs.iter().zip(&records) {
+            file.push(name.len() as u8);
+            file.extend(name.as_bytes());
+            match (version, big_endian) {
+                (1, true) => file.extend((offset as u64).to_be_bytes()),
+                (1, false) => file.extend((offset as u64).to_le_bytes()),
+                _ => file.extend(word(offset as u32)),
+            }
+            offset += record.len();
+        }
+        file.extend(records.concat());
+        file
+    }
+
+    // (id, bases, contig length) of a record read back
+    type ReadBack = (String, Vec<u8>, usize);
+
+    fn read_back(name: &str, file: &[u8]) -> Result<Vec<ReadBack>, Box<dyn Error>> {
+        let path = std::env::temp_dir().join(format!("polyscan-twobit-{}-{}.2bit", name, std::process::id()));
+        std::fs::write(&path, file)?;
+        let records = TwoBitReader::from_path(&path).and_then(|reader| {
+            reader.map(|record| record.map(|record| (record.id, record.seq.to_vec(), record.contig_len))).collect()
+        });
+        std::fs::remove_file(&path)?;
+        records
+    }
+
+    // Lengths 1 to 3 past a multiple of 4, N runs at either end and inside, soft-masked N
+    const SEQS: &[(&str, &[u8])] = &[
+        ("one", b"G"),
+        ("five", b"ACGTA"),
+        ("n_ends", b"NNACGTacgtNNN"),
+        ("masked", b"acgtACGTnnNNacgTTGCAtt"),
+        ("all_n", b"NNNNNNN"),
+        ("empty", b""),
+    ];
+
+    fn expected(seqs: &[(&str, &[u8])]) -> Vec<ReadBack> {
+        seqs.iter().map(|(name, seq)| (name.to_string(), seq.to_vec(), seq.len())).collect()
+    }
+
+    #[test]
+    fn reads_back_n_and_soft_masked_runs() {
+        assert_eq!(read_back("runs", &two_bit(SEQS, false, 0)).unwrap(), expected(SEQS));
+    }
+
+    #[test]
+    fn reads_either_byte_order_and_version() {
+        for (big_endian, version) in [(true, 0), (false, 1), (true, 1)] {
+            let name = format!("order-{}-{}", big_endian, version);
+            assert_eq!(read_back(&name, &two_bit(SEQS, big_endian, version)).unwrap(), expected(SEQS), "{}", name);
+        }
+    }
+
+    #[test]
+    fn unpacks_sequences_longer_than_a_block() {
+        let mut seq: Vec<u8> = b"ACGT".iter().cycle().take(BLOCK_BYTES * 4 + 7).copied().collect();
+        seq[BLOCK_BYTES * 4 - 2..BLOCK_BYTES * 4 + 3].fill(b'N');
+        seq[10..20].make_ascii_lowercase();
+        let seqs: &[(&str, &[u8])] = &[("long", &seq), ("after", b"TTA")];
+        assert_eq!(read_back("long", &two_bit(seqs, false, 0)).unwrap(), expected(seqs));
+    }
+
+    #[test]
+    fn refuses_other_files() {
+        let mut file = two_bit(SEQS, false, 0);
+        file[0] ^= 0xFF;
+        assert!(read_back("signature", &file).unwrap_err().to_string().contains("signature"));
+        let version = two_bit(SEQS, false, 2);
+        assert!(read_back("version", &version).unwrap_err().to_string().contains("version 2"));
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+use crate::dust::{sdust, DEFAULT_THRESHOLD, DEFAULT_WINDOW};
+use crate::input::Records;
+use crate::runs::homopolymers;
+
+/// INFO header lines of the tags added by [`annotate_vcf`].
+pub const ANNOTATION_HEADER: &[&str] = &[
+    "##INFO=<ID=HRUN,Number=1,Type=Integer,Description=\"Length of the longest homopolymer overlapping or adjacent to the variant (polyscan)\">",
+    "##INFO=<ID=HRUN_BASE,Number=1,Type=String,Description=\"Base of the homopolymer in HRUN (polyscan)\">",
+    "##INFO=<ID=LOWCOMP,Number=0,Type=Flag,Description=\"Variant overlaps or is adjacent to an SDUST low-complexity region (polyscan)\">",
+];
+
+/// ID of the FILTER set by [`filter_vcf`].
+pub const HOMOPOLYMER_FILTER: &str = "HRUN";
+
+/// Homopolymers and low-complexity regions of a reference, per contig, to
+/// give variants their sequence context.
+#[derive(Debug, Default)]
+pub struct SequenceContext {
+    // Sorted homopolymers (start, end, base) at least `min_run` long
+    runs: HashMap<String, Vec<(u64, u64, u8)>>,
+    // Sorted, non-overlapping SDUST intervals
+    low_complexity: HashMap<String, Vec<(u64, u64)>>,
+}
+
+impl SequenceContext {
+    /// Index the homopolymers of at least `min_run` bases and the
+    /// low-complexity regions of every record.
+    pub fn from_records(records: Records, min_run: usize) -> Result<Self, Box<dyn Error>> {
+        let mut context = SequenceContext::default();
+        for result_record in records {
+            let record = result_record?;
+            let offset = record.start as u64;
+            let runs = homopolymers(&record.seq, min_run);
+            context
+                .runs
+                .entry(record.id.clone())
+                .or_default()
+                .extend(runs.into_iter().map(|(start, end, base)| (offset + start as u64, offset + end as u64, base)));
+            let intervals = sdust(&record.seq, DEFAULT_WINDOW, DEFAULT_THRESHOLD);
+            context
+                .low_complexity
+                .entry(record.id)
+                .or_default()
+                .extend(intervals.into_iter().map(|(start, end)| (offset + start as u64, offset + end as u64)));
+        }
+        Ok(context)
+    }
+
+    /// The longest homopolymer overlapping or touching `[start, end)` on
+    /// `chrom`, as its base and length (the leftmost of equally long ones).
+    pub fn homopolymer(&self, chrom: &str, start: u64, end: u64) -> Option<(u8, u64)> {
+        let runs = self.runs.get(chrom)?;
+        let i = runs.partition_point(|&(_, run_end, _)| run_end < start);
+        runs[i..]
+            .iter()
+            .take_while(|&&(run_start, _, _)| run_start <= end)
+            .map(|&(run_start, run_end, base)| (base, run_end - run_start))
+            .fold(None, |longest: Option<(u8, u64)>, run| match longest {
+                Some(best) if best.1 >= run.1 => Some(best),
+                _ => Some(run),
+            })
+    }
+
+    /// Whether `[start, end)` on `chrom` overlaps or touches a low-complexity region.
+    pub fn is_low_complexity(&self, chrom: &str, start: u64, end: u64) -> bool {
+        let Some(intervals) = self.low_complexity.get(chrom) else {
+            return false;
+        };
+        let i = intervals.partition_point(|&(_, interval_end)| interval_end < start);
+        intervals.get(i).is_some_and(|&(interval_start, _)| interval_start <= end)
+    }
+}
+
+/// Open a VCF file (optionally compressed, including bgzip).
+pub fn open_vcf<P: AsRef<Path>>(path: P) -> Result<Box<dyn BufRead>, Box<dyn Error>> {
+    let (reader, _compression_format) = niffler::get_reader(Box::new(File::open(path)?))?;
+    Ok(Box::new(BufReader::new(reader)))
+}
+
+// The contig and 0-based reference span of a VCF data line
+fn variant_span<'a>(fields: &[&'a str], line_number: usize) -> Result<(&'a str, u64, u64), Box<dyn Error>> {
+    let invalid = || format!("invalid VCF line {}", line_number);
+    if fields.len() < 8 {
+        return Err(invalid().into());
+    }
+    let pos: u64 = fields[1].parse().map_err(|_| invalid())?;
+    let start = pos.checked_sub(1).ok_or_else(invalid)?;
+    Ok((fields[0], start, start + fields[3].len() as u64))
+}
+
+// Whether any ALT allele changes the length of REF; symbolic alleles (e.g.
+// <DEL>) and breakends are structural variants, not small indels
+fn is_indel(reference: &str, alts: &str) -> bool {
+    alts.split(',').any(|alt| {
+        let symbolic = alt.starts_with('<') || alt.contains('[') || alt.contains(']');
+        !symbolic && alt != "*" && alt != "." && alt.len() != reference.len()
+    })
+}
+
+// Append tags to an INFO column, replacing the '.' of an empty one
+fn add_info(info: &str, tags: &[String]) -> String {
+    if tags.is_empty() {
+        return info.to_string();
+    }
+    match info {
+        "." | "" => tags.join(";"),
+        _ => format!("{};{}", info, tags.join(";")),
+    }
+}
+
+/// Copy a VCF, adding `HRUN`/`HRUN_BASE` to variants overlapping or adjacent
+/// to a homopolymer of `context`, and `LOWCOMP` to those overlapping or
+/// adjacent to a low-complexity region. Returns how many variants were tagged.
+pub fn annotate_vcf<R: BufRead, W: Write>(input: R, context: &SequenceContext, mut output: W) -> Result<usize, Box<dyn Error>> {
+    let mut tagged = 0;
+    for (i, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.starts_with("##") {
+            writeln!(output, "{}", line)?;
+            continue;
+        }
+        if line.starts_with('#') {
+            // New INFO definitions go last in the meta-information, before the column header
+            for header in ANNOTATION_HEADER {
+                writeln!(output, "{}", header)?;
+            }
+            writeln!(output, "{}", line)?;
+            continue;
+        }
+        if line.is_empty() {
+            continue;
+        }
+
+        let mut fields: Vec<&str> = line.split('\t').collect();
+        let (chrom, start, end) = variant_span(&fields, i + 1)?;
+        let mut tags = Vec::new();
+        if let Some((base, len)) = context.homopolymer(chrom, start, end) {
+            tags.push(format!("HRUN={}", len));
+            tags.push(format!("HRUN_BASE={}", base as char));
+        }
+        if context.is_low_complexity(chrom, start, end) {
+            tags.push("LOWCOMP".to_string());
+        }
+        if !tags.is_empty() {
+            tagged += 1;
+        }
+        let info = add_info(fields[7], &tags);
+        fields[7] = &info;
+        writeln!(output, "{}", fields.join("\t"))?;
+    }
+    output.flush()?;
+    Ok(tagged)
+}
+
+/// Copy a VCF, setting the `HRUN` filter on indels overlapping or adjacent to
+/// a homopolymer longer than `max_run` bases, or leaving them out if `drop`.
+/// `context` must hold the homopolymers of more than `max_run` bases.
+/// Returns how many indels were seen and how many were filtered.
+pub fn filter_vcf<R: BufRead, W: Write>(
+    input: R,
+    context: &SequenceContext,
+    max_run: u64,
+    drop: bool,
+    mut output: W,
+) -> Result<(usize, usize), Box<dyn Error>> {
+    let (mut indels, mut filtered) = (0, 0);
+    for (i, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.starts_with("##") {
+            writeln!(output, "{}", line)?;
+            continue;
+        }
+        if line.starts_with('#') {
+            if !drop {
+                writeln!(
+                    output,
+                    "##FILTER=<ID={},Description=\"Indel in or next to a homopolymer longer than {} bases (polyscan)\">",
+                    HOMOPOLYMER_FILTER, max_run
+                )?;
+            }
+            writeln!(output, "{}", line)?;
+            continue;
+        }
+        if line.is_empty() {
+            continue;
+        }
+
+        let mut fields: Vec<&str> = line.split('\t').collect();
+        let (chrom, start, end) = variant_span(&fields, i + 1)?;
+        if !is_indel(fields[3], fields[4]) {
+            writeln!(output, "{}", line)?;
+            continue;
+        }
+        indels += 1;
+        if context.homopolymer(chrom, start, end).is_none_or(|(_, len)| len <= max_run) {
+            writeln!(output, "{}", line)?;
+            continue;
+        }
+        filtered += 1;
+        if drop {
+            continue;
+        }
+        let filter = match fields[6] {
+            "." | "PASS" | "" => HOMOPOLYMER_FILTER.to_string(),
+            other => format!("{};{}", other, HOMOPOLYMER_FILTER),
+        };
+        fields[6] = &filter;
+        writeln!(output, "{}", fields.join("\t"))?;
+    }
+    output.flush()?;
+    Ok((indels, filtered))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::fasta_records;
+
+    // A 5 bp poly-A at 6..11 and a 4 bp poly-G at 14..18 of chr1
+    const REFERENCE: &[u8] = b">chr1\nCGTACGAAAAACTCGGGGCTCA\n";
+
+    fn context(min_run: usize) -> SequenceContext {
+        SequenceContext::from_records(fasta_records(REFERENCE), min_run).unwrap()
+    }
+
+    #[test]
+    fn homopolymers_touching_a_span_give_its_context() {
+        let context = context(4);
+        // Deletion of the first A, anchored on the base before the run
+        assert_eq!(context.homopolymer("chr1", 5, 7), Some((b'A', 5)));
+        // Insertion right before the run, anchored on the base before it
+        assert_eq!(context.homopolymer("chr1", 5, 6), Some((b'A', 5)));
+        // Insertion right after the run, anchored on its last base
+        assert_eq!(context.homopolymer("chr1", 10, 11), Some((b'A', 5)));
+        assert_eq!(context.homopolymer("chr1", 11, 12), Some((b'A', 5)));
+        // One base further out on either side
+        assert_eq!(context.homopolymer("chr1", 4, 5), None);
+        assert_eq!(context.homopolymer("chr1", 12, 13), None);
+        // Both runs: the longer one
+        assert_eq!(context.homopolymer("chr1", 9, 15), Some((b'A', 5)));
+        assert_eq!(context.homopolymer("chr1", 13, 14), Some((b'G', 4)));
+        assert_eq!(context.homopolymer("chr2", 5, 7), None);
+    }
+
+    #[test]
+    fn runs_shorter_than_min_run_are_not_context() {
+        let context = context(5);
+        assert_eq!(context.homopolymer("chr1", 5, 7), Some((b'A', 5)));
+        assert_eq!(context.homopolymer("chr1", 13, 14), None);
+    }
+
+    #[test]
+    fn low_complexity_regions_touching_a_span_are_found() {
+        let reference = format!(">chr1\n{}{}{}\n", "ACGTTGCAAGCT", "CA".repeat(40), "TGCATCGATGCA");
+        let context = SequenceContext::from_records(fasta_records(std::io::Cursor::new(reference)), 4).unwrap();
+        assert!(context.is_low_complexity("chr1", 40, 41));
+        assert!(!context.is_low_complexity("chr1", 0, 1));
+        assert!(!context.is_low_complexity("chr2", 40, 41));
+    }
+
+    #[test]
+    fn annotation_keeps_the_header_and_tags_variants() {
+        let vcf = "##fileformat=VCFv4.2\n\
+                   ##contig=<ID=chr1>\n\
+                   #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n\
+                   chr1\t6\t.\tGA\tG\t50\tPASS\tDP=10\n\
+                   chr1\t2\t.\tG\tT\t50\tPASS\t.\n";
+        let mut output = Vec::new();
+        assert_eq!(annotate_vcf(vcf.as_bytes(), &context(4), &mut output).unwrap(), 1);
+        let lines: Vec<&str> = std::str::from_utf8(&output).unwrap().lines().collect();
+        assert_eq!(&lines[..2], ["##fileformat=VCFv4.2", "##contig=<ID=chr1>"]);
+        assert_eq!(&lines[2..5], ANNOTATION_HEADER);
+        assert_eq!(lines[5], "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO");
+        assert_eq!(lines[6], "chr1\t6\t.\tGA\tG\t50\tPASS\tDP=10;HRUN=5;HRUN_BASE=A");
+        assert_eq!(lines[7], "chr1\t2\t.\tG\tT\t50\tPASS\t.");
+        assert_eq!(lines.len(), 8);
+    }
+}
@@ -0,0 +1,243 @@
+//! `polyscan view`: browse the records of a BED file contig by contig in the
+//! terminal, with the sequence around the selected record.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::IsTerminal;
+use std::path::Path;
+
+use polyscan::bed::{read_bed_entries, BedEntry};
+use polyscan::error::PolyscanError;
+use polyscan::faidx::IndexedFasta;
+use polyscan::input::{open_records, InputFormat};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Row, Table, TableState, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+/// Bases shown on each side of the selected record, to start with.
+const FLANK: usize = 50;
+const MAX_FLANK: usize = 5000;
+/// Rows moved by Page Up and Page Down.
+const PAGE: usize = 10;
+
+struct Contig {
+    name: String,
+    records: Vec<BedEntry>,
+}
+
+/// Where the bases shown come from: fetched from an indexed FASTA as records
+/// are selected, or, without an index, read up front for the contigs with
+/// records.
+enum Sequences {
+    Indexed(IndexedFasta),
+    Loaded(HashMap<String, Vec<u8>>),
+}
+
+impl Sequences {
+    /// Length of contig `name`, or `None` when the FASTA has no such contig.
+    fn length(&self, name: &str) -> Option<u64> {
+        match self {
+            Sequences::Indexed(indexed) => indexed.fai.get(name).map(|entry| entry.length),
+            Sequences::Loaded(seqs) => seqs.get(name).map(|seq| seq.len() as u64),
+        }
+    }
+
+    /// Bases `[start, end)` of contig `name` (0-based, within its length).
+    fn fetch(&self, name: &str, start: u64, end: u64) -> Result<Vec<u8>, Box<dyn Error>> {
+        match self {
+            Sequences::Indexed(indexed) => indexed.fetch(name, start, end),
+            Sequences::Loaded(seqs) => Ok(seqs.get(name).map(|seq| seq[start as usize..end as usize].to_vec()).unwrap_or_default()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Contigs,
+    Records,
+}
+
+struct Viewer {
+    contigs: Vec<Contig>,
+    sequences: Sequences,
+    contig: usize,
+    record: usize,
+    focus: Focus,
+    flank: usize,
+}
+
+/// Open the browser on the records of `bed`, with the sequences of their
+/// contigs from `fasta` (seeked through its `.fai` when it has one), until
+/// the user quits.
+pub fn run(bed: &Path, fasta: &Path) -> Result<(), Box<dyn Error>> {
+    if !std::io::stdout().is_terminal() {
+        return Err(PolyscanError::parameter("polyscan view needs a terminal to draw in."));
+    }
+    let mut contigs: Vec<Contig> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for entry in read_bed_entries(bed)? {
+        let i = *index.entry(entry.chrom.clone()).or_insert_with(|| {
+            contigs.push(Contig { name: entry.chrom.clone(), records: Vec::new() });
+            contigs.len() - 1
+        });
+        contigs[i].records.push(entry);
+    }
+    if contigs.is_empty() {
+        return Err(PolyscanError::input(format!("{} has no records to view.", bed.display())));
+    }
+    for contig in &mut contigs {
+        contig.records.sort_by_key(|entry| (entry.start, entry.end));
+    }
+    let sequences = match IndexedFasta::open(fasta)? {
+        Some(indexed) => Sequences::Indexed(indexed),
+        None => {
+            // Only the contigs with records are kept in memory
+            let mut seqs: HashMap<String, Vec<u8>> = HashMap::new();
+            for record in open_records(fasta, InputFormat::Auto, None)? {
+                let record = record?;
+                if index.contains_key(&record.id) {
+                    seqs.entry(record.id).or_insert_with(|| record.seq.to_vec());
+                }
+            }
+            Sequences::Loaded(seqs)
+        }
+    };
+
+    let mut viewer = Viewer { contigs, sequences, contig: 0, record: 0, focus: Focus::Records, flank: FLANK };
+    let result = ratatui::run(|terminal| viewer.browse(terminal));
+    Ok(result?)
+}
+
+impl Viewer {
+    fn browse(&mut self, terminal: &mut DefaultTerminal) -> std::io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Tab | KeyCode::Left | KeyCode::Right | KeyCode::Char('h') | KeyCode::Char('l') => {
+                    self.focus = match self.focus {
+                        Focus::Contigs => Focus::Records,
+                        Focus::Records => Focus::Contigs,
+                    };
+                }
+                KeyCode::Down | KeyCode::Char('j') => self.step(1),
+                KeyCode::Up | KeyCode::Char('k') => self.step(-1),
+                KeyCode::PageDown => self.step(PAGE as isize),
+                KeyCode::PageUp => self.step(-(PAGE as isize)),
+                KeyCode::Home | KeyCode::Char('g') => self.step(isize::MIN),
+                KeyCode::End | KeyCode::Char('G') => self.step(isize::MAX),
+                KeyCode::Char('+') | KeyCode::Char('=') => self.flank = (self.flank * 2).min(MAX_FLANK),
+                KeyCode::Char('-') => self.flank = (self.flank / 2).max(1),
+                _ => (),
+            }
+        }
+    }
+
+    // Move the selection of the focused list by `by` rows, stopping at its ends
+    fn step(&mut self, by: isize) {
+        let (selected, len) = match self.focus {
+            Focus::Contigs => (&mut self.contig, self.contigs.len()),
+            Focus::Records => (&mut self.record, self.contigs[self.contig].records.len()),
+        };
+        *selected = selected.saturating_add_signed(by).min(len.saturating_sub(1));
+        if self.focus == Focus::Contigs {
+            self.record = 0;
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [lists, sequence, help] = Layout::vertical([Constraint::Min(6), Constraint::Percentage(40), Constraint::Length(1)]).areas(frame.area());
+        let [contig_list, record_table] = Layout::horizontal([Constraint::Percentage(25), Constraint::Percentage(75)]).areas(lists);
+        let focused = |focus: Focus| {
+            let style = if self.focus == focus { Style::new().fg(Color::Cyan) } else { Style::new() };
+            Block::bordered().border_style(style)
+        };
+        let highlight = Style::new().add_modifier(Modifier::REVERSED);
+
+        let items: Vec<ListItem> = self.contigs.iter().map(|contig| ListItem::new(format!("{} ({})", contig.name, contig.records.len()))).collect();
+        let mut contig_state = ListState::default().with_selected(Some(self.contig));
+        let list = List::new(items).block(focused(Focus::Contigs).title(" Contigs ")).highlight_style(highlight);
+        frame.render_stateful_widget(list, contig_list, &mut contig_state);
+
+        let contig = &self.contigs[self.contig];
+        let rows = contig.records.iter().map(|entry| {
+            Row::new([
+                entry.start.to_string(),
+                entry.end.to_string(),
+                (entry.end - entry.start).to_string(),
+                entry.name.clone(),
+                entry.score.to_string(),
+                entry.strand.symbol().to_string(),
+            ])
+        });
+        let widths = [Constraint::Length(12), Constraint::Length(12), Constraint::Length(8), Constraint::Min(8), Constraint::Length(8), Constraint::Length(6)];
+        let header = Row::new(["start", "end", "length", "name", "score", "strand"]).style(Style::new().add_modifier(Modifier::BOLD));
+        let title = format!(" Records on {} ", contig.name);
+        let table = Table::new(rows, widths).header(header).block(focused(Focus::Records).title(title)).row_highlight_style(highlight);
+        let mut record_state = TableState::default().with_selected(Some(self.record));
+        frame.render_stateful_widget(table, record_table, &mut record_state);
+
+        frame.render_widget(self.sequence_view(contig), sequence);
+        let keys = " ↑/↓ move  Tab/←/→ switch list  PgUp/PgDn page  +/- flank  q quit";
+        frame.render_widget(Line::from(keys).style(Style::new().add_modifier(Modifier::DIM)), help);
+    }
+
+    // The bases of the selected record, highlighted, between its flanks
+    fn sequence_view(&self, contig: &Contig) -> Paragraph<'_> {
+        let entry = &contig.records[self.record.min(contig.records.len() - 1)];
+        let Some(length) = self.sequences.length(&contig.name) else {
+            return Paragraph::new(format!("{} is not in the FASTA file.", contig.name)).block(Block::bordered().title(" Sequence "));
+        };
+        let (start, end) = (entry.start.min(length) as usize, entry.end.min(length) as usize);
+        let (from, to) = (start.saturating_sub(self.flank), (end + self.flank).min(length as usize));
+        // Only the bases on screen are fetched
+        let seq = match self.sequences.fetch(&contig.name, from as u64, to as u64) {
+            Ok(seq) => seq,
+            Err(e) => return Paragraph::new(format!("Failed to read {}: {}", contig.name, e)).block(Block::bordered().title(" Sequence ")),
+        };
+        let title = format!(
+            " {}:{}-{} ({} bp, strand {}) with {} bp of flank; plus strand shown, 1-based ",
+            contig.name,
+            start + 1,
+            end,
+            end - start,
+            entry.strand.symbol(),
+            self.flank
+        );
+        let spans: Vec<Span> = (from..to)
+            .zip(seq)
+            .map(|(i, base)| {
+                let style = base_style(base);
+                let style = if (start..end).contains(&i) { style.add_modifier(Modifier::REVERSED | Modifier::BOLD) } else { style };
+                Span::styled((base as char).to_string(), style)
+            })
+            .collect();
+        Paragraph::new(Line::from(spans)).wrap(Wrap { trim: false }).block(Block::bordered().title(title))
+    }
+}
+
+// Each nucleotide in a color of its own; soft-masked bases are dimmed
+fn base_style(base: u8) -> Style {
+    let color = match base.to_ascii_uppercase() {
+        b'A' => Color::Green,
+        b'C' => Color::Blue,
+        b'G' => Color::Yellow,
+        b'T' | b'U' => Color::Red,
+        _ => Color::Gray,
+    };
+    let style = Style::new().fg(color);
+    if base.is_ascii_lowercase() {
+        style.add_modifier(Modifier::DIM)
+    } else {
+        style
+    }
+}
@@ -0,0 +1,61 @@
+use std::error::Error;
+
+use crate::scan::{BaseClass, ScanHit, Steps, Strand};
+
+/// Weights rising linearly from both edges of a `window`-sized window to its
+/// centre (1, 2, ..., 2, 1), so central bases count the most.
+pub fn triangular(window: usize) -> Vec<f64> {
+    (0..window).map(|i| (i + 1).min(window - i) as f64).collect()
+}
+
+/// Slide a window with a weight per position across `seq` and call `emit`
+/// for every window starting on `steps` and target where the weights of the positions holding
+/// target bases make up at least `percentage`% of the total weight, with the
+/// target's index in `ScanHit::scorer`.
+///
+/// The window is `weights.len()` bases long and the score is the weighted
+/// percentage. Weights run 5' to 3' along the target's strand, so they are
+/// reversed for minus-strand targets (which makes no difference for a
+/// symmetric profile such as [`triangular`]). Uniform weights give the same
+/// windows as the usual unweighted scan.
+pub fn weighted_windows<F>(seq: &[u8], weights: &[f64], steps: Steps, percentage: f64, targets: &[(&BaseClass, Strand)], mut emit: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(ScanHit) -> Result<(), Box<dyn Error>>,
+{
+    let window = weights.len();
+    if window == 0 || seq.len() < window {
+        return Ok(());
+    }
+    let total: f64 = weights.iter().sum();
+    let heaviest = weights.iter().copied().fold(0.0, f64::max);
+    let needed = percentage / 100.0 * total;
+    let reversed: Vec<f64> = weights.iter().rev().copied().collect();
+
+    // Target bases in [start, start + window), per target
+    let mut counts: Vec<usize> = targets.iter().map(|(class, _)| seq[..window].iter().filter(|&&nuc| class.contains(nuc)).count()).collect();
+    for start in 0..=(seq.len() - window) {
+        if start > 0 {
+            for ((class, _), count) in targets.iter().zip(counts.iter_mut()) {
+                *count -= class.contains(seq[start - 1]) as usize;
+                *count += class.contains(seq[start + window - 1]) as usize;
+            }
+        }
+        if !steps.contains(start) {
+            continue;
+        }
+        for (i, ((class, strand), &count)) in targets.iter().zip(&counts).enumerate() {
+            // Not even this many target bases at the heaviest positions would pass
+            if count as f64 * heaviest < needed {
+                continue;
+            }
+            let weights = if *strand == Strand::Minus { &reversed } else { weights };
+            let weighted: f64 = seq[start..start + window].iter().zip(weights).filter(|&(&nuc, _)| class.contains(nuc)).map(|(_, &weight)| weight).sum();
+            let score = weighted / total * 100.0;
+            // Allow for rounding in the sum, so uniform weights pass the windows an unweighted scan would
+            if score + 1e-9 >= percentage {
+                emit(ScanHit { start, end: start + window, score, strand: *strand, scorer: i })?;
+            }
+        }
+    }
+    Ok(())
+}
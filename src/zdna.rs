@@ -0,0 +1,41 @@
+use std::error::Error;
+
+use crate::bases::is_purine;
+use crate::scan::{ScanHit, Steps, Strand};
+
+/// Whether the bases at `i` and `i + 1` alternate between purine (A/G) and
+/// pyrimidine (C/T), as in (GC)n, (GT)n or (CA)n.
+fn alternates(seq: &[u8], i: usize) -> bool {
+    matches!((is_purine(seq[i]), is_purine(seq[i + 1])), (Some(a), Some(b)) if a != b)
+}
+
+/// Slide a `window`-sized window across `seq` and call `emit` for every
+/// window starting on `steps` where at least `percentage`% of adjacent base pairs alternate
+/// between purine and pyrimidine: candidate Z-DNA forming regions.
+///
+/// Hits are unstranded (alternation reads the same on both strands) and
+/// scored by the percentage of alternating pairs.
+pub fn alternation_windows<F>(seq: &[u8], window: usize, steps: Steps, percentage: f64, mut emit: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(ScanHit) -> Result<(), Box<dyn Error>>,
+{
+    if window < 2 || seq.len() < window {
+        return Ok(());
+    }
+    let pairs = window - 1;
+    let threshold_count = ((percentage / 100.0) * pairs as f64).ceil() as usize;
+
+    // Alternating pairs among the window's pairs (i, i + 1) for i in [start, start + window - 1)
+    let mut count = (0..pairs).filter(|&i| alternates(seq, i)).count();
+    for start in 0..=(seq.len() - window) {
+        if start > 0 {
+            count -= alternates(seq, start - 1) as usize;
+            count += alternates(seq, start + window - 2) as usize;
+        }
+        if count >= threshold_count && steps.contains(start) {
+            let score = count as f64 / pairs as f64 * 100.0;
+            emit(ScanHit { start, end: start + window, score, strand: Strand::Unstranded, scorer: 0 })?;
+        }
+    }
+    Ok(())
+}
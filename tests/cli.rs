@@ -0,0 +1,213 @@
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+// Write `fasta` to a file of its own and run polyscan on it with `args`
+fn polyscan(name: &str, fasta: &str, args: &[&str]) -> Output {
+    let path: PathBuf = std::env::temp_dir().join(format!("polyscan-cli-{}-{}.fa", name, std::process::id()));
+    std::fs::write(&path, fasta).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_polyscan")).arg("--fasta").arg(&path).args(args).output().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    output
+}
+
+// Write `content` to a file named after `name` and this test process
+fn temp_file(name: &str, content: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("polyscan-cli-{}-{}", std::process::id(), name));
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+// Run polyscan with `args`, where the names of `files` stand for their paths once written
+fn subcommand(args: &[&str], files: &[(&str, &str)]) -> Output {
+    let paths: Vec<PathBuf> = files.iter().map(|(name, content)| temp_file(name, content)).collect();
+    let args: Vec<&std::ffi::OsStr> = args
+        .iter()
+        .map(|arg| match files.iter().position(|(name, _)| name == arg) {
+            Some(i) => paths[i].as_os_str(),
+            None => std::ffi::OsStr::new(arg),
+        })
+        .collect();
+    let output = Command::new(env!("CARGO_BIN_EXE_polyscan")).args(args).output().unwrap();
+    for path in paths {
+        std::fs::remove_file(path).unwrap();
+    }
+    output
+}
+
+fn stdout(output: &Output) -> &str {
+    assert!(output.status.success(), "polyscan failed: {}", String::from_utf8_lossy(&output.stderr));
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+#[test]
+fn zdna_windows_are_merged() {
+    let fasta = ">z\nAAAAAAAAAACGCGCGCGCGCGCGCGCGAAAAAAAAAA\n";
+    let output = polyscan("zdna", fasta, &["--mode", "zdna", "-w", "6", "-p", "80", "--merge"]);
+    assert_eq!(stdout(&output), "z\t8\t29\tzdna\t100\t.\n");
+}
+
+#[test]
+fn g4hunter_windows_are_merged_per_strand() {
+    let fasta = ">g\nTTTTTTTTTTGGGAGGGAGGGAGGGTTTTTTTTTTTCCCTCCCTCCCTCCCTTTTTT\n";
+    let output = polyscan("g4hunter", fasta, &["--mode", "g4", "--g4hunter", "1.2", "-w", "10", "--merge"]);
+    assert_eq!(stdout(&output), "g\t5\t30\tG4\t240\t+\ng\t31\t56\tG4\t240\t-\n");
+}
+
+#[test]
+fn regions_past_the_contig_end_are_input_errors() {
+    let fasta = ">c1\nACGTACGTAC\n>c2\nAAAAAAAAAAAAAAAAAAAA\n";
+    let output = polyscan("region", fasta, &["--region", "c2:1-20", "--region", "c1:11", "-w", "5"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("region c1:11 starts past the end of c1, which has 10 bases"));
+}
+
+#[test]
+fn telomeres_of_a_short_contig_go_with_the_nearer_end() {
+    let fasta = format!(">t\n{}{}{}\n", "CCCTAA".repeat(5), "ACGTTGCA".repeat(3), "TTAGGG".repeat(5));
+    let output = polyscan("telomere", &fasta, &["--mode", "telomere", "-w", "10"]);
+    assert_eq!(stdout(&output), "t\t0\t30\t(TTAGGG)5:start\t100\t-\nt\t54\t84\t(TTAGGG)5:end\t100\t+\n");
+}
+
+#[test]
+fn low_sweep_thresholds_point_at_allow_low_threshold() {
+    let output = polyscan("sweep", ">s\nACGT\n", &["-w", "4", "--percentage-sweep", "40:60:10"]);
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--percentage-sweep thresholds must be between 50.0 and 100.0 (use --allow-low-threshold to go below 50)"), "{}", stderr);
+    assert!(stdout(&polyscan("sweep-low", ">s\nACGT\n", &["-w", "4", "--percentage-sweep", "40:60:10", "--allow-low-threshold"])).is_empty());
+}
+
+// A 5 bp poly-A at 6..11 and a 4 bp poly-G at 14..18
+const VCF_REFERENCE: &str = ">chr1\nCGTACGAAAAACTCGGGGCTCA\n";
+
+const VCF_HEADER: &str = "##fileformat=VCFv4.2\n##contig=<ID=chr1,length=22>\n#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n";
+
+#[test]
+fn annotate_vcf_tags_variants_at_homopolymer_edges() {
+    let vcf = format!(
+        "{}chr1\t5\t.\tC\tCT\t50\tPASS\t.\nchr1\t6\t.\tG\tGA\t50\tPASS\tDP=9\nchr1\t13\t.\tT\tTC\t50\tPASS\t.\nchr1\t14\t.\tC\tCG\t50\tPASS\t.\n",
+        VCF_HEADER
+    );
+    let output = subcommand(&["annotate-vcf", "--fasta", "ref.fa", "calls.vcf"], &[("ref.fa", VCF_REFERENCE), ("calls.vcf", &vcf)]);
+    let lines: Vec<&str> = stdout(&output).lines().collect();
+    assert_eq!(&lines[..2], ["##fileformat=VCFv4.2", "##contig=<ID=chr1,length=22>"]);
+    assert!(lines[2..5].iter().all(|line| line.starts_with("##INFO=<ID=")), "{:?}", lines);
+    assert_eq!(
+        &lines[5..],
+        [
+            "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO",
+            "chr1\t5\t.\tC\tCT\t50\tPASS\t.",
+            "chr1\t6\t.\tG\tGA\t50\tPASS\tDP=9;HRUN=5;HRUN_BASE=A",
+            "chr1\t13\t.\tT\tTC\t50\tPASS\t.",
+            "chr1\t14\t.\tC\tCG\t50\tPASS\tHRUN=4;HRUN_BASE=G",
+        ]
+    );
+}
+
+#[test]
+fn filter_vcf_flags_or_drops_indels_next_to_long_homopolymers() {
+    let calls = [
+        "chr1\t6\t.\tGA\tG\t50\tPASS\t.",
+        "chr1\t7\t.\tA\tT\t50\tPASS\t.",
+        "chr1\t14\t.\tC\tCG\t50\tLowQual\t.",
+        "chr1\t16\t.\tG\tGG\t50\tPASS\t.",
+    ];
+    let vcf = format!("{}{}\n", VCF_HEADER, calls.join("\n"));
+    let files = [("ref.fa", VCF_REFERENCE), ("calls.vcf", vcf.as_str())];
+
+    // The 5 bp poly-A is longer than --max-run 4, the 4 bp poly-G is not
+    let output = subcommand(&["filter-vcf", "--fasta", "ref.fa", "calls.vcf", "--max-run", "4"], &files);
+    let lines: Vec<&str> = stdout(&output).lines().collect();
+    assert_eq!(lines[2], "##FILTER=<ID=HRUN,Description=\"Indel in or next to a homopolymer longer than 4 bases (polyscan)\">");
+    assert_eq!(&lines[4..], ["chr1\t6\t.\tGA\tG\t50\tHRUN\t.", calls[1], calls[2], calls[3]]);
+
+    // Both are longer than --max-run 3
+    let output = subcommand(&["filter-vcf", "--fasta", "ref.fa", "calls.vcf", "--max-run", "3"], &files);
+    let lines: Vec<&str> = stdout(&output).lines().collect();
+    assert_eq!(
+        &lines[4..],
+        ["chr1\t6\t.\tGA\tG\t50\tHRUN\t.", calls[1], "chr1\t14\t.\tC\tCG\t50\tLowQual;HRUN\t.", "chr1\t16\t.\tG\tGG\t50\tHRUN\t."]
+    );
+
+    // --drop leaves them out, and the filter undeclared
+    let output = subcommand(&["filter-vcf", "--fasta", "ref.fa", "calls.vcf", "--max-run", "3", "--drop"], &files);
+    let lines: Vec<&str> = stdout(&output).lines().collect();
+    assert_eq!(lines, [&VCF_HEADER.lines().collect::<Vec<_>>()[..], &[calls[1]]].concat());
+}
+
+#[test]
+fn strs_are_named_by_unit_and_copies() {
+    let fasta = format!(">s\nNNNNN{}NNNNN{}NNNNN\n", "CAG".repeat(6) + "CA", "AT".repeat(5));
+    let output = polyscan("str", &fasta, &["--mode", "str"]);
+    assert_eq!(stdout(&output), "s\t5\t25\t(CAG)6.7\t100\t.\n");
+}
+
+#[test]
+fn merge_keeps_contigs_names_and_strands_apart() {
+    let bed = "c1\t0\t10\tA\t80\t+\n\
+               c2\t5\t15\tA\t90\t+\n\
+               c1\t5\t15\tA\t90\t+\n\
+               c1\t15\t20\tA\t70\t+\n\
+               c1\t5\t15\tT\t90\t+\n\
+               c1\t5\t15\tA\t100\t-\n";
+    let output = subcommand(&["merge", "hits.bed"], &[("hits.bed", bed)]);
+    assert_eq!(stdout(&output), "c1\t0\t20\tA\t90\t+\nc1\t5\t15\tA\t100\t-\nc1\t5\t15\tT\t90\t+\nc2\t5\t15\tA\t90\t+\n");
+}
+
+#[test]
+fn threads_write_the_same_records_as_one_thread() {
+    // Contigs of every size; the two longest are cut into pieces across the threads
+    let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+    let mut fasta = String::new();
+    for (i, len) in [2_500_000, 300, 70_000, 12, 1_200_000, 5_000].into_iter().enumerate() {
+        let seq: String = (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                b"AAAAACGT"[(state % 8) as usize] as char
+            })
+            .collect();
+        fasta.push_str(&format!(">c{}\n{}\n", i, seq));
+    }
+    let prefix = |threads: &str| std::env::temp_dir().join(format!("polyscan-cli-{}-threads{}", std::process::id(), threads));
+    let scan = |threads: &str| {
+        let prefix = prefix(threads);
+        let args = ["-n", "A", "-w", "12", "-p", "100", "--threads", threads, "--split-by", "strand", "--split-prefix", prefix.to_str().unwrap()];
+        let records = stdout(&polyscan(&format!("threads{}", threads), &fasta, &args)).to_string();
+        let split: Vec<String> = ["plus", "minus"]
+            .iter()
+            .map(|strand| {
+                let path = prefix.with_extension(format!("{}.bed", strand));
+                let records = std::fs::read_to_string(&path).unwrap();
+                std::fs::remove_file(path).unwrap();
+                records
+            })
+            .collect();
+        (records, split)
+    };
+    let (records, split) = scan("1");
+    assert!(records.lines().count() > 1000);
+    assert!(records.lines().any(|line| line.starts_with("c4\t")));
+    for threads in ["2", "5"] {
+        assert_eq!(scan(threads), (records.clone(), split.clone()), "--threads {}", threads);
+    }
+}
+
+#[test]
+fn plot_writes_an_svg_per_contig() {
+    let fasta = ">chr1\nCGTAAAAAAAAAAAACGTCGTACGTA\n>chr2\nCGTACGTACGT\n>un:3\nAAAAAAAAAAAAAAAAAAAA\n";
+    let outdir = std::env::temp_dir().join(format!("polyscan-cli-{}-plots", std::process::id()));
+    let output = subcommand(&["plot", "--fasta", "ref.fa", "--outdir", outdir.to_str().unwrap(), "-w", "10", "--bins", "5"], &[("ref.fa", fasta)]);
+    stdout(&output);
+    let mut plots: Vec<String> = std::fs::read_dir(&outdir).unwrap().map(|entry| entry.unwrap().file_name().into_string().unwrap()).collect();
+    plots.sort();
+    // Contigs without records get an empty plot, and names are made safe for files
+    assert_eq!(plots, ["chr1.svg", "chr2.svg", "un_3.svg"]);
+    for plot in &plots {
+        let svg = std::fs::read_to_string(outdir.join(plot)).unwrap();
+        assert!(svg.starts_with("<svg") && svg.trim_end().ends_with("</svg>"), "{}", plot);
+    }
+    assert!(std::fs::read_to_string(outdir.join("un_3.svg")).unwrap().contains("un:3"));
+    std::fs::remove_dir_all(outdir).unwrap();
+}
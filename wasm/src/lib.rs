@@ -0,0 +1,80 @@
+//! WebAssembly bindings for the polyscan scanner, for scanning pasted
+//! sequences in the browser. Build with wasm-pack
+//! (`wasm-pack build wasm --target web`).
+
+use clap::ValueEnum;
+use wasm_bindgen::prelude::*;
+
+use polyscan::scan::{Alphabet, BaseClass};
+use polyscan::scanner::{MaskedPolicy, NPolicy, StrandChoice};
+use polyscan::{ScanParams, Scanner};
+
+/// A window passing the scan, in 0-based half-open coordinates of the
+/// sequence as scanned (without its header line and whitespace).
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone)]
+pub struct Hit {
+    pub chrom: String,
+    pub start: usize,
+    pub end: usize,
+    pub target: String,
+    pub score: f64,
+    /// "+", "-" or "."
+    pub strand: String,
+}
+
+impl From<polyscan::Hit> for Hit {
+    fn from(hit: polyscan::Hit) -> Self {
+        Hit { chrom: hit.chrom, start: hit.start, end: hit.end, target: hit.target, score: hit.score, strand: hit.strand.symbol().to_string() }
+    }
+}
+
+// An option value by its command-line name (e.g. "count-against")
+fn choice<T: ValueEnum>(option: &str, value: &str) -> Result<T, JsError> {
+    T::from_str(value, true).map_err(|_| JsError::new(&format!("invalid {} '{}'", option, value)))
+}
+
+// The name and bases of pasted text: a FASTA header line, if there is one,
+// names the sequence, and line breaks and spaces inside it are dropped
+fn pasted(text: &str) -> (String, Vec<u8>) {
+    let text = text.trim_start();
+    let (name, body) = match text.strip_prefix('>') {
+        Some(rest) => {
+            let (header, body) = rest.split_once('\n').unwrap_or((rest, ""));
+            (header.split_whitespace().next().unwrap_or("seq").to_string(), body)
+        }
+        None => ("seq".to_string(), text),
+    };
+    (name, body.bytes().filter(|c| !c.is_ascii_whitespace()).collect())
+}
+
+/// Scan pasted sequence text (bare bases or a single FASTA record) for
+/// windows where `base` makes up at least `pct` percent of `window` bases.
+///
+/// `base` takes one or more comma-separated targets as `--nucleotide` does,
+/// and `strand`, `n_policy` and `ignore_masked` take the values of the
+/// command-line options of the same names (`undefined` for their defaults).
+#[wasm_bindgen]
+pub fn scan(
+    text: &str,
+    base: &str,
+    window: usize,
+    pct: f64,
+    strand: Option<String>,
+    n_policy: Option<String>,
+    ignore_masked: Option<String>,
+) -> Result<Vec<Hit>, JsError> {
+    let params = ScanParams {
+        targets: base.split(',').map(|target| BaseClass::new(target.trim().as_bytes())).collect(),
+        window,
+        percentage: pct,
+        alphabet: Alphabet::Dna,
+        strand: strand.map(|strand| choice::<StrandChoice>("strand", &strand)).transpose()?.unwrap_or_default(),
+        n_policy: n_policy.map(|policy| choice::<NPolicy>("n_policy", &policy)).transpose()?.unwrap_or_default(),
+        ignore_masked: ignore_masked.map(|policy| choice::<MaskedPolicy>("ignore_masked", &policy)).transpose()?,
+        ..ScanParams::default()
+    };
+    let scanner = Scanner::new(params).map_err(|e| JsError::new(&e.to_string()))?;
+    let (name, seq) = pasted(text);
+    Ok(scanner.hits(&name, &seq).map(Hit::from).collect())
+}